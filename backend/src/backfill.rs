@@ -0,0 +1,41 @@
+//! Running the metadata backfill (see [`scanner::backfill`]) alongside the
+//! server, and `GET /scan/status` to follow it.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use serde::Serialize;
+
+use crate::scanner::{self, BackfillStatus, ScanOptions};
+use crate::server::AppState;
+
+#[derive(Serialize)]
+pub struct ScanStatus {
+    backfill: BackfillStatus,
+}
+
+/// Starts a backfill of the files recorded without metadata on a thread of its
+/// own, one collection after the other. Each batch is stored through
+/// [`AppState::write`], so queries keep being answered between batches.
+pub fn start(state: Arc<AppState>, options: ScanOptions) {
+    std::thread::spawn(move || {
+        for collection_path in &state.collection_paths {
+            let result = scanner::backfill(collection_path, &options, &state.backfill, |task| {
+                state
+                    .write(|conn| task(conn).map_err(|e| e.to_string()))
+                    .map_err(Into::into)
+            });
+            if let Err(e) = result {
+                tracing::error!("Backfill of {} failed: {e}", collection_path.display());
+            }
+        }
+    });
+}
+
+/// `GET /scan/status`: the progress of the latest backfill.
+pub async fn scan_status(State(state): State<Arc<AppState>>) -> Json<ScanStatus> {
+    Json(ScanStatus {
+        backfill: state.backfill.status(),
+    })
+}
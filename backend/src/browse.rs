@@ -0,0 +1,122 @@
+//! Read-only endpoints for common library views that don't warrant a
+//! hand-written query on the client.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::{self, ScanFailure};
+use crate::server::AppState;
+
+const DEFAULT_RECENT_LIMIT: u32 = 50;
+const MAX_RECENT_LIMIT: u32 = 1000;
+
+/// The file timestamp `/recent` orders by.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RecentBy {
+    /// When the file was first scanned into the library.
+    #[default]
+    Added,
+    /// When a scan last found the file's content changed.
+    Modified,
+}
+
+#[derive(Deserialize)]
+pub struct RecentParams {
+    #[serde(default)]
+    by: RecentBy,
+    #[serde(default = "default_recent_limit")]
+    limit: u32,
+}
+
+fn default_recent_limit() -> u32 {
+    DEFAULT_RECENT_LIMIT
+}
+
+/// A track as listed by browse endpoints. Timestamps are i64 epoch seconds.
+#[derive(Serialize)]
+pub struct BrowseTrack {
+    id: String,
+    title: Option<String>,
+    album: Option<String>,
+    artists: Option<String>,
+    path: String,
+    added: i64,
+    modified: i64,
+}
+
+/// `GET /recent?by=added|modified&limit=N`: live tracks, newest first.
+pub async fn recent(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<RecentParams>,
+) -> Result<Json<Vec<BrowseTrack>>, (StatusCode, String)> {
+    let column = match params.by {
+        RecentBy::Added => "added",
+        RecentBy::Modified => "modified",
+    };
+    let limit = params.limit.min(MAX_RECENT_LIMIT);
+    let sql = format!(
+        "SELECT t.id, t.title, al.title,
+                string_agg(ar.name, ', ' ORDER BY c.ord), f.path,
+                epoch(f.added)::BIGINT AS added,
+                epoch(f.modified)::BIGINT AS modified
+         FROM track t
+         JOIN file f ON f.id = t.file
+         LEFT JOIN album al ON al.id = t.album
+         LEFT JOIN credit c ON c.track = t.id AND c.role = ''
+         LEFT JOIN artist ar ON ar.id = c.artist
+         WHERE f.deletion IS NULL
+         GROUP BY t.id, t.title, al.title, f.path, f.added, f.modified,
+                  t.disc_number, t.track_number
+         ORDER BY {column} DESC, f.path, t.disc_number, t.track_number
+         LIMIT ?"
+    );
+
+    let result = tokio::task::spawn_blocking(move || {
+        state.read(|conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map([limit], |row| {
+                Ok(BrowseTrack {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    album: row.get(2)?,
+                    artists: row.get(3)?,
+                    path: row.get(4)?,
+                    added: row.get(5)?,
+                    modified: row.get(6)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(tracks)) => Ok(Json(tracks)),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "recent task panicked".to_string(),
+        )),
+    }
+}
+
+/// `GET /failures`: the files the last scan couldn't read metadata from.
+pub async fn failures(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ScanFailure>>, (StatusCode, String)> {
+    let result = tokio::task::spawn_blocking(move || state.read(scanner::load_failures)).await;
+
+    match result {
+        Ok(Ok(failures)) => Ok(Json(failures)),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failures task panicked".to_string(),
+        )),
+    }
+}
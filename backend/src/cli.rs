@@ -0,0 +1,239 @@
+//! Command-line pieces shared by the `collectune` and `collectune-server` binaries.
+
+use axum::Router;
+use clap::{Args, Subcommand};
+use duckdb::Connection;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use tracing::level_filters::LevelFilter;
+
+use crate::export::{self, ExportFormat};
+use crate::{db, scanner, server};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Rebuild tracks, albums and artists from stored tags without re-reading any files
+    Rederive(RederiveArgs),
+    /// List the files the last scan couldn't read metadata from
+    Failures(CollectionArgs),
+    /// Quickly compare the library's paths with the files on disk, without hashing
+    Check(CollectionArgs),
+    /// Write the tracks with their albums and artists to one flat file
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct CollectionArgs {
+    /// Path to the collection of audio files
+    pub collection_path: String,
+
+    /// Path to the database file (defaults to `collectune.db` in the collection root)
+    #[arg(long)]
+    pub db_path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RederiveArgs {
+    #[command(flatten)]
+    pub collection: CollectionArgs,
+
+    #[command(flatten)]
+    pub genre: scanner::GenreOptions,
+
+    #[command(flatten)]
+    pub album: scanner::AlbumOptions,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub collection: CollectionArgs,
+
+    /// The file to write
+    pub output: PathBuf,
+
+    /// The format of the file
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ExportFormat,
+}
+
+/// The arguments for scanning the collections and serving the library, which
+/// both binaries take when no subcommand is given.
+#[derive(Args)]
+pub struct ServerArgs {
+    /// Paths to the collections of audio files, scanned into one library
+    #[arg(required = true)]
+    pub collection_paths: Vec<String>,
+
+    /// Start without running a full collection scan
+    #[arg(long)]
+    pub no_scan: bool,
+
+    #[command(flatten)]
+    pub scan: scanner::ScanOptions,
+
+    /// Path to the database file (defaults to `collectune.db` in the root of the
+    /// first collection)
+    #[arg(long)]
+    pub db_path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub serve: server::ServeOptions,
+
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 3000)]
+    pub port: u16,
+
+    /// Address to listen on, e.g. `127.0.0.1` to only accept local connections
+    #[arg(long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    pub bind: IpAddr,
+
+    /// Migrate the database up or down to this schema version and exit, e.g.
+    /// to undo a migration under development
+    #[arg(long, value_name = "N")]
+    pub migrate_to: Option<u32>,
+}
+
+#[derive(Args, Clone)]
+pub struct LogOptions {
+    /// How much to log: `off`, `error`, `warn`, `info`, `debug` (adds the SQL
+    /// of each query) or `trace`
+    #[arg(long, value_name = "LEVEL", default_value_t = LevelFilter::INFO)]
+    pub log_level: LevelFilter,
+}
+
+impl LogOptions {
+    /// Installs the subscriber writing the log to stderr. Call it once, before
+    /// anything logs.
+    pub fn init(&self) {
+        tracing_subscriber::fmt()
+            .with_max_level(self.log_level)
+            .with_target(false)
+            .with_writer(std::io::stderr)
+            .init();
+    }
+}
+
+impl CollectionArgs {
+    pub fn open_db(&self) -> Result<Connection, Box<dyn std::error::Error>> {
+        let collection_path = get_collection_path(&self.collection_path)?;
+        let db_path = self
+            .db_path
+            .clone()
+            .unwrap_or_else(|| db::default_db_path(collection_path));
+        db::get_db(&db_path)
+    }
+}
+
+impl ServerArgs {
+    /// Scans the collections, unless told not to, then serves the library until
+    /// the process is signalled to stop. `app` builds the app to serve from the
+    /// API's router, e.g. to nest it alongside static files.
+    pub async fn run(
+        self,
+        app: impl FnOnce(Router) -> Router,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let collection_paths = self
+            .collection_paths
+            .iter()
+            .map(String::as_str)
+            .map(get_collection_path)
+            .collect::<Result<Vec<_>, _>>()?;
+        let db_path = self
+            .db_path
+            .unwrap_or_else(|| db::default_db_path(collection_paths[0]));
+        if let Some(version) = self.migrate_to {
+            return db::migrate_to(&mut db::open_db(&db_path)?, version);
+        }
+        let conn = db::get_db(&db_path)?;
+        if !self.no_scan {
+            for collection_path in &collection_paths {
+                scanner::scan(collection_path, &conn, self.scan.clone())?;
+            }
+        }
+        // A dry run only reports what a scan would do, so there's nothing to serve.
+        if self.scan.dry_run {
+            return Ok(());
+        }
+        server::serve(
+            conn,
+            collection_paths
+                .into_iter()
+                .map(Path::to_path_buf)
+                .collect(),
+            SocketAddr::new(self.bind, self.port),
+            self.scan,
+            &self.serve,
+            app,
+        )
+        .await
+    }
+}
+
+impl Command {
+    pub fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Command::Rederive(args) => {
+                scanner::rederive(&args.collection.open_db()?, &args.genre, &args.album)
+            }
+            Command::Failures(args) => print_failures(&args.open_db()?),
+            Command::Check(args) => print_check(args),
+            Command::Export(args) => run_export(args),
+        }
+    }
+}
+
+/// Prints one tab-separated line per failure: category, path and message.
+fn print_failures(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let failures = scanner::load_failures(conn)?;
+    for f in &failures {
+        println!("{}\t{}\t{}", f.category, f.path, f.message);
+    }
+    tracing::info!("Failures: {}", failures.len());
+    Ok(())
+}
+
+fn run_export(args: &ExportArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let count = export::export(&args.collection.open_db()?, &args.output, args.format)?;
+    tracing::info!("Exported {count} tracks to {}", args.output.display());
+    Ok(())
+}
+
+/// Prints one tab-separated line per inconsistency: `missing` or `untracked`,
+/// then the path. Fails when there are any, so scripts can tell.
+fn print_check(args: &CollectionArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let report = scanner::check(
+        &args.open_db()?,
+        get_collection_path(&args.collection_path)?,
+    )?;
+    for path in &report.missing {
+        println!("missing\t{path}");
+    }
+    for path in &report.untracked {
+        println!("untracked\t{path}");
+    }
+    if report.is_consistent() {
+        tracing::info!("Check: the library matches the collection");
+        return Ok(());
+    }
+    Err(format!(
+        "{} files missing on disk, {} untracked",
+        report.missing.len(),
+        report.untracked.len()
+    )
+    .into())
+}
+
+pub fn get_collection_path(path_str: &str) -> Result<&Path, String> {
+    let path = Path::new(path_str);
+
+    if !path.exists() {
+        return Err(format!("The path '{path_str}' does not exist."));
+    }
+
+    if !path.is_dir() {
+        return Err(format!("The path '{path_str}' is not a directory."));
+    }
+
+    Ok(path)
+}
@@ -1,4 +1,5 @@
 use duckdb::Connection;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 static DB_FILE_NAME: &str = "collectune.db";
@@ -13,28 +14,209 @@ pub fn default_db_path(collection_path: &Path) -> PathBuf {
 struct Migration {
     version: u32,
     sql: &'static str,
+    /// Undoes `sql`, for [`migrate_to`] to roll the migration back. `None` for
+    /// migrations that can't be undone, such as ones that delete data.
+    down_sql: Option<&'static str>,
+}
+
+impl Migration {
+    /// The blake3 hash of `sql`, recorded in `meta.migrations` when the
+    /// migration is applied so that later edits to it are noticed.
+    fn checksum(&self) -> String {
+        blake3::hash(self.sql.as_bytes()).to_hex().to_string()
+    }
 }
 
 /// All known migrations, embedded at compile time.
 ///
 /// To add a new migration, create a SQL file in this directory named with a
 /// four-digit version prefix (e.g. `0002.sql`) and append a corresponding
-/// entry here. Migrations must be listed in strictly ascending order.
-const MIGRATIONS: &[Migration] = &[Migration {
-    version: 1,
-    sql: include_str!("migrations/0001.sql"),
-}];
+/// entry here. Migrations must be listed in strictly ascending order. A
+/// `NNNN.down.sql` file next to it, if the migration can be undone, makes it
+/// possible to roll it back while developing it.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: include_str!("migrations/0001.sql"),
+        down_sql: None,
+    },
+    Migration {
+        version: 2,
+        sql: include_str!("migrations/0002.sql"),
+        down_sql: Some(include_str!("migrations/0002.down.sql")),
+    },
+    Migration {
+        version: 3,
+        sql: include_str!("migrations/0003.sql"),
+        down_sql: Some(include_str!("migrations/0003.down.sql")),
+    },
+    Migration {
+        version: 4,
+        sql: include_str!("migrations/0004.sql"),
+        down_sql: Some(include_str!("migrations/0004.down.sql")),
+    },
+    Migration {
+        version: 5,
+        sql: include_str!("migrations/0005.sql"),
+        down_sql: None,
+    },
+    Migration {
+        version: 6,
+        sql: include_str!("migrations/0006.sql"),
+        down_sql: Some(include_str!("migrations/0006.down.sql")),
+    },
+    Migration {
+        version: 7,
+        sql: include_str!("migrations/0007.sql"),
+        down_sql: Some(include_str!("migrations/0007.down.sql")),
+    },
+    Migration {
+        version: 8,
+        sql: include_str!("migrations/0008.sql"),
+        down_sql: Some(include_str!("migrations/0008.down.sql")),
+    },
+    Migration {
+        version: 9,
+        sql: include_str!("migrations/0009.sql"),
+        down_sql: Some(include_str!("migrations/0009.down.sql")),
+    },
+    Migration {
+        version: 10,
+        sql: include_str!("migrations/0010.sql"),
+        down_sql: Some(include_str!("migrations/0010.down.sql")),
+    },
+    Migration {
+        version: 11,
+        sql: include_str!("migrations/0011.sql"),
+        down_sql: None,
+    },
+    Migration {
+        version: 12,
+        sql: include_str!("migrations/0012.sql"),
+        down_sql: Some(include_str!("migrations/0012.down.sql")),
+    },
+    Migration {
+        version: 13,
+        sql: include_str!("migrations/0013.sql"),
+        down_sql: None,
+    },
+    Migration {
+        version: 14,
+        sql: include_str!("migrations/0014.sql"),
+        down_sql: Some(include_str!("migrations/0014.down.sql")),
+    },
+    Migration {
+        version: 15,
+        sql: include_str!("migrations/0015.sql"),
+        down_sql: Some(include_str!("migrations/0015.down.sql")),
+    },
+    Migration {
+        version: 16,
+        sql: include_str!("migrations/0016.sql"),
+        down_sql: Some(include_str!("migrations/0016.down.sql")),
+    },
+    Migration {
+        version: 17,
+        sql: include_str!("migrations/0017.sql"),
+        down_sql: Some(include_str!("migrations/0017.down.sql")),
+    },
+    Migration {
+        version: 18,
+        sql: include_str!("migrations/0018.sql"),
+        down_sql: Some(include_str!("migrations/0018.down.sql")),
+    },
+    Migration {
+        version: 19,
+        sql: include_str!("migrations/0019.sql"),
+        down_sql: Some(include_str!("migrations/0019.down.sql")),
+    },
+    Migration {
+        version: 20,
+        sql: include_str!("migrations/0020.sql"),
+        down_sql: Some(include_str!("migrations/0020.down.sql")),
+    },
+    Migration {
+        version: 21,
+        sql: include_str!("migrations/0021.sql"),
+        down_sql: Some(include_str!("migrations/0021.down.sql")),
+    },
+    Migration {
+        version: 22,
+        sql: include_str!("migrations/0022.sql"),
+        down_sql: Some(include_str!("migrations/0022.down.sql")),
+    },
+    Migration {
+        version: 23,
+        sql: include_str!("migrations/0023.sql"),
+        down_sql: Some(include_str!("migrations/0023.down.sql")),
+    },
+    Migration {
+        version: 24,
+        sql: include_str!("migrations/0024.sql"),
+        down_sql: Some(include_str!("migrations/0024.down.sql")),
+    },
+    Migration {
+        version: 25,
+        sql: include_str!("migrations/0025.sql"),
+        down_sql: Some(include_str!("migrations/0025.down.sql")),
+    },
+    Migration {
+        version: 26,
+        sql: include_str!("migrations/0026.sql"),
+        down_sql: None,
+    },
+    Migration {
+        version: 27,
+        sql: include_str!("migrations/0027.sql"),
+        down_sql: Some(include_str!("migrations/0027.down.sql")),
+    },
+    Migration {
+        version: 28,
+        sql: include_str!("migrations/0028.sql"),
+        down_sql: Some(include_str!("migrations/0028.down.sql")),
+    },
+    Migration {
+        version: 29,
+        sql: include_str!("migrations/0029.sql"),
+        down_sql: Some(include_str!("migrations/0029.down.sql")),
+    },
+    Migration {
+        version: 30,
+        sql: include_str!("migrations/0030.sql"),
+        down_sql: Some(include_str!("migrations/0030.down.sql")),
+    },
+    Migration {
+        version: 31,
+        sql: include_str!("migrations/0031.sql"),
+        down_sql: Some(include_str!("migrations/0031.down.sql")),
+    },
+    Migration {
+        version: 32,
+        sql: include_str!("migrations/0032.sql"),
+        down_sql: Some(include_str!("migrations/0032.down.sql")),
+    },
+];
+
+/// The version of the last migration, which [`get_db`] brings databases to.
+fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
 
 fn init_db_version_metadata(conn: &Connection) -> Result<(), duckdb::Error> {
     let sql = "
         CREATE SCHEMA IF NOT EXISTS meta;
         CREATE TABLE IF NOT EXISTS meta.version (value UINTEGER NOT NULL);
         INSERT INTO meta.version SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM meta.version);
+        CREATE TABLE IF NOT EXISTS meta.migrations (
+            version UINTEGER PRIMARY KEY,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMP NOT NULL DEFAULT now()
+        );
     ";
     conn.execute_batch(sql)
 }
 
-fn get_current_version(conn: &Connection) -> Result<u32, duckdb::Error> {
+pub(crate) fn get_current_version(conn: &Connection) -> Result<u32, duckdb::Error> {
     conn.query_row("SELECT value FROM meta.version", [], |row| row.get(0))
 }
 
@@ -42,23 +224,160 @@ fn run_migration(conn: &mut Connection, migration: &Migration) -> Result<(), duc
     let tx = conn.transaction()?;
     tx.execute_batch(migration.sql)?;
     tx.execute("UPDATE meta.version SET value = ?", [migration.version])?;
+    tx.execute(
+        "INSERT OR REPLACE INTO meta.migrations (version, checksum) VALUES (?, ?)",
+        duckdb::params![migration.version, migration.checksum()],
+    )?;
     tx.commit()?;
-    println!("Migration {:04} applied.", migration.version);
+    tracing::info!("Migration {:04} applied.", migration.version);
     Ok(())
 }
 
-pub fn get_db(db_path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
-    let mut conn = Connection::open(db_path)?;
+/// Runs the down SQL of `migration`, leaving the database at `previous_version`.
+fn roll_back_migration(
+    conn: &mut Connection,
+    migration: &Migration,
+    down_sql: &str,
+    previous_version: u32,
+) -> Result<(), duckdb::Error> {
+    let tx = conn.transaction()?;
+    tx.execute_batch(down_sql)?;
+    tx.execute("UPDATE meta.version SET value = ?", [previous_version])?;
+    tx.execute(
+        "DELETE FROM meta.migrations WHERE version = ?",
+        [migration.version],
+    )?;
+    tx.commit()?;
+    tracing::info!("Migration {:04} rolled back.", migration.version);
+    Ok(())
+}
+
+/// Migrates the database up or down to `target_version`. Going down rolls
+/// migrations back newest first, and is refused up front when one of them has
+/// no down SQL.
+pub fn migrate_to(
+    conn: &mut Connection,
+    target_version: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let latest_version = latest_version();
+    if target_version > latest_version {
+        return Err(format!(
+            "No migration {target_version:04}: the latest is {latest_version:04}."
+        )
+        .into());
+    }
+    let current_version = get_current_version(conn)?;
+
+    if target_version >= current_version {
+        let pending_migrations = MIGRATIONS
+            .iter()
+            .filter(|m| m.version > current_version && m.version <= target_version);
+        for migration in pending_migrations {
+            run_migration(conn, migration)?;
+        }
+        return Ok(());
+    }
+
+    let mut rollbacks = Vec::new();
+    for (i, migration) in MIGRATIONS.iter().enumerate().rev() {
+        if migration.version <= target_version || migration.version > current_version {
+            continue;
+        }
+        let Some(down_sql) = migration.down_sql else {
+            return Err(format!(
+                "Migration {:04} can't be rolled back: it has no down SQL.",
+                migration.version
+            )
+            .into());
+        };
+        let previous_version = i.checked_sub(1).map_or(0, |i| MIGRATIONS[i].version);
+        rollbacks.push((migration, down_sql, previous_version));
+    }
+    for (migration, down_sql, previous_version) in rollbacks {
+        roll_back_migration(conn, migration, down_sql, previous_version)?;
+    }
+    Ok(())
+}
+
+/// Checks that the database can live at `db_path`, which may be outside the
+/// collection: its directory must exist and be writable.
+fn check_db_location(db_path: &Path) -> Result<(), String> {
+    if db_path == Path::new(":memory:") {
+        return Ok(());
+    }
+    let dir = match db_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(metadata) = fs::metadata(dir) else {
+        return Err(format!(
+            "The database directory '{}' does not exist.",
+            dir.display()
+        ));
+    };
+    if !metadata.is_dir() {
+        return Err(format!("'{}' is not a directory.", dir.display()));
+    }
+    // The permission bits don't tell about ACLs, read-only mounts or another
+    // owner's directory, so try writing a file there.
+    let probe = dir.join(format!(".collectune-write-test-{}", uuid::Uuid::new_v4()));
+    if let Err(e) = fs::File::create_new(&probe) {
+        return Err(format!(
+            "The database directory '{}' is not writable: {e}",
+            dir.display()
+        ));
+    }
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Opens the database as it is, without migrating it; see [`get_db`].
+pub fn open_db(db_path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    check_db_location(db_path)?;
+    let conn = Connection::open(db_path)?;
     init_db_version_metadata(&conn)?;
-    let current_version = get_current_version(&conn)?;
-    let pending_migrations = MIGRATIONS
-        .iter()
-        .filter(|m| m.version > current_version)
-        .collect::<Vec<_>>();
-
-    for migration in pending_migrations {
-        run_migration(&mut conn, migration)?;
+    Ok(conn)
+}
+
+/// Checks that the migrations applied to the database are the ones embedded:
+/// an applied migration whose SQL was edited since would leave the database's
+/// schema different from a fresh one's. Databases migrated before checksums
+/// were recorded have those of their applied migrations recorded as they are.
+fn verify_checksums(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let current_version = get_current_version(conn)?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version <= current_version) {
+        conn.execute(
+            "INSERT INTO meta.migrations (version, checksum) VALUES (?, ?) ON CONFLICT DO NOTHING",
+            duckdb::params![migration.version, migration.checksum()],
+        )?;
+    }
+
+    let mut stmt =
+        conn.prepare("SELECT version, checksum FROM meta.migrations ORDER BY version")?;
+    let recorded = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (version, checksum) in recorded {
+        if let Some(migration) = MIGRATIONS.iter().find(|m| m.version == version)
+            && migration.checksum() != checksum
+        {
+            return Err(format!(
+                "Migration {version:04} was edited after it was applied to this database. \
+                 Restore its original SQL, or roll it back with the original first."
+            )
+            .into());
+        }
     }
+    Ok(())
+}
 
+/// Opens the database, checks the migrations applied to it and applies any
+/// pending ones.
+pub fn get_db(db_path: &Path) -> Result<Connection, Box<dyn std::error::Error>> {
+    let mut conn = open_db(db_path)?;
+    verify_checksums(&conn)?;
+    migrate_to(&mut conn, latest_version())?;
     Ok(conn)
 }
@@ -0,0 +1,70 @@
+//! `export`: the library as one flat file for analysis in other tools, with a
+//! row per track of a live file and its album and artists alongside.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use duckdb::Connection;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Apache Parquet, keeping the column types
+    #[default]
+    Parquet,
+    /// CSV with a header row
+    Csv,
+    /// Newline-delimited JSON, an object per track
+    Json,
+}
+
+impl ExportFormat {
+    /// The options of `DuckDB`'s `COPY ... TO` writing this format.
+    fn copy_options(self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "FORMAT PARQUET",
+            ExportFormat::Csv => "FORMAT CSV, HEADER",
+            ExportFormat::Json => "FORMAT JSON",
+        }
+    }
+}
+
+/// The rows exported, in path order. `artists` only lists the track's artists,
+/// not those credited in another role; `artists` and `genres` are lists, in
+/// tag order.
+const EXPORT_SQL: &str = "
+SELECT t.id AS track_id, f.path, t.title,
+       (SELECT list(ar.name ORDER BY c.ord)
+        FROM credit c JOIN artist ar ON ar.id = c.artist
+        WHERE c.track = t.id AND c.role = '') AS artists,
+       al.title AS album, aa.name AS album_artist, al.year, al.release_date,
+       t.disc_number, t.track_number,
+       (SELECT list(g.name ORDER BY tg.ord)
+        FROM track_genre tg JOIN genre g ON g.id = tg.genre
+        WHERE tg.track = t.id) AS genres,
+       t.primary_genre, coalesce(t.end_position, f.duration) - coalesce(t.start_position, 0)
+           AS duration,
+       t.rating, t.bpm, t.musical_key, f.format, f.added
+FROM track t
+JOIN file f ON f.id = t.file
+LEFT JOIN album al ON al.id = t.album
+LEFT JOIN artist aa ON aa.id = al.artist
+WHERE f.deletion IS NULL
+ORDER BY f.path, t.start_position NULLS FIRST
+";
+
+/// Writes the library to `path` in `format`, returning the number of tracks
+/// written. An existing file at `path` is overwritten.
+pub fn export(
+    conn: &Connection,
+    path: &Path,
+    format: ExportFormat,
+) -> Result<usize, duckdb::Error> {
+    let path = path.to_string_lossy().replace('\'', "''");
+    conn.execute(
+        &format!(
+            "COPY ({EXPORT_SQL}) TO '{path}' ({})",
+            format.copy_options()
+        ),
+        [],
+    )
+}
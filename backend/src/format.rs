@@ -0,0 +1,194 @@
+//! Audio file formats, mirroring the `format` enum type in the database.
+//!
+//! The SQL type and [`Format`] must list the same values: the scanner appends
+//! [`Format::as_str`] into `format` columns, and `DuckDB` rejects any value the
+//! type doesn't know. To add a format, add the variant here (the exhaustive
+//! matches below will point at what else needs deciding) and a migration that
+//! rebuilds the SQL type with the new value, as `migrations/0005.sql` does.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Aac,
+    Adpcm,
+    Aiff,
+    Alac,
+    Ape,
+    Caf,
+    Flac,
+    Mka,
+    Mkv,
+    Mp1,
+    Mp2,
+    Mp3,
+    Mp4,
+    Ogg,
+    Opus,
+    Vorbis,
+    Wav,
+    Webm,
+    Wma,
+    Wv,
+}
+
+impl Format {
+    /// Every format, in the order of the SQL enum type.
+    pub const ALL: &[Format] = &[
+        Format::Aac,
+        Format::Adpcm,
+        Format::Aiff,
+        Format::Alac,
+        Format::Ape,
+        Format::Caf,
+        Format::Flac,
+        Format::Mka,
+        Format::Mkv,
+        Format::Mp1,
+        Format::Mp2,
+        Format::Mp3,
+        Format::Mp4,
+        Format::Ogg,
+        Format::Opus,
+        Format::Vorbis,
+        Format::Wav,
+        Format::Webm,
+        Format::Wma,
+        Format::Wv,
+    ];
+
+    /// The value of the SQL enum type.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Aac => "aac",
+            Format::Adpcm => "adpcm",
+            Format::Aiff => "aiff",
+            Format::Alac => "alac",
+            Format::Ape => "ape",
+            Format::Caf => "caf",
+            Format::Flac => "flac",
+            Format::Mka => "mka",
+            Format::Mkv => "mkv",
+            Format::Mp1 => "mp1",
+            Format::Mp2 => "mp2",
+            Format::Mp3 => "mp3",
+            Format::Mp4 => "mp4",
+            Format::Ogg => "ogg",
+            Format::Opus => "opus",
+            Format::Vorbis => "vorbis",
+            Format::Wav => "wav",
+            Format::Webm => "webm",
+            Format::Wma => "wma",
+            Format::Wv => "wv",
+        }
+    }
+
+    /// Every file extension the scanner can pick up, in lower case, with the
+    /// format of its files.
+    pub const EXTENSIONS: &[(&str, Format)] = &[
+        ("aac", Format::Aac),
+        ("aif", Format::Aiff),
+        ("aiff", Format::Aiff),
+        ("alac", Format::Alac),
+        ("ape", Format::Ape),
+        ("caf", Format::Caf),
+        ("flac", Format::Flac),
+        ("m4a", Format::Mp4),
+        ("m4b", Format::Mp4),
+        ("mka", Format::Mka),
+        ("mkv", Format::Mkv),
+        ("mp1", Format::Mp1),
+        ("mp2", Format::Mp2),
+        ("mp3", Format::Mp3),
+        ("mp4", Format::Mp4),
+        ("ogg", Format::Ogg),
+        ("opus", Format::Opus),
+        ("wav", Format::Wav),
+        ("webm", Format::Webm),
+        ("wma", Format::Wma),
+        ("wv", Format::Wv),
+    ];
+
+    /// The extensions of [`Format::EXTENSIONS`] scanned unless told otherwise.
+    /// The others are left out because files with them are as likely to be
+    /// videos (`mkv`, `mp4`, `webm`) or are rare enough not to look for.
+    pub const DEFAULT_EXTENSIONS: &[&str] = &[
+        "aac", "aif", "aiff", "alac", "ape", "flac", "m4a", "mka", "mp3", "ogg", "opus", "wav",
+        "wma", "wv",
+    ];
+
+    /// The format of a file with the given extension, if it's one the scanner
+    /// can pick up (see [`Format::EXTENSIONS`]).
+    #[must_use]
+    pub fn from_extension(ext: &str) -> Option<Format> {
+        Format::EXTENSIONS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+            .map(|&(_, format)| format)
+    }
+
+    #[must_use]
+    pub fn is_lossless(self) -> bool {
+        match self {
+            Format::Aiff
+            | Format::Alac
+            | Format::Ape
+            | Format::Caf
+            | Format::Flac
+            | Format::Wav
+            | Format::Wv => true,
+            Format::Aac
+            | Format::Adpcm
+            | Format::Mka
+            | Format::Mkv
+            | Format::Mp1
+            | Format::Mp2
+            | Format::Mp3
+            | Format::Mp4
+            | Format::Ogg
+            | Format::Opus
+            | Format::Vorbis
+            | Format::Webm
+            | Format::Wma => false,
+        }
+    }
+
+    #[must_use]
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Aac => "audio/aac",
+            Format::Adpcm | Format::Wav => "audio/wav",
+            Format::Aiff => "audio/aiff",
+            Format::Alac | Format::Mp4 => "audio/mp4",
+            Format::Ape => "audio/x-ape",
+            Format::Caf => "audio/x-caf",
+            Format::Flac => "audio/flac",
+            Format::Mka | Format::Mkv => "audio/x-matroska",
+            Format::Mp1 | Format::Mp2 | Format::Mp3 => "audio/mpeg",
+            Format::Ogg | Format::Vorbis | Format::Opus => "audio/ogg",
+            Format::Webm => "audio/webm",
+            Format::Wma => "audio/x-ms-wma",
+            Format::Wv => "audio/x-wavpack",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .iter()
+            .copied()
+            .find(|format| format.as_str() == s)
+            .ok_or_else(|| format!("unknown format '{s}'"))
+    }
+}
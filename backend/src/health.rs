@@ -0,0 +1,57 @@
+//! `GET /health`: whether the server can answer queries, for load balancers
+//! and container orchestrators to poll.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::db;
+use crate::server::AppState;
+
+#[derive(Serialize)]
+pub struct Health {
+    /// `ok`, or why the server can't answer: `busy` when every reader
+    /// connection is taken, `unavailable` when the database fails.
+    status: &'static str,
+    schema_version: Option<u32>,
+    uptime_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// `GET /health`: 200 once the schema version can be read from the database,
+/// 503 otherwise. Only an idle reader connection is used, so the check never
+/// waits behind a running query; when there is none the server counts as busy.
+pub async fn health(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Health>) {
+    let uptime_secs = state.uptime().as_secs();
+    let result = tokio::task::spawn_blocking(move || state.try_read(db::get_current_version)).await;
+    let (status, schema_version, error) = match result {
+        Ok(Some(Ok(version))) => ("ok", Some(version), None),
+        Ok(Some(Err(e))) => ("unavailable", None, Some(e.to_string())),
+        Ok(None) => (
+            "busy",
+            None,
+            Some("every database connection is busy".to_string()),
+        ),
+        Err(_) => (
+            "unavailable",
+            None,
+            Some("health check panicked".to_string()),
+        ),
+    };
+    let code = if error.is_none() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let health = Health {
+        status,
+        schema_version,
+        uptime_secs,
+        error,
+    };
+    (code, Json(health))
+}
@@ -0,0 +1,69 @@
+//! Queries against the library as it was at an earlier time
+//! (`POST /query?as_of=<timestamp>`).
+//!
+//! Deletions are soft, so the rows a past library held are all still there.
+//! For the duration of one query, temporary views shadow the tables below so
+//! that unqualified references to them only see rows that existed at the given
+//! time:
+//!
+//! - `file`: files added at or before the time and not deleted by then. Their
+//!   `deletion` column reads NULL, as it did at that time.
+//! - `track`: tracks of those files.
+//! - `credit`: credits of those tracks.
+//! - `play`: plays at or before the time.
+//!
+//! Other tables (`album`, `artist`, ...) aren't versioned and are queried as
+//! they are now, as is the metadata of the rows above: a file whose tags
+//! changed since shows its current title, album and credits.
+
+use duckdb::Connection;
+
+/// The shadowed tables, in creation order (each view may depend on the ones
+/// before it).
+const TABLES: &[&str] = &["file", "track", "credit", "play"];
+
+/// Creates the temporary views showing the library as of `as_of`, which may be
+/// anything `DuckDB` can cast to a `TIMESTAMP`.
+pub fn shadow(conn: &Connection, as_of: &str) -> Result<(), String> {
+    let timestamp: String = conn
+        .query_row("SELECT CAST(? AS TIMESTAMP)::VARCHAR", [as_of], |row| {
+            row.get(0)
+        })
+        .map_err(|e| format!("invalid as_of timestamp: {e}"))?;
+    let db: String = conn
+        .query_row("SELECT current_database()", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let db = db.replace('"', "\"\"");
+    let at = format!("TIMESTAMP '{timestamp}'");
+
+    let sql = format!(
+        r#"
+CREATE OR REPLACE TEMP VIEW file AS
+SELECT * REPLACE (NULL::UUID AS deletion) FROM "{db}".main.file
+WHERE added <= {at}
+  AND (deletion IS NULL
+       OR deletion NOT IN (SELECT id FROM "{db}".main.deletion WHERE timestamp <= {at}));
+
+CREATE OR REPLACE TEMP VIEW track AS
+SELECT * FROM "{db}".main.track WHERE file IN (SELECT id FROM temp.main.file);
+
+CREATE OR REPLACE TEMP VIEW credit AS
+SELECT * FROM "{db}".main.credit WHERE track IN (SELECT id FROM temp.main.track);
+
+CREATE OR REPLACE TEMP VIEW play AS
+SELECT * FROM "{db}".main.play WHERE timestamp <= {at};
+"#
+    );
+    conn.execute_batch(&sql).map_err(|e| {
+        unshadow(conn);
+        e.to_string()
+    })
+}
+
+/// Drops the views created by [`shadow`], so that later queries on the
+/// connection see the current library again.
+pub fn unshadow(conn: &Connection) {
+    for table in TABLES.iter().rev() {
+        let _ = conn.execute_batch(&format!("DROP VIEW IF EXISTS temp.main.{table};"));
+    }
+}
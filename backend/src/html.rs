@@ -0,0 +1,135 @@
+//! `GET /query?sql=...&format=html`: query results as a plain HTML table, for ad
+//! hoc queries from a browser without the app.
+//!
+//! Results are buffered in full, which is fine for the small results of ad hoc
+//! browsing but not meant for dumping whole tables.
+
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Html;
+use duckdb::Connection;
+use duckdb::arrow::datatypes::Schema;
+use duckdb::arrow::error::ArrowError;
+use duckdb::arrow::record_batch::RecordBatch;
+use duckdb::arrow::util::display::{ArrayFormatter, FormatOptions};
+use serde::Deserialize;
+
+use crate::history;
+use crate::query::{check_read_only, is_query, with_default_timeout};
+use crate::server::AppState;
+
+/// The formats `GET /query` can render results in.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryFormat {
+    Html,
+}
+
+#[derive(Deserialize)]
+pub struct HtmlQueryParams {
+    sql: String,
+    format: QueryFormat,
+    /// As for `POST /query` (see [`crate::history`]).
+    as_of: Option<String>,
+}
+
+/// Appends `text` to `html` with the characters that could start markup or end
+/// an attribute escaped, so that tag values can't inject HTML.
+fn push_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            _ => html.push(c),
+        }
+    }
+}
+
+/// Renders the results as an HTML page holding a single table, with the column
+/// names as headers and cells in Arrow's display format (NULL as empty).
+fn render_table(sql: &str, schema: &Schema, batches: &[RecordBatch]) -> Result<String, ArrowError> {
+    let mut html =
+        String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+    push_escaped(&mut html, sql);
+    html.push_str("</title>\n</head>\n<body>\n<table>\n<thead><tr>");
+    for field in schema.fields() {
+        html.push_str("<th>");
+        push_escaped(&mut html, field.name());
+        html.push_str("</th>");
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+
+    let options = FormatOptions::default();
+    for batch in batches {
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            html.push_str("<tr>");
+            for formatter in &formatters {
+                html.push_str("<td>");
+                push_escaped(&mut html, &formatter.value(row).try_to_string()?);
+                html.push_str("</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+    }
+
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    Ok(html)
+}
+
+fn run_query(conn: &Connection, sql: &str) -> Result<String, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    let batches = stmt.query_arrow([]).map_err(|e| e.to_string())?;
+    let schema = batches.get_schema();
+    let batches: Vec<RecordBatch> = batches.collect();
+    render_table(sql, &schema, &batches).map_err(|e| e.to_string())
+}
+
+/// `GET /query?sql=...&format=html[&as_of=...]`: only queries, which only read,
+/// since a GET must not modify the library.
+pub async fn query_html(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<HtmlQueryParams>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let result = tokio::task::spawn_blocking(move || {
+        state.read(|conn| {
+            if !is_query(conn, &params.sql) {
+                // A query that doesn't parse is better told by its own error.
+                conn.prepare(&params.sql).map_err(|e| e.to_string())?;
+                return Err("only queries that only read can be rendered".to_string());
+            }
+            if state.read_only {
+                check_read_only(conn, &params.sql)?;
+            }
+            if let Some(as_of) = &params.as_of {
+                history::shadow(conn, as_of)?;
+            }
+            let rendered = match params.format {
+                QueryFormat::Html => with_default_timeout(conn, || run_query(conn, &params.sql)),
+            };
+            if params.as_of.is_some() {
+                history::unshadow(conn);
+            }
+            rendered
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(html)) => Ok(Html(html)),
+        Ok(Err(msg)) => Err((StatusCode::BAD_REQUEST, msg)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "query task panicked".to_string(),
+        )),
+    }
+}
@@ -1,5 +1,20 @@
+pub mod backfill;
+pub mod browse;
+pub mod cli;
 pub mod db;
+pub mod export;
+pub mod format;
+pub mod health;
+pub mod history;
+pub mod html;
+pub mod query;
 pub mod rpc;
 pub mod scanner;
+pub mod schema;
+pub mod search;
 pub mod server;
+pub mod settings;
 pub mod stream;
+pub mod tracks;
+pub mod watch;
+pub mod ws;
@@ -1,52 +1,27 @@
-use backend::{db, scanner, server};
+use backend::cli::{Command, LogOptions, ServerArgs};
 use clap::Parser;
-use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "collectune-server")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 #[command(about = "A tool for managing audio file collections")]
 struct Args {
-    /// Path to the collection of audio files
-    collection_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Start without running a full collection scan
-    #[arg(long)]
-    no_scan: bool,
+    #[command(flatten)]
+    server: ServerArgs,
 
-    /// Path to the database file (defaults to `collectune.db` in the collection root)
-    #[arg(long)]
-    db_path: Option<PathBuf>,
-
-    /// Port to listen on
-    #[arg(short, long, default_value_t = 3000)]
-    port: u16,
-}
-
-fn get_collection_path(path_str: &String) -> Result<&Path, String> {
-    let path = Path::new(path_str);
-
-    if !path.exists() {
-        return Err(format!("The path '{path_str}' does not exist."));
-    }
-
-    if !path.is_dir() {
-        return Err(format!("The path '{path_str}' is not a directory."));
-    }
-
-    Ok(path)
+    #[command(flatten)]
+    log: LogOptions,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let collection_path = get_collection_path(&args.collection_path)?;
-    let db_path = args
-        .db_path
-        .unwrap_or_else(|| db::default_db_path(collection_path));
-    let conn = db::get_db(&db_path)?;
-    if !args.no_scan {
-        scanner::scan(collection_path, &conn)?;
+    args.log.init();
+    if let Some(command) = &args.command {
+        return command.run();
     }
-    server::serve(conn, collection_path.to_path_buf(), args.port).await?;
-    Ok(())
+    args.server.run(|api| api).await
 }
@@ -0,0 +1,3 @@
+alter table album drop column is_complete;
+alter table track drop column disc_total;
+alter table track drop column track_total;
@@ -0,0 +1,7 @@
+alter table track add column track_total utinyint;
+alter table track add column disc_total utinyint;
+
+-- Whether every expected track of the album is present, derived from the
+-- track/disc totals at the end of each scan. NULL means unknown: the tags don't
+-- say how many tracks to expect.
+alter table album add column is_complete boolean;
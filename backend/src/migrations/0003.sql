@@ -0,0 +1,10 @@
+-- Tags as read from each file, kept so that tracks, albums, artists and credits
+-- can be re-derived (see `collectune rederive`) without reading the files again.
+create table file_tag (
+  file uuid not null,
+  ord usmallint not null, -- position of the tag within the file's metadata
+  key text not null, -- key as written in the file, e.g. 'TRACKNUMBER' or 'TRCK'
+  std_key text, -- symphonia StandardTagKey name, e.g. 'TrackNumber'
+  value text not null,
+  primary key (file, ord)
+);
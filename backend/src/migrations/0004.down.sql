@@ -0,0 +1 @@
+alter table file drop column modified;
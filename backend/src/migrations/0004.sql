@@ -0,0 +1,5 @@
+-- When a scan last found the file's content changed (its hash differed), as
+-- opposed to `mtime`, which follows the filesystem and also moves on touches
+-- that leave the content alone. Starts out equal to `added`.
+alter table file add column modified timestamp;
+update file set modified = added;
@@ -0,0 +1,31 @@
+-- Add 'mka' (Matroska audio) to the format enum.
+--
+-- DuckDB can't add a value to an existing enum type, so the type is rebuilt:
+-- the column is converted to text, the type is recreated with the full list of
+-- values, and the column is converted back. Adding another format means a new
+-- migration like this one, listing every value of `Format` (see src/format.rs).
+alter table file alter column format type text;
+drop type format;
+create type format as enum (
+  'aac',
+  'adpcm',
+  'aiff',
+  'alac',
+  'ape',
+  'caf',
+  'flac',
+  'mka',
+  'mkv',
+  'mp1',
+  'mp2',
+  'mp3',
+  'mp4',
+  'ogg',
+  'opus',
+  'vorbis',
+  'wav',
+  'webm',
+  'wma',
+  'wv'
+);
+alter table file alter column format type format;
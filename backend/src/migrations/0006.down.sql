@@ -0,0 +1 @@
+drop table scan_failure;
@@ -0,0 +1,8 @@
+-- Files the last scan found but couldn't read metadata from, and so didn't add
+-- to `file`. Every scan replaces the whole table (see `collectune failures`).
+create table scan_failure (
+  path text primary key,
+  category text not null, -- 'io' | 'unsupported' | 'malformed' | 'panic'
+  message text not null,
+  scanned_at timestamp not null
+);
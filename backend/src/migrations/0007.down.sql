@@ -0,0 +1 @@
+drop table artist_alias;
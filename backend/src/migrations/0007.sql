@@ -0,0 +1,6 @@
+-- User-maintained alternative spellings of artist names. Scans credit a track
+-- tagged with an alias to the canonical artist instead of creating a new one.
+create table artist_alias (
+  alias text primary key,
+  artist uuid not null -- the canonical artist
+);
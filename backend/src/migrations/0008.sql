@@ -0,0 +1,5 @@
+-- The single genre browse views show for a track whose tags list several,
+-- picked during scans by `--primary-genre`. Existing tracks start out with the
+-- first of their genres, which `genre` joins with ', '.
+alter table track add column primary_genre text;
+update track set primary_genre = nullif(split_part(genre, ', ', 1), '');
@@ -0,0 +1,3 @@
+-- Bumped by every edit through `PATCH /tracks`, so that an edit based on an
+-- older read of the track can be refused instead of overwriting a newer one.
+alter table track add column version uinteger default 0;
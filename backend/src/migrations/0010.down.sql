@@ -0,0 +1,2 @@
+alter table track drop column musical_key;
+alter table track drop column bpm;
@@ -0,0 +1,6 @@
+-- Tempo and musical key, from the standard BPM tag and the tags DJ software
+-- writes (see scanner::dj_tags). Tracks scanned earlier pick up a standard BPM
+-- tag on the next rederive; their DJ tags weren't stored, so the rest stays
+-- empty until their files are added again.
+alter table track add column bpm real;
+alter table track add column musical_key text;
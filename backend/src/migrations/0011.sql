@@ -0,0 +1,4 @@
+-- Tracks without an album tag used to share an untitled album per directory.
+-- They now get no album unless scans are told otherwise (`--missing-album`).
+update track set album = null where album in (select id from album where title = '');
+delete from album where title = '';
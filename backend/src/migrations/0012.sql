@@ -0,0 +1,3 @@
+-- Collection-wide settings edited through `PUT /settings`, as JSON values by
+-- setting name. Unset settings take their defaults.
+create table meta.settings (key text primary key, value text not null);
@@ -0,0 +1,3 @@
+-- Scans with `--defer-metadata` record files before reading their metadata. A
+-- NULL duration marks a file whose metadata a backfill still has to read.
+alter table file alter column duration drop not null;
@@ -0,0 +1,2 @@
+alter table track drop column album_gain;
+alter table track drop column track_gain;
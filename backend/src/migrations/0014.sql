@@ -0,0 +1,6 @@
+-- Loudness gain in dB towards ReplayGain's reference level, from ReplayGain
+-- tags or the R128 tags of Opus files (see scanner::gain). Tracks scanned
+-- earlier pick up ReplayGain tags on the next rederive; their R128 tags weren't
+-- stored, so Opus tracks stay empty until their files are added again.
+alter table track add column track_gain real;
+alter table track add column album_gain real;
@@ -0,0 +1,7 @@
+-- Paths that reach a file of the collection through a symlink, recorded by
+-- scans run with `--symlinks alias` (see scanner::symlink). Every scan replaces
+-- them with the ones it found.
+create table file_alias (
+  path text primary key,
+  file uuid not null
+);
@@ -0,0 +1 @@
+alter table album drop column artist;
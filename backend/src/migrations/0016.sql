@@ -0,0 +1,3 @@
+-- The primary album artist, from the album artist tag or, without one, the
+-- first track artist. Albums scanned earlier get theirs on the next rederive.
+alter table album add column artist uuid;
@@ -0,0 +1,2 @@
+alter table track drop column album_peak;
+alter table track drop column track_peak;
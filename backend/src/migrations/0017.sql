@@ -0,0 +1,5 @@
+-- ReplayGain peaks: the largest sample magnitude of the track or its album,
+-- where 1.0 is full scale (see scanner::gain). Tracks scanned earlier pick them
+-- up on the next rederive.
+alter table track add column track_peak real;
+alter table track add column album_peak real;
@@ -0,0 +1,4 @@
+alter table file drop column bitrate;
+alter table file drop column bits_per_sample;
+alter table file drop column channels;
+alter table file drop column sample_rate;
@@ -0,0 +1,10 @@
+-- Audio properties of each file's default track, from its codec parameters
+-- (see scanner::metadata). The bitrate is the average in kbit/s over the whole
+-- file, so it's filled in here for files scanned earlier; their other
+-- properties stay NULL until the file changes.
+alter table file add column sample_rate uinteger;
+alter table file add column channels utinyint;
+alter table file add column bits_per_sample utinyint;
+alter table file add column bitrate uinteger;
+
+update file set bitrate = round(size * 8 / duration / 1000) where duration > 0;
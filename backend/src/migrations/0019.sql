@@ -0,0 +1,4 @@
+-- The full release date of the album, when its tracks' date tag gives a month
+-- and day as well as the year (see scanner::metadata). Albums scanned earlier
+-- get theirs on the next rederive.
+alter table album add column release_date date;
@@ -0,0 +1,12 @@
+create table credit_old (
+  track uuid not null,
+  artist uuid not null,
+  ord real,
+  role text,
+  primary key (track, artist)
+);
+insert into credit_old
+select distinct on (track, artist) track, artist, ord, role
+from credit order by track, artist, role nulls first, ord;
+drop table credit;
+alter table credit_old rename to credit;
@@ -0,0 +1,14 @@
+-- Credits in a role (composer, conductor, ...; see scanner::metadata) besides
+-- the artists', whose role is NULL. Someone credited in several roles gets a
+-- row for each, so a track and artist no longer identify a credit. DuckDB
+-- can't drop a primary key, so the table is rebuilt.
+create table credit_new (
+  track uuid not null,
+  artist uuid not null,
+  ord real,
+  role text,
+  unique (track, artist, role)
+);
+insert into credit_new select track, artist, ord, role from credit;
+drop table credit;
+alter table credit_new rename to credit;
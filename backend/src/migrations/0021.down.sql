@@ -0,0 +1,10 @@
+alter table track add column genre text;
+update track set genre = s.genre
+from (
+  select tg.track, string_agg(g.name, ', ' order by tg.ord) as genre
+  from track_genre tg join genre g on g.id = tg.genre
+  group by tg.track
+) s
+where track.id = s.track;
+drop table track_genre;
+drop table genre;
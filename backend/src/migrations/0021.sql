@@ -0,0 +1,33 @@
+-- Genres, like artists, get a row each, and tracks list theirs in
+-- `track_genre` instead of `track.genre` joining them with ', ' (which can't
+-- tell a genre "A, B" from the genres "A" and "B"). Existing tracks are split
+-- on ', '; a rederive restores genres with a comma in their name from the
+-- stored tags.
+create table genre (
+  id uuid primary key,
+  name text unique not null
+);
+
+create table track_genre (
+  track uuid not null,
+  genre uuid not null,
+  ord real,
+  primary key (track, genre)
+);
+
+create temp table split_genre as
+select id as track, unnest(string_split(genre, ', ')) as name,
+       unnest(range(len(string_split(genre, ', ')))) as ord
+from track where genre is not null and genre <> '';
+
+insert into genre (id, name)
+select uuid(), name from (select distinct name from split_genre where name <> '');
+
+insert into track_genre (track, genre, ord)
+select s.track, g.id, min(s.ord)
+from split_genre s join genre g on g.name = s.name
+group by s.track, g.id;
+
+drop table split_genre;
+
+alter table track drop column genre;
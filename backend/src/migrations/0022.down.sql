@@ -0,0 +1,3 @@
+alter table artist drop column musicbrainz_id;
+alter table album drop column musicbrainz_release_id;
+alter table track drop column musicbrainz_recording_id;
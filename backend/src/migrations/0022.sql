@@ -0,0 +1,6 @@
+-- MusicBrainz ids read from the tags Picard and other taggers write (see
+-- scanner::metadata). Tracks scanned earlier get theirs on the next rederive;
+-- their albums are then grouped by release.
+alter table track add column musicbrainz_recording_id uuid;
+alter table album add column musicbrainz_release_id uuid;
+alter table artist add column musicbrainz_id uuid;
@@ -0,0 +1 @@
+drop view track_search;
@@ -0,0 +1,13 @@
+-- The text `/search` matches tracks by: their title, and their title, album
+-- title and the names of everyone credited together, lowercased and with
+-- accents stripped.
+create view track_search as
+select t.id as track,
+       strip_accents(lower(coalesce(t.title, ''))) as title,
+       strip_accents(lower(concat_ws(' ', t.title, al.title, (
+         select string_agg(ar.name, ' ')
+         from credit c join artist ar on ar.id = c.artist
+         where c.track = t.id
+       )))) as text
+from track t
+left join album al on al.id = t.album;
@@ -0,0 +1,2 @@
+drop table playlist_entry;
+drop table playlist;
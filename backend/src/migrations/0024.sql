@@ -0,0 +1,16 @@
+-- The playlists of the collection (`.m3u`, `.m3u8` and `.pls` files), read by
+-- scans run with `--playlists`. Each entry keeps the path the playlist gives,
+-- and the live file it leads to, if any.
+create table playlist (
+  id uuid primary key,
+  path text unique not null,
+  name text not null
+);
+
+create table playlist_entry (
+  playlist uuid not null,
+  ord uinteger not null,
+  path text not null,
+  file uuid,
+  primary key (playlist, ord)
+);
@@ -0,0 +1 @@
+alter table file add column fingerprint blob;
@@ -0,0 +1,20 @@
+-- Writes to the library now refuse to commit tracks or credits pointing at
+-- missing rows (see scanner::staging). DuckDB can't add foreign keys to
+-- existing tables, and would turn updates of indexed columns, such as the
+-- `file.path` of a moved file, into a delete and insert that foreign keys
+-- refuse, so the checks run there instead. `artist.name` and
+-- `credit (track, artist, role)` are unique already.
+--
+-- Orphans left behind before are dropped, so that they don't fail every write
+-- to come.
+create temp table orphan_track as
+select id from track where file not in (select id from file);
+delete from play where track in (select id from orphan_track);
+delete from track_genre where track in (select id from orphan_track);
+delete from track where id in (select id from orphan_track);
+drop table orphan_track;
+
+delete from credit where track not in (select id from track);
+delete from credit where artist not in (select id from artist);
+update track set album = null
+where album is not null and album not in (select id from album);
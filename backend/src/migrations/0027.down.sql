@@ -0,0 +1,13 @@
+create or replace view track_search as
+select t.id as track,
+       strip_accents(lower(coalesce(t.title, ''))) as title,
+       strip_accents(lower(concat_ws(' ', t.title, al.title, (
+         select string_agg(ar.name, ' ')
+         from credit c join artist ar on ar.id = c.artist
+         where c.track = t.id
+       )))) as text
+from track t
+left join album al on al.id = t.album;
+
+alter table track drop column comment;
+alter table track drop column lyrics;
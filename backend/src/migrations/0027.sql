@@ -0,0 +1,16 @@
+-- Lyrics and comments, from the LYRICS and COMMENT tags, kept verbatim with
+-- their line breaks. Tracks scanned earlier get theirs on the next rederive.
+alter table track add column lyrics text;
+alter table track add column comment text;
+
+-- `/search` matches the lyrics and comments of tracks too.
+create or replace view track_search as
+select t.id as track,
+       strip_accents(lower(coalesce(t.title, ''))) as title,
+       strip_accents(lower(concat_ws(' ', t.title, al.title, (
+         select string_agg(ar.name, ' ')
+         from credit c join artist ar on ar.id = c.artist
+         where c.track = t.id
+       ), t.lyrics, t.comment))) as text
+from track t
+left join album al on al.id = t.album;
@@ -0,0 +1 @@
+drop table meta.last_scan;
@@ -0,0 +1,3 @@
+-- When the last scan that was written started, in UTC. `scan --since last`
+-- takes the directories unchanged since as they were.
+create table meta.last_scan (started timestamp not null);
@@ -0,0 +1 @@
+drop table acoustid_lookup;
@@ -0,0 +1,8 @@
+-- The AcoustID lookups of `scan --enrich`, by the BLAKE3 hash (in hex) of the
+-- fingerprint looked up, so the same audio is only looked up once. `recording`
+-- is the best match as AcoustID gave it, NULL when there was none.
+create table acoustid_lookup (
+  fingerprint_hash text primary key,
+  recording json,
+  looked_up timestamp not null
+);
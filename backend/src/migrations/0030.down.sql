@@ -0,0 +1,2 @@
+drop macro fmt_duration;
+alter table file drop column duration_interval;
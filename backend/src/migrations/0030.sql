@@ -0,0 +1,18 @@
+-- Each file's duration as an interval too, for date and time arithmetic, and
+-- `fmt_duration`, which writes a number of seconds as `m:ss`, or `h:mm:ss` from
+-- an hour on, rounded to the second.
+alter table file add column duration_interval interval;
+
+update file set duration_interval = to_microseconds((duration::double * 1000000)::bigint);
+
+create macro fmt_duration(sec) as
+  case
+    when sec is null then null
+    when round(sec) >= 3600 then format(
+      '{}:{:02d}:{:02d}',
+      round(sec)::bigint // 3600,
+      round(sec)::bigint % 3600 // 60,
+      round(sec)::bigint % 60
+    )
+    else format('{}:{:02d}', round(sec)::bigint // 60, round(sec)::bigint % 60)
+  end;
@@ -0,0 +1,50 @@
+alter table meta.last_scan drop column collection;
+
+drop table scan_failure;
+create table scan_failure (
+  path text primary key,
+  category text not null,
+  message text not null,
+  scanned_at timestamp not null
+);
+
+drop table file_alias;
+create table file_alias (
+  path text primary key,
+  file uuid not null
+);
+
+-- Fails when two collections have a playlist at the same path.
+create table playlist_old (
+  id uuid primary key,
+  path text unique not null,
+  name text not null
+);
+insert into playlist_old by name select * exclude (collection) from playlist;
+drop table playlist;
+alter table playlist_old rename to playlist;
+
+-- Fails when two collections have a file at the same path.
+create table file_old (
+  id uuid primary key,
+  path text unique not null,
+  hash blob not null,
+  size uinteger not null,
+  format format not null,
+  duration real,
+  mtime bigint not null,
+  added timestamp not null,
+  deletion uuid,
+  modified timestamp,
+  sample_rate uinteger,
+  channels utinyint,
+  bits_per_sample utinyint,
+  bitrate uinteger,
+  fingerprint blob,
+  duration_interval interval
+);
+insert into file_old by name select * exclude (collection) from file;
+drop table file;
+alter table file_old rename to file;
+
+drop table collection;
@@ -0,0 +1,68 @@
+-- The collections scanned into the library, each a directory of its own, by
+-- canonical path (see scanner::collection). Each file's path is relative to
+-- its collection, so paths are only unique within one. DuckDB can't drop a
+-- unique constraint, so `file` and `playlist` are rebuilt. The files scanned so
+-- far have no collection until the first one recorded takes them over, as do
+-- playlists.
+create table collection (
+  id uuid primary key,
+  path text unique not null
+);
+
+create table file_new (
+  id uuid primary key,
+  path text not null,
+  hash blob not null, -- (Note: possible for two files to have the same hash)
+  size uinteger not null,
+  format format not null,
+  duration real,
+  mtime bigint not null, -- filesystem mtime as microseconds since epoch
+  added timestamp not null,
+  deletion uuid,
+  modified timestamp,
+  sample_rate uinteger,
+  channels utinyint,
+  bits_per_sample utinyint,
+  bitrate uinteger,
+  fingerprint blob,
+  duration_interval interval,
+  collection uuid,
+  unique (collection, path)
+);
+insert into file_new by name select * from file;
+drop table file;
+alter table file_new rename to file;
+
+create table playlist_new (
+  id uuid primary key,
+  path text not null,
+  name text not null,
+  collection uuid,
+  unique (collection, path)
+);
+insert into playlist_new by name select * from playlist;
+drop table playlist;
+alter table playlist_new rename to playlist;
+
+-- Scans replace the aliases and failures of their own collection only. Both
+-- are found again by the next scan, so those recorded so far are dropped.
+drop table file_alias;
+create table file_alias (
+  collection uuid,
+  path text not null,
+  file uuid not null,
+  unique (collection, path)
+);
+
+drop table scan_failure;
+create table scan_failure (
+  collection uuid,
+  path text not null,
+  category text not null, -- 'io' | 'unsupported' | 'malformed' | 'panic'
+  message text not null,
+  scanned_at timestamp not null,
+  unique (collection, path)
+);
+
+-- `scan --since last` goes by the last scan of the collection scanned.
+alter table meta.last_scan add column collection uuid;
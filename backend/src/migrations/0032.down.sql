@@ -0,0 +1,10 @@
+create table credit_old (
+  track uuid not null,
+  artist uuid not null,
+  ord real,
+  role text,
+  unique (track, artist, role)
+);
+insert into credit_old select track, artist, ord, nullif(role, '') from credit;
+drop table credit;
+alter table credit_old rename to credit;
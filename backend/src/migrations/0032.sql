@@ -0,0 +1,17 @@
+-- The artists' credits have the role '' rather than NULL. DuckDB takes NULLs
+-- for distinct in a unique constraint, so `unique (track, artist, role)` let a
+-- track credit the same artist twice as long as the role was NULL. Duplicates
+-- left behind are dropped, and the table is rebuilt since DuckDB can't alter a
+-- column of a constrained table.
+create table credit_new (
+  track uuid not null,
+  artist uuid not null,
+  ord real,
+  role text not null default '',
+  unique (track, artist, role)
+);
+insert into credit_new
+select distinct on (track, artist, coalesce(role, '')) track, artist, ord, coalesce(role, '')
+from credit order by track, artist, coalesce(role, ''), ord;
+drop table credit;
+alter table credit_new rename to credit;
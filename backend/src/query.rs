@@ -0,0 +1,679 @@
+//! Running queries against the library: the service behind `POST /query`, and
+//! the entry point for clients that link the backend and query it in-process.
+//!
+//! A row-returning query is written out as an Arrow IPC stream, so an
+//! in-process client decodes it just as it would a `/query` response, with its
+//! own version of Arrow. Scripts can ask for newline-delimited JSON instead.
+//! A query, which only reads, runs on one of the readers; any other statement
+//! is executed as a write, and its rows, if it returns any, are written out
+//! just the same.
+//!
+//! A statement can take `?` parameters, bound to values given alongside it
+//! rather than spliced into its SQL.
+//!
+//! A statement that runs longer than its timeout ([`DEFAULT_TIMEOUT`] unless
+//! the client asks otherwise), or whose client goes away, is interrupted
+//! through `DuckDB`'s interrupt API, which frees its connection for the next
+//! one.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::OnceLock;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use arrow_ipc::writer::StreamWriter;
+use arrow_json::writer::{LineDelimited, WriterBuilder};
+use duckdb::arrow::datatypes::{DataType, Schema};
+use duckdb::arrow::record_batch::RecordBatch;
+use duckdb::types::Value;
+use duckdb::{Connection, params_from_iter};
+use serde::{Deserialize, Serialize};
+
+use crate::history;
+use crate::server::AppState;
+
+/// How long a statement may run unless its client gives a `timeout` of its own.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// How often a running statement's client is checked on.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Leading keywords of the statements a read-only server runs.
+static READ_ONLY_KEYWORDS: &[&str] = &["SELECT", "WITH"];
+
+const READ_ONLY_ERROR: &str =
+    "the server is read-only: only SELECT, WITH and EXPLAIN queries are allowed";
+
+/// Splits the first keyword of `sql` from the rest, skipping whitespace,
+/// comments and opening parentheses.
+fn split_leading_keyword(sql: &str) -> (&str, &str) {
+    let mut rest = sql;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            break;
+        }
+    }
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    rest.split_at(end)
+}
+
+/// `sql` as the subquery `q` of a statement wrapped around it. The closing
+/// parenthesis goes on a line of its own, so a comment that ends `sql` can't
+/// take it in.
+fn subquery(sql: &str) -> String {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    format!("({sql}\n) AS q")
+}
+
+/// Whether `sql` is a query: one that parses as a subquery. No statement that
+/// writes does, even those that return rows too (`INSERT ... RETURNING`,
+/// `CALL`, `PRAGMA`, ...).
+pub(crate) fn is_query(conn: &Connection, sql: &str) -> bool {
+    conn.prepare(&format!("SELECT * FROM {} LIMIT 0", subquery(sql)))
+        .is_ok()
+}
+
+/// Checks that `sql` only reads the library: a `SELECT` or `WITH` query, or an
+/// `EXPLAIN` of one. The query must also parse as a subquery, which no
+/// statement that writes does, so that a CTE can't lead into one.
+pub(crate) fn check_read_only(conn: &Connection, sql: &str) -> Result<(), String> {
+    let (keyword, rest) = split_leading_keyword(sql);
+    if keyword.eq_ignore_ascii_case("EXPLAIN") {
+        // `EXPLAIN ANALYZE` runs the statement it explains.
+        let (next, after) = split_leading_keyword(rest);
+        let explained = if next.eq_ignore_ascii_case("ANALYZE") {
+            after
+        } else {
+            rest
+        };
+        return check_read_only(conn, explained);
+    }
+    if !READ_ONLY_KEYWORDS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
+    {
+        return Err(READ_ONLY_ERROR.to_string());
+    }
+    conn.prepare(&format!("SELECT * FROM {} LIMIT 0", subquery(sql)))
+        .map(|_| ())
+        .map_err(|e| format!("{READ_ONLY_ERROR} ({e})"))
+}
+
+/// The direction of a `?order_by=` sort.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How the rows of a query are written out.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultFormat {
+    /// An Arrow IPC stream.
+    #[default]
+    Arrow,
+    /// One JSON object per row and line, keyed by column name. Nulls are
+    /// written out, structs become objects and lists arrays.
+    Json,
+}
+
+impl ResultFormat {
+    #[must_use]
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ResultFormat::Arrow => "application/vnd.apache.arrow.stream",
+            ResultFormat::Json => "application/x-ndjson",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct QueryParams {
+    /// Run the query against the library as it was at this time (see
+    /// [`crate::history`]).
+    pub as_of: Option<String>,
+    /// Sort the results by this result column, in place of any order the query
+    /// gives them.
+    pub order_by: Option<String>,
+    #[serde(default)]
+    pub dir: SortDir,
+    /// Return at most this many rows, applied after `order_by`. A statement
+    /// other than a query runs whole, so it ignores this.
+    pub limit: Option<u64>,
+    /// Skip this many rows before the ones returned, applied after `order_by`.
+    /// Together with `limit`, pages through the rows. Like `limit`, only
+    /// applies to queries.
+    pub offset: Option<u64>,
+    /// Interrupt the statement after this many seconds rather than after
+    /// [`DEFAULT_TIMEOUT`]. `0` lets it run for as long as it takes.
+    pub timeout: Option<u64>,
+    /// How to write out the rows, when not given by the `Accept` header.
+    pub format: Option<ResultFormat>,
+}
+
+impl QueryParams {
+    fn wraps_query(&self) -> bool {
+        self.order_by.is_some() || self.pages()
+    }
+
+    /// Whether only a page of the rows is asked for, so their total is counted.
+    fn pages(&self) -> bool {
+        self.limit.is_some() || self.offset.is_some()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        match self.timeout {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => Some(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+/// Why a statement was interrupted.
+#[derive(Clone, Copy)]
+enum Interruption {
+    TimedOut(Duration),
+    Cancelled,
+}
+
+impl Interruption {
+    fn message(self) -> String {
+        match self {
+            Interruption::TimedOut(timeout) => {
+                format!("query timed out after {}s", timeout.as_secs())
+            }
+            Interruption::Cancelled => "query cancelled".to_string(),
+        }
+    }
+}
+
+/// Runs `f`, interrupting whatever it runs on `conn` once `timeout` has passed
+/// or `cancelled` says the client has gone away. `f` is handed a function that
+/// tells whether that happened, to report it in place of `DuckDB`'s error.
+fn interruptible<T>(
+    conn: &Connection,
+    timeout: Option<Duration>,
+    cancelled: &(dyn Fn() -> bool + Sync),
+    f: impl FnOnce(&dyn Fn() -> Option<Interruption>) -> T,
+) -> T {
+    let handle = conn.interrupt_handle();
+    let deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+    let interruption = OnceLock::new();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    thread::scope(|s| {
+        let interruption = &interruption;
+        s.spawn(move || {
+            loop {
+                let wait = deadline.map_or(POLL_INTERVAL, |(at, _)| {
+                    at.saturating_duration_since(Instant::now())
+                        .min(POLL_INTERVAL)
+                });
+                if done_rx.recv_timeout(wait) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                let why = match deadline {
+                    Some((at, timeout)) if Instant::now() >= at => {
+                        Some(Interruption::TimedOut(timeout))
+                    }
+                    _ => cancelled().then_some(Interruption::Cancelled),
+                };
+                if let Some(why) = why {
+                    let _ = interruption.set(why);
+                    handle.interrupt();
+                    return;
+                }
+            }
+        });
+        let value = f(&|| interruption.get().copied());
+        drop(done_tx);
+        value
+    })
+}
+
+/// What a query turned out to produce, reported before any of its rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ready {
+    /// Result rows, which are being written out as an Arrow IPC stream. With
+    /// `limit` or `offset`, `total` is how many rows the whole query has.
+    Rows { total: Option<u64> },
+    /// No result columns; the statement changed this many rows.
+    RowsAffected(usize),
+}
+
+/// The names of the result columns of a row-returning query, found without
+/// fetching any of its rows.
+fn result_columns(conn: &Connection, sql: &str, bind: &[Value]) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {} LIMIT 0", subquery(sql)))
+        .map_err(|e| e.to_string())?;
+    let batches = stmt
+        .query_arrow(params_from_iter(bind))
+        .map_err(|e| e.to_string())?;
+    let schema = batches.get_schema();
+    Ok(schema.fields().iter().map(|f| f.name().clone()).collect())
+}
+
+/// Counts the rows of a row-returning query.
+fn count_rows(conn: &Connection, sql: &str, bind: &[Value]) -> Result<u64, duckdb::Error> {
+    conn.query_row(
+        &format!("SELECT count(*) FROM {}", subquery(sql)),
+        params_from_iter(bind),
+        |row| row.get(0),
+    )
+}
+
+/// Applies `order_by`, `limit` and `offset` to `sql` by wrapping it as a
+/// subquery, so the sort covers every row of the result rather than just the
+/// ones a client has fetched, and a `LIMIT` of the query's own still holds.
+/// The column is only spliced into the SQL once it's known to be one of the
+/// query's result columns.
+fn sorted_query<'a>(
+    conn: &Connection,
+    sql: &'a str,
+    bind: &[Value],
+    params: &QueryParams,
+) -> Result<Cow<'a, str>, String> {
+    if !params.wraps_query() {
+        return Ok(Cow::Borrowed(sql));
+    }
+    let mut wrapped = format!("SELECT * FROM {}", subquery(sql));
+    if let Some(column) = &params.order_by {
+        if !result_columns(conn, sql, bind)?.contains(column) {
+            return Err(format!("order_by: no result column named {column}"));
+        }
+        let dir = match params.dir {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        };
+        let _ = write!(
+            wrapped,
+            " ORDER BY \"{}\" {dir}",
+            column.replace('"', "\"\"")
+        );
+    }
+    if let Some(limit) = params.limit {
+        let _ = write!(wrapped, " LIMIT {limit}");
+    }
+    if let Some(offset) = params.offset {
+        let _ = write!(wrapped, " OFFSET {offset}");
+    }
+    Ok(Cow::Owned(wrapped))
+}
+
+/// Runs `f`, which runs something on `conn`, interrupting it after
+/// [`DEFAULT_TIMEOUT`].
+pub(crate) fn with_default_timeout<T>(
+    conn: &Connection,
+    f: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    interruptible(conn, Some(DEFAULT_TIMEOUT), &|| false, |interrupted| {
+        f().map_err(|e| interrupted().map_or(e, Interruption::message))
+    })
+}
+
+/// Writes `batches` to `out` as an Arrow IPC stream.
+fn write_ipc(
+    schema: &Schema,
+    batches: impl Iterator<Item = RecordBatch>,
+    out: impl Write,
+    interrupted: &dyn Fn() -> Option<Interruption>,
+) -> Result<(), String> {
+    // The schema goes out first, even when no batch follows, so a result without
+    // rows still tells its columns. Errors truncate the stream, which the reader
+    // detects by the missing IPC EOS marker.
+    let mut ipc_writer = StreamWriter::try_new(out, schema).map_err(|e| e.to_string())?;
+    for batch in batches {
+        ipc_writer.write(&batch).map_err(|e| e.to_string())?;
+    }
+    // An interrupted statement ends its batches early.
+    if let Some(interruption) = interrupted() {
+        return Err(interruption.message());
+    }
+    ipc_writer.finish().map_err(|e| e.to_string())
+}
+
+/// Writes `batches` to `out` as newline-delimited JSON, flushing each batch's
+/// rows as soon as they're written.
+fn write_json(
+    batches: impl Iterator<Item = RecordBatch>,
+    out: impl Write,
+    interrupted: &dyn Fn() -> Option<Interruption>,
+) -> Result<(), String> {
+    let mut json_writer = WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, LineDelimited>(out);
+    for batch in batches {
+        json_writer.write(&batch).map_err(|e| e.to_string())?;
+        json_writer.get_mut().flush().map_err(|e| e.to_string())?;
+    }
+    if let Some(interruption) = interrupted() {
+        return Err(interruption.message());
+    }
+    json_writer.finish().map_err(|e| e.to_string())
+}
+
+/// Runs a row-returning query, reporting through `ready` what it produces and
+/// then writing its rows to `out` in `format`. With `count`, the rows of
+/// `count` are counted first and reported as the total. Errs when the rows
+/// written were cut short.
+#[allow(clippy::too_many_arguments)]
+fn stream_rows(
+    conn: &Connection,
+    sql: &str,
+    bind: &[Value],
+    count: Option<&str>,
+    format: ResultFormat,
+    interrupted: &dyn Fn() -> Option<Interruption>,
+    ready: impl FnOnce(Result<Ready, String>),
+    out: impl Write,
+) -> Result<(), String> {
+    let error =
+        |e: duckdb::Error| interrupted().map_or_else(|| e.to_string(), Interruption::message);
+    let total = match count.map(|sql| count_rows(conn, sql, bind)).transpose() {
+        Ok(total) => total,
+        Err(e) => {
+            ready(Err(error(e)));
+            return Ok(());
+        }
+    };
+    let mut stmt = match conn.prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            ready(Err(error(e)));
+            return Ok(());
+        }
+    };
+
+    let batches = match stmt.query_arrow(params_from_iter(bind)) {
+        Ok(b) => b,
+        Err(e) => {
+            ready(Err(error(e)));
+            return Ok(());
+        }
+    };
+
+    let schema = batches.get_schema();
+    if schema.fields().is_empty() {
+        ready(Ok(Ready::RowsAffected(0)));
+        return Ok(());
+    }
+    ready(Ok(Ready::Rows { total }));
+
+    match format {
+        ResultFormat::Arrow => write_ipc(&schema, batches, out, interrupted),
+        ResultFormat::Json => write_json(batches, out, interrupted),
+    }
+}
+
+/// Whether a statement's result `schema` is only the count of rows it changed,
+/// as `DuckDB` answers `INSERT`, `UPDATE` and `DELETE`, or is empty.
+fn counts_changed_rows(schema: &Schema) -> bool {
+    match schema.fields().as_ref() {
+        [] => true,
+        [field] => field.name() == "Count" && field.data_type() == &DataType::Int64,
+        _ => false,
+    }
+}
+
+/// Executes a statement that isn't a query as a write. The rows it returns, if
+/// any, are reported and written out as a query's are, all of them whatever
+/// `limit` and `offset` say; otherwise the count of rows it changed is
+/// reported once the write has been checkpointed.
+fn execute(
+    state: &AppState,
+    sql: &str,
+    bind: &[Value],
+    params: &QueryParams,
+    cancelled: &(dyn Fn() -> bool + Sync),
+    ready: impl FnOnce(Result<Ready, String>),
+    out: impl Write,
+) -> Result<(), String> {
+    if params.as_of.is_some() {
+        ready(Err(
+            "as_of only applies to queries that only read".to_string()
+        ));
+        return Ok(());
+    }
+    if params.order_by.is_some() {
+        ready(Err(
+            "order_by only applies to queries that only read".to_string()
+        ));
+        return Ok(());
+    }
+    let mut ready = Some(ready);
+    let executed = state.write(|conn| {
+        interruptible(conn, params.timeout(), cancelled, |interrupted| {
+            let error = |e: duckdb::Error| {
+                interrupted().map_or_else(|| e.to_string(), Interruption::message)
+            };
+            let mut stmt = conn.prepare(sql).map_err(error)?;
+            let changed = stmt.execute(params_from_iter(bind)).map_err(error)?;
+            let schema = stmt.schema();
+            if counts_changed_rows(&schema) {
+                return Ok(changed);
+            }
+            if let Some(ready) = ready.take() {
+                ready(Ok(Ready::Rows { total: None }));
+            }
+            let batches = std::iter::from_fn(|| stmt.step().map(|rows| RecordBatch::from(&rows)));
+            match params.format.unwrap_or_default() {
+                ResultFormat::Arrow => write_ipc(&schema, batches, out, interrupted),
+                ResultFormat::Json => write_json(batches, out, interrupted),
+            }
+            .map(|()| changed)
+        })
+    });
+    match ready.take() {
+        Some(ready) => {
+            ready(executed.map(Ready::RowsAffected));
+            Ok(())
+        }
+        // The rows went out: errs when they were cut short, or the checkpoint
+        // after them failed.
+        None => executed.map(|_| ()),
+    }
+}
+
+/// A failed query, as `/query` answers it to clients that accept JSON.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct QueryError {
+    /// The message, which is all plain-text clients get.
+    pub error: String,
+    /// What went wrong: `DuckDB`'s error type in snake case (`parser`,
+    /// `binder`, `catalog`, ...), or `read_only`, `timeout`, `cancelled`,
+    /// `request` or `internal` for the server's own errors.
+    pub kind: String,
+    /// The character offset in the query's SQL that `DuckDB` points the error
+    /// at, when it does.
+    pub position: Option<usize>,
+}
+
+impl QueryError {
+    /// The error `message` that running `sql` failed with.
+    #[must_use]
+    pub fn new(message: String, sql: &str) -> Self {
+        Self {
+            kind: error_kind(&message),
+            position: error_position(&message, sql),
+            error: message,
+        }
+    }
+}
+
+fn error_kind(message: &str) -> String {
+    if message.starts_with(READ_ONLY_ERROR) {
+        return "read_only".to_string();
+    }
+    if message.starts_with("query timed out") {
+        return "timeout".to_string();
+    }
+    if message == Interruption::Cancelled.message() {
+        return "cancelled".to_string();
+    }
+    // DuckDB's messages start with their type, e.g. `Parser Error: ...`.
+    message
+        .split_once(" Error:")
+        .map(|(kind, _)| kind)
+        .filter(|kind| {
+            !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic() || c == ' ')
+        })
+        .map_or_else(
+            || "request".to_string(),
+            |kind| kind.to_ascii_lowercase().replace(' ', "_"),
+        )
+}
+
+/// Where in `sql` `DuckDB`'s `message` points, from the line it quotes and the
+/// caret under it:
+///
+/// ```text
+/// LINE 1: SELECT * FORM file
+///                  ^
+/// ```
+///
+/// The quoted line may be cut short with `...` at either end, or hold the
+/// statement the server wrapped `sql` in.
+fn error_position(message: &str, sql: &str) -> Option<usize> {
+    let mut lines = message.lines();
+    let (number, quoted, prefix) = lines.by_ref().find_map(|line| {
+        let (number, quoted) = line.strip_prefix("LINE ")?.split_once(": ")?;
+        Some((
+            number.parse::<usize>().ok()?,
+            quoted,
+            line.len() - quoted.len(),
+        ))
+    })?;
+    let caret = lines.next()?.find('^')?.checked_sub(prefix)?;
+    let (quoted, caret) = match quoted.strip_prefix("...") {
+        Some(quoted) => (quoted, caret.checked_sub(3)?),
+        None => (quoted, caret),
+    };
+    let quoted = quoted.strip_suffix("...").unwrap_or(quoted);
+
+    let index = number.checked_sub(1)?;
+    let line = sql.split('\n').nth(index)?;
+    let column = if let Some(at) = line.find(quoted) {
+        line[..at].chars().count() + caret
+    } else {
+        let at = quoted.find(line)?;
+        caret
+            .checked_sub(quoted[..at].chars().count())
+            .filter(|column| *column <= line.chars().count())?
+    };
+    let line_start: usize = sql
+        .split('\n')
+        .take(index)
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    Some(line_start + column)
+}
+
+/// Converts a JSON value to the value it binds to a `?` parameter: a string,
+/// number, boolean or null. Arrays and objects aren't supported.
+pub fn param_value(value: &serde_json::Value) -> Result<Value, String> {
+    match value {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Boolean(*b)),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Value::BigInt)
+            .or_else(|| n.as_u64().map(Value::UBigInt))
+            .or_else(|| n.as_f64().map(Value::Double))
+            .ok_or_else(|| format!("unsupported number parameter {n}")),
+        serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => Err(format!(
+            "unsupported parameter {value}: only strings, numbers, booleans and null can be bound"
+        )),
+    }
+}
+
+/// Runs `sql` against the library with `params`, blocking until it's done.
+///
+/// `ready` is called once, with what the query produces or the error that kept
+/// it from running. When that's [`Ready::Rows`], the rows are then written to
+/// `out` in the `format` of `params` (an Arrow IPC stream by default);
+/// otherwise nothing is written. A read-only `state` rejects any query that
+/// doesn't only read (see [`check_read_only`]).
+///
+/// The statement is interrupted once its timeout passes, or once `cancelled`
+/// returns true, which is checked every so often while it runs. Returns an
+/// error when the rows written to `out` were cut short, by an interruption or
+/// because `out` failed.
+pub fn run(
+    state: &AppState,
+    sql: &str,
+    params: &QueryParams,
+    cancelled: impl Fn() -> bool + Sync,
+    ready: impl FnOnce(Result<Ready, String>),
+    out: impl Write,
+) -> Result<(), String> {
+    run_with_params(state, sql, &[], params, cancelled, ready, out)
+}
+
+/// Runs `sql` as [`run`] does, binding `bind` to its `?` parameters in order.
+pub fn run_with_params(
+    state: &AppState,
+    sql: &str,
+    bind: &[Value],
+    params: &QueryParams,
+    cancelled: impl Fn() -> bool + Sync,
+    ready: impl FnOnce(Result<Ready, String>),
+    out: impl Write,
+) -> Result<(), String> {
+    tracing::debug!(sql, "Running query");
+    if state.read_only
+        && let Err(e) = state.read(|conn| check_read_only(conn, sql))
+    {
+        ready(Err(e));
+        return Ok(());
+    }
+    if !state.read(|conn| is_query(conn, sql)) {
+        return execute(state, sql, bind, params, &cancelled, ready, out);
+    }
+    state.read(|conn| {
+        // The connection stays locked until the views are dropped, so no
+        // other query ever sees them.
+        if let Some(as_of) = &params.as_of
+            && let Err(e) = history::shadow(conn, as_of)
+        {
+            ready(Err(e));
+            return Ok(());
+        }
+        let streamed = match sorted_query(conn, sql, bind, params) {
+            Ok(sorted) => interruptible(conn, params.timeout(), &cancelled, |interrupted| {
+                stream_rows(
+                    conn,
+                    &sorted,
+                    bind,
+                    params.pages().then_some(sql),
+                    params.format.unwrap_or_default(),
+                    interrupted,
+                    ready,
+                    out,
+                )
+            }),
+            Err(e) => {
+                ready(Err(e));
+                Ok(())
+            }
+        };
+        if params.as_of.is_some() {
+            history::unshadow(conn);
+        }
+        streamed
+    })
+}
@@ -9,7 +9,7 @@ use std::sync::Arc;
 
 use axum::Json;
 use axum::extract::State;
-use duckdb::Connection;
+use duckdb::{Connection, OptionalExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -42,6 +42,14 @@ struct Preset {
     modified_at: i64,
 }
 
+/// An artist alias as exchanged over the wire: tracks credited to `alias` are
+/// credited to the artist with id `artist` instead.
+#[derive(Serialize, Deserialize)]
+struct Alias {
+    alias: String,
+    artist: String,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct RpcRequest {
     method: String,
@@ -102,10 +110,12 @@ pub(crate) async fn rpc(
     }
 }
 
+/// Runs the RPC `method` with `params`, blocking until it's done. This is what
+/// `POST /rpc` answers with, and what in-process clients call directly.
 // A flat match over every RPC method; splitting it up would just scatter the
 // per-method param structs and handlers.
 #[allow(clippy::too_many_lines)]
-fn dispatch(state: &AppState, method: &str, params: Value) -> Result<Value, String> {
+pub fn dispatch(state: &AppState, method: &str, params: Value) -> Result<Value, String> {
     match method {
         "query.list" => state.read(|conn| -> Result<Value, String> {
             let queries = list_queries(conn)?;
@@ -210,6 +220,28 @@ fn dispatch(state: &AppState, method: &str, params: Value) -> Result<Value, Stri
                 Ok(Value::Null)
             })
         }
+        "alias.list" => state.read(|conn| -> Result<Value, String> {
+            let aliases = list_aliases(conn)?;
+            serde_json::to_value(aliases).map_err(|e| e.to_string())
+        }),
+        "alias.set" => {
+            let alias: Alias = from_params(params)?;
+            state.write(|conn| {
+                set_alias(conn, &alias)?;
+                Ok(Value::Null)
+            })
+        }
+        "alias.delete" => {
+            #[derive(Deserialize)]
+            struct P {
+                alias: String,
+            }
+            let p: P = from_params(params)?;
+            state.write(|conn| {
+                delete_alias(conn, &p.alias)?;
+                Ok(Value::Null)
+            })
+        }
         other => Err(format!("method not found: {other}")),
     }
 }
@@ -372,3 +404,95 @@ fn update_definition(
     .map_err(|e| e.to_string())?;
     Ok(())
 }
+
+fn list_aliases(conn: &Connection) -> Result<Vec<Alias>, String> {
+    let mut stmt = conn
+        .prepare("SELECT alias, artist::text FROM artist_alias ORDER BY alias")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Alias {
+                alias: row.get(0)?,
+                artist: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+}
+
+/// Records the alias and merges an existing artist of that name into the
+/// canonical one: its credits and albums, and any aliases pointing at it, move
+/// over.
+fn set_alias(conn: &Connection, alias: &Alias) -> Result<(), String> {
+    let canonical_name: Option<String> = conn
+        .query_row(
+            "SELECT name FROM artist WHERE id = TRY_CAST(? AS UUID)",
+            duckdb::params![alias.artist],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match canonical_name {
+        None => return Err(format!("artist not found: {}", alias.artist)),
+        Some(name) if name == alias.alias => {
+            return Err(format!("'{name}' can't be an alias of itself"));
+        }
+        Some(_) => {}
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT OR REPLACE INTO artist_alias (alias, artist) VALUES (?, TRY_CAST(? AS UUID))",
+        duckdb::params![alias.alias, alias.artist],
+    )
+    .map_err(|e| e.to_string())?;
+    let merged: Option<String> = tx
+        .query_row(
+            "SELECT id::text FROM artist WHERE name = ?",
+            duckdb::params![alias.alias],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(merged) = merged {
+        let ids = duckdb::params![merged, alias.artist];
+        tx.execute(
+            "DELETE FROM credit WHERE artist = TRY_CAST(?1 AS UUID) \
+             AND EXISTS (SELECT 1 FROM credit c WHERE c.artist = TRY_CAST(?2 AS UUID) \
+                         AND c.track = credit.track AND c.role = credit.role)",
+            ids,
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE credit SET artist = TRY_CAST(?2 AS UUID) WHERE artist = TRY_CAST(?1 AS UUID)",
+            ids,
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE artist_alias SET artist = TRY_CAST(?2 AS UUID) \
+             WHERE artist = TRY_CAST(?1 AS UUID)",
+            ids,
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE album SET artist = TRY_CAST(?2 AS UUID) WHERE artist = TRY_CAST(?1 AS UUID)",
+            ids,
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM artist WHERE id = TRY_CAST(? AS UUID)",
+            duckdb::params![merged],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn delete_alias(conn: &Connection, alias: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM artist_alias WHERE alias = ?",
+        duckdb::params![alias],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//! What becomes of tracks whose tags don't name an album.
+
+use clap::{Args, ValueEnum};
+
+/// How tracks without an album tag are grouped into albums.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingAlbumRule {
+    /// Leave the track without an album
+    #[default]
+    #[value(name = "none")]
+    Omit,
+    /// Give the track an album of its own, titled after the track
+    Single,
+    /// Group the track with the others of its folder, in an album titled after
+    /// the folder
+    Directory,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct AlbumOptions {
+    /// What to do with tracks whose tags name no album
+    #[arg(long, value_enum, default_value_t)]
+    pub missing_album: MissingAlbumRule,
+}
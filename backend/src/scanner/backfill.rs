@@ -0,0 +1,229 @@
+//! Reading the metadata of files that a scan with `--defer-metadata` only
+//! hashed and recorded, so that a large collection is usable right after a
+//! quick first scan and fills in over time.
+//!
+//! Such files are found by their NULL duration. They are read in batches, each
+//! written in its own transaction, so the database is only held while a batch
+//! is stored and tracks appear as the backfill goes.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use duckdb::Connection;
+use rayon::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::collection;
+use super::cue;
+use super::metadata::get_track_metadata;
+use super::prepare;
+use super::scan::ScanOptions;
+use super::staging;
+use super::types::{BackfilledFile, FailedFile};
+
+/// About how many files are read and stored at a time. Batches only end
+/// between album directories, so that an album's tracks are grouped together.
+const BATCH_SIZE: usize = 200;
+
+/// A database operation run by [`backfill`].
+pub type DbTask<'a> = dyn FnMut(&Connection) -> Result<(), Box<dyn Error>> + 'a;
+
+/// How far a backfill has got, updated as it goes so that it can be reported
+/// while it runs.
+#[derive(Default)]
+pub struct BackfillProgress {
+    running: AtomicBool,
+    total: AtomicUsize,
+    read: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+/// A snapshot of [`BackfillProgress`].
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackfillStatus {
+    pub running: bool,
+    /// Files without metadata when the backfill started.
+    pub total: usize,
+    /// Files whose metadata has been read and stored.
+    pub read: usize,
+    /// Files that couldn't be read, recorded in `scan_failure`.
+    pub failed: usize,
+}
+
+impl BackfillProgress {
+    #[must_use]
+    pub fn status(&self) -> BackfillStatus {
+        BackfillStatus {
+            running: self.running.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            read: self.read.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct PendingFile {
+    id: Uuid,
+    path: String,
+}
+
+/// Load the live files of `collection` whose metadata hasn't been read.
+fn load_pending(conn: &Connection, collection: Uuid) -> Result<Vec<PendingFile>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, path FROM file
+         WHERE deletion IS NULL AND duration IS NULL AND collection = ?::UUID
+         ORDER BY path",
+    )?;
+    let rows = stmt.query_map([collection.to_string()], |row| {
+        let id: String = row.get(0)?;
+        let path: String = row.get(1)?;
+        Ok((id, path))
+    })?;
+
+    let mut files = Vec::new();
+    for row in rows {
+        let (id, path) = row?;
+        if let Ok(id) = Uuid::parse_str(&id) {
+            files.push(PendingFile { id, path });
+        }
+    }
+    Ok(files)
+}
+
+/// Splits `files` into batches of about [`BATCH_SIZE`], keeping the files of
+/// each album directory in the same batch.
+fn into_batches(files: Vec<PendingFile>) -> Vec<Vec<PendingFile>> {
+    let mut by_directory: BTreeMap<PathBuf, Vec<PendingFile>> = BTreeMap::new();
+    for file in files {
+        let directory = prepare::album_directory(Path::new(&file.path)).unwrap_or_default();
+        by_directory.entry(directory).or_default().push(file);
+    }
+
+    let mut batches: Vec<Vec<PendingFile>> = Vec::new();
+    for files in by_directory.into_values() {
+        match batches.last_mut() {
+            Some(batch) if batch.len() < BATCH_SIZE => batch.extend(files),
+            _ => batches.push(files),
+        }
+    }
+    batches
+}
+
+/// Read the metadata of a batch of files in parallel.
+fn read_batch(
+    collection_path: &Path,
+    batch: &[PendingFile],
+    options: &ScanOptions,
+) -> (Vec<BackfilledFile>, Vec<FailedFile>) {
+    let results: Vec<_> = batch
+        .par_iter()
+        .map(|pending| {
+            let real_path = collection_path.join(&pending.path);
+            let result = get_track_metadata(
+                &real_path,
+                options.accurate_duration,
+                options.capture_all_tags,
+            )
+            .map(|(metadata, tags, duration, audio)| {
+                let cue_tracks = cue::cue_tracks(&real_path, &metadata);
+                (metadata, tags, duration, audio, cue_tracks)
+            });
+            (pending, result)
+        })
+        .collect();
+
+    let mut files = Vec::new();
+    let mut failed = Vec::new();
+    for (pending, result) in results {
+        match result {
+            Ok((metadata, tags, duration, audio, cue_tracks)) => files.push(BackfilledFile {
+                path: pending.path.clone(),
+                file: pending.id,
+                duration,
+                audio,
+                metadata,
+                tags,
+                cue_tracks,
+            }),
+            Err(error) => failed.push(FailedFile {
+                path: pending.path.clone(),
+                error,
+            }),
+        }
+    }
+    (files, failed)
+}
+
+/// Stage and store one batch of `collection`.
+fn store_batch(
+    conn: &Connection,
+    collection: Uuid,
+    files: &[BackfilledFile],
+    failed: &[FailedFile],
+    options: &ScanOptions,
+) -> Result<(), Box<dyn Error>> {
+    let existing_artists = staging::load_existing_artists(conn)?;
+    let mut staging_data = prepare::prepare_backfilled_data(
+        files,
+        failed,
+        &existing_artists,
+        &options.genre,
+        &options.album,
+    );
+    staging_data.collection = Some(collection);
+    staging::execute_backfill(conn, &staging_data)?;
+    Ok(())
+}
+
+/// Read the metadata of every file of the collection at `collection_path`
+/// recorded without it, normalizing it with `options` as a scan would, and
+/// record `progress` along the way.
+///
+/// Files are read without holding the database; `with_db` is called to run
+/// each database operation, so that a server can run them under its lock. A
+/// file that can't be read is recorded as a failure and tried again by the
+/// next backfill.
+pub fn backfill(
+    collection_path: &Path,
+    options: &ScanOptions,
+    progress: &BackfillProgress,
+    mut with_db: impl FnMut(&mut DbTask<'_>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut collection = Uuid::nil();
+    let mut pending = Vec::new();
+    with_db(&mut |conn| {
+        collection = collection::register(conn, collection_path, options.adopt_legacy_files)?;
+        pending = load_pending(conn, collection)?;
+        Ok(())
+    })?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    tracing::info!("Backfill: {} files without metadata", pending.len());
+    progress.total.store(pending.len(), Ordering::Relaxed);
+    progress.read.store(0, Ordering::Relaxed);
+    progress.failed.store(0, Ordering::Relaxed);
+    progress.running.store(true, Ordering::Relaxed);
+
+    let result = into_batches(pending).iter().try_for_each(|batch| {
+        let (files, failed) = read_batch(collection_path, batch, options);
+        with_db(&mut |conn| store_batch(conn, collection, &files, &failed, options))?;
+        progress.read.fetch_add(files.len(), Ordering::Relaxed);
+        progress.failed.fetch_add(failed.len(), Ordering::Relaxed);
+        Ok::<_, Box<dyn Error>>(())
+    });
+    progress.running.store(false, Ordering::Relaxed);
+    result?;
+
+    let status = progress.status();
+    tracing::info!(
+        "Backfill complete: {} read, {} failed",
+        status.read,
+        status.failed
+    );
+    Ok(())
+}
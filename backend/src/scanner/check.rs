@@ -0,0 +1,57 @@
+//! A quick consistency check between the library and the collection on disk,
+//! comparing paths only: no file is read or hashed.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use duckdb::Connection;
+
+use super::classify::{get_audio_files, normalize_path};
+use super::collection;
+use super::exclude::Exclude;
+
+/// How the live files of the library differ from the audio files on disk.
+pub struct CheckReport {
+    /// Paths of live files with nothing on disk, sorted.
+    pub missing: Vec<String>,
+    /// Paths of audio files on disk without a live file, sorted.
+    pub untracked: Vec<String>,
+}
+
+impl CheckReport {
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// Diffs the paths of the library's live files against the audio files under
+/// `collection_path`, which are listed as a scan with the default options
+/// lists them. A moved file shows up as both missing and untracked until the
+/// next scan.
+pub fn check(conn: &Connection, collection_path: &Path) -> Result<CheckReport, duckdb::Error> {
+    let recorded = collection::find(conn, collection_path)?;
+    let existing = collection::existing_files(conn, collection_path, recorded, false)?;
+    let canonical_root =
+        fs::canonicalize(collection_path).unwrap_or_else(|_| collection_path.to_path_buf());
+    let on_disk: HashSet<String> =
+        get_audio_files(collection_path, None, false, &Exclude::none(), &[])
+            .iter()
+            .map(|path| normalize_path(path, &canonical_root))
+            .collect();
+
+    let mut missing: Vec<String> = existing
+        .by_path
+        .keys()
+        .filter(|path| !on_disk.contains(*path))
+        .cloned()
+        .collect();
+    let mut untracked: Vec<String> = on_disk
+        .into_iter()
+        .filter(|path| !existing.by_path.contains_key(path))
+        .collect();
+    missing.sort();
+    untracked.sort();
+    Ok(CheckReport { missing, untracked })
+}
@@ -1,44 +1,173 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use rayon::prelude::*;
 use uuid::Uuid;
 
-use super::metadata::{extension_to_format, get_duration, get_track_metadata};
+use crate::format::Format;
+
+use super::cue;
+use super::exclude::Exclude;
+use super::fingerprint::{fingerprint, fingerprints_match};
+use super::metadata::{get_duration, get_track_metadata};
+use super::scan::ScanOptions;
+use super::scan_log::ScanLog;
+use super::symlink::{self, SymlinkRule};
 use super::types::{
-    ExistingFiles, FileClassification, ModifiedEntry, MovedEntry, NewFileData, ScanResults,
+    AudioProperties, ExistingFiles, FailedFile, FileClassification, MetadataError, ModifiedEntry,
+    MovedEntry, NewFileData, Predecessor, ScanResults, TrackMetadata,
 };
 
-static AUDIO_EXTENSIONS: &[&str] = &[
-    "mp3", "flac", "ogg", "m4a", "opus", "wma", "aac", "aiff", "aif", "alac", "ape", "wav", "wv",
-];
+/// Parses an `--extensions` value, refusing extensions without a format.
+pub fn parse_extension(ext: &str) -> Result<String, String> {
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    if Format::from_extension(&ext).is_some() {
+        return Ok(ext);
+    }
+    let known: Vec<&str> = Format::EXTENSIONS.iter().map(|(known, _)| *known).collect();
+    Err(format!(
+        "no audio format is known for '{ext}' (known: {})",
+        known.join(", ")
+    ))
+}
 
-fn is_audio_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+/// Whether `path` has one of `extensions`, or of
+/// [`Format::DEFAULT_EXTENSIONS`] when there are none. Case is ignored.
+pub(super) fn is_audio_file(path: &Path, extensions: &[String]) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    if extensions.is_empty() {
+        Format::DEFAULT_EXTENSIONS
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(ext))
+    } else {
+        extensions
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(ext))
+    }
 }
 
-pub fn get_audio_files(dir: &Path) -> Vec<PathBuf> {
+/// Finds the audio files in `dir` (those with one of `extensions`, see
+/// [`is_audio_file`]), descending at most `max_depth` directory levels below it
+/// (`Some(0)` only looks at `dir` itself; `None` has no limit). Paths `exclude`
+/// matches are skipped, directories with everything in them.
+///
+/// Symlinked directories are skipped unless `follow_symlinks`. Every directory
+/// is listed once however many ways lead to it, so symlink loops end; the real
+/// directories are listed before any symlinked ones, so a symlink to a
+/// directory of the collection finds it already listed.
+pub fn get_audio_files(
+    dir: &Path,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    exclude: &Exclude,
+    extensions: &[String],
+) -> Vec<PathBuf> {
+    let keep = |path: &Path| is_audio_file(path, extensions);
+    find_files(dir, max_depth, follow_symlinks, exclude, None, &keep).0
+}
+
+/// Finds the files in `dir` that `keep` accepts, walking it as
+/// [`get_audio_files`] does.
+///
+/// With a `since` cutoff, the files of directories last modified before it are
+/// returned apart, second, without looking at them beyond their names. Their
+/// subdirectories are walked all the same, as a change below a directory
+/// doesn't modify it.
+pub(super) fn find_files(
+    dir: &Path,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    exclude: &Exclude,
+    since: Option<SystemTime>,
+    keep: &dyn Fn(&Path) -> bool,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
     let mut files = Vec::new();
-    if let Ok(entries) = fs::read_dir(dir) {
+    let mut unchanged = Vec::new();
+    let mut visited = HashSet::new();
+    let mut symlinked = vec![(dir.to_path_buf(), max_depth)];
+    while let Some((dir, max_depth)) = symlinked.pop() {
+        let mut walk = DirWalk {
+            files: &mut files,
+            unchanged: &mut unchanged,
+            visited: &mut visited,
+            symlinked: follow_symlinks.then_some(&mut symlinked),
+            exclude,
+            since,
+            keep,
+        };
+        walk.list(&dir, max_depth);
+    }
+    (files, unchanged)
+}
+
+/// The state of a [`find_files`] walk.
+struct DirWalk<'a> {
+    files: &'a mut Vec<PathBuf>,
+    /// The files of directories unchanged since `since`.
+    unchanged: &'a mut Vec<PathBuf>,
+    /// The canonical paths of the directories listed so far.
+    visited: &'a mut HashSet<PathBuf>,
+    /// Where symlinked directories are put aside, to be listed after the real
+    /// ones; `None` when they're skipped.
+    symlinked: Option<&'a mut Vec<(PathBuf, Option<usize>)>>,
+    exclude: &'a Exclude,
+    since: Option<SystemTime>,
+    keep: &'a dyn Fn(&Path) -> bool,
+}
+
+impl DirWalk<'_> {
+    fn list(&mut self, dir: &Path, max_depth: Option<usize>) {
+        let canonical = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if !self.visited.insert(canonical) {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let unchanged = self.since.is_some_and(|since| {
+            fs::metadata(dir)
+                .and_then(|meta| meta.modified())
+                .is_ok_and(|mtime| mtime < since)
+        });
         for entry in entries.flatten() {
             let path = entry.path();
+            if self.exclude.is_excluded(&path) {
+                continue;
+            }
+            // Told apart by the directory entry alone, which saves a stat.
+            if unchanged && entry.file_type().is_ok_and(|t| t.is_file()) {
+                if (self.keep)(&path) {
+                    self.unchanged.push(path);
+                }
+                continue;
+            }
             if path.is_dir() {
-                files.extend(get_audio_files(&path));
-            } else if path.is_file() && is_audio_file(&path) {
-                files.push(path);
+                if max_depth == Some(0) {
+                    continue;
+                }
+                let max_depth = max_depth.map(|d| d - 1);
+                if !entry.file_type().is_ok_and(|t| t.is_symlink()) {
+                    self.list(&path, max_depth);
+                } else if let Some(symlinked) = self.symlinked.as_mut() {
+                    symlinked.push((path, max_depth));
+                }
+            } else if path.is_file() && (self.keep)(&path) {
+                self.files.push(path);
             }
         }
     }
-    files
 }
 
 /// Returns a normalized path string relative to `collection_root`, prefixed with `./`.
 /// Falls back to the original path string if canonicalization fails.
-fn normalize_path(path: &Path, canonical_root: &Path) -> String {
+pub(super) fn normalize_path(path: &Path, canonical_root: &Path) -> String {
     let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
     canonical.strip_prefix(canonical_root).map_or_else(
         |_| path.to_string_lossy().to_string(),
@@ -46,18 +175,44 @@ fn normalize_path(path: &Path, canonical_root: &Path) -> String {
     )
 }
 
-fn hash_file(path: &Path) -> Option<[u8; 32]> {
-    let data = fs::read(path).ok()?;
-    Some(*blake3::hash(&data).as_bytes())
+/// Hashes a file, streaming it through the hasher in chunks so that memory use
+/// stays flat however large the files hashed in parallel are.
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs::File::open(path)?)?;
+    Ok(*hasher.finalize().as_bytes())
 }
 
+/// The classification of a file at `path_str` that `error` kept from being
+/// read. One that's gone since the walk found it is missing like any other,
+/// but one that's there and can't be read, as on a drive or network mount that
+/// went away, fails, which keeps it from counting as deleted.
+fn unreadable(
+    path_str: String,
+    error: &io::Error,
+) -> Option<(FileClassification, Option<FailedFile>)> {
+    (error.kind() != io::ErrorKind::NotFound).then(|| {
+        let failed = FailedFile {
+            path: path_str,
+            error: MetadataError::Io(error.to_string()),
+        };
+        (FileClassification::Failed(failed), None)
+    })
+}
+
+/// Classifies the file at `path`, along with the error reading it when it's
+/// classified all the same. `None` means it's no longer there.
 fn classify_file(
     path: &Path,
-    existing: &ExistingFiles,
+    path_str: String,
     canonical_root: &Path,
-) -> Option<FileClassification> {
-    let path_str = normalize_path(path, canonical_root);
-    let meta = fs::metadata(path).ok()?;
+    existing: &ExistingFiles,
+    options: &ScanOptions,
+) -> Option<(FileClassification, Option<FailedFile>)> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) => return unreadable(path_str, &e),
+    };
     let size = meta.len();
     let mtime = meta
         .modified()
@@ -66,58 +221,71 @@ fn classify_file(
         .ok()?
         .as_micros() as i64;
 
-    if let Some((_, _, existing_size, existing_mtime)) = existing.by_path.get(&path_str) {
+    if let Some((id, existing_hash, existing_size, existing_mtime)) =
+        existing.by_path.get(&path_str)
+    {
         if size == *existing_size && mtime == *existing_mtime {
-            return Some(FileClassification::Skipped { path: path_str });
-        }
-
-        // mtime or size changed -- hash to determine if content actually changed
-        let hash = hash_file(path)?;
-        let (id, existing_hash, _, _) = existing.by_path.get(&path_str).unwrap();
-
-        if hash == *existing_hash {
-            // Content identical; just mtime drifted. Record as modified so we
-            // persist the new mtime (hash/size/duration will be unchanged).
-            let duration = get_duration(path);
-            return Some(FileClassification::Modified {
-                id: *id,
-                path: path_str,
-                real_path: path.to_path_buf(),
-                hash,
-                size,
-                duration,
-                mtime,
-            });
+            return Some((FileClassification::Skipped { path: path_str }, None));
         }
 
-        let duration = get_duration(path);
-        return Some(FileClassification::Modified {
+        // mtime or size changed -- hash to determine if content actually
+        // changed. Either way it's recorded as modified, to persist the new
+        // mtime; when only the mtime drifted, the hash, size and duration stay
+        // the same.
+        let hash = match hash_file(path) {
+            Ok(hash) => hash,
+            Err(e) => return unreadable(path_str, &e),
+        };
+        let (duration, audio, error) = match get_duration(path, options.accurate_duration) {
+            Ok((duration, audio)) => (duration, audio, None),
+            Err(error) => (
+                0.0,
+                AudioProperties::default(),
+                Some(FailedFile {
+                    path: path_str.clone(),
+                    error,
+                }),
+            ),
+        };
+        let fingerprint = (options.fingerprint && hash != *existing_hash)
+            .then(|| fingerprint(path))
+            .flatten();
+        let modified = FileClassification::Modified {
             id: *id,
             path: path_str,
             real_path: path.to_path_buf(),
             hash,
             size,
             duration,
+            audio,
+            fingerprint,
             mtime,
-        });
+        };
+        return Some((modified, error));
     }
 
     // Path not in DB -- hash to check for moves or treat as new
-    let hash = hash_file(path)?;
+    let hash = match hash_file(path) {
+        Ok(hash) => hash,
+        Err(e) => return unreadable(path_str, &e),
+    };
 
-    if let Some(entries) = existing.by_hash.get(&hash) {
+    if let Some(entries) = existing.by_hash.get(&hash).filter(|_| !options.no_move) {
         for (id, original_path) in entries {
-            if !Path::new(original_path).exists() {
-                return Some(FileClassification::Moved {
+            // An original that can't be checked may still be there, so its copy
+            // isn't taken for a move.
+            if matches!(canonical_root.join(original_path).try_exists(), Ok(false)) {
+                let moved = FileClassification::Moved {
                     id: *id,
                     path: path_str,
                     mtime,
-                });
+                };
+                return Some((moved, None));
             }
         }
     }
 
-    classify_as_new(path, path_str, hash, mtime)
+    classify_as_new(path, path_str, hash, mtime, options).map(|new| (new, None))
 }
 
 fn classify_as_new(
@@ -125,31 +293,71 @@ fn classify_as_new(
     path_str: String,
     hash: [u8; 32],
     mtime: i64,
+    options: &ScanOptions,
 ) -> Option<FileClassification> {
     let ext = real_path.extension()?.to_str()?;
-    let format = extension_to_format(ext)?;
+    let format = Format::from_extension(ext)?;
+    let defer_metadata = options.defer_metadata;
 
-    let (metadata, duration) = get_track_metadata(real_path)?;
+    let (metadata, tags, duration, audio) = if defer_metadata {
+        (
+            TrackMetadata::default(),
+            Vec::new(),
+            None,
+            AudioProperties::default(),
+        )
+    } else {
+        match get_track_metadata(
+            real_path,
+            options.accurate_duration,
+            options.capture_all_tags,
+        ) {
+            Ok((metadata, tags, duration, audio)) => (metadata, tags, Some(duration), audio),
+            Err(error) => {
+                return Some(FileClassification::Failed(FailedFile {
+                    path: path_str,
+                    error,
+                }));
+            }
+        }
+    };
     let size = fs::metadata(real_path).map_or(0, |m| m.len());
+    let cue_tracks = if defer_metadata {
+        Vec::new()
+    } else {
+        cue::cue_tracks(real_path, &metadata)
+    };
+    // Deferring the metadata leaves the audio undecoded, fingerprint included.
+    let fingerprint = (options.fingerprint && !defer_metadata)
+        .then(|| fingerprint(real_path))
+        .flatten();
 
-    Some(FileClassification::New(NewFileData {
+    Some(FileClassification::New(Box::new(NewFileData {
         path: path_str,
         hash,
         size,
         duration,
+        audio,
+        fingerprint,
         mtime,
-        format: format.to_string(),
+        format,
         metadata,
-    }))
+        tags,
+        cue_tracks,
+        predecessor: None,
+    })))
 }
 
-fn aggregate(classifications: Vec<FileClassification>) -> ScanResults {
+fn aggregate(classifications: Vec<(FileClassification, Option<FailedFile>)>) -> ScanResults {
     let mut skipped = Vec::new();
     let mut moved = Vec::new();
     let mut modified = Vec::new();
     let mut new_files = Vec::new();
+    let mut failed = Vec::new();
+    let mut errors = Vec::new();
 
-    for c in classifications {
+    for (c, error) in classifications {
+        errors.extend(error);
         match c {
             FileClassification::Skipped { path } => skipped.push(path),
             FileClassification::Moved { id, path, mtime } => {
@@ -162,6 +370,8 @@ fn aggregate(classifications: Vec<FileClassification>) -> ScanResults {
                 hash,
                 size,
                 duration,
+                audio,
+                fingerprint,
                 mtime,
             } => modified.push(ModifiedEntry {
                 id,
@@ -170,9 +380,12 @@ fn aggregate(classifications: Vec<FileClassification>) -> ScanResults {
                 hash,
                 size,
                 duration,
+                audio,
+                fingerprint,
                 mtime,
             }),
-            FileClassification::New(data) => new_files.push(data),
+            FileClassification::New(data) => new_files.push(*data),
+            FileClassification::Failed(file) => failed.push(file),
         }
     }
 
@@ -181,12 +394,16 @@ fn aggregate(classifications: Vec<FileClassification>) -> ScanResults {
         moved,
         modified,
         new_files,
+        failed,
+        errors,
+        aliases: Vec::new(),
     }
 }
 
 /// If a file ID appears in both moved and modified, the hash-based match (moved)
-/// wins. The path-matched entry is reclassified as new.
-pub fn resolve_conflicts(results: &mut ScanResults) {
+/// wins. The path-matched entry is reclassified as new, taking over the user
+/// data of the file previously at its path.
+pub fn resolve_conflicts(results: &mut ScanResults, options: &ScanOptions, log: &ScanLog) {
     let moved_ids: HashSet<Uuid> = results.moved.iter().map(|m| m.id).collect();
 
     let conflicting: Vec<ModifiedEntry> = results
@@ -195,17 +412,34 @@ pub fn resolve_conflicts(results: &mut ScanResults) {
         .collect();
 
     for entry in conflicting {
-        if let Some(FileClassification::New(data)) =
-            classify_as_new(&entry.real_path, entry.path, entry.hash, entry.mtime)
-        {
-            results.new_files.push(data);
+        let path = entry.path.clone();
+        let classification = classify_as_new(
+            &entry.real_path,
+            entry.path,
+            entry.hash,
+            entry.mtime,
+            options,
+        );
+        log.reclassified(&path, classification.as_ref());
+        match classification {
+            Some(FileClassification::New(mut data)) => {
+                data.predecessor = Some(Predecessor {
+                    file: entry.id,
+                    stays_live: true,
+                });
+                results.new_files.push(*data);
+            }
+            Some(FileClassification::Failed(file)) => results.failed.push(file),
+            _ => {}
         }
     }
 }
 
 /// Compare scanned filesystem paths against the DB to find deleted files.
+/// Moved files keep their rows, so they're never deleted.
 pub fn detect_deletions(results: &ScanResults, existing: &ExistingFiles) -> Vec<Uuid> {
     let mut known_paths: HashSet<&str> = HashSet::new();
+    let moved: HashSet<Uuid> = results.moved.iter().map(|m| m.id).collect();
 
     for p in &results.skipped {
         known_paths.insert(p);
@@ -219,25 +453,189 @@ pub fn detect_deletions(results: &ScanResults, existing: &ExistingFiles) -> Vec<
     for m in &results.modified {
         known_paths.insert(&m.path);
     }
+    for f in &results.failed {
+        known_paths.insert(&f.path);
+    }
 
     existing
         .by_path
         .iter()
         .filter(|(path, _)| !known_paths.contains(path.as_str()))
         .map(|(_, (id, _, _, _))| *id)
+        .filter(|id| !moved.contains(id))
         .collect()
 }
 
-/// Discover audio files and classify them in parallel against existing DB state.
-pub fn classify_all(collection_path: &Path, existing: &ExistingFiles) -> ScanResults {
+/// Links new files to the deleted files they most likely replace, so that user
+/// data carries over: a deleted file with the same content (a move that
+/// `--no-move` turned into an add), or else one whose acoustic fingerprint in
+/// `fingerprints` matches (a move of a re-tagged or re-encoded file), or else
+/// one in the same directory with the same name but another extension (a
+/// re-encode). Only unambiguous matches are linked, and each deleted file to at
+/// most one new file.
+pub fn link_predecessors(
+    results: &mut ScanResults,
+    deleted_ids: &[Uuid],
+    existing: &ExistingFiles,
+    fingerprints: &HashMap<Uuid, Vec<u32>>,
+) {
+    // Moved files keep their rows, so they're never replaced.
+    let moved: HashSet<Uuid> = results.moved.iter().map(|m| m.id).collect();
+    let deleted: HashSet<&Uuid> = deleted_ids
+        .iter()
+        .filter(|id| !moved.contains(*id))
+        .collect();
+    let mut by_hash: HashMap<&[u8; 32], Vec<Uuid>> = HashMap::new();
+    let mut by_stem: HashMap<PathBuf, Vec<Uuid>> = HashMap::new();
+    for (path, (id, hash, _, _)) in &existing.by_path {
+        if deleted.contains(id) {
+            by_hash.entry(hash).or_default().push(*id);
+            by_stem
+                .entry(Path::new(path).with_extension(""))
+                .or_default()
+                .push(*id);
+        }
+    }
+
+    let mut linked: HashSet<Uuid> = HashSet::new();
+    for new_file in &mut results.new_files {
+        if new_file.predecessor.is_some() {
+            continue;
+        }
+        let unique = |ids: &&Vec<Uuid>| ids.len() == 1;
+        let by_fingerprint = new_file.fingerprint.as_ref().map(|fingerprint| {
+            fingerprints
+                .iter()
+                .filter(|(id, other)| {
+                    deleted.contains(id) && fingerprints_match(fingerprint, other)
+                })
+                .map(|(id, _)| *id)
+                .collect::<Vec<Uuid>>()
+        });
+        let candidate = by_hash
+            .get(&new_file.hash)
+            .filter(unique)
+            .or_else(|| by_fingerprint.as_ref().filter(unique))
+            .or_else(|| {
+                by_stem
+                    .get(&Path::new(&new_file.path).with_extension(""))
+                    .filter(unique)
+            })
+            .map(|ids| ids[0]);
+        if let Some(id) = candidate
+            && linked.insert(id)
+        {
+            new_file.predecessor = Some(Predecessor {
+                file: id,
+                stays_live: false,
+            });
+        }
+    }
+}
+
+/// Discover audio files and classify them in parallel against existing DB state
+/// (see [`classify_files`]).
+///
+/// With a `since` cutoff, the library's files in directories unchanged since
+/// are skipped without being looked at (see [`find_files`]); any others there
+/// are classified as usual.
+#[tracing::instrument(skip_all)]
+pub fn classify_all(
+    collection_path: &Path,
+    existing: &ExistingFiles,
+    options: &ScanOptions,
+    since: Option<SystemTime>,
+    log: &ScanLog,
+    progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<ScanResults, globset::Error> {
+    let exclude = Exclude::new(collection_path, &options.exclude)?;
+    let keep = |path: &Path| is_audio_file(path, &options.extensions);
+    let (mut files, unchanged) = find_files(
+        collection_path,
+        options.max_depth,
+        options.follow_symlinks,
+        &exclude,
+        since,
+        &keep,
+    );
+    let mut skipped = Vec::new();
+    for file in unchanged {
+        let known = file
+            .strip_prefix(collection_path)
+            .ok()
+            .map(|rel| format!("./{}", rel.display()))
+            .filter(|path| existing.by_path.contains_key(path));
+        match known {
+            Some(path) => skipped.push(path),
+            None => files.push(file),
+        }
+    }
+    let mut results = classify_files(files, collection_path, existing, options, log, progress);
+    for path in skipped {
+        let classified = FileClassification::Skipped { path: path.clone() };
+        log.classified(&path, Some(&classified), None, existing);
+        results.skipped.push(path);
+    }
+    Ok(results)
+}
+
+/// Classify the audio `files` found in the collection in parallel against
+/// existing DB state. With `no_move`, files that would match a missing file by
+/// hash are classified as new instead of moved. With `defer_metadata`, new
+/// files are hashed but their metadata isn't read. With `fingerprint`, new
+/// files and files whose content changed get an acoustic fingerprint. Paths
+/// that are aliases of another file through a symlink aren't classified, and
+/// are only kept when `symlinks` records them. Files are classified `threads`
+/// at a time. Each file is written to `log` as soon as it's classified, and
+/// `progress` is told how many of how many files are done.
+pub(super) fn classify_files(
+    files: Vec<PathBuf>,
+    collection_path: &Path,
+    existing: &ExistingFiles,
+    options: &ScanOptions,
+    log: &ScanLog,
+    progress: &(dyn Fn(usize, usize) + Sync),
+) -> ScanResults {
     let canonical_root =
         fs::canonicalize(collection_path).unwrap_or_else(|_| collection_path.to_path_buf());
-    let audio_files = get_audio_files(collection_path);
+    let (audio_files, aliases) =
+        symlink::find_aliases(files, collection_path, &canonical_root, existing);
+    for alias in &aliases {
+        log.alias(alias, options.symlinks);
+    }
 
-    let classifications: Vec<FileClassification> = audio_files
-        .par_iter()
-        .filter_map(|path| classify_file(path, existing, &canonical_root))
-        .collect();
+    let total = audio_files.len();
+    let done = AtomicUsize::new(0);
+    let classify = || -> Vec<(FileClassification, Option<FailedFile>)> {
+        audio_files
+            .into_par_iter()
+            .filter_map(|(path, path_str)| {
+                let classification =
+                    classify_file(&path, path_str.clone(), &canonical_root, existing, options);
+                let (classified, error) = classification
+                    .as_ref()
+                    .map_or((None, None), |(c, error)| (Some(c), error.as_ref()));
+                log.classified(&path_str, classified, error, existing);
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+                classification
+            })
+            .collect()
+    };
+    // 0 threads is rayon's default: one per logical CPU.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads.map_or(0, NonZeroUsize::get))
+        .build();
+    let classifications = match pool {
+        Ok(pool) => pool.install(classify),
+        Err(e) => {
+            tracing::warn!("Scan: no thread pool ({e}), classifying on the shared one");
+            classify()
+        }
+    };
 
-    aggregate(classifications)
+    let mut results = aggregate(classifications);
+    if options.symlinks == SymlinkRule::Alias {
+        results.aliases = aliases;
+    }
+    results
 }
@@ -0,0 +1,117 @@
+//! The collections of the library: the directories scanned into it, each
+//! recorded in `collection` by its canonical path. A file's path is relative to
+//! its collection, so scans of one collection leave the files of the others
+//! alone.
+//!
+//! Files scanned before collections were recorded have none. They were all of
+//! the one collection the database was built from, which takes them over with
+//! the playlists and the last scan once it's recorded. That's the collection
+//! whose root holds the database, as it does by default; a database kept
+//! elsewhere needs `--adopt-legacy-files` to say so.
+
+use std::fs;
+use std::path::Path;
+
+use duckdb::{Connection, OptionalExt, params};
+use uuid::Uuid;
+
+use super::staging::{load_existing_files, load_legacy_files};
+use super::types::ExistingFiles;
+
+/// The path `collection_path` is recorded by.
+fn recorded_path(collection_path: &Path) -> String {
+    fs::canonicalize(collection_path)
+        .unwrap_or_else(|_| collection_path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// The id of the collection at `collection_path`, if it's recorded.
+pub(super) fn find(
+    conn: &Connection,
+    collection_path: &Path,
+) -> Result<Option<Uuid>, duckdb::Error> {
+    let id: Option<String> = conn
+        .query_row(
+            "SELECT id::TEXT FROM collection WHERE path = ?",
+            [recorded_path(collection_path)],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id.and_then(|id| Uuid::parse_str(&id).ok()))
+}
+
+/// Whether the collection at `collection_path` takes over the files scanned
+/// before collections were recorded: when its root holds the database, or
+/// whatever collection it is with `adopt`.
+fn takes_legacy_files(
+    conn: &Connection,
+    collection_path: &Path,
+    adopt: bool,
+) -> Result<bool, duckdb::Error> {
+    if adopt {
+        return Ok(true);
+    }
+    let db_path: Option<String> = conn.query_row(
+        "SELECT path FROM duckdb_databases() WHERE database_name = current_database()",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(db_path
+        .as_deref()
+        .and_then(|path| Path::new(path).parent())
+        .is_some_and(|dir| recorded_path(dir) == recorded_path(collection_path)))
+}
+
+/// The id of the collection at `collection_path`, recording it first if it's
+/// new. With `adopt_legacy`, a new collection takes over the files scanned
+/// before collections were recorded wherever the database is.
+pub(super) fn register(
+    conn: &Connection,
+    collection_path: &Path,
+    adopt_legacy: bool,
+) -> Result<Uuid, duckdb::Error> {
+    if let Some(id) = find(conn, collection_path)? {
+        return Ok(id);
+    }
+    let id = Uuid::new_v4();
+    let legacy = takes_legacy_files(conn, collection_path, adopt_legacy)?;
+    conn.execute_batch("BEGIN TRANSACTION;")?;
+    let result = (|| {
+        conn.execute(
+            "INSERT INTO collection (id, path) VALUES (?::UUID, ?)",
+            params![id.to_string(), recorded_path(collection_path)],
+        )?;
+        if legacy {
+            for table in ["file", "playlist", "meta.last_scan"] {
+                conn.execute(
+                    &format!("UPDATE {table} SET collection = ?::UUID WHERE collection IS NULL"),
+                    [id.to_string()],
+                )?;
+            }
+        }
+        conn.execute_batch("COMMIT;")
+    })();
+    if result.is_err() {
+        let _ = conn.execute_batch("ROLLBACK;");
+    }
+    result?;
+    tracing::info!("Collection {} recorded", collection_path.display());
+    Ok(id)
+}
+
+/// The live files of the collection `id` at `collection_path`, or of the one
+/// [`find`] didn't find there: none, unless it takes over the files scanned
+/// before collections were recorded (see [`register`]).
+pub(super) fn existing_files(
+    conn: &Connection,
+    collection_path: &Path,
+    id: Option<Uuid>,
+    adopt_legacy: bool,
+) -> Result<ExistingFiles, duckdb::Error> {
+    match id {
+        Some(id) => load_existing_files(conn, Some(id)),
+        None if takes_legacy_files(conn, collection_path, adopt_legacy)? => load_legacy_files(conn),
+        None => Ok(ExistingFiles::default()),
+    }
+}
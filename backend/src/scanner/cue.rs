@@ -0,0 +1,306 @@
+//! Cue sheets, which describe where the tracks of a single-file album rip start.
+//!
+//! A new audio file is looked up in the `.cue` files of its directory. When one
+//! of them lists two or more tracks for it, the file gets a track for each,
+//! spanning from its `INDEX 01` to the next track's. The sheet's `TITLE` and
+//! `PERFORMER` name the tracks and their artists, and fill in the album and
+//! album artist when the file isn't tagged with them. A directory may hold
+//! several cue sheets, e.g. one per disc: each file goes by the sheet that
+//! names it in a `FILE` entry.
+
+use std::fs;
+use std::path::Path;
+
+use super::prepare::disc_folder_number;
+use super::types::{CueTrack, TrackArtistMetadata, TrackMetadata};
+
+/// Cue sheet times are `mm:ss:ff`, with 75 frames to the second.
+const FRAMES_PER_SECOND: f64 = 75.0;
+
+#[derive(Default)]
+struct CueSheet {
+    title: Option<String>,
+    performer: Option<String>,
+    /// The year of `REM DATE`.
+    year: Option<u16>,
+    genre: Option<String>,
+    disc_number: Option<u8>,
+    disc_total: Option<u8>,
+    files: Vec<CueFile>,
+}
+
+struct CueFile {
+    name: String,
+    tracks: Vec<CueSheetTrack>,
+}
+
+struct CueSheetTrack {
+    number: u8,
+    title: Option<String>,
+    performer: Option<String>,
+    /// `INDEX 01`, where the track proper starts, in seconds.
+    start: Option<f64>,
+    /// `INDEX 00`, where its pregap starts, used when there's no `INDEX 01`.
+    pregap: Option<f64>,
+}
+
+/// The rest of a cue sheet line after its command, unquoted.
+fn argument(rest: &str) -> String {
+    let rest = rest.trim();
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// A `FILE` line's file name: everything but the trailing file type.
+fn file_name(rest: &str) -> String {
+    let rest = rest.trim();
+    if rest.starts_with('"') {
+        return argument(rest);
+    }
+    rest.rsplit_once(char::is_whitespace)
+        .map_or(rest, |(name, _)| name.trim_end())
+        .to_string()
+}
+
+/// An `mm:ss:ff` time in seconds.
+fn parse_time(time: &str) -> Option<f64> {
+    let mut parts = time.trim().split(':').map(|part| part.parse::<u32>().ok());
+    let (Some(Some(m)), Some(Some(s)), Some(Some(f)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    Some(f64::from(m) * 60.0 + f64::from(s) + f64::from(f) / FRAMES_PER_SECOND)
+}
+
+/// The leading number of `value`, e.g. `1999` of `1999-04-01`.
+fn leading_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    let value = value.trim();
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+fn parse(text: &str) -> CueSheet {
+    let mut sheet = CueSheet::default();
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let track = sheet
+            .files
+            .last_mut()
+            .and_then(|file| file.tracks.last_mut());
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => sheet.files.push(CueFile {
+                name: file_name(rest),
+                tracks: Vec::new(),
+            }),
+            "TRACK" => {
+                let Some(file) = sheet.files.last_mut() else {
+                    continue;
+                };
+                if let Some(number) = leading_number(rest) {
+                    file.tracks.push(CueSheetTrack {
+                        number,
+                        title: None,
+                        performer: None,
+                        start: None,
+                        pregap: None,
+                    });
+                }
+            }
+            "TITLE" => match track {
+                Some(track) => track.title = Some(argument(rest)),
+                None => sheet.title = Some(argument(rest)),
+            },
+            "PERFORMER" => match track {
+                Some(track) => track.performer = Some(argument(rest)),
+                None => sheet.performer = Some(argument(rest)),
+            },
+            "INDEX" => {
+                let (Some(track), Some((index, time))) =
+                    (track, rest.trim().split_once(char::is_whitespace))
+                else {
+                    continue;
+                };
+                match leading_number::<u8>(index) {
+                    Some(0) => track.pregap = parse_time(time),
+                    Some(1) => track.start = parse_time(time),
+                    _ => {}
+                }
+            }
+            "REM" => {
+                let rest = rest.trim();
+                let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                match key.to_ascii_uppercase().as_str() {
+                    "DATE" => sheet.year = leading_number(value),
+                    "GENRE" => sheet.genre = Some(argument(value)),
+                    "DISCNUMBER" => sheet.disc_number = leading_number(value),
+                    "TOTALDISCS" => sheet.disc_total = leading_number(value),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    sheet
+}
+
+/// Reads a cue sheet, which is often in a legacy encoding rather than UTF-8. A
+/// sheet that isn't UTF-8 is read as Latin-1.
+fn read_sheet(path: &Path) -> Option<CueSheet> {
+    let bytes = fs::read(path).ok()?;
+    let text = String::from_utf8(bytes)
+        .unwrap_or_else(|e| e.into_bytes().iter().map(|&b| char::from(b)).collect());
+    Some(parse(&text))
+}
+
+fn stem(name: &str) -> &str {
+    Path::new(name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name)
+}
+
+/// The entry of `sheet` for the audio file named `name`. Rips often name the
+/// file they were made from, e.g. `album.wav` for what is now `album.flac`, so
+/// names match by stem, ignoring case.
+fn find_file<'a>(sheet: &'a CueSheet, name: &str) -> Option<&'a CueFile> {
+    let matches = |file: &&CueFile| {
+        let file_name = file.name.rsplit(['/', '\\']).next().unwrap_or_default();
+        stem(file_name).eq_ignore_ascii_case(stem(name))
+    };
+    sheet.files.iter().find(matches)
+}
+
+/// The tracks the cue sheets next to `real_path` split it into, each with
+/// `metadata` (the file's own) completed from the sheet. Empty unless a sheet
+/// lists two or more tracks for the file.
+pub(super) fn cue_tracks(real_path: &Path, metadata: &TrackMetadata) -> Vec<CueTrack> {
+    let (Some(dir), Some(name)) = (
+        real_path.parent(),
+        real_path.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut sheets: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
+        })
+        .collect();
+    sheets.sort();
+
+    for sheet in sheets.iter().filter_map(|path| read_sheet(path)) {
+        if let Some(file) = find_file(&sheet, name)
+            && file.tracks.len() >= 2
+        {
+            let disc_folder = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(disc_folder_number);
+            return split(&sheet, file, metadata, disc_folder);
+        }
+    }
+    Vec::new()
+}
+
+fn split(
+    sheet: &CueSheet,
+    file: &CueFile,
+    metadata: &TrackMetadata,
+    disc_folder: Option<u8>,
+) -> Vec<CueTrack> {
+    let track_total = sheet
+        .files
+        .iter()
+        .map(|file| file.tracks.len())
+        .sum::<usize>();
+    let starts: Vec<f64> = file
+        .tracks
+        .iter()
+        .map(|track| track.start.or(track.pregap).unwrap_or(0.0))
+        .collect();
+
+    file.tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let performer = track.performer.as_ref().or(sheet.performer.as_ref());
+            let album_performer = sheet.performer.as_ref().or(performer);
+            let metadata = TrackMetadata {
+                title: track.title.clone().unwrap_or_default(),
+                track_number: Some(track.number),
+                track_total: u8::try_from(track_total).ok(),
+                disc_number: metadata.disc_number.or(sheet.disc_number).or(disc_folder),
+                disc_total: metadata.disc_total.or(sheet.disc_total),
+                genres: if metadata.genres.is_empty() {
+                    sheet.genre.iter().cloned().collect()
+                } else {
+                    metadata.genres.clone()
+                },
+                album: if metadata.album.trim().is_empty() {
+                    sheet.title.clone().unwrap_or_default()
+                } else {
+                    metadata.album.clone()
+                },
+                album_artists: if metadata.album_artists.is_empty() {
+                    album_performer.into_iter().cloned().collect()
+                } else {
+                    metadata.album_artists.clone()
+                },
+                year: metadata.year.or(sheet.year),
+                release_date: metadata.release_date,
+                // The file's BPM, key, track gain, track peak, lyrics and
+                // comment describe all of it, not any one track.
+                bpm: None,
+                musical_key: None,
+                track_gain: None,
+                album_gain: metadata.album_gain,
+                track_peak: None,
+                album_peak: metadata.album_peak,
+                lyrics: None,
+                comment: None,
+                // The file's recording and artists aren't any one track's.
+                musicbrainz_recording_id: None,
+                musicbrainz_release_id: metadata.musicbrainz_release_id,
+                musicbrainz_artist_ids: Vec::new(),
+                musicbrainz_album_artist_ids: if metadata.album_artists.is_empty() {
+                    Vec::new()
+                } else {
+                    metadata.musicbrainz_album_artist_ids.clone()
+                },
+                // The sheet's performer stands in for the file's artists, but
+                // not for its composers and others credited in a role.
+                artists: match performer {
+                    Some(performer) => std::iter::once(TrackArtistMetadata {
+                        artist: performer.clone(),
+                        role: None,
+                    })
+                    .chain(
+                        metadata
+                            .artists
+                            .iter()
+                            .filter(|ta| ta.role.is_some())
+                            .cloned(),
+                    )
+                    .collect(),
+                    None => metadata.artists.clone(),
+                },
+            };
+            CueTrack {
+                start: starts[i],
+                end: starts.get(i + 1).copied(),
+                metadata,
+            }
+        })
+        .collect()
+}
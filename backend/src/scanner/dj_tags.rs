@@ -0,0 +1,125 @@
+//! Reading the BPM and musical key DJ software writes outside the standard
+//! tags, as tags symphonia can't map to a [`StandardTagKey`](symphonia::core::meta::StandardTagKey).
+//!
+//! The key comes from the `ID3v2` `TKEY` frame or an `INITIALKEY` comment, where
+//! Serato and Mixed In Key write it. Serato also stores its analysed BPM in a
+//! `GEOB` frame described as "Serato Autotags", which is read when the file has
+//! no standard BPM tag. Other `GEOB` frames are skipped.
+
+use symphonia::core::meta::{Tag, Value};
+
+/// The key under which the BPM from a "Serato Autotags" frame is stored in
+/// `file_tag`, decoded, since binary tags aren't stored.
+const SERATO_AUTOTAGS_KEY: &str = "GEOB:Serato Autotags";
+
+/// Raw tag keys that hold the musical key as text.
+const KEY_TAG_KEYS: &[&str] = &["TKEY", "INITIALKEY", "TXXX:INITIALKEY", "KEY"];
+
+/// A DJ field read from a tag without a standard key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DjTag {
+    Bpm(f32),
+    Key(String),
+}
+
+/// Splits the NUL-terminated string at the start of `data` off the rest. Wide
+/// strings (UTF-16) end with two NUL bytes at an even offset.
+fn split_terminated(data: &[u8], wide: bool) -> Option<(&[u8], &[u8])> {
+    let end = if wide {
+        data.chunks_exact(2).position(|c| *c == [0, 0])? * 2
+    } else {
+        data.iter().position(|&b| b == 0)?
+    };
+    let terminator = if wide { 2 } else { 1 };
+    Some((&data[..end], &data[end + terminator..]))
+}
+
+/// Decodes a string of a `GEOB` frame in its text `encoding`: 0 is Latin-1, 1
+/// UTF-16 with a byte order mark, 2 UTF-16BE and 3 UTF-8.
+fn decode(bytes: &[u8], encoding: u8) -> Option<String> {
+    match encoding {
+        0 => Some(bytes.iter().map(|&b| char::from(b)).collect()),
+        1 | 2 => {
+            let (big_endian, bytes) = match bytes {
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                _ => (encoding == 2, bytes),
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| {
+                    if big_endian {
+                        u16::from_be_bytes([c[0], c[1]])
+                    } else {
+                        u16::from_le_bytes([c[0], c[1]])
+                    }
+                })
+                .collect();
+            String::from_utf16(&units).ok()
+        }
+        3 => String::from_utf8(bytes.to_vec()).ok(),
+        _ => None,
+    }
+}
+
+/// Reads the BPM out of the object of a "Serato Autotags" frame: a two-byte
+/// version followed by NUL-terminated ASCII numbers, the first of which is the
+/// BPM (the others are gain adjustments).
+fn read_serato_autotags(object: &[u8]) -> Option<f32> {
+    let [1, 1, rest @ ..] = object else {
+        return None;
+    };
+    let (bpm, _) = split_terminated(rest, false)?;
+    parse_bpm(std::str::from_utf8(bpm).ok()?)
+}
+
+/// Reads a DJ field from the body of an `ID3v2` `GEOB` (general encapsulated
+/// object) frame: a text encoding byte, then the NUL-terminated MIME type,
+/// filename and description, then the object itself. `None` for frames it
+/// doesn't recognize.
+#[must_use]
+pub fn read_geob(frame: &[u8]) -> Option<DjTag> {
+    let (&encoding, rest) = frame.split_first()?;
+    let wide = matches!(encoding, 1 | 2);
+    let (_mime_type, rest) = split_terminated(rest, false)?;
+    let (_filename, rest) = split_terminated(rest, wide)?;
+    let (description, object) = split_terminated(rest, wide)?;
+    match decode(description, encoding)?.as_str() {
+        "Serato Autotags" => read_serato_autotags(object).map(DjTag::Bpm),
+        _ => None,
+    }
+}
+
+/// Parses a BPM, which tags give as a whole or decimal number. Zero, which
+/// some software writes before analysing a track, counts as no BPM.
+pub(super) fn parse_bpm(value: &str) -> Option<f32> {
+    let bpm: f32 = value.trim().parse().ok()?;
+    (bpm.is_finite() && bpm > 0.0).then_some(bpm)
+}
+
+/// Reads a DJ field from a tag without a standard key, either as read from a
+/// file or as rebuilt from `file_tag`.
+pub(super) fn read(tag: &Tag) -> Option<DjTag> {
+    if tag.std_key.is_some() {
+        return None;
+    }
+    let key = tag.key.as_str();
+    match &tag.value {
+        Value::Binary(frame) if key.eq_ignore_ascii_case("GEOB") => read_geob(frame),
+        Value::String(value) if key == SERATO_AUTOTAGS_KEY => parse_bpm(value).map(DjTag::Bpm),
+        Value::String(value) if KEY_TAG_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)) => {
+            let value = value.trim();
+            (!value.is_empty()).then(|| DjTag::Key(value.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// The key and text value to store a DJ tag under in `file_tag`, so that
+/// re-deriving tracks finds it again.
+pub(super) fn stored(tag: &Tag) -> Option<(String, String)> {
+    match read(tag)? {
+        DjTag::Bpm(bpm) => Some((SERATO_AUTOTAGS_KEY.to_string(), bpm.to_string())),
+        DjTag::Key(key) => Some((tag.key.clone(), key)),
+    }
+}
@@ -0,0 +1,125 @@
+//! Files of the library with identical content at more than one path. Unlike a
+//! move, where the file at the old path is gone, every copy is still there.
+//!
+//! Files with acoustic fingerprints (see `--fingerprint`) are also copies of
+//! each other when their fingerprints match, even though tags or an encoding
+//! make their content differ.
+
+use std::collections::HashMap;
+
+use duckdb::Connection;
+
+use super::fingerprint::{fingerprints_match, from_blob};
+use super::staging::load_existing_files;
+
+/// How far apart, in seconds, the durations of files whose fingerprints are
+/// compared may be.
+const MAX_DURATION_DIFFERENCE: f64 = 2.0;
+
+/// The content hashes of two files.
+type HashPair = ([u8; 32], [u8; 32]);
+
+/// Live files sharing one content hash, or matching acoustically.
+pub struct DuplicateGroup {
+    /// The blake3 hash of the content, in hex. `None` when the copies only
+    /// match by their fingerprints, their content differing.
+    pub hash: Option<String>,
+    /// The paths of the copies, sorted.
+    pub paths: Vec<String>,
+}
+
+/// Groups the library's live files by content hash, keeping the groups of two
+/// or more files, ordered by their first path. The groups of hashes whose files
+/// match acoustically are merged into one.
+pub fn find_duplicates(conn: &Connection) -> Result<Vec<DuplicateGroup>, duckdb::Error> {
+    let existing = load_existing_files(conn, None)?;
+    let by_hash: Vec<([u8; 32], Vec<String>)> = existing
+        .by_hash
+        .into_iter()
+        .map(|(hash, files)| (hash, files.into_iter().map(|(_, path)| path).collect()))
+        .collect();
+    let index: HashMap<[u8; 32], usize> = by_hash
+        .iter()
+        .enumerate()
+        .map(|(i, (hash, _))| (*hash, i))
+        .collect();
+
+    // Each hash starts out in a set of its own; acoustic matches join sets.
+    let mut parents: Vec<usize> = (0..by_hash.len()).collect();
+    for (a, b) in acoustic_matches(conn)? {
+        if let (Some(&a), Some(&b)) = (index.get(&a), index.get(&b)) {
+            let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[a] = b;
+        }
+    }
+    let mut sets: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..by_hash.len() {
+        sets.entry(root(&mut parents, i)).or_default().push(i);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = sets
+        .into_values()
+        .filter_map(|set| {
+            let mut paths: Vec<String> = set
+                .iter()
+                .flat_map(|&i| by_hash[i].1.iter().cloned())
+                .collect();
+            if paths.len() < 2 {
+                return None;
+            }
+            paths.sort();
+            let hash = match set[..] {
+                [i] => Some(blake3::Hash::from_bytes(by_hash[i].0).to_hex().to_string()),
+                _ => None,
+            };
+            Some(DuplicateGroup { hash, paths })
+        })
+        .collect();
+    groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+    Ok(groups)
+}
+
+/// The set `i` is in, as the index of its first member.
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// The pairs of content hashes of live files whose fingerprints match. Only
+/// files of about the same duration are compared.
+fn acoustic_matches(conn: &Connection) -> Result<Vec<HashPair>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT hash, duration, fingerprint FROM file
+         WHERE deletion IS NULL AND fingerprint IS NOT NULL AND duration IS NOT NULL
+         ORDER BY duration",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let hash: Vec<u8> = row.get(0)?;
+        let duration: f32 = row.get(1)?;
+        let fingerprint: Vec<u8> = row.get(2)?;
+        Ok((hash, f64::from(duration), fingerprint))
+    })?;
+    let mut files = Vec::new();
+    for row in rows {
+        let (hash, duration, fingerprint) = row?;
+        if let Ok(hash) = <[u8; 32]>::try_from(hash) {
+            files.push((hash, duration, from_blob(&fingerprint)));
+        }
+    }
+
+    let mut matches = Vec::new();
+    for (i, (hash, duration, fingerprint)) in files.iter().enumerate() {
+        for (other_hash, _, other_fingerprint) in files[i + 1..]
+            .iter()
+            .take_while(|(_, other, _)| other - duration <= MAX_DURATION_DIFFERENCE)
+        {
+            if hash != other_hash && fingerprints_match(fingerprint, other_fingerprint) {
+                matches.push((*hash, *other_hash));
+            }
+        }
+    }
+    Ok(matches)
+}
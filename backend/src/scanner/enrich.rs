@@ -0,0 +1,338 @@
+//! `scan --enrich`: titles, artists and albums for new files whose tags give no
+//! title or artist, looked up on [AcoustID](https://acoustid.org) by their
+//! acoustic fingerprint.
+//!
+//! Lookups need an `AcoustID` API key in [`API_KEY_VAR`]. Their answers,
+//! matches or not, are cached in `acoustid_lookup` by fingerprint, so the same
+//! audio is only looked up once. Without a key, or once `AcoustID` can't be
+//! reached, the files not looked up keep what their tags and paths give.
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use duckdb::{Connection, OptionalExt, params};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::fingerprint;
+use super::types::{NewFileData, TrackArtistMetadata, TrackMetadata};
+
+/// The environment variable holding the `AcoustID` API key.
+pub const API_KEY_VAR: &str = "ACOUSTID_API_KEY";
+
+const LOOKUP_URL: &str = "https://api.acoustid.org/v2/lookup";
+
+/// The least time between two lookups: `AcoustID` takes three a second.
+const MIN_INTERVAL: Duration = Duration::from_millis(334);
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The lowest score of a match that's taken, from 0 to 1.
+const MIN_SCORE: f64 = 0.8;
+
+/// `AcoustID`'s error code for an invalid API key.
+const INVALID_API_KEY: u32 = 4;
+
+/// A file to look up.
+struct Lookup {
+    /// Its index in the new files.
+    file: usize,
+    fingerprint_hash: String,
+    /// The fingerprint encoded for `AcoustID`.
+    fingerprint: String,
+    /// The duration in whole seconds.
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    status: String,
+    #[serde(default)]
+    results: Vec<Match>,
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    code: u32,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Match {
+    score: f64,
+    #[serde(default)]
+    recordings: Vec<Recording>,
+}
+
+/// A `MusicBrainz` recording as `AcoustID` gives it.
+#[derive(Deserialize, Serialize)]
+struct Recording {
+    id: Uuid,
+    title: Option<String>,
+    #[serde(default)]
+    artists: Vec<Artist>,
+    #[serde(default)]
+    releasegroups: Vec<ReleaseGroup>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Artist {
+    id: Option<Uuid>,
+    name: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ReleaseGroup {
+    title: Option<String>,
+}
+
+/// Why a lookup failed.
+enum LookupError {
+    /// `AcoustID` couldn't be reached, so the lookups left are given up.
+    Offline(String),
+    /// `AcoustID` refused the API key, which fails the lookups left too.
+    InvalidApiKey(String),
+    Other(String),
+}
+
+/// Fills in the metadata of the `new_files` whose tags give no title or artist
+/// from the recordings `AcoustID` matches them with. Those without a
+/// fingerprint are fingerprinted first, which they keep.
+pub(super) fn enrich(
+    collection_path: &Path,
+    conn: &Connection,
+    new_files: &mut [NewFileData],
+) -> Result<(), duckdb::Error> {
+    new_files
+        .par_iter_mut()
+        .filter(|file| lacks_tags(file) && file.fingerprint.is_none())
+        .for_each(|file| {
+            file.fingerprint = fingerprint::fingerprint(&collection_path.join(&file.path));
+        });
+
+    let mut cached =
+        conn.prepare("SELECT recording::VARCHAR FROM acoustid_lookup WHERE fingerprint_hash = ?")?;
+    let mut lookups: Vec<Lookup> = Vec::new();
+    let mut hits = 0;
+    for (i, file) in new_files.iter_mut().enumerate() {
+        let (Some(fingerprint), Some(duration)) = (&file.fingerprint, file.duration) else {
+            continue;
+        };
+        if !lacks_tags(file) {
+            continue;
+        }
+        let fingerprint_hash = blake3::hash(&fingerprint::to_blob(fingerprint))
+            .to_hex()
+            .to_string();
+        let recording: Option<Option<String>> = cached
+            .query_row([&fingerprint_hash], |row| row.get(0))
+            .optional()?;
+        if let Some(recording) = recording {
+            let recording = recording.and_then(|json| serde_json::from_str(&json).ok());
+            apply(&mut file.metadata, recording.as_ref());
+            hits += 1;
+            continue;
+        }
+        lookups.push(Lookup {
+            file: i,
+            fingerprint: fingerprint::encode(fingerprint),
+            fingerprint_hash,
+            duration: duration.round() as u32,
+        });
+    }
+    if hits > 0 {
+        tracing::info!("Enrich: {hits} files from cached lookups");
+    }
+    if lookups.is_empty() {
+        return Ok(());
+    }
+    let Some(api_key) = std::env::var(API_KEY_VAR)
+        .ok()
+        .filter(|key| !key.is_empty())
+    else {
+        tracing::warn!(
+            "Enrich: {} files not looked up, as {API_KEY_VAR} isn't set",
+            lookups.len()
+        );
+        return Ok(());
+    };
+
+    // The blocking client can't run on the async runtime that scans may be run
+    // from, so it gets a thread of its own.
+    let files: &[NewFileData] = new_files;
+    let answered = thread::scope(|scope| {
+        scope
+            .spawn(|| look_up_all(&api_key, &lookups, files))
+            .join()
+            .unwrap_or_default()
+    });
+    tracing::info!(
+        "Enrich: looked up {} of {} files",
+        answered.len(),
+        lookups.len()
+    );
+    for (lookup, recording) in answered {
+        let lookup = &lookups[lookup];
+        let json = recording
+            .as_ref()
+            .and_then(|recording| serde_json::to_string(recording).ok());
+        conn.execute(
+            "INSERT OR REPLACE INTO acoustid_lookup (fingerprint_hash, recording, looked_up)
+             VALUES (?, ?::JSON, now())",
+            params![lookup.fingerprint_hash, json],
+        )?;
+        apply(&mut new_files[lookup.file].metadata, recording.as_ref());
+    }
+    Ok(())
+}
+
+/// Whether `file` is a single track, with its metadata read, whose tags give no
+/// title or no artist.
+fn lacks_tags(file: &NewFileData) -> bool {
+    file.duration.is_some()
+        && file.cue_tracks.is_empty()
+        && (file.metadata.title.trim().is_empty() || file.metadata.artists.is_empty())
+}
+
+/// Looks up `lookups` one after the other, as fast as `AcoustID` allows.
+/// Returns the index and best match of each lookup answered.
+fn look_up_all(
+    api_key: &str,
+    lookups: &[Lookup],
+    new_files: &[NewFileData],
+) -> Vec<(usize, Option<Recording>)> {
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!("Enrich: no HTTP client ({e}), nothing looked up");
+            return Vec::new();
+        }
+    };
+    let mut answered = Vec::new();
+    let mut last_request: Option<Instant> = None;
+    for (i, lookup) in lookups.iter().enumerate() {
+        if let Some(at) = last_request {
+            thread::sleep(MIN_INTERVAL.saturating_sub(at.elapsed()));
+        }
+        last_request = Some(Instant::now());
+        match look_up(&client, api_key, lookup) {
+            Ok(recording) => answered.push((i, recording)),
+            Err(LookupError::Offline(e)) => {
+                tracing::warn!(
+                    "Enrich: AcoustID can't be reached ({e}), {} files left as tagged",
+                    lookups.len() - i
+                );
+                break;
+            }
+            Err(LookupError::InvalidApiKey(e)) => {
+                tracing::warn!("Enrich: AcoustID refused the key in {API_KEY_VAR}: {e}");
+                break;
+            }
+            Err(LookupError::Other(e)) => {
+                let path = &new_files[lookup.file].path;
+                tracing::warn!("Enrich: lookup of {path} failed: {e}");
+            }
+        }
+    }
+    answered
+}
+
+/// The recording `AcoustID` matches `lookup` with best, if any does well
+/// enough.
+fn look_up(
+    client: &reqwest::blocking::Client,
+    api_key: &str,
+    lookup: &Lookup,
+) -> Result<Option<Recording>, LookupError> {
+    let duration = lookup.duration.to_string();
+    let response = client
+        .post(LOOKUP_URL)
+        .form(&[
+            ("client", api_key),
+            ("duration", &duration),
+            ("fingerprint", &lookup.fingerprint),
+            ("meta", "recordings releasegroups"),
+        ])
+        .send()
+        .map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                LookupError::Offline(e.to_string())
+            } else {
+                LookupError::Other(e.to_string())
+            }
+        })?;
+    let status = response.status();
+    let body = response
+        .text()
+        .map_err(|e| LookupError::Other(e.to_string()))?;
+    let response: Response =
+        serde_json::from_str(&body).map_err(|e| LookupError::Other(format!("{status}: {e}")))?;
+    if response.status != "ok" {
+        return Err(match response.error {
+            Some(error) if error.code == INVALID_API_KEY => {
+                LookupError::InvalidApiKey(error.message)
+            }
+            Some(error) => LookupError::Other(error.message),
+            None => LookupError::Other(status.to_string()),
+        });
+    }
+    Ok(best_recording(response.results))
+}
+
+/// The first recording with a title of the best-scoring match, if it scores at
+/// least [`MIN_SCORE`].
+fn best_recording(results: Vec<Match>) -> Option<Recording> {
+    let best = results
+        .into_iter()
+        .filter(|result| result.score >= MIN_SCORE)
+        .max_by(|a, b| a.score.total_cmp(&b.score))?;
+    best.recordings
+        .into_iter()
+        .find(|recording| recording.title.is_some())
+}
+
+/// Fills in what `metadata` lacks from `recording`: the title, the artists
+/// (with their `MusicBrainz` ids, when each has one), the album from its first
+/// release group, and the recording id.
+fn apply(metadata: &mut TrackMetadata, recording: Option<&Recording>) {
+    let Some(recording) = recording else {
+        return;
+    };
+    if metadata.title.trim().is_empty()
+        && let Some(title) = &recording.title
+    {
+        metadata.title.clone_from(title);
+    }
+    if metadata.artists.is_empty() && !recording.artists.is_empty() {
+        metadata.artists = recording
+            .artists
+            .iter()
+            .map(|artist| TrackArtistMetadata {
+                artist: artist.name.clone(),
+                role: None,
+            })
+            .collect();
+        metadata.musicbrainz_artist_ids = recording
+            .artists
+            .iter()
+            .map(|artist| artist.id)
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+    }
+    let album = recording
+        .releasegroups
+        .iter()
+        .find_map(|group| group.title.as_ref());
+    if let Some(album) = album.filter(|_| metadata.album.trim().is_empty()) {
+        metadata.album.clone_from(album);
+    }
+    metadata
+        .musicbrainz_recording_id
+        .get_or_insert(recording.id);
+}
@@ -0,0 +1,53 @@
+//! `--exclude` patterns: globs of paths, relative to the collection root, that
+//! scans skip. A pattern matching a directory skips everything below it.
+
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Parses an `--exclude` pattern, so that a malformed one is refused with the
+/// other arguments.
+pub fn parse_glob(pattern: &str) -> Result<Glob, String> {
+    Glob::new(pattern).map_err(|e| e.to_string())
+}
+
+/// The compiled `--exclude` patterns of a scan of one collection.
+pub(super) struct Exclude {
+    root: PathBuf,
+    set: GlobSet,
+}
+
+impl Exclude {
+    pub(super) fn new(collection_path: &Path, globs: &[Glob]) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            builder.add(glob.clone());
+        }
+        Ok(Self {
+            root: collection_path.to_path_buf(),
+            set: builder.build()?,
+        })
+    }
+
+    /// Excludes nothing, as for a scan without `--exclude`.
+    pub(super) fn none() -> Self {
+        Self {
+            root: PathBuf::new(),
+            set: GlobSet::empty(),
+        }
+    }
+
+    /// Whether `path`, a path of the collection, or any directory it's in
+    /// matches a pattern. Paths outside the collection never do.
+    pub(super) fn is_excluded(&self, path: &Path) -> bool {
+        if self.set.is_empty() {
+            return false;
+        }
+        let Ok(rel) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        rel.ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| self.set.is_match(ancestor))
+    }
+}
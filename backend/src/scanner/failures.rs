@@ -0,0 +1,25 @@
+use duckdb::Connection;
+use serde::Serialize;
+
+/// A file the last scan couldn't read metadata from.
+#[derive(Serialize)]
+pub struct ScanFailure {
+    pub path: String,
+    /// One of `io`, `unsupported`, `malformed` or `panic`.
+    pub category: String,
+    pub message: String,
+}
+
+/// The failures recorded by the last scan, grouped by category.
+pub fn load_failures(conn: &Connection) -> Result<Vec<ScanFailure>, duckdb::Error> {
+    let mut stmt =
+        conn.prepare("SELECT path, category, message FROM scan_failure ORDER BY category, path")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ScanFailure {
+            path: row.get(0)?,
+            category: row.get(1)?,
+            message: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
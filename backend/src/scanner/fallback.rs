@@ -0,0 +1,59 @@
+//! Titles for tracks and albums whose tags don't give one, taken from their
+//! paths.
+
+use std::path::Path;
+
+use super::prepare::album_directory;
+
+/// The longest leading number taken for a track number, so that a title
+/// starting with a year, such as `1999`, keeps it.
+const MAX_TRACK_NUMBER_DIGITS: usize = 3;
+
+/// The title of a track without a title tag: its file name without the
+/// extension, and without a leading track number such as the `01. ` of
+/// `01. Duck.flac` or the `1-02 ` of `1-02 Duck.flac`.
+#[must_use]
+pub fn title_from_path(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    strip_track_number(&stem).to_string()
+}
+
+/// The title of an album for a track without an album tag: the name of its
+/// album directory (see [`album_directory`]). `None` for a track at the root
+/// of the collection.
+#[must_use]
+pub fn album_from_path(path: &Path) -> Option<String> {
+    let directory = album_directory(path)?;
+    Some(directory.file_name()?.to_string_lossy().into_owned())
+}
+
+/// `name` without the track number it starts with, along with the separator
+/// after it. A name that is nothing but a number is kept whole.
+fn strip_track_number(name: &str) -> &str {
+    let Some(rest) = strip_number(name) else {
+        return name;
+    };
+    // The track number of a disc and track pair, such as `1-02`.
+    let rest = rest
+        .strip_prefix('-')
+        .and_then(strip_number)
+        .unwrap_or(rest);
+    let title = rest.trim_start_matches([' ', '.', '-', '_']);
+    if title.len() == rest.len() || title.is_empty() {
+        return name;
+    }
+    title
+}
+
+/// `text` after the track number it starts with, if it does.
+fn strip_number(text: &str) -> Option<&str> {
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    (1..=MAX_TRACK_NUMBER_DIGITS)
+        .contains(&digits)
+        .then(|| &text[digits..])
+}
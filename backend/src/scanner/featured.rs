@@ -0,0 +1,99 @@
+//! Splitting the featured artists out of track titles and artist tags, e.g.
+//! `Guest` out of `Song (feat. Guest)` or `Main feat. Guest`.
+//!
+//! Only an explicit marker such as `feat.` introduces featured artists. `&` and
+//! `,` separate several of them after a marker, but never split a name on
+//! their own: "Earth, Wind & Fire" is one artist.
+
+/// What tells featured artists apart from the rest of a title or artist tag.
+pub struct FeaturedSeparators<'a> {
+    /// The words introducing featured artists, matched ignoring case and only
+    /// as whole words.
+    pub markers: &'a [&'a str],
+    /// What separates several featured artists after a marker.
+    pub lists: &'a [&'a str],
+}
+
+impl Default for FeaturedSeparators<'static> {
+    fn default() -> Self {
+        Self {
+            markers: &["feat.", "ft.", "featuring"],
+            lists: &[",", "&"],
+        }
+    }
+}
+
+/// Where the first marker of `text` starts and ends.
+fn find_marker(text: &str, markers: &[&str]) -> Option<(usize, usize)> {
+    let mut previous = None;
+    for (start, c) in text.char_indices() {
+        let word_start = previous.is_none_or(|p: char| p.is_whitespace() || p == '(' || p == '[');
+        previous = Some(c);
+        if !word_start {
+            continue;
+        }
+        for marker in markers {
+            let end = start + marker.len();
+            if text
+                .get(start..end)
+                .is_some_and(|word| word.eq_ignore_ascii_case(marker))
+                && text[end..].starts_with(char::is_whitespace)
+            {
+                return Some((start, end));
+            }
+        }
+    }
+    None
+}
+
+/// Splits the featured artists out of `text`, returning `text` without them
+/// and the featured artists in order. A marker in brackets takes the bracketed
+/// part out, e.g. `Song (feat. Guest) [Live]` leaves `Song [Live]`; otherwise
+/// everything from the marker on is taken out. `text` is left whole when
+/// nothing would be left of it, e.g. for an artist named `Featuring Five`.
+#[must_use]
+pub fn split_featured(text: &str, separators: &FeaturedSeparators) -> (String, Vec<String>) {
+    let unchanged = || (text.to_string(), Vec::new());
+    let Some((start, end)) = find_marker(text, separators.markers) else {
+        return unchanged();
+    };
+    let close = match text[..start].chars().next_back() {
+        Some('(') => Some(')'),
+        Some('[') => Some(']'),
+        _ => None,
+    };
+    let (rest, featured) = match close {
+        Some(close) => {
+            let Some(len) = text[end..].find(close) else {
+                return unchanged();
+            };
+            let before = text[..start - 1].trim_end();
+            let after = text[end + len + close.len_utf8()..].trim_start();
+            let rest = if after.is_empty() {
+                before.to_string()
+            } else {
+                format!("{before} {after}")
+            };
+            (rest, &text[end..end + len])
+        }
+        None => (text[..start].trim_end().to_string(), &text[end..]),
+    };
+
+    let mut names = vec![featured];
+    for list in separators.lists {
+        names = names
+            .into_iter()
+            .flat_map(|name| name.split(*list))
+            .collect();
+    }
+    let names: Vec<String> = names
+        .into_iter()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    if rest.is_empty() || names.is_empty() {
+        return unchanged();
+    }
+    (rest, names)
+}
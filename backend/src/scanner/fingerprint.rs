@@ -0,0 +1,200 @@
+//! Acoustic fingerprints, which tell that files hold the same recording even
+//! when their bytes differ: a copy with other tags, or the same audio encoded
+//! again. They're Chromaprint's, computed over the first
+//! [`FINGERPRINT_SECONDS`] of a file's audio.
+//!
+//! Two fingerprints match when, lined up at the best of a few small offsets
+//! (encoders pad the start differently), few enough of their bits differ.
+
+use std::path::Path;
+
+use rusty_chromaprint::{Configuration, Fingerprinter};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+
+use super::metadata::probe_file;
+
+/// How much audio a fingerprint covers, from the start of the file.
+const FINGERPRINT_SECONDS: u64 = 120;
+
+/// How many items (about 0.12s each) one fingerprint is shifted against the
+/// other at most to line them up.
+const MAX_OFFSET: usize = 8;
+
+/// The share of bits that may differ between matching fingerprints. Unrelated
+/// audio differs in about half of them.
+const MAX_BIT_ERROR_RATE: f64 = 0.15;
+
+/// How much of the longer fingerprint the lined up stretch must cover, so that
+/// a file doesn't match a shorter one that only shares its start.
+const MIN_OVERLAP: f64 = 0.9;
+
+/// The acoustic fingerprint of the file's default track, or `None` when it
+/// can't be decoded or is too short to fingerprint.
+pub(super) fn fingerprint(path: &Path) -> Option<Vec<u32>> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| compute(path)));
+    result.unwrap_or_else(|_| {
+        tracing::warn!(
+            "Panic while fingerprinting {}, skipping fingerprint",
+            path.display()
+        );
+        None
+    })
+}
+
+fn compute(path: &Path) -> Option<Vec<u32>> {
+    let (probed, _, _) = probe_file(path).ok()?;
+    let mut format = probed.format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+    let sample_rate = codec_params.sample_rate?;
+    let channels = codec_params.channels?.count();
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&codec_params, &DecoderOptions::default())
+        .ok()?;
+    let mut printer = Fingerprinter::new(&Configuration::preset_test2());
+    printer
+        .start(sample_rate, u32::try_from(channels).ok()?)
+        .ok()?;
+
+    let mut remaining = u64::from(sample_rate) * FINGERPRINT_SECONDS;
+    let mut sample_buf: Option<SampleBuffer<i16>> = None;
+    while remaining > 0 {
+        let Ok(packet) = format.next_packet() else {
+            break;
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let Ok(audio) = decoder.decode(&packet) else {
+            continue;
+        };
+
+        let spec = *audio.spec();
+        let frames = audio.frames() as u64;
+        if sample_buf
+            .as_ref()
+            .is_none_or(|b| b.capacity() < audio.capacity() * spec.channels.count())
+        {
+            sample_buf = Some(SampleBuffer::new(audio.capacity() as u64, spec));
+        }
+        let buf = sample_buf.as_mut().unwrap();
+        buf.copy_interleaved_ref(audio);
+
+        let taken = frames.min(remaining);
+        remaining -= taken;
+        let samples = buf.samples();
+        printer.consume(&samples[..(taken as usize * channels).min(samples.len())]);
+    }
+    printer.finish();
+
+    let fingerprint = printer.fingerprint();
+    (!fingerprint.is_empty()).then(|| fingerprint.to_vec())
+}
+
+/// Whether `a` and `b` are fingerprints of the same audio.
+pub(super) fn fingerprints_match(a: &[u32], b: &[u32]) -> bool {
+    let longer = a.len().max(b.len());
+    let lined_up = |a: &[u32], b: &[u32]| {
+        let overlap = a.len().min(b.len());
+        if overlap == 0 || (overlap as f64) < longer as f64 * MIN_OVERLAP {
+            return false;
+        }
+        let errors: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+        f64::from(errors) / (overlap as f64 * 32.0) <= MAX_BIT_ERROR_RATE
+    };
+    (0..=MAX_OFFSET).any(|offset| {
+        a.get(offset..).is_some_and(|a| lined_up(a, b))
+            || b.get(offset..).is_some_and(|b| lined_up(a, b))
+    })
+}
+
+/// The fingerprint as stored in `file.fingerprint`: its items in little-endian
+/// order.
+pub(super) fn to_blob(fingerprint: &[u32]) -> Vec<u8> {
+    fingerprint
+        .iter()
+        .flat_map(|item| item.to_le_bytes())
+        .collect()
+}
+
+/// The fingerprint stored as `blob` (see [`to_blob`]).
+pub(super) fn from_blob(blob: &[u8]) -> Vec<u32> {
+    blob.chunks_exact(4)
+        .map(|item| u32::from_le_bytes([item[0], item[1], item[2], item[3]]))
+        .collect()
+}
+
+/// Chromaprint's number for the algorithm of [`Configuration::preset_test2`].
+const ALGORITHM: u8 = 1;
+
+/// The highest gap between set bits written to the first, 3-bit stream of an
+/// encoded fingerprint; larger ones go on in the 5-bit stream.
+const MAX_NORMAL_GAP: u32 = 7;
+
+/// The fingerprint compressed and encoded as Chromaprint's
+/// `chromaprint_encode_fingerprint` does, which is how `AcoustID` takes them:
+/// the gaps between the set bits of each item's XOR with the one before, packed
+/// in 3 and 5 bits, after the algorithm and the item count, in URL-safe base64.
+pub(super) fn encode(fingerprint: &[u32]) -> String {
+    let mut normal = Vec::new();
+    let mut exceptional = Vec::new();
+    let mut previous = 0;
+    for &item in fingerprint {
+        let mut changed = item ^ previous;
+        previous = item;
+        let (mut bit, mut last_bit) = (1, 0);
+        while changed != 0 {
+            if changed & 1 != 0 {
+                let gap = bit - last_bit;
+                normal.push(gap.min(MAX_NORMAL_GAP));
+                if gap >= MAX_NORMAL_GAP {
+                    exceptional.push(gap - MAX_NORMAL_GAP);
+                }
+                last_bit = bit;
+            }
+            changed >>= 1;
+            bit += 1;
+        }
+        normal.push(0);
+    }
+    let len = fingerprint.len() as u32;
+    let mut bytes = vec![ALGORITHM, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    pack(&mut bytes, &normal, 3);
+    pack(&mut bytes, &exceptional, 5);
+    base64(&bytes)
+}
+
+/// Appends `values` to `out`, `bits` bits each, least significant bits first.
+fn pack(out: &mut Vec<u8>, values: &[u32], bits: u32) {
+    let (mut pending, mut pending_bits) = (0, 0);
+    for &value in values {
+        pending |= value << pending_bits;
+        pending_bits += bits;
+        while pending_bits >= 8 {
+            out.push(pending as u8);
+            pending >>= 8;
+            pending_bits -= 8;
+        }
+    }
+    if pending_bits > 0 {
+        out.push(pending as u8);
+    }
+}
+
+/// `bytes` in URL-safe base64, without padding.
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            out.push(char::from(DIGITS[(group >> (18 - 6 * i)) as usize & 63]));
+        }
+    }
+    out
+}
@@ -0,0 +1,123 @@
+//! Reading loudness gain from `ReplayGain` tags and from the R128 tags of Opus
+//! files, into one scale: the dB adjustment that brings a track to
+//! `ReplayGain`'s reference level. The `ReplayGain` peaks that come with the
+//! gains are read as well.
+//!
+//! `ReplayGain` tags (`REPLAYGAIN_TRACK_GAIN`, `REPLAYGAIN_ALBUM_GAIN`) give
+//! the gain as text, e.g. `-6.50 dB`, and map to standard tag keys. Opus files
+//! use `R128_TRACK_GAIN` and `R128_ALBUM_GAIN` instead (RFC 7845): a signed
+//! Q7.8 fixed-point integer, the gain in 1/256 dB, towards the EBU R128
+//! reference of -23 LUFS rather than `ReplayGain`'s -18 LUFS. Symphonia doesn't
+//! map those, so they're read from their raw keys. They come on top of the
+//! output gain in the Opus header, which decoders already apply, so the
+//! converted gain applies to decoded audio as a `ReplayGain` one does.
+
+use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+/// `ReplayGain` 2.0's reference loudness, in LUFS.
+const REPLAY_GAIN_REFERENCE: f32 = -18.0;
+
+/// The EBU R128 reference loudness the gain of `R128_*` tags is towards.
+const R128_REFERENCE: f32 = -23.0;
+
+const R128_TRACK_GAIN_KEY: &str = "R128_TRACK_GAIN";
+const R128_ALBUM_GAIN_KEY: &str = "R128_ALBUM_GAIN";
+
+/// Parses the value of an `R128_*_GAIN` tag into the equivalent `ReplayGain`
+/// gain in dB. The value must be a whole number in the range of an `i16`.
+#[must_use]
+pub fn parse_r128_gain(value: &str) -> Option<f32> {
+    let q7_8: i16 = value.trim().parse().ok()?;
+    // Reaching -18 LUFS takes 5 dB more than reaching -23 LUFS.
+    Some(f32::from(q7_8) / 256.0 + (REPLAY_GAIN_REFERENCE - R128_REFERENCE))
+}
+
+/// Parses the value of a `ReplayGain` gain tag, which is a decimal number of dB
+/// usually followed by the unit.
+fn parse_replay_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .or_else(|| value.strip_suffix("DB"))
+        .unwrap_or(value);
+    let gain: f32 = number.trim().parse().ok()?;
+    gain.is_finite().then_some(gain)
+}
+
+/// Parses the value of a `ReplayGain` peak tag: the largest sample magnitude as
+/// a decimal number, where 1.0 is full scale. Peaks above it are valid for
+/// clipped or lossy audio.
+fn parse_peak(value: &str) -> Option<f32> {
+    let peak: f32 = value.trim().parse().ok()?;
+    (peak.is_finite() && peak >= 0.0).then_some(peak)
+}
+
+/// Whether `tag` is one of the R128 gain tags, which are stored in `file_tag`
+/// by their raw key so that re-deriving tracks finds them again.
+pub(super) fn is_r128(tag: &Tag) -> bool {
+    tag.std_key.is_none()
+        && [R128_TRACK_GAIN_KEY, R128_ALBUM_GAIN_KEY]
+            .iter()
+            .any(|key| tag.key.eq_ignore_ascii_case(key))
+}
+
+/// The gains and peaks found in a file's tags, the first of each kind winning.
+#[derive(Default)]
+pub(super) struct Gains {
+    track: Option<f32>,
+    album: Option<f32>,
+    r128_track: Option<f32>,
+    r128_album: Option<f32>,
+    track_peak: Option<f32>,
+    album_peak: Option<f32>,
+}
+
+impl Gains {
+    /// Takes the gain or peak `tag` holds, if any, either as read from a file or
+    /// as rebuilt from `file_tag`.
+    pub(super) fn read(&mut self, tag: &Tag) {
+        let Value::String(value) = &tag.value else {
+            return;
+        };
+        let (gain, parsed) = match tag.std_key {
+            Some(StandardTagKey::ReplayGainTrackGain) => {
+                (&mut self.track, parse_replay_gain(value))
+            }
+            Some(StandardTagKey::ReplayGainAlbumGain) => {
+                (&mut self.album, parse_replay_gain(value))
+            }
+            Some(StandardTagKey::ReplayGainTrackPeak) => (&mut self.track_peak, parse_peak(value)),
+            Some(StandardTagKey::ReplayGainAlbumPeak) => (&mut self.album_peak, parse_peak(value)),
+            None if tag.key.eq_ignore_ascii_case(R128_TRACK_GAIN_KEY) => {
+                (&mut self.r128_track, parse_r128_gain(value))
+            }
+            None if tag.key.eq_ignore_ascii_case(R128_ALBUM_GAIN_KEY) => {
+                (&mut self.r128_album, parse_r128_gain(value))
+            }
+            _ => return,
+        };
+        if gain.is_none() {
+            *gain = parsed;
+        }
+    }
+
+    /// The track gain, from the R128 tag when there is one: that's the tag
+    /// Opus players go by, so it wins over a `ReplayGain` tag an Opus file has.
+    pub(super) fn track(&self) -> Option<f32> {
+        self.r128_track.or(self.track)
+    }
+
+    /// The album gain, picked as for [`Gains::track`].
+    pub(super) fn album(&self) -> Option<f32> {
+        self.r128_album.or(self.album)
+    }
+
+    pub(super) fn track_peak(&self) -> Option<f32> {
+        self.track_peak
+    }
+
+    pub(super) fn album_peak(&self) -> Option<f32> {
+        self.album_peak
+    }
+}
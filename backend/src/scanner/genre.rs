@@ -0,0 +1,57 @@
+//! Picking a track's primary genre, the single genre browse views show for it,
+//! when its tags list several.
+
+use clap::{Args, ValueEnum};
+
+/// How the primary genre is picked among a track's genres.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrimaryGenreRule {
+    /// The genre of the first genre tag
+    #[default]
+    First,
+    /// The genre with the most words, e.g. "Progressive Rock" over "Rock".
+    /// Ties go to the earlier tag
+    MostSpecific,
+    /// The first genre of `--genre-priority` the track has, falling back to the
+    /// first tag
+    Priority,
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct GenreOptions {
+    /// How to pick a track's primary genre when its tags list several
+    #[arg(long, value_enum, default_value_t)]
+    pub primary_genre: PrimaryGenreRule,
+
+    /// Genres in order of preference for `--primary-genre priority`, compared
+    /// case-insensitively
+    #[arg(long, value_name = "GENRE,...", value_delimiter = ',')]
+    pub genre_priority: Vec<String>,
+}
+
+impl GenreOptions {
+    /// Picks the primary genre among `genres`, given in tag order. `None` when
+    /// there are no genres.
+    #[must_use]
+    pub fn primary<'a>(&self, genres: &'a [String]) -> Option<&'a str> {
+        let first = genres.first()?;
+        let primary = match self.primary_genre {
+            PrimaryGenreRule::First => first,
+            PrimaryGenreRule::MostSpecific => genres
+                .iter()
+                .rev()
+                .max_by_key(|genre| genre.split_whitespace().count())
+                .unwrap_or(first),
+            PrimaryGenreRule::Priority => self
+                .genre_priority
+                .iter()
+                .find_map(|preferred| {
+                    genres
+                        .iter()
+                        .find(|genre| genre.trim().eq_ignore_ascii_case(preferred.trim()))
+                })
+                .unwrap_or(first),
+        };
+        Some(primary)
+    }
+}
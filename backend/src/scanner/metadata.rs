@@ -1,28 +1,17 @@
+use jiff::civil::Date;
 use std::path::Path;
-use symphonia::core::formats::FormatOptions;
+use symphonia::core::codecs::{CODEC_TYPE_MP1, CODEC_TYPE_MP2, CODEC_TYPE_MP3};
+use symphonia::core::formats::{FormatOptions, FormatReader};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::{MetadataOptions, StandardTagKey, Tag, Value};
 use symphonia::core::probe::{Hint, ProbeResult};
+use uuid::Uuid;
 
-use super::types::{TrackArtistMetadata, TrackMetadata};
-
-pub fn extension_to_format(ext: &str) -> Option<&'static str> {
-    match ext.to_ascii_lowercase().as_str() {
-        "aac" => Some("aac"),
-        "aif" | "aiff" => Some("aiff"),
-        "alac" => Some("alac"),
-        "ape" => Some("ape"),
-        "flac" => Some("flac"),
-        "m4a" => Some("mp4"),
-        "mp3" => Some("mp3"),
-        "ogg" => Some("ogg"),
-        "opus" => Some("opus"),
-        "wav" => Some("wav"),
-        "wma" => Some("wma"),
-        "wv" => Some("wv"),
-        _ => None,
-    }
-}
+use super::dj_tags::{self, DjTag};
+use super::featured::{FeaturedSeparators, split_featured};
+use super::gain::Gains;
+use super::tags::StoredTag;
+use super::types::{AudioProperties, MetadataError, TrackArtistMetadata, TrackMetadata};
 
 fn parse_tag_value_into_u8(value: &Value) -> Option<u8> {
     match value {
@@ -40,6 +29,22 @@ fn parse_tag_value_into_u8(value: &Value) -> Option<u8> {
     }
 }
 
+/// Parses a "number/total" style value (e.g. `7/12` for track 7 of 12) into its
+/// two halves. Either half may be missing: `7` yields `(Some(7), None)` and `/12`
+/// yields `(None, Some(12))`. Non-string values only ever carry the number.
+fn parse_tag_value_into_number_and_total(value: &Value) -> (Option<u8>, Option<u8>) {
+    let Value::String(v) = value else {
+        return (parse_tag_value_into_u8(value), None);
+    };
+    match v.split_once('/') {
+        Some((number, total)) => (
+            parse_tag_value_into_u8(&Value::String(number.to_string())),
+            parse_tag_value_into_u8(&Value::String(total.to_string())),
+        ),
+        None => (parse_tag_value_into_u8(value), None),
+    }
+}
+
 fn parse_tag_value_into_year(value: &Value) -> Option<u16> {
     let current_year = jiff::Zoned::now().year() as u16;
 
@@ -58,11 +63,79 @@ fn parse_tag_value_into_year(value: &Value) -> Option<u16> {
     (year > 1860 && year <= current_year + 1).then_some(year)
 }
 
-fn assemble_tags_into_metadata<'a, T: IntoIterator<Item = &'a Tag>>(tags: T) -> TrackMetadata {
+/// Parses the full date of a date tag like `2021-07-14` (or with `/` or `.`
+/// between its parts). `None` when the tag only gives a year, or a year and
+/// month.
+fn parse_tag_value_into_date(value: &Value) -> Option<Date> {
+    let Value::String(v) = value else {
+        return None;
+    };
+    let start = v.find(|c: char| c.is_ascii_digit())?;
+    let mut parts = v[start..].splitn(3, ['-', '/', '.']);
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    // The day may be followed by a time, e.g. `2021-07-14T20:00:00`.
+    let day = parts.next()?;
+    let day_end = day.find(|c: char| !c.is_ascii_digit()).unwrap_or(day.len());
+    Date::new(year, month, day[..day_end].parse().ok()?).ok()
+}
+
+/// The credit role of a tag naming someone who took part in the recording other
+/// than as its artist, e.g. `composer` for `COMPOSER`.
+fn credit_role(key: StandardTagKey) -> Option<&'static str> {
+    Some(match key {
+        StandardTagKey::Arranger => "arranger",
+        StandardTagKey::Composer => "composer",
+        StandardTagKey::Conductor => "conductor",
+        StandardTagKey::Engineer => "engineer",
+        StandardTagKey::Ensemble => "ensemble",
+        StandardTagKey::Lyricist => "lyricist",
+        StandardTagKey::MixDj => "mix dj",
+        StandardTagKey::MixEngineer => "mix engineer",
+        StandardTagKey::Performer => "performer",
+        StandardTagKey::Producer => "producer",
+        StandardTagKey::Remixer => "remixer",
+        _ => return None,
+    })
+}
+
+/// Appends the `MusicBrainz` ids of a tag to `ids`, skipping those already
+/// there. ID3 tags may hold several in one frame, separated by `/` or `;`.
+fn append_musicbrainz_ids(value: &Value, ids: &mut Vec<Uuid>) {
+    let Value::String(v) = value else {
+        return;
+    };
+    for id in v
+        .split(['/', ';'])
+        .filter_map(|id| Uuid::parse_str(id.trim()).ok())
+    {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+}
+
+/// The text of a tag as it is, line breaks and all, unless there's nothing but
+/// whitespace to it.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(v) if !v.trim().is_empty() => Some(v.clone()),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+pub fn assemble_tags_into_metadata<'a, T: IntoIterator<Item = &'a Tag>>(tags: T) -> TrackMetadata {
     let mut artist_values = Vec::<String>::new();
     let mut title_values = Vec::<String>::new();
     let mut album_values = Vec::<String>::new();
+    let mut album_artist_values = Vec::<String>::new();
     let mut genre_values = Vec::<String>::new();
+    let mut role_credits = Vec::<TrackArtistMetadata>::new();
+    let mut recording_ids = Vec::<Uuid>::new();
+    let mut release_ids = Vec::<Uuid>::new();
+    let mut artist_ids = Vec::<Uuid>::new();
+    let mut album_artist_ids = Vec::<Uuid>::new();
 
     let append_string_value = |value: &Value, container: &mut Vec<String>| {
         if let Value::String(v) = value
@@ -73,47 +146,217 @@ fn assemble_tags_into_metadata<'a, T: IntoIterator<Item = &'a Tag>>(tags: T) ->
     };
 
     let mut date_value: Option<u16> = None;
+    let mut release_date_value: Option<Date> = None;
     let mut track_number_value: Option<u8> = None;
+    let mut track_total_value: Option<u8> = None;
     let mut disk_number_value: Option<u8> = None;
+    let mut disk_total_value: Option<u8> = None;
+    let mut bpm_value: Option<f32> = None;
+    let mut dj_bpm_value: Option<f32> = None;
+    let mut musical_key_value: Option<String> = None;
+    let mut lyrics_value: Option<String> = None;
+    let mut comment_value: Option<String> = None;
+    let mut gains = Gains::default();
 
     for tag in tags {
-        let Some(key) = tag.std_key else { continue };
+        gains.read(tag);
+        let Some(key) = tag.std_key else {
+            match dj_tags::read(tag) {
+                Some(DjTag::Bpm(bpm)) => dj_bpm_value = dj_bpm_value.or(Some(bpm)),
+                Some(DjTag::Key(key)) => musical_key_value = musical_key_value.or(Some(key)),
+                None => {}
+            }
+            continue;
+        };
         match key {
             StandardTagKey::Artist => append_string_value(&tag.value, &mut artist_values),
             StandardTagKey::TrackTitle => append_string_value(&tag.value, &mut title_values),
             StandardTagKey::Album => append_string_value(&tag.value, &mut album_values),
+            StandardTagKey::AlbumArtist => {
+                append_string_value(&tag.value, &mut album_artist_values);
+            }
             StandardTagKey::Genre => append_string_value(&tag.value, &mut genre_values),
 
-            StandardTagKey::Date => {
-                date_value = date_value.or_else(|| parse_tag_value_into_year(&tag.value));
+            StandardTagKey::Date if date_value.is_none() => {
+                date_value = parse_tag_value_into_year(&tag.value);
+                // The full date only counts when its year passes the year's
+                // sanity check.
+                release_date_value = parse_tag_value_into_date(&tag.value)
+                    .filter(|date| date_value == u16::try_from(date.year()).ok());
             }
             StandardTagKey::TrackNumber => {
-                track_number_value =
-                    track_number_value.or_else(|| parse_tag_value_into_u8(&tag.value));
+                let (number, total) = parse_tag_value_into_number_and_total(&tag.value);
+                track_number_value = track_number_value.or(number);
+                track_total_value = track_total_value.or(total);
+            }
+            StandardTagKey::TrackTotal => {
+                track_total_value =
+                    track_total_value.or_else(|| parse_tag_value_into_u8(&tag.value));
             }
             StandardTagKey::DiscNumber => {
-                disk_number_value =
-                    disk_number_value.or_else(|| parse_tag_value_into_u8(&tag.value));
+                let (number, total) = parse_tag_value_into_number_and_total(&tag.value);
+                disk_number_value = disk_number_value.or(number);
+                disk_total_value = disk_total_value.or(total);
+            }
+            StandardTagKey::DiscTotal => {
+                disk_total_value = disk_total_value.or_else(|| parse_tag_value_into_u8(&tag.value));
+            }
+            // Picard writes the recording id as `MUSICBRAINZ_TRACKID`.
+            StandardTagKey::MusicBrainzRecordingId | StandardTagKey::MusicBrainzTrackId => {
+                append_musicbrainz_ids(&tag.value, &mut recording_ids);
+            }
+            StandardTagKey::MusicBrainzAlbumId => {
+                append_musicbrainz_ids(&tag.value, &mut release_ids);
+            }
+            StandardTagKey::MusicBrainzArtistId => {
+                append_musicbrainz_ids(&tag.value, &mut artist_ids);
+            }
+            StandardTagKey::MusicBrainzAlbumArtistId => {
+                append_musicbrainz_ids(&tag.value, &mut album_artist_ids);
+            }
+            StandardTagKey::Lyrics => lyrics_value = lyrics_value.or_else(|| text(&tag.value)),
+            StandardTagKey::Comment => comment_value = comment_value.or_else(|| text(&tag.value)),
+            StandardTagKey::Bpm => {
+                bpm_value = bpm_value.or_else(|| dj_tags::parse_bpm(&tag.value.to_string()));
+            }
+            _ => {
+                // Someone in several roles gets a credit for each.
+                if let (Some(role), Value::String(name)) = (credit_role(key), &tag.value)
+                    && !name.trim().is_empty()
+                    && !role_credits
+                        .iter()
+                        .any(|c| c.artist == *name && c.role.as_deref() == Some(role))
+                {
+                    role_credits.push(TrackArtistMetadata {
+                        artist: name.clone(),
+                        role: Some(role.to_string()),
+                    });
+                }
             }
-            _ => {}
         }
     }
+
+    // Featured artists are taken out of the titles and artists, and credited
+    // on their own unless they're among the artists already.
+    let separators = FeaturedSeparators::default();
+    let mut featured = Vec::<String>::new();
+    let mut split = |values: Vec<String>| {
+        let mut rest = Vec::<String>::new();
+        for value in values {
+            let (value, names) = split_featured(&value, &separators);
+            if !rest.contains(&value) {
+                rest.push(value);
+            }
+            for name in names {
+                if !featured.contains(&name) {
+                    featured.push(name);
+                }
+            }
+        }
+        rest
+    };
+    let title_values = split(title_values);
+    let artist_values = split(artist_values);
+    featured.retain(|name| !artist_values.contains(name));
+
     TrackMetadata {
         title: title_values.join(", "),
         track_number: track_number_value,
+        track_total: track_total_value,
         disc_number: disk_number_value,
-        genre: genre_values.join(", "),
+        disc_total: disk_total_value,
+        genres: genre_values,
         album: album_values.join(", "),
+        album_artists: album_artist_values,
         year: date_value,
+        release_date: release_date_value,
+        // The BPM DJ software stores on its own only stands in for a standard one.
+        bpm: bpm_value.or(dj_bpm_value),
+        musical_key: musical_key_value,
+        track_gain: gains.track(),
+        album_gain: gains.album(),
+        track_peak: gains.track_peak(),
+        album_peak: gains.album_peak(),
+        lyrics: lyrics_value,
+        comment: comment_value,
+        // The artists come first, then the featured artists, then everyone
+        // credited in another role.
         artists: artist_values
             .into_iter()
             .map(|artist| TrackArtistMetadata { artist, role: None })
+            .chain(featured.into_iter().map(|artist| TrackArtistMetadata {
+                artist,
+                role: Some("featured".to_string()),
+            }))
+            .chain(role_credits)
             .collect(),
+        musicbrainz_recording_id: recording_ids.first().copied(),
+        musicbrainz_release_id: release_ids.first().copied(),
+        musicbrainz_artist_ids: artist_ids,
+        musicbrainz_album_artist_ids: album_artist_ids,
     }
 }
 
-fn probe_file(file_path: &Path) -> Option<(ProbeResult, f64)> {
-    let file = std::fs::File::open(file_path).ok()?;
+/// Whether the header-based duration of the file's default track can be off.
+/// MPEG audio files without a Xing/Info header only get an estimate, which is
+/// wrong by seconds for VBR files.
+fn has_estimated_duration(format: &dyn FormatReader) -> bool {
+    format.default_track().is_some_and(|track| {
+        matches!(
+            track.codec_params.codec,
+            CODEC_TYPE_MP1 | CODEC_TYPE_MP2 | CODEC_TYPE_MP3
+        )
+    })
+}
+
+/// Reads packets of the default track until `limit` have been read or the file
+/// ends, returning the duration they cover in seconds.
+fn read_packets(format: &mut dyn FormatReader, limit: usize) -> Option<f64> {
+    let track = format.default_track()?;
+    let (track_id, time_base) = (track.id, track.codec_params.time_base);
+
+    let mut end = 0;
+    let mut packets_read = 0;
+    while packets_read < limit {
+        match format.next_packet() {
+            Ok(packet) => {
+                packets_read += 1;
+                if packet.track_id() == track_id {
+                    end = end.max(packet.ts() + packet.dur());
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    let time = time_base?.calc_time(end);
+    Some(time.seconds as f64 + time.frac)
+}
+
+/// The audio properties of the default track. Symphonia doesn't report a
+/// bitrate, so it's averaged over the file: `size` bytes in `duration` seconds.
+fn audio_properties(format: &dyn FormatReader, size: u64, duration: f64) -> AudioProperties {
+    let Some(track) = format.default_track() else {
+        return AudioProperties::default();
+    };
+    let params = &track.codec_params;
+    AudioProperties {
+        sample_rate: params.sample_rate,
+        channels: params
+            .channels
+            .and_then(|channels| u8::try_from(channels.count()).ok()),
+        bits_per_sample: params
+            .bits_per_sample
+            .and_then(|bits| u8::try_from(bits).ok()),
+        bitrate: (duration > 0.0).then(|| (size as f64 * 8.0 / duration / 1000.0).round() as u32),
+    }
+}
+
+/// Probes the file, returning its format reader, the duration its header gives
+/// and its size in bytes.
+pub(super) fn probe_file(file_path: &Path) -> Result<(ProbeResult, f64, u64), MetadataError> {
+    let file = std::fs::File::open(file_path).map_err(|e| MetadataError::Io(e.to_string()))?;
+    let size = file.metadata().map_or(0, |m| m.len());
     let mss = MediaSourceStream::new(
         Box::new(file),
         symphonia::core::io::MediaSourceStreamOptions::default(),
@@ -127,9 +370,7 @@ fn probe_file(file_path: &Path) -> Option<(ProbeResult, f64)> {
     let meta_opts = MetadataOptions::default();
     let fmt_opts = FormatOptions::default();
 
-    let probed = symphonia::default::get_probe()
-        .format(&hint, mss, &fmt_opts, &meta_opts)
-        .ok()?;
+    let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
 
     let duration_secs = probed.format.default_track().and_then(|track| {
         let params = &track.codec_params;
@@ -139,37 +380,50 @@ fn probe_file(file_path: &Path) -> Option<(ProbeResult, f64)> {
         Some(time.seconds as f64 + time.frac)
     });
 
-    Some((probed, duration_secs.unwrap_or(0.0)))
+    Ok((probed, duration_secs.unwrap_or(0.0), size))
 }
 
-/// Analyze a file to get its duration in seconds. Returns 0.0 if undetermined.
-pub fn get_duration(file_path: &Path) -> f64 {
+/// Analyze a file to get its duration in seconds and its audio properties.
+///
+/// With `accurate`, files whose header only gives an estimate have every packet
+/// read to measure it instead (see [`has_estimated_duration`]).
+pub fn get_duration(
+    file_path: &Path,
+    accurate: bool,
+) -> Result<(f64, AudioProperties), MetadataError> {
     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        probe_file(file_path).map_or(0.0, |(_, d)| d)
+        let (mut probed, duration, size) = probe_file(file_path)?;
+        let duration = if accurate && has_estimated_duration(probed.format.as_ref()) {
+            read_packets(probed.format.as_mut(), usize::MAX).unwrap_or(duration)
+        } else {
+            duration
+        };
+        Ok((
+            duration,
+            audio_properties(probed.format.as_ref(), size, duration),
+        ))
     }));
-    if let Ok(d) = result {
-        d
-    } else {
-        eprintln!(
-            "Warning: panic while probing {}, skipping duration",
-            file_path.display()
-        );
-        0.0
-    }
+    result.unwrap_or(Err(MetadataError::Panic))
 }
 
-/// Extract full track metadata (tags) plus duration from an audio file.
-pub fn get_track_metadata(file_path: &Path) -> Option<(TrackMetadata, f64)> {
+/// Extract full track metadata, the tags it was assembled from, the duration
+/// and the audio properties from an audio file. See [`get_duration`] for
+/// `accurate_duration`. With `all_tags`, every tag holding text is returned,
+/// not only those the metadata can come from.
+pub fn get_track_metadata(
+    file_path: &Path,
+    accurate_duration: bool,
+    all_tags: bool,
+) -> Result<(TrackMetadata, Vec<StoredTag>, f64, AudioProperties), MetadataError> {
     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        let (mut probed, duration) = probe_file(file_path)?;
+        let (mut probed, mut duration, size) = probe_file(file_path)?;
 
-        // Read a few packets to ensure metadata is fully loaded (especially for FLAC)
-        let mut packets_read = 0;
-        while packets_read < 10 {
-            match probed.format.next_packet() {
-                Ok(_) => packets_read += 1,
-                Err(_) => break,
-            }
+        // Read a few packets to ensure metadata is fully loaded (especially for
+        // FLAC), or all of them when measuring the duration.
+        if accurate_duration && has_estimated_duration(probed.format.as_ref()) {
+            duration = read_packets(probed.format.as_mut(), usize::MAX).unwrap_or(duration);
+        } else {
+            read_packets(probed.format.as_mut(), 10);
         }
 
         // ID3v1/ID3v2 tags (e.g. MP3 files)
@@ -189,18 +443,19 @@ pub fn get_track_metadata(file_path: &Path) -> Option<(TrackMetadata, f64)> {
             .unwrap_or_default()
             .iter();
 
-        let metadata = assemble_tags_into_metadata(probed_tags.chain(format_tags));
+        let tags = probed_tags.chain(format_tags);
+        let metadata = assemble_tags_into_metadata(tags.clone());
+        let keep: fn(&Tag) -> Option<StoredTag> = if all_tags {
+            StoredTag::from_any_tag
+        } else {
+            StoredTag::from_tag
+        };
+        let stored_tags = tags.filter_map(keep).collect();
+
+        let audio = audio_properties(probed.format.as_ref(), size, duration);
 
-        Some((metadata, duration))
+        Ok((metadata, stored_tags, duration, audio))
     }));
 
-    if let Ok(inner) = result {
-        inner
-    } else {
-        eprintln!(
-            "Warning: panic while reading {}, skipping",
-            file_path.display()
-        );
-        None
-    }
+    result.unwrap_or(Err(MetadataError::Panic))
 }
@@ -1,8 +1,45 @@
+mod album;
+mod backfill;
+mod check;
 mod classify;
+mod collection;
+mod cue;
+mod dj_tags;
+mod duplicates;
+mod enrich;
+mod exclude;
+mod failures;
+mod fallback;
+mod featured;
+mod fingerprint;
+mod gain;
+mod genre;
 mod metadata;
+mod playlist;
 mod prepare;
+mod progress;
+mod rederive;
 mod scan;
+mod scan_log;
+mod since;
 mod staging;
+mod symlink;
+mod tags;
 mod types;
+mod watch;
 
-pub use scan::scan;
+pub use album::{AlbumOptions, MissingAlbumRule};
+pub use backfill::{BackfillProgress, BackfillStatus, DbTask, backfill};
+pub use check::{CheckReport, check};
+pub use dj_tags::{DjTag, read_geob};
+pub use duplicates::{DuplicateGroup, find_duplicates};
+pub use failures::{ScanFailure, load_failures};
+pub use fallback::{album_from_path, title_from_path};
+pub use featured::{FeaturedSeparators, split_featured};
+pub use gain::parse_r128_gain;
+pub use genre::{GenreOptions, PrimaryGenreRule};
+pub use rederive::rederive;
+pub use scan::{ScanOptions, scan};
+pub use since::{Since, parse_since};
+pub use symlink::SymlinkRule;
+pub use watch::{sync_paths, watch};
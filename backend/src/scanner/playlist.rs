@@ -0,0 +1,184 @@
+//! Playlists: the `.m3u`, `.m3u8` and `.pls` files of the collection, which
+//! list tracks by path.
+//!
+//! A scan with `--playlists` reads every playlist it finds and lists its
+//! entries in order. Relative paths are relative to the playlist's directory;
+//! absolute paths and `file://` URLs are taken as they are. Each entry leads to
+//! the live file at its path, or to none when it's a stream URL, leads outside
+//! the collection or to a file the library doesn't have: such entries are kept
+//! as the playlist gives them.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::classify::{find_files, normalize_path};
+use super::exclude::Exclude;
+use super::scan::ScanOptions;
+use super::types::{StagingData, StagingPlaylist, StagingPlaylistEntry};
+
+static PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls"];
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub(super) fn is_playlist_file(path: &Path) -> bool {
+    has_extension(path, PLAYLIST_EXTENSIONS)
+}
+
+/// The entries of an M3U playlist, and the name its `#PLAYLIST:` directive
+/// gives it, if any. Other `#` lines are comments or directives such as
+/// `#EXTINF`, which only describe the entry after them.
+fn parse_m3u(text: &str) -> (Option<String>, Vec<String>) {
+    let mut name = None;
+    let mut entries = Vec::new();
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if let Some(playlist) = line.strip_prefix("#PLAYLIST:") {
+            name = Some(playlist.trim().to_string()).filter(|name| !name.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push(line.to_string());
+        }
+    }
+    (name, entries)
+}
+
+/// The entries of a PLS playlist, in the order of the numbers of their `FileN`
+/// keys.
+fn parse_pls(text: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        let number = key
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("file"))
+            .and_then(|_| key[4..].parse::<u32>().ok());
+        if let Some(number) = number
+            && !value.trim().is_empty()
+        {
+            entries.push((number, value.trim().to_string()));
+        }
+    }
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// `text` with its `%XX` escapes decoded, as in a `file://` URL.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Where `entry` of a playlist in `dir` points, or `None` for a URL other than
+/// a `file://` one.
+fn entry_path(entry: &str, dir: &Path) -> Option<PathBuf> {
+    let path = if let Some(url) = entry.strip_prefix("file://") {
+        // `file:///music/a.flac`, or `file://localhost/music/a.flac`.
+        PathBuf::from(percent_decode(url.strip_prefix("localhost").unwrap_or(url)))
+    } else if entry.contains("://") {
+        return None;
+    } else {
+        PathBuf::from(entry)
+    };
+    Some(dir.join(path))
+}
+
+/// The collection path (like `./Artist/01.flac`) of the file `entry` of a
+/// playlist in `dir` leads to, if it's in the collection.
+fn resolve(entry: &str, dir: &Path, canonical_root: &Path) -> Option<String> {
+    let path = entry_path(entry, dir)?;
+    // Playlists written on Windows separate directories with backslashes.
+    let path = if path.exists() || !entry.contains('\\') {
+        path
+    } else {
+        entry_path(&entry.replace('\\', "/"), dir)?
+    };
+    let canonical = fs::canonicalize(path).ok()?;
+    let relative = canonical.strip_prefix(canonical_root).ok()?;
+    Some(format!("./{}", relative.display()))
+}
+
+/// Stages the playlists in the collection and their entries, walking it as
+/// the scan does, and the playlists of `existing` (the library's, by path) that
+/// are gone.
+pub(super) fn stage_playlists(
+    collection_path: &Path,
+    options: &ScanOptions,
+    existing: &HashSet<String>,
+    data: &mut StagingData,
+) -> Result<(), globset::Error> {
+    let exclude = Exclude::new(collection_path, &options.exclude)?;
+    let canonical_root =
+        fs::canonicalize(collection_path).unwrap_or_else(|_| collection_path.to_path_buf());
+    let (files, _) = find_files(
+        collection_path,
+        options.max_depth,
+        options.follow_symlinks,
+        &exclude,
+        None,
+        &is_playlist_file,
+    );
+
+    let mut found = HashSet::new();
+    for file in files {
+        let path = normalize_path(&file, &canonical_root);
+        // The same playlist may be reached through a symlink.
+        if !found.insert(path.clone()) {
+            continue;
+        }
+        // A playlist that can't be read this time is left as it was.
+        let Ok(bytes) = fs::read(&file) else {
+            continue;
+        };
+        let text = String::from_utf8_lossy(&bytes);
+        let (name, entries) = if has_extension(&file, &["pls"]) {
+            (None, parse_pls(&text))
+        } else {
+            parse_m3u(&text)
+        };
+        let name = name.unwrap_or_else(|| {
+            file.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        let dir = file.parent().unwrap_or(collection_path);
+        for (ord, entry) in (0_u32..).zip(entries) {
+            data.playlist_entries.push(StagingPlaylistEntry {
+                playlist: path.clone(),
+                ord,
+                file_path: resolve(&entry, dir, &canonical_root),
+                path: entry,
+            });
+        }
+        data.playlists.push(StagingPlaylist { path, name });
+    }
+
+    data.deleted_playlists = existing
+        .iter()
+        .filter(|path| !found.contains(*path))
+        .cloned()
+        .collect();
+    Ok(())
+}
@@ -3,9 +3,15 @@ use std::path::{Path, PathBuf};
 
 use uuid::Uuid;
 
+use super::album::{AlbumOptions, MissingAlbumRule};
+use super::fallback::{album_from_path, title_from_path};
+use super::genre::GenreOptions;
+use super::tags::StoredTag;
 use super::types::{
-    ScanResults, StagingAlbum, StagingArtist, StagingCredit, StagingData, StagingDeleted,
-    StagingFile, StagingModified, StagingMoved, StagingTrack,
+    BackfilledFile, CueTrack, ExistingArtists, FailedFile, RederivedFile, ScanResults,
+    StagingAlbum, StagingAlias, StagingArtist, StagingCredit, StagingData, StagingDeleted,
+    StagingDuration, StagingFailure, StagingFile, StagingFileTag, StagingModified, StagingMoved,
+    StagingMusicBrainzArtist, StagingPredecessor, StagingTrack, StagingTrackGenre, TrackMetadata,
 };
 
 static DISC_FOLDER_PATTERN: &[&str] = &["disc", "cd", "disk"];
@@ -21,8 +27,20 @@ fn is_disc_folder(name: &str) -> bool {
     })
 }
 
+/// The disc number a disc folder is named after, e.g. 2 for `CD2`.
+pub(super) fn disc_folder_number(name: &str) -> Option<u8> {
+    if !is_disc_folder(name) {
+        return None;
+    }
+    let lower = name.to_ascii_lowercase();
+    DISC_FOLDER_PATTERN
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .and_then(|rest| rest.trim().parse().ok())
+}
+
 /// Determine the "album directory" for a file, looking through disc folders.
-fn album_directory(file_path: &Path) -> Option<PathBuf> {
+pub(super) fn album_directory(file_path: &Path) -> Option<PathBuf> {
     let parent = file_path.parent()?;
     let dir_name = parent.file_name()?.to_str()?;
 
@@ -33,49 +51,338 @@ fn album_directory(file_path: &Path) -> Option<PathBuf> {
     }
 }
 
-fn collect_artists(
-    results: &ScanResults,
-    existing_artists: &HashMap<String, Uuid>,
+/// The key under which tracks are grouped into albums.
+#[derive(PartialEq, Eq, Hash)]
+struct AlbumKey {
+    /// The `MusicBrainz` release, which identifies the album on its own: the
+    /// other fields are left empty when there is one, so that its tracks stay
+    /// together whatever their album tags and folders.
+    release: Option<Uuid>,
+    title: String,
+    /// The id of the primary album artist, so that the tracks of a compilation
+    /// stay together however many performers they have, and same-named albums
+    /// of different artists stay apart.
+    artist: Option<Uuid>,
+    /// The year, so that same-named albums of one artist kept in one folder,
+    /// such as a folder of singles, stay apart when they came out in different
+    /// years.
+    year: Option<u16>,
+    /// The album directory, which breaks the tie between same-named albums of
+    /// one artist in different folders.
+    directory: PathBuf,
+}
+
+/// The name of a track's primary album artist: its first album artist, or its
+/// first artist for a track tagged without any. Composers and others credited
+/// in a role don't count.
+fn album_artist(metadata: &TrackMetadata) -> Option<&str> {
+    metadata
+        .album_artists
+        .first()
+        .or_else(|| {
+            metadata
+                .artists
+                .iter()
+                .find(|ta| ta.role.is_none())
+                .map(|ta| &ta.artist)
+        })
+        .map(String::as_str)
+}
+
+/// The key of a track's album, `None` for a track that gets no album: its
+/// `MusicBrainz` release, or else [`tagged_album_key`].
+fn album_key(
+    path: &str,
+    metadata: &TrackMetadata,
+    all_artists: &HashMap<String, Uuid>,
+    albums: &AlbumOptions,
+) -> Option<AlbumKey> {
+    match metadata.musicbrainz_release_id {
+        Some(release) => Some(AlbumKey {
+            release: Some(release),
+            title: String::new(),
+            artist: None,
+            year: None,
+            directory: PathBuf::new(),
+        }),
+        None => tagged_album_key(path, metadata, all_artists, albums),
+    }
+}
+
+/// The key of a track's album by its album title, album artist, year and
+/// directory.
+///
+/// A track without an album tag only gets an album under
+/// [`MissingAlbumRule::Single`], keyed by its own path so that no other track
+/// joins it, or under [`MissingAlbumRule::Directory`], titled after its album
+/// directory.
+fn tagged_album_key(
+    path: &str,
+    metadata: &TrackMetadata,
+    all_artists: &HashMap<String, Uuid>,
+    albums: &AlbumOptions,
+) -> Option<AlbumKey> {
+    let (title, directory) = if metadata.album.trim().is_empty() {
+        match albums.missing_album {
+            MissingAlbumRule::Omit => return None,
+            MissingAlbumRule::Single => (track_title(path, metadata), PathBuf::from(path)),
+            MissingAlbumRule::Directory => (
+                album_from_path(Path::new(path))?,
+                album_directory(Path::new(path)).unwrap_or_default(),
+            ),
+        }
+    } else {
+        let album_dir = album_directory(Path::new(path)).unwrap_or_default();
+        (metadata.album.clone(), album_dir)
+    };
+    Some(AlbumKey {
+        release: None,
+        title,
+        artist: album_artist(metadata).and_then(|name| all_artists.get(name).copied()),
+        year: metadata.year,
+        directory,
+    })
+}
+
+/// A track's title, or the one its file name gives (see [`title_from_path`])
+/// when its tags have none.
+fn track_title(path: &str, metadata: &TrackMetadata) -> String {
+    if metadata.title.trim().is_empty() {
+        title_from_path(Path::new(path))
+    } else {
+        metadata.title.clone()
+    }
+}
+
+/// Maps every artist and album artist name in `files` to an artist id,
+/// creating artists for names not seen before. An alias name maps to its
+/// canonical artist, even when an artist of that name exists too.
+///
+/// Names that only differ in case and whitespace (see [`artist_key`]) are the
+/// same artist: a name without an exact match takes the artist or alias it
+/// matches that way, and the first spelling seen of a new artist names it.
+fn collect_artists<'a>(
+    files: impl IntoIterator<Item = &'a TrackMetadata>,
+    existing_artists: &ExistingArtists,
 ) -> (HashMap<String, Uuid>, Vec<StagingArtist>) {
-    let mut all_artists: HashMap<String, Uuid> = existing_artists.clone();
+    let mut all_artists: HashMap<String, Uuid> = existing_artists.by_name.clone();
+    all_artists.extend(existing_artists.aliases.clone());
+    let mut by_key: HashMap<String, Uuid> = existing_artists
+        .by_name
+        .iter()
+        .chain(&existing_artists.aliases)
+        .map(|(name, id)| (artist_key(name), *id))
+        .collect();
     let mut new_artist_records: Vec<StagingArtist> = Vec::new();
 
-    for nf in &results.new_files {
-        for ta in &nf.metadata.artists {
-            if !all_artists.contains_key(&ta.artist) {
+    for metadata in files {
+        let track_artists = metadata.artists.iter().map(|ta| &ta.artist);
+        for name in track_artists.chain(&metadata.album_artists) {
+            if all_artists.contains_key(name) {
+                continue;
+            }
+            let id = *by_key.entry(artist_key(name)).or_insert_with(|| {
                 let id = Uuid::new_v4();
-                all_artists.insert(ta.artist.clone(), id);
                 new_artist_records.push(StagingArtist {
                     id,
-                    name: ta.artist.clone(),
+                    name: name.clone(),
                 });
-            }
+                id
+            });
+            all_artists.insert(name.clone(), id);
         }
     }
     (all_artists, new_artist_records)
 }
 
-fn collect_albums(results: &ScanResults) -> (HashMap<(String, PathBuf), Uuid>, Vec<StagingAlbum>) {
-    let mut album_map: HashMap<(String, PathBuf), Uuid> = HashMap::new();
-    let mut album_years: HashMap<Uuid, Option<u16>> = HashMap::new();
+/// What artist names are matched by: lower case, with runs of whitespace
+/// collapsed to a space and none at either end.
+fn artist_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
 
-    for nf in &results.new_files {
-        let album_dir = album_directory(Path::new(&nf.path)).unwrap_or_default();
-        let key = (nf.metadata.album.clone(), album_dir);
-        let album_id = *album_map.entry(key).or_insert_with(Uuid::new_v4);
-        album_years.entry(album_id).or_insert(nf.metadata.year);
+/// Maps the `MusicBrainz` ids of the artists and album artists in `files` to
+/// the artists they name, when a track tags as many ids as artists.
+fn collect_musicbrainz_artists<'a>(
+    files: impl IntoIterator<Item = &'a TrackMetadata>,
+    all_artists: &HashMap<String, Uuid>,
+) -> Vec<StagingMusicBrainzArtist> {
+    let mut by_artist: HashMap<Uuid, Uuid> = HashMap::new();
+    for metadata in files {
+        let artists: Vec<&String> = metadata
+            .artists
+            .iter()
+            .filter(|ta| ta.role.is_none())
+            .map(|ta| &ta.artist)
+            .collect();
+        let album_artists: Vec<&String> = metadata.album_artists.iter().collect();
+        for (names, ids) in [
+            (artists, &metadata.musicbrainz_artist_ids),
+            (album_artists, &metadata.musicbrainz_album_artist_ids),
+        ] {
+            if names.len() != ids.len() {
+                continue;
+            }
+            for (name, &id) in names.into_iter().zip(ids) {
+                if let Some(&artist) = all_artists.get(name) {
+                    by_artist.entry(artist).or_insert(id);
+                }
+            }
+        }
     }
+    by_artist
+        .into_iter()
+        .map(|(artist, musicbrainz_id)| StagingMusicBrainzArtist {
+            artist,
+            musicbrainz_id,
+        })
+        .collect()
+}
 
-    let staging_albums: Vec<StagingAlbum> = album_map
-        .iter()
-        .map(|((title, _), &id)| StagingAlbum {
+fn collect_albums<'a>(
+    files: impl IntoIterator<Item = (&'a str, &'a TrackMetadata)>,
+    all_artists: &HashMap<String, Uuid>,
+    albums: &AlbumOptions,
+) -> (HashMap<AlbumKey, Uuid>, Vec<StagingAlbum>) {
+    let mut album_map: HashMap<AlbumKey, Uuid> = HashMap::new();
+    let mut staging_albums: Vec<StagingAlbum> = Vec::new();
+
+    for (path, metadata) in files {
+        let Some(key) = album_key(path, metadata, all_artists, albums) else {
+            continue;
+        };
+        if album_map.contains_key(&key) {
+            continue;
+        }
+        // An album takes its title, artist and dates from its first track. A
+        // MusicBrainz release goes by the album tags as well.
+        let id = Uuid::new_v4();
+        let (title, artist) = match tagged_album_key(path, metadata, all_artists, albums) {
+            Some(tagged) => (tagged.title, tagged.artist),
+            None => (
+                metadata.album.clone(),
+                album_artist(metadata).and_then(|name| all_artists.get(name).copied()),
+            ),
+        };
+        staging_albums.push(StagingAlbum {
             id,
-            title: title.clone(),
-            year: album_years.get(&id).copied().flatten(),
+            title,
+            year: metadata.year,
+            release_date: metadata.release_date,
+            musicbrainz_release_id: key.release,
+            artist,
+        });
+        album_map.insert(key, id);
+    }
+
+    (album_map, staging_albums)
+}
+
+/// The rows storing a file's tags, in tag order.
+fn file_tags(file: Uuid, tags: &[StoredTag]) -> impl Iterator<Item = StagingFileTag> + '_ {
+    (0..).zip(tags).map(move |(ord, tag)| StagingFileTag {
+        file,
+        ord,
+        key: tag.key.clone(),
+        std_key: tag.std_key.clone(),
+        value: tag.value.clone(),
+    })
+}
+
+/// A track of a file and the stretch of the file it spans.
+struct FileTrack<'a> {
+    start: Option<f64>,
+    end: Option<f64>,
+    metadata: &'a TrackMetadata,
+}
+
+/// The tracks of a file: the ones a cue sheet splits it into, or else the
+/// whole file as one track.
+fn file_tracks<'a>(metadata: &'a TrackMetadata, cue_tracks: &'a [CueTrack]) -> Vec<FileTrack<'a>> {
+    if cue_tracks.is_empty() {
+        return vec![FileTrack {
+            start: None,
+            end: None,
+            metadata,
+        }];
+    }
+    cue_tracks
+        .iter()
+        .map(|track| FileTrack {
+            start: Some(track.start),
+            end: track.end,
+            metadata: &track.metadata,
+        })
+        .collect()
+}
+
+/// Build a track row and its credits.
+fn track_with_credits(
+    track_id: Uuid,
+    file_id: Uuid,
+    path: &str,
+    file_track: &FileTrack,
+    album: Option<Uuid>,
+    all_artists: &HashMap<String, Uuid>,
+    genre: &GenreOptions,
+) -> (StagingTrack, Vec<StagingCredit>) {
+    let metadata = file_track.metadata;
+    let track = StagingTrack {
+        id: track_id,
+        file: file_id,
+        start_position: file_track.start,
+        end_position: file_track.end,
+        title: track_title(path, metadata),
+        album,
+        disc_number: metadata.disc_number,
+        disc_total: metadata.disc_total,
+        track_number: metadata.track_number,
+        track_total: metadata.track_total,
+        primary_genre: genre.primary(&metadata.genres).map(str::to_string),
+        bpm: metadata.bpm,
+        musical_key: metadata.musical_key.clone(),
+        track_gain: metadata.track_gain,
+        album_gain: metadata.album_gain,
+        track_peak: metadata.track_peak,
+        album_peak: metadata.album_peak,
+        lyrics: metadata.lyrics.clone(),
+        comment: metadata.comment.clone(),
+        musicbrainz_recording_id: metadata.musicbrainz_recording_id,
+    };
+
+    let credits = metadata
+        .artists
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ta)| {
+            let &artist = all_artists.get(&ta.artist)?;
+            Some(StagingCredit {
+                track: track_id,
+                artist,
+                ord: i as f64,
+                role: ta.role.clone(),
+            })
         })
         .collect();
 
-    (album_map, staging_albums)
+    (track, credits)
+}
+
+/// The rows listing a track's genres, in tag order.
+fn track_genres(
+    track: Uuid,
+    metadata: &TrackMetadata,
+) -> impl Iterator<Item = StagingTrackGenre> + '_ {
+    (0_u16..)
+        .zip(&metadata.genres)
+        .map(move |(ord, genre)| StagingTrackGenre {
+            track,
+            genre: genre.clone(),
+            ord: f64::from(ord),
+        })
 }
 
 fn collect_changes(
@@ -100,6 +407,8 @@ fn collect_changes(
             hash: m.hash,
             size: m.size,
             duration: m.duration,
+            audio: m.audio,
+            fingerprint: m.fingerprint.clone(),
             mtime: m.mtime,
         })
         .collect();
@@ -116,68 +425,270 @@ fn collect_changes(
     (staging_moved, staging_modified, staging_deleted)
 }
 
+fn staging_failures(failed: &[FailedFile]) -> Vec<StagingFailure> {
+    failed
+        .iter()
+        .map(|f| StagingFailure {
+            path: f.path.clone(),
+            category: f.error.category(),
+            message: f.error.message().to_string(),
+        })
+        .collect()
+}
+
 pub fn prepare_staging_data(
     results: &ScanResults,
-    existing_artists: &HashMap<String, Uuid>,
+    existing_artists: &ExistingArtists,
     deleted_ids: Vec<Uuid>,
+    genre: &GenreOptions,
+    albums: &AlbumOptions,
 ) -> StagingData {
-    let (all_artists, new_artist_records) = collect_artists(results, existing_artists);
-    let (album_map, staging_albums) = collect_albums(results);
+    // Files whose metadata the scan deferred get their tracks from a backfill.
+    let read_files = || results.new_files.iter().filter(|nf| nf.duration.is_some());
+    let read_tracks = || {
+        read_files().flat_map(|nf| {
+            file_tracks(&nf.metadata, &nf.cue_tracks)
+                .into_iter()
+                .map(move |track| (nf.path.as_str(), track.metadata))
+        })
+    };
+    let (all_artists, new_artist_records) = collect_artists(
+        read_tracks().map(|(_, metadata)| metadata),
+        existing_artists,
+    );
+    let musicbrainz_artists =
+        collect_musicbrainz_artists(read_tracks().map(|(_, metadata)| metadata), &all_artists);
+    let (album_map, staging_albums) = collect_albums(read_tracks(), &all_artists, albums);
 
     let mut staging_files: Vec<StagingFile> = Vec::new();
+    let mut staging_file_tags: Vec<StagingFileTag> = Vec::new();
     let mut staging_tracks: Vec<StagingTrack> = Vec::new();
+    let mut staging_track_genres: Vec<StagingTrackGenre> = Vec::new();
     let mut staging_credits: Vec<StagingCredit> = Vec::new();
+    let mut staging_predecessors: Vec<StagingPredecessor> = Vec::new();
 
     for nf in &results.new_files {
         let file_id = Uuid::new_v4();
-        let track_id = Uuid::new_v4();
 
         staging_files.push(StagingFile {
             id: file_id,
             path: nf.path.clone(),
             hash: nf.hash,
             size: nf.size,
-            format: nf.format.clone(),
+            format: nf.format,
             duration: nf.duration,
+            audio: nf.audio,
+            fingerprint: nf.fingerprint.clone(),
             mtime: nf.mtime,
         });
+        if nf.duration.is_none() {
+            continue;
+        }
 
-        let album_dir = album_directory(Path::new(&nf.path)).unwrap_or_default();
-        let album_key = (nf.metadata.album.clone(), album_dir);
-        let album_id = album_map.get(&album_key).copied();
-
-        staging_tracks.push(StagingTrack {
-            id: track_id,
-            file: file_id,
-            title: nf.metadata.title.clone(),
-            album: album_id,
-            disc_number: nf.metadata.disc_number,
-            track_number: nf.metadata.track_number,
-            genre: nf.metadata.genre.clone(),
-        });
+        staging_file_tags.extend(file_tags(file_id, &nf.tags));
 
-        for (i, ta) in nf.metadata.artists.iter().enumerate() {
-            if let Some(&artist_id) = all_artists.get(&ta.artist) {
-                staging_credits.push(StagingCredit {
-                    track: track_id,
-                    artist: artist_id,
-                    ord: i as f64,
-                    role: ta.role.clone(),
-                });
-            }
+        for file_track in file_tracks(&nf.metadata, &nf.cue_tracks) {
+            let album_id = album_key(&nf.path, file_track.metadata, &all_artists, albums)
+                .and_then(|key| album_map.get(&key).copied());
+            let (track, credits) = track_with_credits(
+                Uuid::new_v4(),
+                file_id,
+                &nf.path,
+                &file_track,
+                album_id,
+                &all_artists,
+                genre,
+            );
+            staging_track_genres.extend(track_genres(track.id, file_track.metadata));
+            staging_tracks.push(track);
+            staging_credits.extend(credits);
+        }
+
+        // A file split into tracks has no one track to take over the user data
+        // of the file it replaces.
+        if let Some(predecessor) = &nf.predecessor
+            && nf.cue_tracks.is_empty()
+        {
+            staging_predecessors.push(StagingPredecessor {
+                file: file_id,
+                predecessor: predecessor.file,
+                move_plays: !predecessor.stays_live,
+            });
         }
     }
 
     let (staging_moved, staging_modified, staging_deleted) = collect_changes(results, deleted_ids);
 
     StagingData {
+        collection: None,
         artists: new_artist_records,
+        musicbrainz_artists,
         albums: staging_albums,
         files: staging_files,
+        file_tags: staging_file_tags,
         tracks: staging_tracks,
+        track_genres: staging_track_genres,
         credits: staging_credits,
         moved: staging_moved,
         modified: staging_modified,
         deleted: staging_deleted,
+        predecessors: staging_predecessors,
+        failures: staging_failures(&results.failed),
+        durations: Vec::new(),
+        aliases: results
+            .aliases
+            .iter()
+            .map(|alias| StagingAlias {
+                path: alias.path.clone(),
+                target: alias.target.clone(),
+            })
+            .collect(),
+        playlists: Vec::new(),
+        playlist_entries: Vec::new(),
+        deleted_playlists: Vec::new(),
+    }
+}
+
+/// Like [`prepare_staging_data`], but for files already in the database: only
+/// the artist, album, track, genre and credit tables are populated.
+pub fn prepare_rederived_data(
+    files: &[RederivedFile],
+    existing_artists: &ExistingArtists,
+    genre: &GenreOptions,
+    albums: &AlbumOptions,
+) -> StagingData {
+    let (all_artists, new_artist_records) =
+        collect_artists(files.iter().map(|f| &f.metadata), existing_artists);
+    let musicbrainz_artists =
+        collect_musicbrainz_artists(files.iter().map(|f| &f.metadata), &all_artists);
+    let (album_map, staging_albums) = collect_albums(
+        files.iter().map(|f| (f.path.as_str(), &f.metadata)),
+        &all_artists,
+        albums,
+    );
+
+    let mut staging_tracks: Vec<StagingTrack> = Vec::new();
+    let mut staging_track_genres: Vec<StagingTrackGenre> = Vec::new();
+    let mut staging_credits: Vec<StagingCredit> = Vec::new();
+
+    for f in files {
+        let album_id = album_key(&f.path, &f.metadata, &all_artists, albums)
+            .and_then(|key| album_map.get(&key).copied());
+        let file_track = FileTrack {
+            start: None,
+            end: None,
+            metadata: &f.metadata,
+        };
+        let (track, credits) = track_with_credits(
+            f.track,
+            f.file,
+            &f.path,
+            &file_track,
+            album_id,
+            &all_artists,
+            genre,
+        );
+        staging_track_genres.extend(track_genres(track.id, &f.metadata));
+        staging_tracks.push(track);
+        staging_credits.extend(credits);
+    }
+
+    StagingData {
+        collection: None,
+        artists: new_artist_records,
+        musicbrainz_artists,
+        albums: staging_albums,
+        files: Vec::new(),
+        file_tags: Vec::new(),
+        tracks: staging_tracks,
+        track_genres: staging_track_genres,
+        credits: staging_credits,
+        moved: Vec::new(),
+        modified: Vec::new(),
+        deleted: Vec::new(),
+        predecessors: Vec::new(),
+        failures: Vec::new(),
+        durations: Vec::new(),
+        aliases: Vec::new(),
+        playlists: Vec::new(),
+        playlist_entries: Vec::new(),
+        deleted_playlists: Vec::new(),
+    }
+}
+
+/// Like [`prepare_staging_data`], but for files recorded without metadata: the
+/// tags, track and duration read for each are added to its existing row, and
+/// the files that couldn't be read are recorded as failures.
+pub fn prepare_backfilled_data(
+    files: &[BackfilledFile],
+    failed: &[FailedFile],
+    existing_artists: &ExistingArtists,
+    genre: &GenreOptions,
+    albums: &AlbumOptions,
+) -> StagingData {
+    let tracks = || {
+        files.iter().flat_map(|f| {
+            file_tracks(&f.metadata, &f.cue_tracks)
+                .into_iter()
+                .map(move |track| (f.path.as_str(), track.metadata))
+        })
+    };
+    let (all_artists, new_artist_records) =
+        collect_artists(tracks().map(|(_, metadata)| metadata), existing_artists);
+    let musicbrainz_artists =
+        collect_musicbrainz_artists(tracks().map(|(_, metadata)| metadata), &all_artists);
+    let (album_map, staging_albums) = collect_albums(tracks(), &all_artists, albums);
+
+    let mut staging_file_tags: Vec<StagingFileTag> = Vec::new();
+    let mut staging_tracks: Vec<StagingTrack> = Vec::new();
+    let mut staging_track_genres: Vec<StagingTrackGenre> = Vec::new();
+    let mut staging_credits: Vec<StagingCredit> = Vec::new();
+    let mut staging_durations: Vec<StagingDuration> = Vec::new();
+
+    for f in files {
+        staging_file_tags.extend(file_tags(f.file, &f.tags));
+        for file_track in file_tracks(&f.metadata, &f.cue_tracks) {
+            let album_id = album_key(&f.path, file_track.metadata, &all_artists, albums)
+                .and_then(|key| album_map.get(&key).copied());
+            let (track, credits) = track_with_credits(
+                Uuid::new_v4(),
+                f.file,
+                &f.path,
+                &file_track,
+                album_id,
+                &all_artists,
+                genre,
+            );
+            staging_track_genres.extend(track_genres(track.id, file_track.metadata));
+            staging_tracks.push(track);
+            staging_credits.extend(credits);
+        }
+        staging_durations.push(StagingDuration {
+            id: f.file,
+            duration: f.duration,
+            audio: f.audio,
+        });
+    }
+
+    StagingData {
+        collection: None,
+        artists: new_artist_records,
+        musicbrainz_artists,
+        albums: staging_albums,
+        files: Vec::new(),
+        file_tags: staging_file_tags,
+        tracks: staging_tracks,
+        track_genres: staging_track_genres,
+        credits: staging_credits,
+        moved: Vec::new(),
+        modified: Vec::new(),
+        deleted: Vec::new(),
+        predecessors: Vec::new(),
+        failures: staging_failures(failed),
+        durations: staging_durations,
+        aliases: Vec::new(),
+        playlists: Vec::new(),
+        playlist_entries: Vec::new(),
+        deleted_playlists: Vec::new(),
     }
 }
@@ -0,0 +1,81 @@
+//! The progress line a scan keeps updating on stderr while it classifies files.
+//!
+//! Files are classified on many threads at once, so updates come from all of
+//! them. The line is redrawn at most every [`INTERVAL`], and not at all by a
+//! scan that finishes within it, so fast scans don't print anything. Nothing is
+//! drawn unless stderr is a terminal.
+
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How often the line is redrawn at most.
+const INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct ProgressLine {
+    start: Instant,
+    /// When the line was last drawn, if it has been. Held while drawing.
+    drawn: Mutex<Option<Instant>>,
+    enabled: bool,
+}
+
+impl ProgressLine {
+    /// Starts timing the scan, for its ETA.
+    pub fn start() -> ProgressLine {
+        ProgressLine {
+            start: Instant::now(),
+            drawn: Mutex::new(None),
+            enabled: io::stderr().is_terminal(),
+        }
+    }
+
+    /// Reports `done` of `total` files classified, redrawing the line if it's
+    /// due. Threads that find it being drawn move on rather than wait.
+    pub fn update(&self, done: usize, total: usize) {
+        if !self.enabled {
+            return;
+        }
+        let Ok(mut drawn) = self.drawn.try_lock() else {
+            return;
+        };
+        let now = Instant::now();
+        // A line that was drawn is always brought to its end.
+        let finished = done == total && drawn.is_some();
+        if !finished && now.duration_since(drawn.unwrap_or(self.start)) < INTERVAL {
+            return;
+        }
+        let elapsed = now.duration_since(self.start);
+        let mut line = format!("\rScan: {done}/{total} files");
+        if let Some(percent) = (done * 100).checked_div(total) {
+            let _ = write!(line, " ({percent}%)");
+        }
+        if done > 0 && done < total {
+            let eta = elapsed.mul_f64((total - done) as f64 / done as f64);
+            let _ = write!(line, ", about {} left", format_duration(eta));
+        }
+        // Clear what's left of a longer line drawn before.
+        line.push_str("\x1b[K");
+        let _ = io::stderr().write_all(line.as_bytes());
+        *drawn = Some(now);
+    }
+
+    /// Ends the line, if one was drawn, so that later output starts on a line
+    /// of its own.
+    pub fn finish(&self) {
+        let drawn = self.drawn.lock().unwrap_or_else(PoisonError::into_inner);
+        if drawn.is_some() {
+            eprintln!();
+        }
+    }
+}
+
+/// `1h05m`, `3m20s` or `42s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use duckdb::Connection;
+use uuid::Uuid;
+
+use super::album::AlbumOptions;
+use super::genre::GenreOptions;
+use super::metadata::assemble_tags_into_metadata;
+use super::prepare;
+use super::staging;
+use super::tags::StoredTag;
+use super::types::RederivedFile;
+
+/// Load the stored tags of every live file, keyed by file id.
+fn load_stored_tags(conn: &Connection) -> Result<HashMap<Uuid, Vec<StoredTag>>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT ft.file, ft.key, ft.std_key, ft.value
+         FROM file_tag ft JOIN file f ON f.id = ft.file
+         WHERE f.deletion IS NULL
+         ORDER BY ft.file, ft.ord",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let file: String = row.get(0)?;
+        let tag = StoredTag {
+            key: row.get(1)?,
+            std_key: row.get(2)?,
+            value: row.get(3)?,
+        };
+        Ok((file, tag))
+    })?;
+
+    let mut map: HashMap<Uuid, Vec<StoredTag>> = HashMap::new();
+    for row in rows {
+        let (file, tag) = row?;
+        if let Ok(id) = Uuid::parse_str(&file) {
+            map.entry(id).or_default().push(tag);
+        }
+    }
+    Ok(map)
+}
+
+/// Load `(file id, path, track id)` for every track of a live file, except the
+/// tracks a cue sheet split a file into, which the file's tags don't describe.
+fn load_live_tracks(conn: &Connection) -> Result<Vec<(Uuid, String, Uuid)>, duckdb::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT f.id, f.path, t.id
+         FROM track t JOIN file f ON f.id = t.file
+         WHERE f.deletion IS NULL AND t.start_position IS NULL",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let file: String = row.get(0)?;
+        let path: String = row.get(1)?;
+        let track: String = row.get(2)?;
+        Ok((file, path, track))
+    })?;
+
+    let mut tracks = Vec::new();
+    for row in rows {
+        let (file, path, track) = row?;
+        if let (Ok(file), Ok(track)) = (Uuid::parse_str(&file), Uuid::parse_str(&track)) {
+            tracks.push((file, path, track));
+        }
+    }
+    Ok(tracks)
+}
+
+/// Rebuild tracks, albums, artists and credits from the tags stored during
+/// previous scans, without reading the audio files.
+///
+/// Useful after changing how tags are normalized. Files scanned before tags
+/// were stored have nothing to re-derive from and are left as they are.
+pub fn rederive(
+    conn: &Connection,
+    genre: &GenreOptions,
+    albums: &AlbumOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let existing_artists = staging::load_existing_artists(conn)?;
+    let stored_tags = load_stored_tags(conn)?;
+
+    let mut files = Vec::new();
+    let mut without_tags = 0;
+    for (file, path, track) in load_live_tracks(conn)? {
+        let Some(tags) = stored_tags.get(&file) else {
+            without_tags += 1;
+            continue;
+        };
+        let tags: Vec<_> = tags.iter().map(StoredTag::to_tag).collect();
+        files.push(RederivedFile {
+            path,
+            file,
+            track,
+            metadata: assemble_tags_into_metadata(&tags),
+        });
+    }
+
+    tracing::info!(
+        "Rederive: {} tracks, {} skipped without stored tags",
+        files.len(),
+        without_tags,
+    );
+
+    let staging_data = prepare::prepare_rederived_data(&files, &existing_artists, genre, albums);
+
+    staging::execute_rederive(conn, &staging_data)?;
+    conn.execute_batch("CHECKPOINT;")?;
+
+    tracing::info!("Rederive complete.");
+    Ok(())
+}
@@ -1,37 +1,514 @@
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use clap::Args;
 use duckdb::Connection;
+use globset::Glob;
+use uuid::Uuid;
 
-use super::classify;
+use super::album::AlbumOptions;
+use super::classify::{self, parse_extension};
+use super::collection;
+use super::duplicates::find_duplicates;
+use super::enrich;
+use super::exclude::parse_glob;
+use super::genre::GenreOptions;
+use super::playlist;
 use super::prepare;
+use super::progress::ProgressLine;
+use super::scan_log::ScanLog;
+use super::since::{self, Since, parse_since};
 use super::staging;
+use super::symlink::SymlinkRule;
+use super::types::{ExistingFiles, FailedFile, MetadataError, ScanResults};
 
-pub fn scan(collection_path: &Path, conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+/// The share of files, in percent, that a scan may fail to read before it's
+/// taken for a collection that went away, unless `--max-unreadable` says.
+const MAX_UNREADABLE: f64 = 10.0;
+
+/// The share of the library's files, in percent, that a scan may mark deleted,
+/// unless `--max-delete` says.
+const MAX_DELETE: f64 = 50.0;
+
+/// The fewest deletions [`check_mass_delete`] refuses, so that small libraries
+/// stay easy to change.
+const MIN_MASS_DELETE: usize = 10;
+
+/// Options for a scan. The safety switches still let the scan add new files and
+/// update modified ones.
+#[derive(Args, Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ScanOptions {
+    /// Never mark files missing from the collection as deleted
+    #[arg(long)]
+    pub no_delete: bool,
+
+    /// Add files that look like moves of missing files as new files instead of
+    /// re-pointing the existing ones
+    #[arg(long)]
+    pub no_move: bool,
+
+    /// Measure the duration of MPEG audio files by reading every packet instead
+    /// of trusting the header-based estimate. Slower: each new or modified MP3
+    /// is read in full
+    #[arg(long)]
+    pub accurate_duration: bool,
+
+    /// Store every tag of each new or modified file that holds text, rather
+    /// than only those the library is derived from, so any of them can be
+    /// queried from `file_tag`. Makes the database larger
+    #[arg(long)]
+    pub capture_all_tags: bool,
+
+    /// Only hash and record new files, leaving their metadata to be read by a
+    /// backfill (which the server runs in the background). New files have no
+    /// track until then, and don't take over the user data of files they replace
+    #[arg(long)]
+    pub defer_metadata: bool,
+
+    /// Descend at most this many directory levels below the collection root (0
+    /// only scans files directly in it). Files below the limit count as missing
+    #[arg(long, value_name = "DEPTH")]
+    pub max_depth: Option<usize>,
+
+    /// Classify this many files at once [default: the number of logical CPUs].
+    /// Hashing and reading files is mostly waiting on the disk, so on a hard
+    /// disk, where reads of many files at once fight over the head, fewer
+    /// threads are often faster
+    #[arg(long, value_name = "N")]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Take the files of directories not modified since this time as they
+    /// were, without looking at them: `last` for the start of the last scan,
+    /// or a timestamp such as `2026-10-01T12:00:00Z` or date such as
+    /// `2026-10-01`. Misses files edited in place, which leave their directory
+    /// as it was
+    #[arg(long, value_name = "TIME", value_parser = parse_since)]
+    pub since: Option<Since>,
+
+    /// Descend into symlinked directories, which are skipped otherwise. A
+    /// directory reached more than once, e.g. through a symlink loop, is only
+    /// scanned the first time
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Skip paths matching this glob, relative to the collection root, e.g.
+    /// `_artwork` or `**/*.bak`. Repeatable. A matching directory is skipped
+    /// with everything in it. Files already in the library count as missing
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub exclude: Vec<Glob>,
+
+    /// Only scan files with these extensions, comma-separated, instead of the
+    /// usual audio ones, e.g. `flac,mp3` or, to add some, `flac,mp3,caf,mp4`.
+    /// Files already in the library with other extensions count as missing
+    #[arg(long, value_name = "EXT,...", value_delimiter = ',', value_parser = parse_extension)]
+    pub extensions: Vec<String>,
+
+    /// What to do with paths that go through a symlink to another file of the
+    /// collection
+    #[arg(long, value_enum, default_value_t)]
+    pub symlinks: SymlinkRule,
+
+    /// Write one JSON line per file to this path, recording how the scan
+    /// classified it and why
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// After the scan, list the files whose content is identical to another
+    /// file's, grouped by content hash
+    #[arg(long)]
+    pub report_duplicates: bool,
+
+    /// Classify the files and list every new, moved, modified, deleted and
+    /// failed file, without writing anything to the database
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Read the `.m3u`, `.m3u8` and `.pls` playlists in the collection into the
+    /// library, each entry with the file its path leads to
+    #[arg(long)]
+    pub playlists: bool,
+
+    /// Compute an acoustic fingerprint of each new or modified file, so that
+    /// re-tagged and re-encoded copies count as duplicates, and a missing file
+    /// is replaced by a new one with the same audio. CPU-heavy: the first two
+    /// minutes of each file are decoded
+    #[arg(long)]
+    pub fingerprint: bool,
+
+    /// Look up the new files whose tags give no title or artist on `AcoustID`
+    /// by their acoustic fingerprint, and fill in the title, artists and album
+    /// of the recording found. Needs an `AcoustID` API key in
+    /// `ACOUSTID_API_KEY`. Lookups are cached; files `AcoustID` can't be
+    /// reached for stay as tagged
+    #[arg(long)]
+    pub enrich: bool,
+
+    /// Save what the scan found to the database before writing it, so that a
+    /// scan killed while writing can be finished with `--resume` instead of
+    /// reading every file again
+    #[arg(long)]
+    pub checkpoint: bool,
+
+    /// First write what an interrupted `--checkpoint` scan of the collection
+    /// saved, then scan as usual
+    #[arg(long, conflicts_with = "discard_checkpoint")]
+    pub resume: bool,
+
+    /// Drop what an interrupted `--checkpoint` scan saved, then scan as usual
+    #[arg(long)]
+    pub discard_checkpoint: bool,
+
+    /// Stop the scan, writing nothing, when more than this percentage of the
+    /// files found can't be read, as when the drive or network mount the
+    /// collection is on goes away mid-scan [default: 10]. Files that can't be
+    /// read are never marked deleted
+    #[arg(long, value_name = "PERCENT")]
+    pub max_unreadable: Option<f64>,
+
+    /// Refuse to mark more than this percentage of the library's files deleted
+    /// in one scan, listing them instead, as when the scan is pointed at the
+    /// wrong directory [default: 50]. Scans deleting fewer than 10 files are
+    /// let through
+    #[arg(long, value_name = "PERCENT")]
+    pub max_delete: Option<f64>,
+
+    /// Mark the files missing from the collection deleted however many there
+    /// are, past `--max-delete`
+    #[arg(long)]
+    pub allow_mass_delete: bool,
+
+    /// Give the files scanned before collections were recorded to the first
+    /// collection scanned, as when the database is kept outside it. Otherwise
+    /// only the collection whose root holds the database takes them over
+    #[arg(long)]
+    pub adopt_legacy_files: bool,
+
+    #[command(flatten)]
+    pub genre: GenreOptions,
+
+    #[command(flatten)]
+    pub album: AlbumOptions,
+}
+
+#[tracing::instrument(skip_all, fields(collection = %collection_path.display()))]
+#[allow(clippy::needless_pass_by_value)]
+pub fn scan(
+    collection_path: &Path,
+    conn: &Connection,
+    options: ScanOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let started = SystemTime::now();
+    if !options.dry_run {
+        finish_interrupted(collection_path, conn, &options)?;
+    }
+    // Recorded once there's something to write, so dry runs record nothing.
+    let recorded = collection::find(conn, collection_path)?;
+    let since = match options.since {
+        Some(since) => since::cutoff(conn, recorded, since)?,
+        None => None,
+    };
+    if matches!(options.since, Some(Since::LastScan)) && since.is_none() {
+        tracing::info!("Scan: no scan recorded yet, so every directory is scanned");
+    }
     let existing_artists = staging::load_existing_artists(conn)?;
-    let existing_files = staging::load_existing_files(conn)?;
+    let existing_files =
+        collection::existing_files(conn, collection_path, recorded, options.adopt_legacy_files)?;
+    let log = ScanLog::open(options.log_file.as_deref())?;
 
-    let mut results = classify::classify_all(collection_path, &existing_files);
+    let progress = ProgressLine::start();
+    let mut results = classify::classify_all(
+        collection_path,
+        &existing_files,
+        &options,
+        since,
+        &log,
+        &|done, total| progress.update(done, total),
+    )?;
+    progress.finish();
 
-    println!(
-        "Scan: {} skipped, {} moved, {} modified, {} new",
+    tracing::info!(
+        "Scan: {} skipped, {} moved, {} modified, {} new, {} failed",
         results.skipped.len(),
         results.moved.len(),
         results.modified.len(),
         results.new_files.len(),
+        results.failed.len(),
     );
 
-    classify::resolve_conflicts(&mut results);
+    classify::resolve_conflicts(&mut results, &options, &log);
+    log_failures(&results);
+    check_readable(collection_path, &results, &options)?;
+
+    let deleted_ids = if options.no_delete {
+        tracing::info!("Scan: deletions not recorded (--no-delete)");
+        Vec::new()
+    } else {
+        let deleted_ids = classify::detect_deletions(&results, &existing_files);
+        log.deleted(&deleted_ids, &existing_files);
+        let fingerprints = if options.fingerprint {
+            staging::load_fingerprints(conn, &deleted_ids)?
+        } else {
+            HashMap::new()
+        };
+        classify::link_predecessors(&mut results, &deleted_ids, &existing_files, &fingerprints);
+        tracing::info!("Scan: {} deleted", deleted_ids.len());
+        deleted_ids
+    };
+
+    log.finish()?;
 
-    let deleted_ids = classify::detect_deletions(&results, &existing_files);
-    println!("Scan: {} deleted", deleted_ids.len());
+    if options.dry_run {
+        print_dry_run(&results, &deleted_ids, &existing_files);
+        return Ok(());
+    }
 
-    let staging_data = prepare::prepare_staging_data(&results, &existing_artists, deleted_ids);
+    check_mass_delete(collection_path, &deleted_ids, &existing_files, &options)?;
+    let collection = collection::register(conn, collection_path, options.adopt_legacy_files)?;
 
-    staging::create_staging_tables(conn)?;
-    staging::insert_staging_data(conn, &staging_data)?;
-    staging::execute_batch(conn)?;
+    if options.enrich {
+        enrich::enrich(collection_path, conn, &mut results.new_files)?;
+    }
+
+    let mut staging_data = prepare::prepare_staging_data(
+        &results,
+        &existing_artists,
+        deleted_ids,
+        &options.genre,
+        &options.album,
+    );
+    staging_data.collection = Some(collection);
+    if options.playlists {
+        let existing_playlists = staging::load_existing_playlists(conn, collection)?;
+        playlist::stage_playlists(
+            collection_path,
+            &options,
+            &existing_playlists,
+            &mut staging_data,
+        )?;
+        tracing::info!(
+            "Scan: {} playlists, {} removed",
+            staging_data.playlists.len(),
+            staging_data.deleted_playlists.len(),
+        );
+    }
+
+    if options.checkpoint {
+        let path = collection_path.display().to_string();
+        staging::execute_batch_with_checkpoint(conn, &staging_data, &path)?;
+    } else {
+        staging::execute_batch(conn, &staging_data)?;
+    }
+    since::record_scan(conn, collection, started)?;
     conn.execute_batch("CHECKPOINT;")?;
 
-    println!("Scan complete.");
+    if options.report_duplicates {
+        print_duplicates(conn)?;
+    }
+
+    tracing::info!("Scan complete.");
+    Ok(())
+}
+
+/// Refuses to go on with a scan that lost its collection: one whose directory
+/// can't be listed any more, or that couldn't read more than
+/// `--max-unreadable` percent of its files. Going on would mark the files it
+/// didn't find deleted.
+pub(super) fn check_readable(
+    collection_path: &Path,
+    results: &ScanResults,
+    options: &ScanOptions,
+) -> Result<(), String> {
+    if let Err(e) = fs::read_dir(collection_path) {
+        return Err(format!(
+            "The collection at {} can't be read any more ({e}). Is its drive or \
+             network mount still there? Nothing was written.",
+            collection_path.display()
+        ));
+    }
+    let unreadable: Vec<&FailedFile> = results
+        .failed
+        .iter()
+        .filter(|file| matches!(file.error, MetadataError::Io(_)))
+        .collect();
+    let Some(first) = unreadable.first() else {
+        return Ok(());
+    };
+    let found = results.skipped.len()
+        + results.moved.len()
+        + results.modified.len()
+        + results.new_files.len()
+        + results.failed.len();
+    let percent = 100.0 * unreadable.len() as f64 / found as f64;
+    let max = options.max_unreadable.unwrap_or(MAX_UNREADABLE);
+    if percent <= max {
+        return Ok(());
+    }
+    Err(format!(
+        "{} of {found} files ({percent:.0}%) couldn't be read, more than the {max}% \
+         --max-unreadable allows; the first was {} ({}). Is the collection's drive \
+         or network mount still there? Nothing was written; pass \
+         --max-unreadable 100 to scan anyway.",
+        unreadable.len(),
+        first.path,
+        first.error.message()
+    ))
+}
+
+/// Refuses to mark more than `--max-delete` percent of the library's files
+/// deleted at once, unless `--allow-mass-delete` is given, listing them instead:
+/// a scan of the wrong directory would otherwise delete the whole library.
+pub(super) fn check_mass_delete(
+    collection_path: &Path,
+    deleted_ids: &[Uuid],
+    existing: &ExistingFiles,
+    options: &ScanOptions,
+) -> Result<(), String> {
+    let live = existing.by_path.len();
+    let percent = 100.0 * deleted_ids.len() as f64 / live.max(1) as f64;
+    let max = options.max_delete.unwrap_or(MAX_DELETE);
+    if options.allow_mass_delete || deleted_ids.len() < MIN_MASS_DELETE || percent <= max {
+        return Ok(());
+    }
+    let deleted: HashSet<&Uuid> = deleted_ids.iter().collect();
+    print_category(
+        "Would delete",
+        existing
+            .by_path
+            .iter()
+            .filter(|(_, (id, ..))| deleted.contains(id))
+            .map(|(path, _)| path.clone()),
+    );
+    Err(format!(
+        "The scan would mark {} of the library's {live} files ({percent:.0}%) deleted, \
+         more than the {max}% --max-delete allows; they're listed above. Is {} the \
+         right collection? Nothing was written; pass --allow-mass-delete to delete \
+         them anyway.",
+        deleted_ids.len(),
+        collection_path.display()
+    ))
+}
+
+/// Writes or drops what an interrupted `--checkpoint` scan saved, as `options`
+/// say. A scan that finds one and was told neither is refused, since going on
+/// would lose it.
+fn finish_interrupted(
+    collection_path: &Path,
+    conn: &Connection,
+    options: &ScanOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(checkpoint) = staging::load_checkpoint(conn)? else {
+        return Ok(());
+    };
+    let collection = collection_path.display().to_string();
+    if options.discard_checkpoint {
+        staging::discard_checkpoint(conn)?;
+        tracing::info!("Scan: dropped interrupted scan {}", checkpoint.id);
+        Ok(())
+    } else if !options.resume {
+        Err(format!(
+            "Scan {} of {} was interrupted at {} before its findings were written. \
+             Pass --resume to write them or --discard-checkpoint to drop them.",
+            checkpoint.id, checkpoint.collection, checkpoint.saved
+        )
+        .into())
+    } else if checkpoint.collection != collection {
+        Err(format!(
+            "Scan {} to resume was of {}, not {collection}.",
+            checkpoint.id, checkpoint.collection
+        )
+        .into())
+    } else {
+        staging::execute_checkpoint(conn)?;
+        tracing::info!(
+            "Scan: wrote interrupted scan {} from {}",
+            checkpoint.id,
+            checkpoint.saved
+        );
+        Ok(())
+    }
+}
+
+/// Prints the paths of the files a scan would change, grouped by how.
+fn print_dry_run(results: &ScanResults, deleted_ids: &[Uuid], existing: &ExistingFiles) {
+    let paths: HashMap<Uuid, &str> = existing
+        .by_path
+        .iter()
+        .map(|(path, (id, ..))| (*id, path.as_str()))
+        .collect();
+    let path_of = |id: &Uuid| paths.get(id).copied().unwrap_or_default();
+
+    print_category(
+        "New",
+        results.new_files.iter().map(|nf| match &nf.predecessor {
+            Some(predecessor) if !predecessor.stays_live => {
+                format!("{} (replaces {})", nf.path, path_of(&predecessor.file))
+            }
+            _ => nf.path.clone(),
+        }),
+    );
+    print_category(
+        "Moved",
+        results
+            .moved
+            .iter()
+            .map(|m| format!("{} -> {}", path_of(&m.id), m.path)),
+    );
+    print_category("Modified", results.modified.iter().map(|m| m.path.clone()));
+    print_category(
+        "Deleted",
+        deleted_ids.iter().map(|id| path_of(id).to_string()),
+    );
+    print_category(
+        "Failed",
+        results
+            .failed
+            .iter()
+            .chain(&results.errors)
+            .map(|f| format!("{}: {}", f.path, f.error.message())),
+    );
+    tracing::info!("Scan: dry run, nothing written");
+}
+
+/// Warns of the files that couldn't be read, grouped by why, so they can be
+/// fixed or removed.
+pub(super) fn log_failures(results: &ScanResults) {
+    for (category, paths) in results.failures_by_category() {
+        tracing::warn!(
+            "{} files failed to read ({category}):\n\t{}",
+            paths.len(),
+            paths.join("\n\t")
+        );
+    }
+}
+
+/// Prints `lines` sorted and indented under a heading with their count, or
+/// nothing when there are none.
+fn print_category(name: &str, lines: impl Iterator<Item = String>) {
+    let mut lines: Vec<String> = lines.collect();
+    if lines.is_empty() {
+        return;
+    }
+    lines.sort();
+    println!("{name} ({}):", lines.len());
+    for line in &lines {
+        println!("\t{line}");
+    }
+}
+
+/// Prints each group of duplicates as its hash (or `acoustic match` for copies
+/// whose content differs) followed by its paths, one per indented line.
+fn print_duplicates(conn: &Connection) -> Result<(), duckdb::Error> {
+    let groups = find_duplicates(conn)?;
+    for group in &groups {
+        println!("{}", group.hash.as_deref().unwrap_or("acoustic match"));
+        for path in &group.paths {
+            println!("\t{path}");
+        }
+    }
+    tracing::info!("Scan: {} groups of duplicates", groups.len());
     Ok(())
 }
@@ -0,0 +1,210 @@
+//! The optional JSON Lines audit log of a scan (`--log-file`).
+//!
+//! Every file the scan looks at gets one line, written as soon as the file is
+//! classified, so a scan that's interrupted still leaves a usable log. Files
+//! reconsidered after classification (moves conflicting with modifications,
+//! deletions) get an additional line each. When no log file is configured
+//! every method is a no-op.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use super::symlink::SymlinkRule;
+use super::types::{
+    ExistingFiles, FailedFile, FileAlias, FileClassification, NewFileData, TrackMetadata,
+};
+
+pub struct ScanLog {
+    inner: Option<Mutex<LogWriter>>,
+}
+
+struct LogWriter {
+    out: BufWriter<File>,
+    /// The first write error, reported by [`ScanLog::finish`]. Later lines are
+    /// dropped.
+    error: Option<io::Error>,
+}
+
+impl ScanLog {
+    /// Creates (or truncates) the log file, or returns a disabled log.
+    pub fn open(path: Option<&Path>) -> io::Result<ScanLog> {
+        let inner = match path {
+            Some(path) => Some(Mutex::new(LogWriter {
+                out: BufWriter::new(File::create(path)?),
+                error: None,
+            })),
+            None => None,
+        };
+        Ok(ScanLog { inner })
+    }
+
+    fn write(&self, entry: &Value) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let mut writer = inner.lock().unwrap();
+        if writer.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(writer.out, "{entry}") {
+            writer.error = Some(e);
+        }
+    }
+
+    /// Logs how a file was classified, and `error` when reading it failed all
+    /// the same. `None` means the file couldn't be read at all.
+    pub fn classified(
+        &self,
+        path: &str,
+        classification: Option<&FileClassification>,
+        error: Option<&FailedFile>,
+        existing: &ExistingFiles,
+    ) {
+        if self.inner.is_none() {
+            return;
+        }
+        let mut entry = match classification {
+            Some(FileClassification::Skipped { .. }) => json!({
+                "path": path,
+                "classification": "skipped",
+                "reason": "size and mtime unchanged",
+            }),
+            Some(FileClassification::Moved { id, .. }) => json!({
+                "path": path,
+                "classification": "moved",
+                "reason": "content matches a file missing from its recorded path",
+                "file": id.to_string(),
+                "from": existing_path(existing, *id),
+            }),
+            Some(FileClassification::Modified { id, hash, .. }) => {
+                let unchanged = existing.by_path.get(path).is_some_and(|e| e.1 == *hash);
+                let reason = if unchanged {
+                    "mtime changed, content unchanged"
+                } else {
+                    "content changed"
+                };
+                json!({
+                    "path": path,
+                    "classification": "modified",
+                    "reason": reason,
+                    "file": id.to_string(),
+                })
+            }
+            Some(FileClassification::New(data)) => json!({
+                "path": path,
+                "classification": "new",
+                "reason": "not in the library",
+                "format": data.format.as_str(),
+                "metadata": read_metadata(data),
+            }),
+            Some(FileClassification::Failed(failed)) => json!({
+                "path": path,
+                "classification": "error",
+                "reason": failed.error.message(),
+                "category": failed.error.category(),
+            }),
+            None => json!({
+                "path": path,
+                "classification": "error",
+                "reason": "could not read the file",
+                "category": "io",
+            }),
+        };
+        if let Some(failed) = error {
+            entry["error"] = json!(failed.error.message());
+            entry["category"] = json!(failed.error.category());
+        }
+        self.write(&entry);
+    }
+
+    /// Logs a path-matched modification that was re-read as a new file because
+    /// its file id was matched as moved elsewhere.
+    pub fn reclassified(&self, path: &str, classification: Option<&FileClassification>) {
+        if self.inner.is_none() {
+            return;
+        }
+        let reason = "the file recorded at this path was matched as moved elsewhere";
+        let entry = match classification {
+            Some(FileClassification::New(data)) => json!({
+                "path": path,
+                "classification": "new",
+                "reason": reason,
+                "format": data.format.as_str(),
+                "metadata": read_metadata(data),
+            }),
+            Some(FileClassification::Failed(failed)) => json!({
+                "path": path,
+                "classification": "error",
+                "reason": failed.error.message(),
+                "category": failed.error.category(),
+            }),
+            _ => return,
+        };
+        self.write(&entry);
+    }
+
+    /// Logs a path that is an alias of another file through a symlink, which
+    /// `rule` either records or skips.
+    pub fn alias(&self, alias: &FileAlias, rule: SymlinkRule) {
+        if self.inner.is_none() {
+            return;
+        }
+        let classification = match rule {
+            SymlinkRule::Skip => "skipped",
+            SymlinkRule::Alias => "alias",
+        };
+        self.write(&json!({
+            "path": alias.path,
+            "classification": classification,
+            "reason": "symlink to another file of the collection",
+            "target": alias.target,
+        }));
+    }
+
+    pub fn deleted(&self, deleted_ids: &[Uuid], existing: &ExistingFiles) {
+        if self.inner.is_none() {
+            return;
+        }
+        for (path, (id, ..)) in &existing.by_path {
+            if deleted_ids.contains(id) {
+                self.write(&json!({
+                    "path": path,
+                    "classification": "deleted",
+                    "reason": "no longer in the collection",
+                    "file": id.to_string(),
+                }));
+            }
+        }
+    }
+
+    /// Flushes the log, returning the first error hit while writing it.
+    pub fn finish(self) -> io::Result<()> {
+        let Some(inner) = self.inner else {
+            return Ok(());
+        };
+        let mut writer = inner.into_inner().unwrap();
+        match writer.error.take() {
+            Some(e) => Err(e),
+            None => writer.out.flush(),
+        }
+    }
+}
+
+fn existing_path(existing: &ExistingFiles, id: Uuid) -> Option<&str> {
+    existing
+        .by_path
+        .iter()
+        .find(|(_, entry)| entry.0 == id)
+        .map(|(path, _)| path.as_str())
+}
+
+/// The metadata read from a new file, or `None` (logged as null) when the scan
+/// deferred reading it.
+fn read_metadata(data: &NewFileData) -> Option<&TrackMetadata> {
+    data.duration.map(|_| &data.metadata)
+}
@@ -0,0 +1,82 @@
+//! `scan --since`: the cutoff before which directories count as unchanged, and
+//! the record of the last scan it can default to.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use duckdb::{Connection, OptionalExt, params};
+use uuid::Uuid;
+
+/// Where a `--since` cutoff comes from.
+#[derive(Clone, Copy, Debug)]
+pub enum Since {
+    /// The start of the last scan written (see [`record_scan`]).
+    LastScan,
+    At(SystemTime),
+}
+
+/// Parses a `--since` value: `last`, an RFC 3339 timestamp such as
+/// `2026-10-01T12:00:00Z`, or a date such as `2026-10-01`, taken as its
+/// midnight in the local time zone.
+pub fn parse_since(value: &str) -> Result<Since, String> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("last") {
+        return Ok(Since::LastScan);
+    }
+    if let Ok(timestamp) = value.parse::<jiff::Timestamp>() {
+        return Ok(Since::At(timestamp.into()));
+    }
+    let date: jiff::civil::Date = value.parse().map_err(|_| {
+        format!("expected `last`, a timestamp like 2026-10-01T12:00:00Z or a date, not '{value}'")
+    })?;
+    let zoned = date
+        .to_zoned(jiff::tz::TimeZone::system())
+        .map_err(|e| e.to_string())?;
+    Ok(Since::At(zoned.timestamp().into()))
+}
+
+/// The cutoff `since` stands for in `collection`. `None` when it's the last
+/// scan and none was recorded yet. A collection not recorded yet goes by the
+/// scan recorded before collections were, if any, which it's about to take
+/// over.
+pub(super) fn cutoff(
+    conn: &Connection,
+    collection: Option<Uuid>,
+    since: Since,
+) -> Result<Option<SystemTime>, duckdb::Error> {
+    match since {
+        Since::At(time) => Ok(Some(time)),
+        Since::LastScan => {
+            let micros: Option<i64> = conn
+                .query_row(
+                    "SELECT epoch_us(started) FROM meta.last_scan \
+                     WHERE collection IS NOT DISTINCT FROM ?::UUID",
+                    [collection.map(|id| id.to_string())],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(micros.map(|micros| UNIX_EPOCH + std::time::Duration::from_micros(micros as u64)))
+        }
+    }
+}
+
+/// Records that the scan of `collection` started at `started` was written, for
+/// a later `--since last`.
+pub(super) fn record_scan(
+    conn: &Connection,
+    collection: Uuid,
+    started: SystemTime,
+) -> Result<(), duckdb::Error> {
+    let micros = started
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_micros() as i64);
+    conn.execute(
+        "DELETE FROM meta.last_scan \
+                     WHERE collection IS NOT DISTINCT FROM ?::UUID",
+        [collection.to_string()],
+    )?;
+    conn.execute(
+        "INSERT INTO meta.last_scan (started, collection) VALUES (make_timestamp(?), ?::UUID)",
+        params![micros, collection.to_string()],
+    )?;
+    Ok(())
+}
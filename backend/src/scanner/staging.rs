@@ -1,12 +1,23 @@
-use duckdb::Connection;
 use duckdb::params;
-use std::collections::HashMap;
+use duckdb::{Connection, OptionalExt, Params};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::time::Duration;
 use uuid::Uuid;
 
-use super::types::{ExistingFiles, StagingData};
+use super::fingerprint;
+use super::types::{ExistingArtists, ExistingFiles, StagingData};
 
-pub fn load_existing_artists(conn: &Connection) -> Result<HashMap<String, Uuid>, duckdb::Error> {
-    let mut stmt = conn.prepare("SELECT id, name FROM artist")?;
+pub fn load_existing_artists(conn: &Connection) -> Result<ExistingArtists, duckdb::Error> {
+    Ok(ExistingArtists {
+        by_name: load_name_map(conn, "SELECT id, name FROM artist")?,
+        aliases: load_name_map(conn, "SELECT artist, alias FROM artist_alias")?,
+    })
+}
+
+/// Runs `sql`, which selects an id and a name, into a map from name to id.
+fn load_name_map(conn: &Connection, sql: &str) -> Result<HashMap<String, Uuid>, duckdb::Error> {
+    let mut stmt = conn.prepare(sql)?;
     let rows = stmt.query_map([], |row| {
         let id_str: String = row.get(0)?;
         let name: String = row.get(1)?;
@@ -23,10 +34,35 @@ pub fn load_existing_artists(conn: &Connection) -> Result<HashMap<String, Uuid>,
     Ok(map)
 }
 
-pub fn load_existing_files(conn: &Connection) -> Result<ExistingFiles, duckdb::Error> {
-    let mut stmt =
-        conn.prepare("SELECT id, path, hash, size, mtime FROM file WHERE deletion IS NULL")?;
-    let rows = stmt.query_map([], |row| {
+/// The live files of `collection`, or of the whole library when `None`.
+pub fn load_existing_files(
+    conn: &Connection,
+    collection: Option<Uuid>,
+) -> Result<ExistingFiles, duckdb::Error> {
+    let collection = collection.map(|id| id.to_string());
+    load_files(
+        conn,
+        "?::UUID IS NULL OR collection = ?::UUID",
+        params![collection, collection],
+    )
+}
+
+/// The live files scanned before collections were recorded, which have none.
+pub(super) fn load_legacy_files(conn: &Connection) -> Result<ExistingFiles, duckdb::Error> {
+    load_files(conn, "collection IS NULL", [])
+}
+
+/// The live files that `filter` holds for.
+fn load_files(
+    conn: &Connection,
+    filter: &str,
+    params: impl Params,
+) -> Result<ExistingFiles, duckdb::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, path, hash, size, mtime FROM file
+         WHERE deletion IS NULL AND ({filter})"
+    ))?;
+    let rows = stmt.query_map(params, |row| {
         let id_str: String = row.get(0)?;
         let path: String = row.get(1)?;
         let hash_blob: Vec<u8> = row.get(2)?;
@@ -53,28 +89,132 @@ pub fn load_existing_files(conn: &Connection) -> Result<ExistingFiles, duckdb::E
     Ok(ExistingFiles { by_path, by_hash })
 }
 
-pub fn create_staging_tables(conn: &Connection) -> Result<(), duckdb::Error> {
+/// The acoustic fingerprints of the files `ids`, for those that have one.
+pub fn load_fingerprints(
+    conn: &Connection,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<u32>>, duckdb::Error> {
+    let mut stmt = conn.prepare("SELECT fingerprint FROM file WHERE id = ?::UUID")?;
+    let mut fingerprints = HashMap::new();
+    for id in ids {
+        let blob: Option<Vec<u8>> = stmt
+            .query_row([id.to_string()], |row| row.get(0))
+            .optional()?
+            .flatten();
+        if let Some(blob) = blob {
+            fingerprints.insert(*id, fingerprint::from_blob(&blob));
+        }
+    }
+    Ok(fingerprints)
+}
+
+/// The paths of the playlists of `collection`.
+pub fn load_existing_playlists(
+    conn: &Connection,
+    collection: Uuid,
+) -> Result<HashSet<String>, duckdb::Error> {
+    let mut stmt = conn.prepare("SELECT path FROM playlist WHERE collection = ?::UUID")?;
+    let paths = stmt.query_map([collection.to_string()], |row| row.get(0))?;
+    paths.collect()
+}
+
+/// The temporary tables [`create_staging_tables`] creates.
+const STAGING_TABLES: &[&str] = &[
+    "staging_collection",
+    "staging_artist",
+    "staging_musicbrainz_artist",
+    "staging_album",
+    "staging_file",
+    "staging_file_tag",
+    "staging_track",
+    "staging_track_genre",
+    "staging_credit",
+    "staging_moved",
+    "staging_modified",
+    "staging_deleted",
+    "staging_predecessor",
+    "staging_failure",
+    "staging_duration",
+    "staging_alias",
+    "staging_playlist",
+    "staging_playlist_entry",
+    "staging_deleted_playlist",
+];
+
+fn create_staging_tables(conn: &Connection) -> Result<(), duckdb::Error> {
     conn.execute_batch(
         "
-        CREATE TEMP TABLE staging_artist (id UUID, name TEXT);
-        CREATE TEMP TABLE staging_album (id UUID, title TEXT, year USMALLINT);
-        CREATE TEMP TABLE staging_file (
+        CREATE OR REPLACE TEMP TABLE staging_collection (id UUID);
+        CREATE OR REPLACE TEMP TABLE staging_artist (id UUID, name TEXT);
+        CREATE OR REPLACE TEMP TABLE staging_musicbrainz_artist (artist UUID, musicbrainz_id UUID);
+        CREATE OR REPLACE TEMP TABLE staging_album (
+            id UUID, title TEXT, year USMALLINT, release_date TEXT, musicbrainz_release_id UUID,
+            artist UUID
+        );
+        CREATE OR REPLACE TEMP TABLE staging_file (
             id UUID, path TEXT, hash BLOB, size UINTEGER,
-            format format, duration REAL, mtime BIGINT
+            format format, duration REAL, sample_rate UINTEGER, channels UTINYINT,
+            bits_per_sample UTINYINT, bitrate UINTEGER, fingerprint BLOB, mtime BIGINT
         );
-        CREATE TEMP TABLE staging_track (
-            id UUID, file UUID, title TEXT, album UUID,
-            disc_number UTINYINT, track_number UTINYINT, genre TEXT
+        CREATE OR REPLACE TEMP TABLE staging_file_tag (
+            file UUID, ord USMALLINT, key TEXT, std_key TEXT, value TEXT
         );
-        CREATE TEMP TABLE staging_credit (track UUID, artist UUID, ord REAL, role TEXT);
-        CREATE TEMP TABLE staging_moved (id UUID, new_path TEXT, mtime BIGINT);
-        CREATE TEMP TABLE staging_modified (id UUID, hash BLOB, size UINTEGER, duration REAL, mtime BIGINT);
-        CREATE TEMP TABLE staging_deleted (file_id UUID, deletion_id UUID);
+        CREATE OR REPLACE TEMP TABLE staging_track (
+            id UUID, file UUID, start_position REAL, end_position REAL, title TEXT, album UUID,
+            disc_number UTINYINT, disc_total UTINYINT,
+            track_number UTINYINT, track_total UTINYINT, primary_genre TEXT,
+            bpm REAL, musical_key TEXT, track_gain REAL, album_gain REAL,
+            track_peak REAL, album_peak REAL, lyrics TEXT, comment TEXT,
+            musicbrainz_recording_id UUID
+        );
+        CREATE OR REPLACE TEMP TABLE staging_track_genre (track UUID, genre TEXT, ord REAL);
+        CREATE OR REPLACE TEMP TABLE staging_credit (track UUID, artist UUID, ord REAL, role TEXT);
+        CREATE OR REPLACE TEMP TABLE staging_moved (id UUID, new_path TEXT, mtime BIGINT);
+        CREATE OR REPLACE TEMP TABLE staging_modified (
+            id UUID, hash BLOB, size UINTEGER, duration REAL, sample_rate UINTEGER,
+            channels UTINYINT, bits_per_sample UTINYINT, bitrate UINTEGER, fingerprint BLOB,
+            mtime BIGINT
+        );
+        CREATE OR REPLACE TEMP TABLE staging_deleted (file_id UUID, deletion_id UUID);
+        CREATE OR REPLACE TEMP TABLE staging_predecessor (file UUID, predecessor UUID, move_plays BOOLEAN);
+        CREATE OR REPLACE TEMP TABLE staging_failure (path TEXT, category TEXT, message TEXT);
+        CREATE OR REPLACE TEMP TABLE staging_duration (
+            id UUID, duration REAL, sample_rate UINTEGER, channels UTINYINT,
+            bits_per_sample UTINYINT, bitrate UINTEGER
+        );
+        CREATE OR REPLACE TEMP TABLE staging_alias (path TEXT, target TEXT);
+        CREATE OR REPLACE TEMP TABLE staging_playlist (path TEXT, name TEXT);
+        CREATE OR REPLACE TEMP TABLE staging_playlist_entry (
+            playlist TEXT, ord UINTEGER, path TEXT, file_path TEXT
+        );
+        CREATE OR REPLACE TEMP TABLE staging_deleted_playlist (path TEXT);
         ",
     )
 }
 
-pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+fn drop_staging_tables(conn: &Connection) -> Result<(), duckdb::Error> {
+    let sql = STAGING_TABLES.iter().fold(String::new(), |mut sql, table| {
+        let _ = write!(sql, "DROP TABLE IF EXISTS {table};");
+        sql
+    });
+    conn.execute_batch(&sql)
+}
+
+fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    insert_staging_rows(conn, data)?;
+    insert_staging_changes(conn, data)
+}
+
+/// Stage the rows to be inserted: artists, albums, files and their tracks.
+#[allow(clippy::too_many_lines)]
+fn insert_staging_rows(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    if let Some(collection) = data.collection {
+        conn.execute(
+            "INSERT INTO staging_collection VALUES (?::UUID)",
+            [collection.to_string()],
+        )?;
+    }
+
     {
         let mut app = conn.appender("staging_artist")?;
         for a in &data.artists {
@@ -83,11 +223,29 @@ pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(),
         app.flush()?;
     }
 
+    {
+        let mut app = conn.appender("staging_musicbrainz_artist")?;
+        for a in &data.musicbrainz_artists {
+            app.append_row(params![a.artist.to_string(), a.musicbrainz_id.to_string()])?;
+        }
+        app.flush()?;
+    }
+
     {
         let mut app = conn.appender("staging_album")?;
         for a in &data.albums {
             let year: Option<u16> = a.year;
-            app.append_row(params![a.id.to_string(), a.title, year])?;
+            let release_date: Option<String> = a.release_date.map(|d| d.to_string());
+            let release: Option<String> = a.musicbrainz_release_id.map(|u| u.to_string());
+            let artist: Option<String> = a.artist.map(|u| u.to_string());
+            app.append_row(params![
+                a.id.to_string(),
+                a.title,
+                year,
+                release_date,
+                release,
+                artist
+            ])?;
         }
         app.flush()?;
     }
@@ -100,37 +258,75 @@ pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(),
                 f.path,
                 f.hash.as_slice(),
                 f.size as u32,
-                f.format,
-                f.duration as f32,
+                f.format.as_str(),
+                f.duration.map(|d| d as f32),
+                f.audio.sample_rate,
+                f.audio.channels,
+                f.audio.bits_per_sample,
+                f.audio.bitrate,
+                f.fingerprint.as_deref().map(fingerprint::to_blob),
                 f.mtime,
             ])?;
         }
         app.flush()?;
     }
 
+    {
+        let mut app = conn.appender("staging_file_tag")?;
+        for t in &data.file_tags {
+            let std_key: Option<&str> = t.std_key.as_deref();
+            app.append_row(params![t.file.to_string(), t.ord, t.key, std_key, t.value])?;
+        }
+        app.flush()?;
+    }
+
     {
         let mut app = conn.appender("staging_track")?;
         for t in &data.tracks {
             let album: Option<String> = t.album.map(|u| u.to_string());
             let disc: Option<u8> = t.disc_number;
+            let disc_total: Option<u8> = t.disc_total;
             let track_num: Option<u8> = t.track_number;
+            let track_total: Option<u8> = t.track_total;
             app.append_row(params![
                 t.id.to_string(),
                 t.file.to_string(),
+                t.start_position.map(|p| p as f32),
+                t.end_position.map(|p| p as f32),
                 t.title,
                 album,
                 disc,
+                disc_total,
                 track_num,
-                t.genre,
+                track_total,
+                t.primary_genre,
+                t.bpm,
+                t.musical_key,
+                t.track_gain,
+                t.album_gain,
+                t.track_peak,
+                t.album_peak,
+                t.lyrics,
+                t.comment,
+                t.musicbrainz_recording_id.map(|u| u.to_string()),
             ])?;
         }
         app.flush()?;
     }
 
+    {
+        let mut app = conn.appender("staging_track_genre")?;
+        for g in &data.track_genres {
+            app.append_row(params![g.track.to_string(), g.genre, g.ord as f32])?;
+        }
+        app.flush()?;
+    }
+
     {
         let mut app = conn.appender("staging_credit")?;
         for c in &data.credits {
-            let role: Option<&str> = c.role.as_deref();
+            // The artists' own credits have the role '' (see migration 0032).
+            let role = c.role.as_deref().unwrap_or_default();
             app.append_row(params![
                 c.track.to_string(),
                 c.artist.to_string(),
@@ -141,6 +337,13 @@ pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(),
         app.flush()?;
     }
 
+    Ok(())
+}
+
+/// Stage the changes to files already in the database: moves, modifications,
+/// deletions, the files new files replace and the durations a backfill read.
+/// Also stages this scan's metadata failures, symlink aliases and playlists.
+fn insert_staging_changes(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
     {
         let mut app = conn.appender("staging_moved")?;
         for m in &data.moved {
@@ -157,6 +360,11 @@ pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(),
                 m.hash.as_slice(),
                 m.size as u32,
                 m.duration as f32,
+                m.audio.sample_rate,
+                m.audio.channels,
+                m.audio.bits_per_sample,
+                m.audio.bitrate,
+                m.fingerprint.as_deref().map(fingerprint::to_blob),
                 m.mtime,
             ])?;
         }
@@ -171,30 +379,167 @@ pub fn insert_staging_data(conn: &Connection, data: &StagingData) -> Result<(),
         app.flush()?;
     }
 
+    {
+        let mut app = conn.appender("staging_predecessor")?;
+        for p in &data.predecessors {
+            app.append_row(params![
+                p.file.to_string(),
+                p.predecessor.to_string(),
+                p.move_plays,
+            ])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_failure")?;
+        for f in &data.failures {
+            app.append_row(params![f.path, f.category, f.message])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_duration")?;
+        for d in &data.durations {
+            app.append_row(params![
+                d.id.to_string(),
+                d.duration as f32,
+                d.audio.sample_rate,
+                d.audio.channels,
+                d.audio.bits_per_sample,
+                d.audio.bitrate,
+            ])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_alias")?;
+        for a in &data.aliases {
+            app.append_row(params![a.path, a.target])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_playlist")?;
+        for p in &data.playlists {
+            app.append_row(params![p.path, p.name])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_playlist_entry")?;
+        for e in &data.playlist_entries {
+            let file_path: Option<&str> = e.file_path.as_deref();
+            app.append_row(params![e.playlist, e.ord, e.path, file_path])?;
+        }
+        app.flush()?;
+    }
+
+    {
+        let mut app = conn.appender("staging_deleted_playlist")?;
+        for path in &data.deleted_playlists {
+            app.append_row(params![path])?;
+        }
+        app.flush()?;
+    }
+
     Ok(())
 }
 
-const BATCH_SQL: &str = "
-BEGIN TRANSACTION;
+/// Lists the staged tracks' genres in `track_genre`, creating the genres new
+/// to the library and dropping those no track has any more.
+const TRACK_GENRE_SQL: &str = "
+INSERT INTO genre (id, name)
+SELECT uuid(), genre FROM (SELECT DISTINCT genre FROM staging_track_genre)
+WHERE genre NOT IN (SELECT name FROM genre);
+
+INSERT INTO track_genre (track, genre, ord)
+SELECT stg.track, g.id, stg.ord
+FROM staging_track_genre stg JOIN genre g ON g.name = stg.genre;
 
+DELETE FROM genre WHERE id NOT IN (SELECT genre FROM track_genre);
+";
+
+const BATCH_SQL: &str = "
 INSERT INTO artist (id, name) SELECT id, name FROM staging_artist;
-INSERT INTO album (id, title, year) SELECT id, title, year FROM staging_album;
+UPDATE artist SET musicbrainz_id = sma.musicbrainz_id
+FROM staging_musicbrainz_artist sma WHERE artist.id = sma.artist;
+INSERT INTO album (id, title, year, release_date, musicbrainz_release_id, artist)
+SELECT id, title, year, release_date::DATE, musicbrainz_release_id, artist FROM staging_album;
+
+INSERT INTO file (id, path, hash, size, format, duration, duration_interval, sample_rate,
+                  channels, bits_per_sample, bitrate, fingerprint, mtime, added, modified,
+                  deletion, collection)
+SELECT id, path, hash, size, format, duration,
+       to_microseconds((duration::DOUBLE * 1000000)::BIGINT), sample_rate, channels,
+       bits_per_sample, bitrate, fingerprint, mtime, now(), now(), NULL,
+       (SELECT id FROM staging_collection)
+FROM staging_file;
 
-INSERT INTO file (id, path, hash, size, format, duration, mtime, added, deletion)
-SELECT id, path, hash, size, format, duration, mtime, now(), NULL FROM staging_file;
+INSERT INTO file_tag (file, ord, key, std_key, value)
+SELECT file, ord, key, std_key, value FROM staging_file_tag;
 
 INSERT INTO track (id, file, start_position, end_position, title, album,
-                   disc_number, track_number, genre, rating)
-SELECT id, file, NULL, NULL, title, album, disc_number, track_number, genre, NULL
+                   disc_number, disc_total, track_number, track_total, primary_genre,
+                   bpm, musical_key, track_gain, album_gain, track_peak, album_peak,
+                   lyrics, comment, musicbrainz_recording_id, rating)
+SELECT id, file, start_position, end_position, title, album,
+       disc_number, disc_total, track_number, track_total,
+       primary_genre, bpm, musical_key, track_gain, album_gain, track_peak, album_peak,
+       lyrics, comment, musicbrainz_recording_id, NULL
 FROM staging_track;
 
 INSERT INTO credit (track, artist, ord, role)
 SELECT track, artist, ord, role FROM staging_credit;
 
+-- New files that replace older ones take over their rating and added date, and
+-- their plays when the older file leaves the library. The tracks of an older
+-- file split by a cue sheet keep theirs.
+UPDATE track SET rating = old.rating
+FROM (
+    SELECT sp.file, t.rating
+    FROM staging_predecessor sp JOIN track t ON t.file = sp.predecessor
+    WHERE t.rating IS NOT NULL AND t.start_position IS NULL
+) old
+WHERE track.file = old.file;
+
+UPDATE file SET added = old.added
+FROM (
+    SELECT sp.file, f.added
+    FROM staging_predecessor sp JOIN file f ON f.id = sp.predecessor
+) old
+WHERE file.id = old.file;
+
+INSERT OR IGNORE INTO play (track, timestamp)
+SELECT new_track.id, p.timestamp
+FROM staging_predecessor sp
+JOIN track old_track ON old_track.file = sp.predecessor
+JOIN play p ON p.track = old_track.id
+JOIN track new_track ON new_track.file = sp.file
+WHERE sp.move_plays AND old_track.start_position IS NULL;
+
+DELETE FROM play WHERE track IN (
+    SELECT t.id FROM staging_predecessor sp JOIN track t ON t.file = sp.predecessor
+    WHERE sp.move_plays AND t.start_position IS NULL
+);
+
 UPDATE file SET path = sm.new_path, mtime = sm.mtime
 FROM staging_moved sm WHERE file.id = sm.id;
 
-UPDATE file SET hash = sm.hash, size = sm.size, duration = sm.duration, mtime = sm.mtime
+-- A file whose metadata a backfill hasn't read yet keeps its NULL duration.
+UPDATE file SET hash = sm.hash, size = sm.size, mtime = sm.mtime,
+                duration = CASE WHEN file.duration IS NULL THEN NULL ELSE sm.duration END,
+                duration_interval = CASE WHEN file.duration IS NULL THEN NULL
+                    ELSE to_microseconds((sm.duration::DOUBLE * 1000000)::BIGINT) END,
+                sample_rate = sm.sample_rate, channels = sm.channels,
+                bits_per_sample = sm.bits_per_sample, bitrate = sm.bitrate,
+                fingerprint = CASE WHEN file.hash = sm.hash THEN file.fingerprint
+                                   ELSE sm.fingerprint END,
+                modified = CASE WHEN file.hash = sm.hash THEN file.modified ELSE now() END
 FROM staging_modified sm WHERE file.id = sm.id;
 
 INSERT INTO deletion (id, timestamp)
@@ -203,9 +548,383 @@ SELECT DISTINCT deletion_id, now() FROM staging_deleted;
 UPDATE file SET deletion = sd.deletion_id
 FROM staging_deleted sd WHERE file.id = sd.file_id;
 
-COMMIT;
+-- Playlists read again replace their entries, each pointing at the live file
+-- at its path once the files above are in place; playlists gone from the
+-- collection are dropped with theirs.
+DELETE FROM playlist_entry WHERE playlist IN (
+    SELECT id FROM playlist
+    WHERE collection = (SELECT id FROM staging_collection)
+      AND path IN (SELECT path FROM staging_playlist UNION ALL
+                   SELECT path FROM staging_deleted_playlist)
+);
+DELETE FROM playlist
+WHERE collection = (SELECT id FROM staging_collection)
+  AND path IN (SELECT path FROM staging_deleted_playlist);
+INSERT INTO playlist (id, path, name, collection)
+SELECT uuid(), path, name, (SELECT id FROM staging_collection) FROM staging_playlist
+WHERE path NOT IN (SELECT path FROM playlist
+                   WHERE collection = (SELECT id FROM staging_collection));
+UPDATE playlist SET name = sp.name
+FROM staging_playlist sp
+WHERE playlist.collection = (SELECT id FROM staging_collection)
+  AND playlist.path = sp.path AND playlist.name <> sp.name;
+INSERT INTO playlist_entry (playlist, ord, path, file)
+SELECT p.id, spe.ord, spe.path, f.id
+FROM staging_playlist_entry spe
+JOIN playlist p ON p.path = spe.playlist AND p.collection = (SELECT id FROM staging_collection)
+LEFT JOIN file f ON f.path = spe.file_path AND f.deletion IS NULL
+                AND f.collection = p.collection;
+";
+
+/// The findings of a full scan, which replace those of the previous one of the
+/// same collection.
+const SCAN_FINDINGS_SQL: &str = "
+-- Every scan finds the symlinks in the collection afresh, so its aliases
+-- replace the previous ones. Each points at the live file of its target path,
+-- inserted or moved there above.
+DELETE FROM file_alias WHERE collection = (SELECT id FROM staging_collection);
+INSERT INTO file_alias (collection, path, file)
+SELECT f.collection, sa.path, f.id
+FROM staging_alias sa
+JOIN file f ON f.path = sa.target AND f.deletion IS NULL
+           AND f.collection = (SELECT id FROM staging_collection);
+
+-- Every scan re-reads the files that failed before, so its failures replace
+-- the previous ones.
+DELETE FROM scan_failure WHERE collection = (SELECT id FROM staging_collection);
+INSERT INTO scan_failure (collection, path, category, message, scanned_at)
+SELECT (SELECT id FROM staging_collection), path, category, message, now()
+FROM staging_failure;
+";
+
+/// The findings of a watched change, which only looked at some paths: its
+/// failures replace those of the same paths, and aliases are left for the next
+/// scan to find.
+const WATCH_FINDINGS_SQL: &str = "
+DELETE FROM scan_failure
+WHERE collection = (SELECT id FROM staging_collection)
+  AND path IN (SELECT path FROM staging_failure UNION ALL SELECT path FROM staging_file);
+INSERT INTO scan_failure (collection, path, category, message, scanned_at)
+SELECT (SELECT id FROM staging_collection), path, category, message, now()
+FROM staging_failure;
+";
+
+/// Replaces the normalized model of the staged tracks in place. Track ids are
+/// kept; albums are recreated, and albums left without any track and artists
+/// left without any credit or album are dropped.
+const REDERIVE_SQL: &str = "
+INSERT INTO artist (id, name) SELECT id, name FROM staging_artist;
+UPDATE artist SET musicbrainz_id = sma.musicbrainz_id
+FROM staging_musicbrainz_artist sma WHERE artist.id = sma.artist;
+INSERT INTO album (id, title, year, release_date, musicbrainz_release_id, artist)
+SELECT id, title, year, release_date::DATE, musicbrainz_release_id, artist FROM staging_album;
+
+DELETE FROM credit WHERE track IN (SELECT id FROM staging_track);
+DELETE FROM track_genre WHERE track IN (SELECT id FROM staging_track);
+
+UPDATE track SET title = st.title, album = st.album,
+                 disc_number = st.disc_number, disc_total = st.disc_total,
+                 track_number = st.track_number, track_total = st.track_total,
+                 primary_genre = st.primary_genre,
+                 bpm = st.bpm, musical_key = st.musical_key,
+                 track_gain = st.track_gain, album_gain = st.album_gain,
+                 track_peak = st.track_peak, album_peak = st.album_peak,
+                 lyrics = st.lyrics, comment = st.comment,
+                 musicbrainz_recording_id = st.musicbrainz_recording_id
+FROM staging_track st WHERE track.id = st.id;
+
+INSERT INTO credit (track, artist, ord, role)
+SELECT track, artist, ord, role FROM staging_credit;
+
+DELETE FROM album WHERE id NOT IN (SELECT album FROM track WHERE album IS NOT NULL);
+DELETE FROM artist WHERE id NOT IN (SELECT artist FROM credit)
+                     AND id NOT IN (SELECT artist FROM album WHERE artist IS NOT NULL)
+                     AND id NOT IN (SELECT artist FROM artist_alias);
+";
+
+/// Adds the tracks of files whose metadata a backfill read, and records the
+/// files it couldn't read, alongside the failures of the last scan.
+const BACKFILL_SQL: &str = "
+INSERT INTO artist (id, name) SELECT id, name FROM staging_artist;
+UPDATE artist SET musicbrainz_id = sma.musicbrainz_id
+FROM staging_musicbrainz_artist sma WHERE artist.id = sma.artist;
+INSERT INTO album (id, title, year, release_date, musicbrainz_release_id, artist)
+SELECT id, title, year, release_date::DATE, musicbrainz_release_id, artist FROM staging_album;
+
+INSERT INTO file_tag (file, ord, key, std_key, value)
+SELECT file, ord, key, std_key, value FROM staging_file_tag;
+
+INSERT INTO track (id, file, start_position, end_position, title, album,
+                   disc_number, disc_total, track_number, track_total, primary_genre,
+                   bpm, musical_key, track_gain, album_gain, track_peak, album_peak,
+                   lyrics, comment, musicbrainz_recording_id, rating)
+SELECT id, file, start_position, end_position, title, album,
+       disc_number, disc_total, track_number, track_total,
+       primary_genre, bpm, musical_key, track_gain, album_gain, track_peak, album_peak,
+       lyrics, comment, musicbrainz_recording_id, NULL
+FROM staging_track;
+
+INSERT INTO credit (track, artist, ord, role)
+SELECT track, artist, ord, role FROM staging_credit;
+
+UPDATE file SET duration = sd.duration,
+                duration_interval = to_microseconds((sd.duration::DOUBLE * 1000000)::BIGINT),
+                sample_rate = sd.sample_rate, channels = sd.channels,
+                bits_per_sample = sd.bits_per_sample, bitrate = sd.bitrate
+FROM staging_duration sd WHERE file.id = sd.id;
+
+DELETE FROM scan_failure
+WHERE collection = (SELECT id FROM staging_collection)
+  AND path IN (SELECT path FROM staging_failure);
+INSERT INTO scan_failure (collection, path, category, message, scanned_at)
+SELECT (SELECT id FROM staging_collection), path, category, message, now()
+FROM staging_failure;
+";
+
+/// Refuses to commit a write that leaves the library pointing at rows that
+/// aren't there. `DuckDB` can't enforce foreign keys on these tables (see
+/// migration 0026), so a bug in classifying or preparing files would
+/// otherwise leave orphans behind without a word.
+const INTEGRITY_SQL: &str = "
+SELECT error(count(*) || ' track(s) would point at a missing file') FROM track
+WHERE file NOT IN (SELECT id FROM file) HAVING count(*) > 0;
+SELECT error(count(*) || ' track(s) would point at a missing album') FROM track
+WHERE album IS NOT NULL AND album NOT IN (SELECT id FROM album) HAVING count(*) > 0;
+SELECT error(count(*) || ' credit(s) would point at a missing track') FROM credit
+WHERE track NOT IN (SELECT id FROM track) HAVING count(*) > 0;
+SELECT error(count(*) || ' credit(s) would point at a missing artist') FROM credit
+WHERE artist NOT IN (SELECT id FROM artist) HAVING count(*) > 0;
 ";
 
-pub fn execute_batch(conn: &Connection) -> Result<(), duckdb::Error> {
-    conn.execute_batch(BATCH_SQL)
+const ALBUM_COMPLETENESS_SQL: &str = "
+-- Recompute album completeness from the live tracks. Each disc expects as many
+-- tracks as its largest track_total and is complete when every number from 1 to
+-- that total is present; the album additionally expects disc_total discs. An
+-- album with any disc lacking a track_total is left unknown (NULL), as is one
+-- left without live tracks.
+UPDATE album SET is_complete = s.is_complete
+FROM (
+    WITH disc AS (
+        SELECT t.album,
+               coalesce(t.disc_number, 1) AS disc,
+               max(t.track_total) AS expected,
+               count(DISTINCT t.track_number)
+                   FILTER (WHERE t.track_number BETWEEN 1 AND t.track_total) AS present,
+               max(t.disc_total) AS disc_total
+        FROM track t JOIN file f ON f.id = t.file
+        WHERE t.album IS NOT NULL AND f.deletion IS NULL
+        GROUP BY t.album, coalesce(t.disc_number, 1)
+    )
+    SELECT album,
+           CASE WHEN bool_or(expected IS NULL) THEN NULL
+                ELSE bool_and(present >= expected) AND count(*) >= coalesce(max(disc_total), 0)
+           END AS is_complete
+    FROM disc
+    GROUP BY album
+) s
+WHERE album.id = s.album;
+UPDATE album SET is_complete = NULL
+WHERE is_complete IS NOT NULL
+  AND id NOT IN (
+      SELECT t.album FROM track t JOIN file f ON f.id = t.file
+      WHERE t.album IS NOT NULL AND f.deletion IS NULL
+  );
+";
+
+/// How many times a write is attempted before a transient error is given up on.
+const WRITE_ATTEMPTS: u32 = 5;
+/// How long to wait before retrying a write the first time; each retry waits
+/// twice as long as the one before.
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Stage `data`, then run `sql` on it followed by the integrity checks and the
+/// album completeness refresh, all as one transaction.
+fn execute_staged(conn: &Connection, data: &StagingData, sql: &str) -> Result<(), duckdb::Error> {
+    let sql = [sql, INTEGRITY_SQL, ALBUM_COMPLETENESS_SQL].concat();
+    with_retries(|| try_in_transaction(conn, || stage(conn, data), &sql))
+}
+
+/// Creates the staging tables and fills them with `data`.
+fn stage(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    create_staging_tables(conn)?;
+    insert_staging_data(conn, data)
+}
+
+/// Runs `write` until it succeeds, fails for good or runs out of attempts.
+///
+/// A write that loses a conflict with a concurrent one (such as the server
+/// writing while a scan runs) is rolled back and retried from scratch, with
+/// exponential backoff. Other errors are returned right away.
+fn with_retries(mut write: impl FnMut() -> Result<(), duckdb::Error>) -> Result<(), duckdb::Error> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match write() {
+            Err(e) if attempt < WRITE_ATTEMPTS && is_transient(&e) => {
+                tracing::warn!("Write failed ({e}), retrying in {delay:?}");
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// One attempt of a staged write: `stage` fills the staging tables, then `sql`
+/// runs on them, all as one transaction. On failure the transaction is rolled
+/// back and the staging tables dropped, so the next attempt starts clean.
+fn try_in_transaction(
+    conn: &Connection,
+    stage: impl FnOnce() -> Result<(), duckdb::Error>,
+    sql: &str,
+) -> Result<(), duckdb::Error> {
+    conn.execute_batch("BEGIN TRANSACTION;")?;
+    let result = stage().and_then(|()| conn.execute_batch(&[sql, "COMMIT;"].concat()));
+    if result.is_err() {
+        // Fails when the error already ended the transaction.
+        let _ = conn.execute_batch("ROLLBACK;");
+        let _ = drop_staging_tables(conn);
+    }
+    result
+}
+
+/// Whether `e` comes from a conflict with another write or a lock held
+/// elsewhere, which may well not happen again.
+fn is_transient(e: &duckdb::Error) -> bool {
+    let message = e.to_string().to_lowercase();
+    message.contains("conflict") || message.contains("could not set lock")
+}
+
+/// The write of a full scan.
+fn scan_sql() -> String {
+    [BATCH_SQL, TRACK_GENRE_SQL, SCAN_FINDINGS_SQL].concat()
+}
+
+pub fn execute_batch(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    execute_staged(conn, data, &scan_sql())
+}
+
+pub fn execute_watched(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    execute_staged(
+        conn,
+        data,
+        &[BATCH_SQL, TRACK_GENRE_SQL, WATCH_FINDINGS_SQL].concat(),
+    )
+}
+
+pub fn execute_rederive(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    execute_staged(conn, data, &[REDERIVE_SQL, TRACK_GENRE_SQL].concat())
+}
+
+pub fn execute_backfill(conn: &Connection, data: &StagingData) -> Result<(), duckdb::Error> {
+    execute_staged(conn, data, &[BACKFILL_SQL, TRACK_GENRE_SQL].concat())
+}
+
+/// The schema a scan run with `--checkpoint` saves its staging tables in until
+/// they're written, so that a scan killed while writing can be resumed.
+const CHECKPOINT_SCHEMA: &str = "scan_checkpoint";
+
+/// The findings of a scan saved with `--checkpoint` and not written yet.
+pub struct Checkpoint {
+    pub id: Uuid,
+    /// The collection the scan was of, as given to it.
+    pub collection: String,
+    /// When the findings were saved.
+    pub saved: String,
+}
+
+/// The findings of an interrupted scan, if there are any.
+pub fn load_checkpoint(conn: &Connection) -> Result<Option<Checkpoint>, duckdb::Error> {
+    let saved: bool = conn.query_row(
+        "SELECT count(*) > 0 FROM information_schema.tables
+         WHERE table_schema = ? AND table_name = 'scan'",
+        [CHECKPOINT_SCHEMA],
+        |row| row.get(0),
+    )?;
+    if !saved {
+        return Ok(None);
+    }
+    conn.query_row(
+        &format!("SELECT id::TEXT, collection, saved::TEXT FROM {CHECKPOINT_SCHEMA}.scan"),
+        [],
+        |row| {
+            let id: String = row.get(0)?;
+            Ok(Checkpoint {
+                id: Uuid::parse_str(&id).unwrap_or_default(),
+                collection: row.get(1)?,
+                saved: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Like [`execute_batch`], but first saves `data` as a checkpoint of the scan
+/// of `collection`, committed on its own. Should the write then be cut short,
+/// the checkpoint stays behind for [`execute_checkpoint`] to write later.
+pub fn execute_batch_with_checkpoint(
+    conn: &Connection,
+    data: &StagingData,
+    collection: &str,
+) -> Result<(), duckdb::Error> {
+    let copies = STAGING_TABLES
+        .iter()
+        .fold(String::new(), |mut copies, table| {
+            let _ = write!(
+                copies,
+                "CREATE OR REPLACE TABLE {CHECKPOINT_SCHEMA}.{table} AS SELECT * FROM {table};"
+            );
+            copies
+        });
+    let save = format!(
+        "CREATE SCHEMA IF NOT EXISTS {CHECKPOINT_SCHEMA};
+         CREATE OR REPLACE TABLE {CHECKPOINT_SCHEMA}.scan (
+             id UUID, collection TEXT, saved TIMESTAMP
+         );
+         {copies}"
+    );
+    let id = Uuid::new_v4().to_string();
+    with_retries(|| {
+        let stage_and_save = || {
+            stage(conn, data)?;
+            conn.execute_batch(&save)?;
+            conn.execute(
+                &format!("INSERT INTO {CHECKPOINT_SCHEMA}.scan VALUES (?::UUID, ?, now())"),
+                params![id, collection],
+            )
+            .map(|_| ())
+        };
+        try_in_transaction(conn, stage_and_save, "")
+    })?;
+    execute_checkpoint(conn)
+}
+
+/// Writes the findings of the interrupted scan, as its write would have, and
+/// drops them.
+pub fn execute_checkpoint(conn: &Connection) -> Result<(), duckdb::Error> {
+    let restore = STAGING_TABLES
+        .iter()
+        .fold(String::new(), |mut restore, table| {
+            let _ = write!(
+                restore,
+                "CREATE OR REPLACE TEMP TABLE {table} AS SELECT * FROM {CHECKPOINT_SCHEMA}.{table};"
+            );
+            restore
+        });
+    let sql = [
+        scan_sql().as_str(),
+        INTEGRITY_SQL,
+        ALBUM_COMPLETENESS_SQL,
+        format!("DROP SCHEMA {CHECKPOINT_SCHEMA} CASCADE;").as_str(),
+    ]
+    .concat();
+    with_retries(|| try_in_transaction(conn, || conn.execute_batch(&restore), &sql))
+}
+
+/// Drops the findings of the interrupted scan without writing them.
+pub fn discard_checkpoint(conn: &Connection) -> Result<(), duckdb::Error> {
+    conn.execute_batch(&format!(
+        "DROP SCHEMA IF EXISTS {CHECKPOINT_SCHEMA} CASCADE;"
+    ))
 }
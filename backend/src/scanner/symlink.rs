@@ -0,0 +1,89 @@
+//! Paths that reach a file of the collection through a symlink, either to the
+//! file itself or to a directory above it. Scans only go through symlinked
+//! directories with `--follow-symlinks`, and skip those leading to a directory
+//! they've already been through, so it's mostly the former.
+//!
+//! Paths are normalized by resolving symlinks, so such a path would otherwise
+//! be classified a second time under its target's path, colliding with the
+//! target. When the target is in the library or found directly by the scan,
+//! the path is an alias of it instead: left out of the library, or recorded in
+//! `file_alias` pointing at the target's file.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use super::classify::normalize_path;
+use super::types::{ExistingFiles, FileAlias};
+
+/// What a scan does with a path that is an alias of another file.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkRule {
+    /// Leave the alias out of the library
+    #[default]
+    Skip,
+    /// Record the alias in `file_alias`, pointing at the file it resolves to
+    Alias,
+}
+
+/// The path of `path` relative to the collection as found, without resolving
+/// symlinks, prefixed with `./` like the paths the library stores.
+pub(super) fn found_path(path: &Path, collection_path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(collection_path).ok()?;
+    Some(format!("./{}", rel.display()))
+}
+
+/// The path `path` resolves to within the collection, or `None` when it
+/// resolves to somewhere outside it.
+fn resolved_path(path: &Path, canonical_root: &Path) -> Option<String> {
+    let canonical = fs::canonicalize(path).ok()?;
+    let rel = canonical.strip_prefix(canonical_root).ok()?;
+    Some(format!("./{}", rel.display()))
+}
+
+/// Splits the audio files found in the collection into the files to classify,
+/// each with its normalized path, and the aliases. A path going through a
+/// symlink is an alias when the file it resolves to is in the library or
+/// found directly; of several paths resolving to a file that's neither, the
+/// first is classified and the others are its aliases.
+pub(super) fn find_aliases(
+    files: Vec<PathBuf>,
+    collection_path: &Path,
+    canonical_root: &Path,
+    existing: &ExistingFiles,
+) -> (Vec<(PathBuf, String)>, Vec<FileAlias>) {
+    let mut direct = Vec::new();
+    let mut symlinked = Vec::new();
+    for path in files {
+        match (
+            found_path(&path, collection_path),
+            resolved_path(&path, canonical_root),
+        ) {
+            (Some(found), Some(target)) if found != target => {
+                symlinked.push((path, found, target));
+            }
+            _ => {
+                let path_str = normalize_path(&path, canonical_root);
+                direct.push((path, path_str));
+            }
+        }
+    }
+
+    let mut indexed: HashSet<String> = existing.by_path.keys().cloned().collect();
+    indexed.extend(direct.iter().map(|(_, path_str)| path_str.clone()));
+    let mut aliases = Vec::new();
+    for (path, found, target) in symlinked {
+        if indexed.contains(&target) {
+            aliases.push(FileAlias {
+                path: found,
+                target,
+            });
+        } else {
+            indexed.insert(target.clone());
+            direct.push((path, target));
+        }
+    }
+    (direct, aliases)
+}
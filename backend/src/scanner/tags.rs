@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use symphonia::core::meta::{StandardTagKey, Tag, Value};
+
+use super::{dj_tags, gain};
+
+/// Every `StandardTagKey` variant. Symphonia can't enumerate them or parse one
+/// back from its name, which is needed to rebuild tags read from `file_tag`.
+static STANDARD_TAG_KEYS: &[StandardTagKey] = &[
+    StandardTagKey::AcoustidFingerprint,
+    StandardTagKey::AcoustidId,
+    StandardTagKey::Album,
+    StandardTagKey::AlbumArtist,
+    StandardTagKey::Arranger,
+    StandardTagKey::Artist,
+    StandardTagKey::Bpm,
+    StandardTagKey::Comment,
+    StandardTagKey::Compilation,
+    StandardTagKey::Composer,
+    StandardTagKey::Conductor,
+    StandardTagKey::ContentGroup,
+    StandardTagKey::Copyright,
+    StandardTagKey::Date,
+    StandardTagKey::Description,
+    StandardTagKey::DiscNumber,
+    StandardTagKey::DiscSubtitle,
+    StandardTagKey::DiscTotal,
+    StandardTagKey::EncodedBy,
+    StandardTagKey::Encoder,
+    StandardTagKey::EncoderSettings,
+    StandardTagKey::EncodingDate,
+    StandardTagKey::Engineer,
+    StandardTagKey::Ensemble,
+    StandardTagKey::Genre,
+    StandardTagKey::IdentAsin,
+    StandardTagKey::IdentBarcode,
+    StandardTagKey::IdentCatalogNumber,
+    StandardTagKey::IdentEanUpn,
+    StandardTagKey::IdentIsrc,
+    StandardTagKey::IdentPn,
+    StandardTagKey::IdentPodcast,
+    StandardTagKey::IdentUpc,
+    StandardTagKey::Label,
+    StandardTagKey::Language,
+    StandardTagKey::License,
+    StandardTagKey::Lyricist,
+    StandardTagKey::Lyrics,
+    StandardTagKey::MediaFormat,
+    StandardTagKey::MixDj,
+    StandardTagKey::MixEngineer,
+    StandardTagKey::Mood,
+    StandardTagKey::MovementName,
+    StandardTagKey::MovementNumber,
+    StandardTagKey::MusicBrainzAlbumArtistId,
+    StandardTagKey::MusicBrainzAlbumId,
+    StandardTagKey::MusicBrainzArtistId,
+    StandardTagKey::MusicBrainzDiscId,
+    StandardTagKey::MusicBrainzGenreId,
+    StandardTagKey::MusicBrainzLabelId,
+    StandardTagKey::MusicBrainzOriginalAlbumId,
+    StandardTagKey::MusicBrainzOriginalArtistId,
+    StandardTagKey::MusicBrainzRecordingId,
+    StandardTagKey::MusicBrainzReleaseGroupId,
+    StandardTagKey::MusicBrainzReleaseStatus,
+    StandardTagKey::MusicBrainzReleaseTrackId,
+    StandardTagKey::MusicBrainzReleaseType,
+    StandardTagKey::MusicBrainzTrackId,
+    StandardTagKey::MusicBrainzWorkId,
+    StandardTagKey::Opus,
+    StandardTagKey::OriginalAlbum,
+    StandardTagKey::OriginalArtist,
+    StandardTagKey::OriginalDate,
+    StandardTagKey::OriginalFile,
+    StandardTagKey::OriginalWriter,
+    StandardTagKey::Owner,
+    StandardTagKey::Part,
+    StandardTagKey::PartTotal,
+    StandardTagKey::Performer,
+    StandardTagKey::Podcast,
+    StandardTagKey::PodcastCategory,
+    StandardTagKey::PodcastDescription,
+    StandardTagKey::PodcastKeywords,
+    StandardTagKey::Producer,
+    StandardTagKey::PurchaseDate,
+    StandardTagKey::Rating,
+    StandardTagKey::ReleaseCountry,
+    StandardTagKey::ReleaseDate,
+    StandardTagKey::Remixer,
+    StandardTagKey::ReplayGainAlbumGain,
+    StandardTagKey::ReplayGainAlbumPeak,
+    StandardTagKey::ReplayGainTrackGain,
+    StandardTagKey::ReplayGainTrackPeak,
+    StandardTagKey::Script,
+    StandardTagKey::SortAlbum,
+    StandardTagKey::SortAlbumArtist,
+    StandardTagKey::SortArtist,
+    StandardTagKey::SortComposer,
+    StandardTagKey::SortTrackTitle,
+    StandardTagKey::TaggingDate,
+    StandardTagKey::TrackNumber,
+    StandardTagKey::TrackSubtitle,
+    StandardTagKey::TrackTitle,
+    StandardTagKey::TrackTotal,
+    StandardTagKey::TvEpisode,
+    StandardTagKey::TvEpisodeTitle,
+    StandardTagKey::TvNetwork,
+    StandardTagKey::TvSeason,
+    StandardTagKey::TvShowTitle,
+    StandardTagKey::Url,
+    StandardTagKey::UrlArtist,
+    StandardTagKey::UrlCopyright,
+    StandardTagKey::UrlInternetRadio,
+    StandardTagKey::UrlLabel,
+    StandardTagKey::UrlOfficial,
+    StandardTagKey::UrlPayment,
+    StandardTagKey::UrlPodcast,
+    StandardTagKey::UrlPurchase,
+    StandardTagKey::UrlSource,
+    StandardTagKey::Version,
+    StandardTagKey::Writer,
+];
+
+static STANDARD_TAG_KEYS_BY_NAME: LazyLock<HashMap<String, StandardTagKey>> = LazyLock::new(|| {
+    STANDARD_TAG_KEYS
+        .iter()
+        .map(|&key| (std_key_name(key), key))
+        .collect()
+});
+
+/// The name under which a standard tag key is stored in `file_tag.std_key`.
+fn std_key_name(key: StandardTagKey) -> String {
+    format!("{key:?}")
+}
+
+/// A tag in the form it is stored in the `file_tag` table.
+#[derive(Debug)]
+pub struct StoredTag {
+    pub key: String,
+    pub std_key: Option<String>,
+    pub value: String,
+}
+
+impl StoredTag {
+    /// Keep a tag read from a file if it can contribute to the normalized model:
+    /// it must map to a standard key and not hold binary data (e.g. cover art),
+    /// or carry a DJ field (see [`dj_tags`]), which is stored decoded, or an
+    /// R128 gain (see [`gain`]).
+    pub fn from_tag(tag: &Tag) -> Option<Self> {
+        let Some(std_key) = tag.std_key else {
+            let (key, value) = dj_tags::stored(tag)
+                .or_else(|| gain::is_r128(tag).then(|| (tag.key.clone(), tag.value.to_string())))?;
+            return Some(StoredTag {
+                key,
+                std_key: None,
+                value,
+            });
+        };
+        if matches!(tag.value, Value::Binary(_)) {
+            return None;
+        }
+        Some(StoredTag {
+            key: tag.key.clone(),
+            std_key: Some(std_key_name(std_key)),
+            value: tag.value.to_string(),
+        })
+    }
+
+    /// Keep any tag read from a file that holds text, for
+    /// `--capture-all-tags`: those [`StoredTag::from_tag`] keeps, and every
+    /// other one as it is, under its raw key.
+    pub fn from_any_tag(tag: &Tag) -> Option<Self> {
+        Self::from_tag(tag).or_else(|| {
+            (!matches!(tag.value, Value::Binary(_))).then(|| StoredTag {
+                key: tag.key.clone(),
+                std_key: None,
+                value: tag.value.to_string(),
+            })
+        })
+    }
+
+    /// Rebuild the symphonia tag. Values come back as strings, which the
+    /// metadata parsers accept for every field they read.
+    pub fn to_tag(&self) -> Tag {
+        let std_key = self
+            .std_key
+            .as_deref()
+            .and_then(|name| STANDARD_TAG_KEYS_BY_NAME.get(name).copied());
+        Tag::new(std_key, &self.key, Value::String(self.value.clone()))
+    }
+}
@@ -1,24 +1,73 @@
-use std::collections::HashMap;
+use jiff::civil::Date;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use uuid::Uuid;
 
-#[derive(Debug)]
+use super::tags::StoredTag;
+use crate::format::Format;
+
+/// The audio properties of a file's default track, as far as its codec
+/// parameters tell them.
+#[derive(Clone, Copy, Default)]
+pub struct AudioProperties {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    /// The bit depth of lossless formats; lossy ones have none.
+    pub bits_per_sample: Option<u8>,
+    /// The average bitrate in kbit/s, tags and cover art included.
+    pub bitrate: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct TrackMetadata {
     pub title: String,
     pub track_number: Option<u8>,
+    pub track_total: Option<u8>,
     pub disc_number: Option<u8>,
-    pub genre: String,
+    pub disc_total: Option<u8>,
+    /// Distinct genres in tag order.
+    pub genres: Vec<String>,
     pub album: String,
+    /// Distinct album artists in tag order.
+    pub album_artists: Vec<String>,
     pub year: Option<u16>,
+    /// The full date the year comes from, when the tag gives a month and day.
+    pub release_date: Option<Date>,
+    pub bpm: Option<f32>,
+    pub musical_key: Option<String>,
+    /// `ReplayGain` gains in dB, converted from R128 tags for Opus files.
+    pub track_gain: Option<f32>,
+    pub album_gain: Option<f32>,
+    /// `ReplayGain` peaks, where 1.0 is full scale.
+    pub track_peak: Option<f32>,
+    pub album_peak: Option<f32>,
+    /// Lyrics and comment as tagged, line breaks included.
+    pub lyrics: Option<String>,
+    pub comment: Option<String>,
     pub artists: Vec<TrackArtistMetadata>,
+    pub musicbrainz_recording_id: Option<Uuid>,
+    /// The `MusicBrainz` release, which identifies the track's album.
+    pub musicbrainz_release_id: Option<Uuid>,
+    /// The `MusicBrainz` ids of the artists and album artists in tag order,
+    /// matching them one to one when there are as many.
+    pub musicbrainz_artist_ids: Vec<Uuid>,
+    pub musicbrainz_album_artist_ids: Vec<Uuid>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct TrackArtistMetadata {
     pub artist: String,
     pub role: Option<String>,
 }
 
+pub struct ExistingArtists {
+    pub by_name: HashMap<String, Uuid>,
+    /// User-maintained alias names, each resolving to a canonical artist id.
+    pub aliases: HashMap<String, Uuid>,
+}
+
+#[derive(Default)]
 pub struct ExistingFiles {
     pub by_path: HashMap<String, (Uuid, [u8; 32], u64, i64)>, // id, hash, size, mtime_us
     pub by_hash: HashMap<[u8; 32], Vec<(Uuid, String)>>,
@@ -40,18 +89,124 @@ pub enum FileClassification {
         hash: [u8; 32],
         size: u64,
         duration: f64,
+        audio: AudioProperties,
+        fingerprint: Option<Vec<u32>>,
         mtime: i64,
     },
-    New(NewFileData),
+    New(Box<NewFileData>),
+    Failed(FailedFile),
 }
 
 pub struct NewFileData {
     pub path: String,
     pub hash: [u8; 32],
     pub size: u64,
-    pub duration: f64,
+    /// `None` when the scan deferred reading the file's metadata, in which case
+    /// it has no tags and gets no track until a backfill reads them.
+    pub duration: Option<f64>,
+    pub audio: AudioProperties,
+    /// The acoustic fingerprint, when the scan computes them and the file could
+    /// be decoded.
+    pub fingerprint: Option<Vec<u32>>,
     pub mtime: i64,
-    pub format: String,
+    pub format: Format,
+    pub metadata: TrackMetadata,
+    pub tags: Vec<StoredTag>,
+    /// The tracks a cue sheet splits the file into. Empty for a file that is a
+    /// single track, described by `metadata`.
+    pub cue_tracks: Vec<CueTrack>,
+    /// The file this one replaces, whose track's user data it takes over.
+    pub predecessor: Option<Predecessor>,
+}
+
+/// One of the tracks a cue sheet splits a file into.
+pub struct CueTrack {
+    /// Where the track starts in the file, in seconds.
+    pub start: f64,
+    /// Where the next track starts, `None` for the last track.
+    pub end: Option<f64>,
+    pub metadata: TrackMetadata,
+}
+
+/// A file in the database that a new file is confidently linked to. Its track's
+/// rating and its `added` date carry over to the new file.
+pub struct Predecessor {
+    pub file: Uuid,
+    /// Whether the predecessor stays in the library. If not, its plays move to
+    /// the new track too; otherwise they stay where they are.
+    pub stays_live: bool,
+}
+
+/// A file the scanner couldn't read metadata from. It isn't added to the
+/// library, but recorded in `scan_failure` until a scan reads it successfully.
+pub struct FailedFile {
+    pub path: String,
+    pub error: MetadataError,
+}
+
+/// Why metadata couldn't be read from a file.
+#[derive(Debug)]
+pub enum MetadataError {
+    /// The file couldn't be opened or read.
+    Io(String),
+    /// No reader recognizes the file's container or codec.
+    Unsupported(String),
+    /// The file was recognized but is malformed or truncated.
+    Malformed(String),
+    /// Reading the file panicked.
+    Panic,
+}
+
+impl MetadataError {
+    /// The short category stored alongside the failure.
+    pub fn category(&self) -> &'static str {
+        match self {
+            MetadataError::Io(_) => "io",
+            MetadataError::Unsupported(_) => "unsupported",
+            MetadataError::Malformed(_) => "malformed",
+            MetadataError::Panic => "panic",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            MetadataError::Io(msg)
+            | MetadataError::Unsupported(msg)
+            | MetadataError::Malformed(msg) => msg,
+            MetadataError::Panic => "panicked while reading the file",
+        }
+    }
+}
+
+impl From<symphonia::core::errors::Error> for MetadataError {
+    fn from(err: symphonia::core::errors::Error) -> Self {
+        use symphonia::core::errors::Error;
+        match err {
+            Error::IoError(e) => MetadataError::Io(e.to_string()),
+            Error::Unsupported(_) => MetadataError::Unsupported(err.to_string()),
+            _ => MetadataError::Malformed(err.to_string()),
+        }
+    }
+}
+
+/// A file recorded without metadata whose metadata a backfill has now read. It
+/// keeps its id; its tags and track are added to it.
+pub struct BackfilledFile {
+    pub path: String,
+    pub file: Uuid,
+    pub duration: f64,
+    pub audio: AudioProperties,
+    pub metadata: TrackMetadata,
+    pub tags: Vec<StoredTag>,
+    pub cue_tracks: Vec<CueTrack>,
+}
+
+/// A file whose normalized model is being re-derived from its stored tags. The
+/// track keeps its id so that plays and ratings stay attached to it.
+pub struct RederivedFile {
+    pub path: String,
+    pub file: Uuid,
+    pub track: Uuid,
     pub metadata: TrackMetadata,
 }
 
@@ -68,14 +223,49 @@ pub struct ModifiedEntry {
     pub hash: [u8; 32],
     pub size: u64,
     pub duration: f64,
+    pub audio: AudioProperties,
+    /// The acoustic fingerprint of the new content. `None` when the content
+    /// didn't change, which keeps the file's fingerprint.
+    pub fingerprint: Option<Vec<u32>>,
     pub mtime: i64,
 }
 
+/// A path that goes through a symlink to another file of the collection.
+pub struct FileAlias {
+    pub path: String,
+    /// The normalized path of the file it resolves to.
+    pub target: String,
+}
+
 pub struct ScanResults {
     pub skipped: Vec<String>,
     pub moved: Vec<MovedEntry>,
     pub modified: Vec<ModifiedEntry>,
     pub new_files: Vec<NewFileData>,
+    pub failed: Vec<FailedFile>,
+    /// Files recorded even though reading them failed: modified files whose
+    /// duration couldn't be measured, which is stored as 0.
+    pub errors: Vec<FailedFile>,
+    /// Aliases to record, empty unless the scan records them.
+    pub aliases: Vec<FileAlias>,
+}
+
+impl ScanResults {
+    /// The sorted paths of the files in `failed` and `errors`, by the category
+    /// of their error.
+    pub fn failures_by_category(&self) -> BTreeMap<&'static str, Vec<&str>> {
+        let mut by_category: BTreeMap<&'static str, Vec<&str>> = BTreeMap::new();
+        for file in self.failed.iter().chain(&self.errors) {
+            by_category
+                .entry(file.error.category())
+                .or_default()
+                .push(&file.path);
+        }
+        for paths in by_category.values_mut() {
+            paths.sort_unstable();
+        }
+        by_category
+    }
 }
 
 pub struct StagingArtist {
@@ -83,10 +273,21 @@ pub struct StagingArtist {
     pub name: String,
 }
 
+/// The `MusicBrainz` id of an artist, new or existing.
+pub struct StagingMusicBrainzArtist {
+    pub artist: Uuid,
+    pub musicbrainz_id: Uuid,
+}
+
 pub struct StagingAlbum {
     pub id: Uuid,
     pub title: String,
     pub year: Option<u16>,
+    pub release_date: Option<Date>,
+    pub musicbrainz_release_id: Option<Uuid>,
+    /// The primary album artist: the first album artist tagged, or the first
+    /// track artist when there's none.
+    pub artist: Option<Uuid>,
 }
 
 pub struct StagingFile {
@@ -94,19 +295,64 @@ pub struct StagingFile {
     pub path: String,
     pub hash: [u8; 32],
     pub size: u64,
-    pub format: String,
-    pub duration: f64,
+    pub format: Format,
+    pub duration: Option<f64>,
+    pub audio: AudioProperties,
+    pub fingerprint: Option<Vec<u32>>,
     pub mtime: i64,
 }
 
 pub struct StagingTrack {
     pub id: Uuid,
     pub file: Uuid,
+    /// The stretch of the file the track spans, in seconds, for a track of a
+    /// file split by a cue sheet. `None` for the start or end of the file.
+    pub start_position: Option<f64>,
+    pub end_position: Option<f64>,
     pub title: String,
     pub album: Option<Uuid>,
     pub disc_number: Option<u8>,
+    pub disc_total: Option<u8>,
     pub track_number: Option<u8>,
+    pub track_total: Option<u8>,
+    pub primary_genre: Option<String>,
+    pub bpm: Option<f32>,
+    pub musical_key: Option<String>,
+    pub track_gain: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_peak: Option<f32>,
+    pub lyrics: Option<String>,
+    pub comment: Option<String>,
+    pub musicbrainz_recording_id: Option<Uuid>,
+}
+
+pub struct StagingFileTag {
+    pub file: Uuid,
+    pub ord: u16,
+    pub key: String,
+    pub std_key: Option<String>,
+    pub value: String,
+}
+
+pub struct StagingPredecessor {
+    pub file: Uuid,
+    pub predecessor: Uuid,
+    pub move_plays: bool,
+}
+
+pub struct StagingFailure {
+    pub path: String,
+    pub category: &'static str,
+    pub message: String,
+}
+
+/// One of a track's genres, by name: genres new to the library are created
+/// when the staged rows are inserted.
+pub struct StagingTrackGenre {
+    pub track: Uuid,
     pub genre: String,
+    pub ord: f64,
 }
 
 pub struct StagingCredit {
@@ -127,21 +373,65 @@ pub struct StagingModified {
     pub hash: [u8; 32],
     pub size: u64,
     pub duration: f64,
+    pub audio: AudioProperties,
+    pub fingerprint: Option<Vec<u32>>,
     pub mtime: i64,
 }
 
+/// The duration and audio properties a backfill read for a file recorded
+/// without them.
+pub struct StagingDuration {
+    pub id: Uuid,
+    pub duration: f64,
+    pub audio: AudioProperties,
+}
+
+pub struct StagingAlias {
+    pub path: String,
+    pub target: String,
+}
+
 pub struct StagingDeleted {
     pub file_id: Uuid,
     pub deletion_id: Uuid,
 }
 
+/// A playlist of the collection, by its collection path.
+pub struct StagingPlaylist {
+    pub path: String,
+    pub name: String,
+}
+
+/// An entry of the playlist at the collection path `playlist`: the path the
+/// playlist gives, and the collection path of the file it leads to, if any.
+pub struct StagingPlaylistEntry {
+    pub playlist: String,
+    pub ord: u32,
+    pub path: String,
+    pub file_path: Option<String>,
+}
+
 pub struct StagingData {
+    /// The collection the files are of, which new files, failures, aliases
+    /// and playlists are recorded in.
+    pub collection: Option<Uuid>,
     pub artists: Vec<StagingArtist>,
+    pub musicbrainz_artists: Vec<StagingMusicBrainzArtist>,
     pub albums: Vec<StagingAlbum>,
     pub files: Vec<StagingFile>,
+    pub file_tags: Vec<StagingFileTag>,
     pub tracks: Vec<StagingTrack>,
+    pub track_genres: Vec<StagingTrackGenre>,
     pub credits: Vec<StagingCredit>,
     pub moved: Vec<StagingMoved>,
     pub modified: Vec<StagingModified>,
     pub deleted: Vec<StagingDeleted>,
+    pub predecessors: Vec<StagingPredecessor>,
+    pub failures: Vec<StagingFailure>,
+    pub durations: Vec<StagingDuration>,
+    pub aliases: Vec<StagingAlias>,
+    pub playlists: Vec<StagingPlaylist>,
+    pub playlist_entries: Vec<StagingPlaylistEntry>,
+    /// The collection paths of the library's playlists that are gone.
+    pub deleted_playlists: Vec<String>,
 }
@@ -0,0 +1,242 @@
+//! Keeping the library in sync with the collection while the server runs, by
+//! watching the collection for changes rather than scanning all of it again.
+//!
+//! Events only tell which paths to look at. Once the collection has been quiet
+//! for [`DEBOUNCE`], the audio files at and below those paths are classified
+//! as a scan would classify them, and the library files there that are gone
+//! are deleted. Comparing against the library rather than trusting the events
+//! is what makes an editor's temp file renamed over the original a
+//! modification of the original, and a directory moved within the collection
+//! a move of each of its files.
+//!
+//! The changes are held to the same safety checks as a scan's: none are
+//! applied when the collection can't be read any more, or when they'd mark
+//! more of the library deleted than `--max-delete` allows.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use duckdb::Connection;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use uuid::Uuid;
+
+use super::backfill::DbTask;
+use super::classify::{self, get_audio_files, is_audio_file};
+use super::collection;
+use super::exclude::Exclude;
+use super::prepare;
+use super::scan::{self, ScanOptions};
+use super::scan_log::ScanLog;
+use super::staging;
+use super::symlink::found_path;
+use super::types::{ExistingFiles, ScanResults};
+
+/// How long the collection must go without events before the paths they name
+/// are looked at, so that a file being copied or saved in steps is only read
+/// once it's complete.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches `collection_path`, bringing the library up to date with every
+/// change as it settles, until the watcher stops.
+///
+/// Each database operation is run through `with_db`, as for
+/// [`backfill`](super::backfill()). Changes are few at a time, so their
+/// metadata is read right away even with `defer_metadata`.
+pub fn watch(
+    collection_path: &Path,
+    options: &ScanOptions,
+    mut with_db: impl FnMut(&mut DbTask<'_>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let options = ScanOptions {
+        defer_metadata: false,
+        ..options.clone()
+    };
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(collection_path, RecursiveMode::Recursive)?;
+    tracing::info!("Watching {}", collection_path.display());
+
+    while let Some(paths) = next_changes(&rx) {
+        let paths: Vec<PathBuf> = paths.into_iter().collect();
+        if let Err(e) = sync_paths(collection_path, &paths, &options, &mut with_db) {
+            tracing::error!("Watch: failed to apply changes: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Waits for the next events and gathers the paths they name until none have
+/// come for [`DEBOUNCE`]. `None` once the watcher has stopped.
+fn next_changes(rx: &Receiver<notify::Result<Event>>) -> Option<HashSet<PathBuf>> {
+    let mut paths = HashSet::new();
+    let mut add = |event: notify::Result<Event>| match event {
+        // Reading a file changes nothing.
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => paths.extend(event.paths),
+        Err(e) => tracing::warn!("Watch: {e}"),
+    };
+    add(rx.recv().ok()?);
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(event) => add(event),
+            Err(RecvTimeoutError::Timeout) => return Some(paths),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+/// Whether the library path `path` is `scope` or below it.
+fn is_within(path: &str, scope: &str) -> bool {
+    let scope = scope.trim_end_matches('/');
+    path.strip_prefix(scope)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The live files at or below `scopes` that `results` didn't find again.
+fn deletions_within(
+    results: &ScanResults,
+    existing: &ExistingFiles,
+    scopes: &[String],
+) -> Vec<Uuid> {
+    let in_scope: HashSet<Uuid> = existing
+        .by_path
+        .iter()
+        .filter(|(path, _)| scopes.iter().any(|scope| is_within(path, scope)))
+        .map(|(_, (id, _, _, _))| *id)
+        .collect();
+    classify::detect_deletions(results, existing)
+        .into_iter()
+        .filter(|id| in_scope.contains(id))
+        .collect()
+}
+
+/// Brings the library up to date with `paths` of the collection, each a file
+/// or directory that was created, modified or removed, or a path something was
+/// renamed from or to. The audio files at and below them are classified as a
+/// scan would classify them, and the library files at and below them that
+/// weren't found are deleted.
+pub fn sync_paths(
+    collection_path: &Path,
+    paths: &[PathBuf],
+    options: &ScanOptions,
+    mut with_db: impl FnMut(&mut DbTask<'_>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let exclude = Exclude::new(collection_path, &options.exclude)?;
+    let mut scopes = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        let Some(scope) = found_path(path, collection_path) else {
+            continue;
+        };
+        scopes.push(scope);
+        if exclude.is_excluded(path) {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(get_audio_files(
+                path,
+                None,
+                options.follow_symlinks,
+                &exclude,
+                &options.extensions,
+            ));
+        } else if path.is_file() && is_audio_file(path, &options.extensions) {
+            files.push(path.clone());
+        }
+    }
+    if let Some(max_depth) = options.max_depth {
+        // `./a/b.flac` is one level below the collection root.
+        files.retain(|file| {
+            found_path(file, collection_path)
+                .is_some_and(|path| Path::new(&path).components().count() <= max_depth + 2)
+        });
+    }
+    files.sort();
+    files.dedup();
+
+    let mut collection = Uuid::nil();
+    let mut existing_files = ExistingFiles::default();
+    with_db(&mut |conn| {
+        collection = collection::register(conn, collection_path, options.adopt_legacy_files)?;
+        existing_files = staging::load_existing_files(conn, Some(collection))?;
+        Ok(())
+    })?;
+    let (mut results, deleted_ids) =
+        classify_changes(files, collection_path, &existing_files, &scopes, options)?;
+    // An unmounted drive shows up as an event on the root, which would
+    // otherwise delete every file of the collection.
+    scan::check_readable(collection_path, &results, options)?;
+    scan::check_mass_delete(collection_path, &deleted_ids, &existing_files, options)?;
+    let mut fingerprints = HashMap::new();
+    if options.fingerprint && !deleted_ids.is_empty() {
+        with_db(&mut |conn| {
+            fingerprints = staging::load_fingerprints(conn, &deleted_ids)?;
+            Ok(())
+        })?;
+    }
+    classify::link_predecessors(&mut results, &deleted_ids, &existing_files, &fingerprints);
+    if results.moved.is_empty()
+        && results.modified.is_empty()
+        && results.new_files.is_empty()
+        && results.failed.is_empty()
+        && deleted_ids.is_empty()
+    {
+        return Ok(());
+    }
+    tracing::info!(
+        "Watch: {} moved, {} modified, {} new, {} failed, {} deleted",
+        results.moved.len(),
+        results.modified.len(),
+        results.new_files.len(),
+        results.failed.len(),
+        deleted_ids.len(),
+    );
+    scan::log_failures(&results);
+    with_db(&mut |conn| store_changes(conn, collection, &results, &deleted_ids, options))
+}
+
+/// Classifies `files` and finds the deletions within `scopes`, as a scan does
+/// for the whole collection. The new files are left to be linked to the deleted
+/// ones they replace.
+fn classify_changes(
+    files: Vec<PathBuf>,
+    collection_path: &Path,
+    existing: &ExistingFiles,
+    scopes: &[String],
+    options: &ScanOptions,
+) -> Result<(ScanResults, Vec<Uuid>), Box<dyn Error>> {
+    let log = ScanLog::open(None)?;
+    let mut results =
+        classify::classify_files(files, collection_path, existing, options, &log, &|_, _| {});
+    classify::resolve_conflicts(&mut results, options, &log);
+    let deleted_ids = if options.no_delete {
+        Vec::new()
+    } else {
+        deletions_within(&results, existing, scopes)
+    };
+    Ok((results, deleted_ids))
+}
+
+/// Stage and store the changes to `collection`.
+fn store_changes(
+    conn: &Connection,
+    collection: Uuid,
+    results: &ScanResults,
+    deleted_ids: &[Uuid],
+    options: &ScanOptions,
+) -> Result<(), Box<dyn Error>> {
+    let existing_artists = staging::load_existing_artists(conn)?;
+    let mut staging_data = prepare::prepare_staging_data(
+        results,
+        &existing_artists,
+        deleted_ids.to_vec(),
+        &options.genre,
+        &options.album,
+    );
+    staging_data.collection = Some(collection);
+    staging::execute_watched(conn, &staging_data)?;
+    Ok(())
+}
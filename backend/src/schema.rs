@@ -0,0 +1,116 @@
+//! `GET /schema`: the tables and views of the library with their columns, for
+//! clients that complete or check queries without running `DESCRIBE`.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use duckdb::Connection;
+use serde::Serialize;
+
+use crate::db;
+use crate::server::AppState;
+
+/// Schemas that aren't part of the library: `DuckDB`'s catalogs and the
+/// migration bookkeeping, which [`Schema::version`] reports instead.
+const HIDDEN_SCHEMAS: &[&str] = &["information_schema", "pg_catalog", "meta"];
+
+#[derive(Serialize)]
+pub struct Schema {
+    /// The `meta.version` of the database: the last migration applied. A
+    /// client built against another version may find tables or columns
+    /// missing.
+    version: u32,
+    tables: Vec<Table>,
+}
+
+#[derive(Serialize)]
+pub struct Table {
+    /// The schema the table is in, `main` for the library's own tables.
+    schema: String,
+    name: String,
+    /// `table` or `view`.
+    kind: &'static str,
+    columns: Vec<Column>,
+}
+
+#[derive(Serialize)]
+pub struct Column {
+    name: String,
+    /// The `DuckDB` type, as `DESCRIBE` shows it, e.g. `VARCHAR[]`.
+    #[serde(rename = "type")]
+    data_type: String,
+    nullable: bool,
+}
+
+fn load_schema(conn: &Connection) -> Result<Schema, duckdb::Error> {
+    let hidden = HIDDEN_SCHEMAS
+        .iter()
+        .map(|schema| format!("'{schema}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Temporary tables, such as a scan's staging tables, are in another
+    // catalog.
+    let sql = format!(
+        "SELECT t.table_schema, t.table_name, t.table_type, c.column_name, c.data_type,
+                c.is_nullable = 'YES'
+         FROM information_schema.tables t
+         JOIN information_schema.columns c
+           USING (table_catalog, table_schema, table_name)
+         WHERE t.table_catalog = current_database()
+           AND t.table_schema NOT IN ({hidden})
+         ORDER BY t.table_schema, t.table_name, c.ordinal_position"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+
+    let mut tables: Vec<Table> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let schema: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let table_type: String = row.get(2)?;
+        let column = Column {
+            name: row.get(3)?,
+            data_type: row.get(4)?,
+            nullable: row.get(5)?,
+        };
+        match tables.last_mut() {
+            Some(table) if table.schema == schema && table.name == name => {
+                table.columns.push(column);
+            }
+            _ => tables.push(Table {
+                schema,
+                name,
+                kind: if table_type == "VIEW" {
+                    "view"
+                } else {
+                    "table"
+                },
+                columns: vec![column],
+            }),
+        }
+    }
+
+    Ok(Schema {
+        version: db::get_current_version(conn)?,
+        tables,
+    })
+}
+
+/// `GET /schema`: every table and view of the library, in name order, with
+/// its columns in order, and the migration version of the database.
+pub async fn schema(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Schema>, (StatusCode, String)> {
+    let result = tokio::task::spawn_blocking(move || state.read(load_schema)).await;
+
+    match result {
+        Ok(Ok(schema)) => Ok(Json(schema)),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "schema task panicked".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,95 @@
+//! `GET /search?q=`: the tracks whose title, album title, credits, lyrics or
+//! comment match every word of a search, best matches first, without writing
+//! SQL.
+//!
+//! Matching goes by the `track_search` view, which lowercases the text and
+//! strips its accents, so `beyonce` finds "Beyoncé". `DuckDB`'s `fts` extension
+//! would index the text instead, but it isn't bundled and can't be loaded
+//! offline; a scan of the view stays fast at library sizes.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, Response, StatusCode};
+use serde::Deserialize;
+
+use crate::query::{QueryParams, ResultFormat};
+use crate::server::{AppState, accepted_format, stream_query, wants_json_errors};
+
+/// How many tracks a search returns unless it asks for another number.
+const DEFAULT_LIMIT: u64 = 100;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    /// The words to search for, in any order.
+    q: String,
+    limit: Option<u64>,
+    format: Option<ResultFormat>,
+}
+
+/// `word` as a SQL string literal, normalized as `track_search` is.
+fn normalized_literal(word: &str) -> String {
+    format!("strip_accents(lower('{}'))", word.replace('\'', "''"))
+}
+
+/// The query finding the live tracks that match every one of `words`. Tracks
+/// whose title is the whole search come first, then those whose title has the
+/// most of its words, then the others, each by title.
+fn search_sql(words: &[&str], limit: u64) -> String {
+    let matches = words
+        .iter()
+        .map(|word| format!("contains(s.text, {})", normalized_literal(word)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let title_matches = words
+        .iter()
+        .map(|word| format!("contains(s.title, {})::INTEGER", normalized_literal(word)))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let whole = normalized_literal(&words.join(" "));
+    format!(
+        "SELECT t.id, t.title,
+                (SELECT list(ar.name ORDER BY c.ord)
+                 FROM credit c JOIN artist ar ON ar.id = c.artist
+                 WHERE c.track = t.id AND c.role = '') AS artists,
+                al.title AS album
+         FROM track_search s
+         JOIN track t ON t.id = s.track
+         JOIN file f ON f.id = t.file
+         LEFT JOIN album al ON al.id = t.album
+         WHERE f.deletion IS NULL AND {matches}
+         ORDER BY s.title = {whole} DESC, {title_matches} DESC, t.title, t.id
+         LIMIT {limit}"
+    )
+}
+
+/// `GET /search?q=`: the matching tracks' `id`, `title`, `artists` and
+/// `album`, as an Arrow stream (or JSON, as `/query` writes it), at most
+/// `limit` of them.
+pub async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let words: Vec<&str> = params.q.split_whitespace().collect();
+    if words.is_empty() {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::from("q: nothing to search for"))
+            .unwrap();
+    }
+    let sql = search_sql(&words, params.limit.unwrap_or(DEFAULT_LIMIT));
+    let query_params = QueryParams {
+        format: params.format.or_else(|| accepted_format(&headers)),
+        ..QueryParams::default()
+    };
+    stream_query(
+        state,
+        sql,
+        Vec::new(),
+        query_params,
+        wants_json_errors(&headers),
+    )
+    .await
+}
@@ -1,31 +1,130 @@
 use std::io::{self, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use arrow_ipc::writer::StreamWriter;
 use axum::Router;
 use axum::body::Body;
-use axum::extract::State;
-use axum::http::{Response, StatusCode};
-use axum::routing::{get, post};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, Response, StatusCode, header};
+use axum::routing::{get, patch, post};
 use bytes::Bytes;
+use clap::Args;
 use duckdb::Connection;
+use duckdb::types::Value;
+use serde::Deserialize;
 use tokio::sync::{mpsc, oneshot};
 use tokio_stream::wrappers::ReceiverStream;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
 use tower_http::cors::CorsLayer;
 
+use crate::query::{QueryError, QueryParams, Ready, ResultFormat};
+use crate::scanner::{BackfillProgress, ScanOptions};
+
+/// How many connections read queries are spread over by default.
+const DEFAULT_CONNECTIONS: usize = 4;
+
+/// The response header giving how many rows a query paged with `limit` or
+/// `offset` has in all.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// How long a shutdown waits for the requests in flight, and then for the
+/// write in progress.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often [`AppState::close`] checks whether the write in progress is done.
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options for serving the library.
+#[derive(Args, Clone, Debug)]
+pub struct ServeOptions {
+    /// Keep the library in sync with the collection while serving, by watching
+    /// it for changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Only run queries that read the library (`SELECT`, `WITH` and `EXPLAIN`),
+    /// rejecting any other statement sent to `/query`
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// How many database connections read queries are spread over, so that a
+    /// slow query doesn't hold up the others. Writes always go through one
+    /// connection of their own
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CONNECTIONS)]
+    pub connections: usize,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        ServeOptions {
+            watch: false,
+            read_only: false,
+            connections: DEFAULT_CONNECTIONS,
+        }
+    }
+}
+
 pub struct AppState {
-    db: Mutex<Connection>,
-    pub collection_path: PathBuf,
+    /// The connection every write goes through, one at a time, until
+    /// [`AppState::close`] takes it.
+    db: Mutex<Option<Connection>>,
+    /// Connections to the same database for reads, which run concurrently.
+    readers: Vec<Mutex<Connection>>,
+    /// The reader to wait for when all of them are busy.
+    next_reader: AtomicUsize,
+    /// The collections served, the first of which files recorded without one
+    /// are found in.
+    pub collection_paths: Vec<PathBuf>,
+    pub backfill: BackfillProgress,
+    /// Whether queries may only read the library (see
+    /// [`crate::query::check_read_only`]). The server's own writes, such as
+    /// ratings or a backfill, aren't affected.
+    pub read_only: bool,
+    /// When the state was created, as the server started.
+    started: Instant,
 }
 
 impl AppState {
-    /// Run a read-only DB operation under the connection lock.
+    /// Run a read-only DB operation on a reader connection, under its lock. An
+    /// idle reader is taken if there is one; otherwise the readers are waited
+    /// for in turn.
     pub fn read<T>(&self, f: impl FnOnce(&Connection) -> T) -> T {
-        let conn = self.db.lock().unwrap();
+        let conn = self
+            .readers
+            .iter()
+            .find_map(|reader| reader.try_lock().ok())
+            .unwrap_or_else(|| {
+                let i = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+                self.readers[i].lock().unwrap()
+            });
         f(&conn)
     }
 
+    /// Like [`AppState::read`], but only on an idle reader: `None` when every
+    /// reader is busy.
+    pub fn try_read<T>(&self, f: impl FnOnce(&Connection) -> T) -> Option<T> {
+        let conn = self
+            .readers
+            .iter()
+            .find_map(|reader| reader.try_lock().ok())?;
+        Some(f(&conn))
+    }
+
+    /// The first of the collections served.
+    pub fn collection_path(&self) -> &Path {
+        &self.collection_paths[0]
+    }
+
+    /// How long the server has been running.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
     /// Run a data-modifying DB operation under the lock, then `CHECKPOINT`.
     ///
     /// On success the WAL has been flushed to the main database file. Every
@@ -35,38 +134,145 @@ impl AppState {
     /// If the mutation succeeds but the `CHECKPOINT` fails, this returns
     /// `Err("checkpoint failed after write: ...")`. The mutation is already
     /// committed and durable in the WAL, so that error is not a signal to retry
-    /// the write.
+    /// the write. Once the state is closed, every write fails.
     pub fn write<T>(&self, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
         let conn = self.db.lock().unwrap();
-        let value = f(&conn)?;
+        let conn = conn
+            .as_ref()
+            .ok_or_else(|| "the database is closed".to_string())?;
+        let value = f(conn)?;
         conn.execute_batch("CHECKPOINT;")
             .map_err(|e| format!("checkpoint failed after write: {e}"))?;
         Ok(value)
     }
+
+    /// Readies the database for the process to exit: waits up to `timeout`
+    /// for the write in progress, if any, rolls back the transaction a failed
+    /// write may have left open, checkpoints, and closes the writer, so that no
+    /// background task starts another write. A write still running after
+    /// `timeout` is left to be cut off as the process exits.
+    pub fn close(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut db = loop {
+            match self.db.try_lock() {
+                Ok(db) => break db,
+                Err(TryLockError::Poisoned(e)) => break e.into_inner(),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    thread::sleep(CLOSE_POLL_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    tracing::warn!(
+                        "A write still running after {}s was cut off",
+                        timeout.as_secs()
+                    );
+                    return;
+                }
+            }
+        };
+        let Some(conn) = db.take() else {
+            return;
+        };
+        // Fails when no transaction is open, as it should be.
+        let _ = conn.execute_batch("ROLLBACK;");
+        if let Err(e) = conn.execute_batch("CHECKPOINT;") {
+            tracing::error!("Checkpoint on shutdown failed: {e}");
+        }
+        if let Err((_, e)) = conn.close() {
+            tracing::error!("Closing the database on shutdown failed: {e}");
+        }
+    }
 }
 
 pub fn app_state(conn: Connection, collection_path: PathBuf) -> Arc<AppState> {
-    let collection_path = std::fs::canonicalize(&collection_path).unwrap_or(collection_path);
+    app_state_with(conn, collection_path, &ServeOptions::default())
+}
+
+/// Like [`app_state`], with `options` deciding how queries are run. `conn`
+/// becomes the writer, and the readers are further connections to its
+/// database.
+pub fn app_state_with(
+    conn: Connection,
+    collection_path: PathBuf,
+    options: &ServeOptions,
+) -> Arc<AppState> {
+    collections_state(conn, vec![collection_path], options)
+}
+
+/// Like [`app_state_with`], serving each of `collection_paths`, of which there
+/// must be at least one.
+pub fn collections_state(
+    conn: Connection,
+    collection_paths: Vec<PathBuf>,
+    options: &ServeOptions,
+) -> Arc<AppState> {
+    assert!(!collection_paths.is_empty(), "no collection to serve");
+    let collection_paths = collection_paths
+        .into_iter()
+        .map(|path| std::fs::canonicalize(&path).unwrap_or(path))
+        .collect();
+    let readers = (0..options.connections.max(1))
+        .map(|_| {
+            let reader = conn
+                .try_clone()
+                .expect("opening another connection to an open database");
+            Mutex::new(reader)
+        })
+        .collect();
     Arc::new(AppState {
-        db: Mutex::new(conn),
-        collection_path,
+        db: Mutex::new(Some(conn)),
+        readers,
+        next_reader: AtomicUsize::new(0),
+        collection_paths,
+        backfill: BackfillProgress::default(),
+        read_only: options.read_only,
+        started: Instant::now(),
     })
 }
 
 pub fn router(state: Arc<AppState>) -> Router {
     Router::new()
-        .route("/query", post(query))
+        .route("/query", post(query).get(crate::html::query_html))
         .route("/rpc", post(crate::rpc::rpc))
+        .route("/recent", get(crate::browse::recent))
+        .route("/failures", get(crate::browse::failures))
+        .route("/health", get(crate::health::health))
+        .route("/scan/status", get(crate::backfill::scan_status))
+        .route("/schema", get(crate::schema::schema))
+        .route("/search", get(crate::search::search))
+        .route(
+            "/settings",
+            get(crate::settings::get_settings).put(crate::settings::put_settings),
+        )
+        .route("/tracks", patch(crate::tracks::patch_tracks))
+        .route("/tracks/{id}/rating", post(crate::tracks::post_rating))
         .route("/tracks/{id}/stream", get(crate::stream::stream_track))
+        .route("/files/stream", get(crate::stream::stream_file))
+        .route("/ws", get(crate::ws::ws))
+        .layer(compression())
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
 
+/// Compresses responses with gzip or zstd for clients that accept it, chunk
+/// by chunk as they stream. Audio is already compressed, and served in ranges
+/// for seeking, so it's left alone.
+fn compression() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .compress_when(DefaultPredicate::new().and(NotForContentType::new("audio/")))
+}
+
+/// How much [`ChannelWriter`] buffers before sending it on unflushed, so that a
+/// large batch reaches the client piece by piece. The channel holds few chunks,
+/// so this also bounds what a slow client leaves in memory.
+const FLUSH_THRESHOLD: usize = 256 * 1024;
+
 /// Bridges synchronous Arrow IPC writes to an async byte stream.
 ///
 /// Arrow's `StreamWriter` requires a synchronous `Write` target. This adapter
 /// buffers incoming writes and flushes completed chunks through a tokio mpsc
-/// channel, which the Axum handler consumes as a streaming HTTP response body.
+/// channel, which the Axum handler consumes as a streaming HTTP response body,
+/// or [`crate::ws`] as the frames of a socket. It takes no more of a write
+/// than fills [`FLUSH_THRESHOLD`], and flushes on its own once it's full.
 struct ChannelWriter {
     tx: mpsc::Sender<io::Result<Bytes>>,
     buf: Vec<u8>,
@@ -86,8 +292,12 @@ impl ChannelWriter {
 
 impl Write for ChannelWriter {
     fn write(&mut self, data: &[u8]) -> io::Result<usize> {
-        self.buf.extend_from_slice(data);
-        Ok(data.len())
+        let taken = data.len().min(FLUSH_THRESHOLD - self.buf.len());
+        self.buf.extend_from_slice(&data[..taken]);
+        if self.buf.len() >= FLUSH_THRESHOLD {
+            self.send_buffered()?;
+        }
+        Ok(taken)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -101,78 +311,265 @@ impl Drop for ChannelWriter {
     }
 }
 
-async fn query(State(state): State<Arc<AppState>>, body: String) -> Response<Body> {
+/// The response to a statement without result columns, in place of an Arrow
+/// stream with an empty schema.
+fn rows_affected_response(count: usize) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::json!({ "rows_affected": count }).to_string(),
+        ))
+        .unwrap()
+}
+
+/// The response to a failed query: its message as plain text, or with
+/// `json_errors` the whole `error` as JSON.
+fn query_error(status: StatusCode, error: QueryError, json_errors: bool) -> Response<Body> {
+    if !json_errors {
+        return Response::builder()
+            .status(status)
+            .body(Body::from(error.error))
+            .unwrap();
+    }
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_string(&error).unwrap()))
+        .unwrap()
+}
+
+/// Whether the `Accept` header names `media_type`.
+fn accepts(headers: &HeaderMap, media_type: &str) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| {
+            accept
+                .split(',')
+                .map(|accepted| accepted.split(';').next().unwrap_or_default().trim())
+                .any(|accepted| accepted.eq_ignore_ascii_case(media_type))
+        })
+}
+
+/// The format asked for by an `Accept` header naming JSON. `?format=` takes
+/// precedence over it.
+pub(crate) fn accepted_format(headers: &HeaderMap) -> Option<ResultFormat> {
+    (accepts(headers, "application/x-ndjson") || accepts(headers, "application/json"))
+        .then_some(ResultFormat::Json)
+}
+
+/// Whether errors are answered as [`QueryError`]s rather than plain text: when
+/// the `Accept` header names `application/json`.
+pub(crate) fn wants_json_errors(headers: &HeaderMap) -> bool {
+    accepts(headers, "application/json")
+}
+
+/// A `/query` body sent as JSON, whose `params` are bound to the `?`
+/// parameters of `sql` in order.
+#[derive(Deserialize)]
+struct ParameterizedQuery {
+    sql: String,
+    #[serde(default)]
+    params: Vec<serde_json::Value>,
+}
+
+/// Whether the `Content-Type` header says the body is JSON.
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+async fn query(
+    State(state): State<Arc<AppState>>,
+    Query(mut params): Query<QueryParams>,
+    headers: HeaderMap,
+    body: String,
+) -> Response<Body> {
+    params.format = params.format.or_else(|| accepted_format(&headers));
+    let json_errors = wants_json_errors(&headers);
+    if !is_json(&headers) {
+        return stream_query(state, body, Vec::new(), params, json_errors).await;
+    }
+    let parsed = match serde_json::from_str::<ParameterizedQuery>(&body) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let error = QueryError::new(format!("invalid query body: {e}"), &body);
+            return query_error(StatusCode::BAD_REQUEST, error, json_errors);
+        }
+    };
+    let bind = match parsed
+        .params
+        .iter()
+        .map(crate::query::param_value)
+        .collect()
+    {
+        Ok(bind) => bind,
+        Err(e) => {
+            let error = QueryError::new(e, &parsed.sql);
+            return query_error(StatusCode::BAD_REQUEST, error, json_errors);
+        }
+    };
+    stream_query(state, parsed.sql, bind, params, json_errors).await
+}
+
+/// What a query run by [`spawn_query`] produces, once it's ready.
+pub(crate) type ReadyReceiver = oneshot::Receiver<Result<Ready, String>>;
+
+/// The chunks of a query's rows, as [`spawn_query`] writes them. An error ends
+/// them short.
+pub(crate) type RowReceiver = mpsc::Receiver<io::Result<Bytes>>;
+
+/// Runs `sql` as `/query` does on a blocking task, binding `bind` to its
+/// parameters. Once dropped, the row receiver cancels the query.
+pub(crate) fn spawn_query(
+    state: Arc<AppState>,
+    sql: String,
+    bind: Vec<Value>,
+    params: QueryParams,
+) -> (ReadyReceiver, RowReceiver) {
     let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(8);
-    let (ready_tx, ready_rx) = oneshot::channel::<Result<(), String>>();
+    let (ready_tx, ready_rx) = oneshot::channel::<Result<Ready, String>>();
 
+    let span = tracing::info_span!("query", format = ?params.format.unwrap_or_default());
     tokio::task::spawn_blocking(move || {
-        state.read(|conn| {
-            let mut stmt = match conn.prepare(&body) {
-                Ok(stmt) => stmt,
-                Err(e) => {
-                    let _ = ready_tx.send(Err(e.to_string()));
-                    return;
-                }
-            };
-
-            let batches = match stmt.query_arrow([]) {
-                Ok(b) => b,
-                Err(e) => {
-                    let _ = ready_tx.send(Err(e.to_string()));
-                    return;
-                }
-            };
-
-            let schema = batches.get_schema();
-            let _ = ready_tx.send(Ok(()));
-
-            // Past this point, errors during streaming simply truncate the
-            // response. The client will detect the missing IPC EOS marker.
-            let writer = ChannelWriter {
-                tx,
-                buf: Vec::new(),
-            };
-            let Ok(mut ipc_writer) = StreamWriter::try_new(writer, &schema) else {
-                return;
-            };
-            for batch in batches {
-                if ipc_writer.write(&batch).is_err() {
-                    return;
-                }
-            }
-            let _ = ipc_writer.finish();
-        });
+        let _entered = span.entered();
+        // The receiving end goes away with the client.
+        let probe = tx.clone();
+        let cancelled = || probe.is_closed();
+        let out = ChannelWriter {
+            tx: tx.clone(),
+            buf: Vec::new(),
+        };
+        let ready = |outcome| {
+            let _ = ready_tx.send(outcome);
+        };
+        if let Err(e) =
+            crate::query::run_with_params(&state, &sql, &bind, &params, cancelled, ready, out)
+        {
+            // Fail the response rather than let it end as if complete.
+            let _ = tx.blocking_send(Err(io::Error::other(e)));
+        }
     });
+    (ready_rx, rx)
+}
+
+/// Runs `body` as `/query` does, binding `bind` to its parameters, and streams
+/// its rows as the response. With `json_errors`, an error keeping it from
+/// running is answered as a [`QueryError`].
+pub(crate) async fn stream_query(
+    state: Arc<AppState>,
+    body: String,
+    bind: Vec<Value>,
+    params: QueryParams,
+    json_errors: bool,
+) -> Response<Body> {
+    let format = params.format.unwrap_or_default();
+    let sql = body.clone();
+    let (ready_rx, rx) = spawn_query(state, body, bind, params);
 
     match ready_rx.await {
-        Ok(Ok(())) => {
+        Ok(Ok(Ready::Rows { total })) => {
             let stream = ReceiverStream::new(rx);
-            Response::builder()
+            let mut response = Response::builder()
                 .status(StatusCode::OK)
-                .header("content-type", "application/vnd.apache.arrow.stream")
-                .body(Body::from_stream(stream))
-                .unwrap()
+                .header("content-type", format.content_type());
+            if let Some(total) = total {
+                response = response.header(TOTAL_COUNT_HEADER, total);
+            }
+            response.body(Body::from_stream(stream)).unwrap()
         }
-        Ok(Err(msg)) => Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(Body::from(msg))
-            .unwrap(),
-        Err(_) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("query task panicked"))
-            .unwrap(),
+        Ok(Ok(Ready::RowsAffected(count))) => rows_affected_response(count),
+        Ok(Err(msg)) => query_error(
+            StatusCode::BAD_REQUEST,
+            QueryError::new(msg, &sql),
+            json_errors,
+        ),
+        Err(_) => query_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            QueryError {
+                error: "query task panicked".to_string(),
+                kind: "internal".to_string(),
+                position: None,
+            },
+            json_errors,
+        ),
     }
 }
 
+/// Serves the library of `collection_paths` with `options`, backfilling the
+/// metadata of files recorded without it in the background with
+/// `scan_options`. `app` builds what's served from the API's [`router`].
 pub async fn serve(
     conn: Connection,
-    collection_path: PathBuf,
-    port: u16,
+    collection_paths: Vec<PathBuf>,
+    addr: SocketAddr,
+    scan_options: ScanOptions,
+    options: &ServeOptions,
+    app: impl FnOnce(Router) -> Router,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let app = router(app_state(conn, collection_path));
-    let addr = format!("0.0.0.0:{port}");
-    println!("Listening on {addr}");
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| io::Error::new(e.kind(), format!("could not listen on {addr}: {e}")))?;
+    let state = collections_state(conn, collection_paths, options);
+    if options.watch {
+        crate::watch::start(&state, &scan_options);
+    }
+    crate::backfill::start(Arc::clone(&state), scan_options);
+    tracing::info!("Listening on {addr}");
+
+    // Once signalled, the server stops accepting connections and waits for the
+    // requests in flight, such as streaming queries, for up to
+    // SHUTDOWN_TIMEOUT.
+    let (signalled_tx, signalled_rx) = oneshot::channel();
+    let server =
+        axum::serve(listener, app(router(Arc::clone(&state)))).with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            tracing::info!("Shutting down: waiting for the requests in flight");
+            let _ = signalled_tx.send(());
+        });
+    let timeout = async {
+        if signalled_rx.await.is_err() {
+            return std::future::pending().await;
+        }
+        tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+    };
+    tokio::select! {
+        result = server => result?,
+        () = timeout => tracing::warn!(
+            "Requests still running after {}s were cut off",
+            SHUTDOWN_TIMEOUT.as_secs()
+        ),
+    }
+
+    tokio::task::spawn_blocking(move || state.close(SHUTDOWN_TIMEOUT)).await?;
+    tracing::info!("Shut down cleanly");
     Ok(())
 }
+
+/// Resolves on Ctrl-C, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
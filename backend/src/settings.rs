@@ -0,0 +1,178 @@
+//! `GET/PUT /settings`: collection-wide settings, stored in `meta.settings` so
+//! they live with the library rather than in command-line flags.
+//!
+//! Every setting so far decides how tags are normalized into tracks and albums,
+//! so a `PUT` that changes any of them re-derives the library (see
+//! [`scanner::rederive`]) with the new values.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use clap::ValueEnum;
+use duckdb::Connection;
+use serde_json::{Map, Value};
+
+use crate::scanner::{self, AlbumOptions, GenreOptions, MissingAlbumRule, PrimaryGenreRule};
+use crate::server::AppState;
+
+/// The known settings, as they appear in `GET /settings`.
+const SETTINGS: &[&str] = &["primary_genre", "genre_priority", "missing_album"];
+
+/// The command-line name of a value enum variant, which settings use too.
+fn value_name<T: ValueEnum>(value: &T) -> Value {
+    value
+        .to_possible_value()
+        .map_or(Value::Null, |v| Value::String(v.get_name().to_string()))
+}
+
+/// Parses a value enum setting from its command-line name.
+fn parse_enum<T: ValueEnum>(key: &str, value: &Value) -> Result<T, String> {
+    let names = || {
+        T::value_variants()
+            .iter()
+            .filter_map(|v| Some(v.to_possible_value()?.get_name().to_string()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    value
+        .as_str()
+        .and_then(|name| T::from_str(name, false).ok())
+        .ok_or_else(|| format!("{key}: expected one of {}", names()))
+}
+
+fn parse_string_list(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let invalid = || format!("{key}: expected an array of strings");
+    value
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|item| item.as_str().map(str::to_string).ok_or_else(invalid))
+        .collect()
+}
+
+/// The normalization options the settings make up, with defaults for any that
+/// aren't set.
+#[derive(Default)]
+struct Settings {
+    genre: GenreOptions,
+    album: AlbumOptions,
+}
+
+impl Settings {
+    /// Applies one setting, checking its key and value.
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "primary_genre" => {
+                self.genre.primary_genre = parse_enum::<PrimaryGenreRule>(key, value)?;
+            }
+            "genre_priority" => self.genre.genre_priority = parse_string_list(key, value)?,
+            "missing_album" => {
+                self.album.missing_album = parse_enum::<MissingAlbumRule>(key, value)?;
+            }
+            _ => return Err(format!("unknown setting: {key}")),
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Value {
+        match key {
+            "primary_genre" => value_name(&self.genre.primary_genre),
+            "genre_priority" => Value::from(self.genre.genre_priority.clone()),
+            "missing_album" => value_name(&self.album.missing_album),
+            _ => Value::Null,
+        }
+    }
+
+    fn to_json(&self) -> Map<String, Value> {
+        SETTINGS
+            .iter()
+            .map(|&key| (key.to_string(), self.get(key)))
+            .collect()
+    }
+}
+
+/// Loads the stored settings. Rows that no longer parse, say after a setting
+/// was dropped, are ignored.
+fn load(conn: &Connection) -> Result<Settings, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM meta.settings")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut settings = Settings::default();
+    for row in rows {
+        let (key, value) = row.map_err(|e| e.to_string())?;
+        if let Ok(value) = serde_json::from_str(&value) {
+            let _ = settings.set(&key, &value);
+        }
+    }
+    Ok(settings)
+}
+
+/// Stores `changes` and re-derives the library if they changed any setting.
+fn save(conn: &Connection, changes: &Map<String, Value>) -> Result<Settings, String> {
+    let before = load(conn)?.to_json();
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    for (key, value) in changes {
+        tx.execute(
+            "INSERT OR REPLACE INTO meta.settings (key, value) VALUES (?, ?)",
+            [key, &value.to_string()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    let settings = load(conn)?;
+    if settings.to_json() != before {
+        scanner::rederive(conn, &settings.genre, &settings.album).map_err(|e| e.to_string())?;
+    }
+    Ok(settings)
+}
+
+/// `GET /settings`: every setting, with the defaults of those not set.
+pub async fn get_settings(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Map<String, Value>>, (StatusCode, String)> {
+    let result = tokio::task::spawn_blocking(move || state.read(load)).await;
+
+    match result {
+        Ok(Ok(settings)) => Ok(Json(settings.to_json())),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "settings task panicked".to_string(),
+        )),
+    }
+}
+
+/// `PUT /settings`: `{ key: value, ... }` for the settings to change, leaving
+/// the others as they are. Answers with every setting, as `GET` does, or 400
+/// without changing anything when a key is unknown or a value invalid.
+pub async fn put_settings(
+    State(state): State<Arc<AppState>>,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<Json<Map<String, Value>>, (StatusCode, String)> {
+    let mut check = Settings::default();
+    for (key, value) in &changes {
+        check
+            .set(key, value)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    let result =
+        tokio::task::spawn_blocking(move || state.write(|conn| save(conn, &changes))).await;
+
+    match result {
+        Ok(Ok(settings)) => Ok(Json(settings.to_json())),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "settings task panicked".to_string(),
+        )),
+    }
+}
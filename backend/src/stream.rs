@@ -25,6 +25,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tower::ServiceExt;
 use tower_http::services::ServeFile;
 
+use crate::format::Format;
 use crate::server::AppState;
 
 const OPUS_SAMPLE_RATE: u32 = 48_000;
@@ -43,52 +44,36 @@ fn default_quality() -> String {
     "original".to_string()
 }
 
-fn is_lossless(format: &str) -> bool {
-    matches!(
-        format,
-        "flac" | "wav" | "aiff" | "alac" | "ape" | "wv" | "caf"
-    )
-}
-
-fn format_content_type(format: &str) -> &'static str {
-    match format {
-        "aac" => "audio/aac",
-        "adpcm" | "wav" => "audio/wav",
-        "aiff" => "audio/aiff",
-        "alac" | "mp4" => "audio/mp4",
-        "ape" => "audio/x-ape",
-        "caf" => "audio/x-caf",
-        "flac" => "audio/flac",
-        "mkv" => "audio/x-matroska",
-        "mp1" | "mp2" | "mp3" => "audio/mpeg",
-        "ogg" | "vorbis" | "opus" => "audio/ogg",
-        "webm" => "audio/webm",
-        "wma" => "audio/x-ms-wma",
-        "wv" => "audio/x-wavpack",
-        _ => "application/octet-stream",
-    }
+#[derive(Deserialize)]
+pub struct FileParams {
+    /// The file's path in the library, like `./Artist/01.flac`.
+    path: String,
 }
 
 struct TrackFile {
     path: PathBuf,
-    format: String,
+    format: Format,
 }
 
-fn resolve_path(collection_path: &Path, relative: &str) -> PathBuf {
+/// The path on disk of library path `relative` of the collection at `root`, or
+/// of the first collection when it has none recorded.
+fn resolve_path(state: &AppState, root: Option<String>, relative: &str) -> PathBuf {
     let trimmed = relative.strip_prefix("./").unwrap_or(relative);
-    collection_path.join(trimmed)
+    root.map_or_else(|| state.collection_path().to_path_buf(), PathBuf::from)
+        .join(trimmed)
 }
 
 fn lookup_track(state: &AppState, track_id: &str) -> Result<TrackFile, StatusCode> {
-    let (relative, format) = state.read(|conn| {
+    let (relative, format, root) = state.read(|conn| {
         let mut stmt = conn
             .prepare(
-                "SELECT f.path, f.format::VARCHAR \
+                "SELECT f.path, f.format::VARCHAR, c.path \
                  FROM track t JOIN file f ON t.file = f.id \
+                 LEFT JOIN collection c ON c.id = f.collection \
                  WHERE t.id = TRY_CAST(? AS UUID)",
             )
             .map_err(|e| {
-                eprintln!("stream: prepare failed: {e}");
+                tracing::error!("stream: prepare failed: {e}");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
@@ -96,21 +81,26 @@ fn lookup_track(state: &AppState, track_id: &str) -> Result<TrackFile, StatusCod
             .query_map([track_id], |row| {
                 let relative: String = row.get(0)?;
                 let format: String = row.get(1)?;
-                Ok((relative, format))
+                let root: Option<String> = row.get(2)?;
+                Ok((relative, format, root))
             })
             .map_err(|e| {
-                eprintln!("stream: query_map failed: {e}");
+                tracing::error!("stream: query_map failed: {e}");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
         rows.next().ok_or(StatusCode::NOT_FOUND)?.map_err(|e| {
-            eprintln!("stream: row decode failed: {e}");
+            tracing::error!("stream: row decode failed: {e}");
             StatusCode::INTERNAL_SERVER_ERROR
         })
     })?;
+    let format = format.parse().map_err(|e| {
+        tracing::error!("stream: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
     Ok(TrackFile {
-        path: resolve_path(&state.collection_path, &relative),
+        path: resolve_path(state, root, &relative),
         format,
     })
 }
@@ -133,7 +123,7 @@ pub async fn stream_track(
         return (StatusCode::NOT_FOUND, "file not found on disk").into_response();
     }
 
-    let should_transcode = params.quality == "opus128" && is_lossless(&track.format);
+    let should_transcode = params.quality == "opus128" && track.format.is_lossless();
 
     if should_transcode {
         transcode_response(&track, params.start).await
@@ -142,14 +132,69 @@ pub async fn stream_track(
     }
 }
 
+/// Looks up the live file at library path `relative`, in the first collection
+/// that has one. Only paths the library has are served, so this can't reach
+/// anything else on disk.
+fn lookup_file(state: &AppState, relative: &str) -> Result<TrackFile, StatusCode> {
+    let (format, root): (String, Option<String>) = state.read(|conn| {
+        conn.query_row(
+            "SELECT f.format::VARCHAR, c.path \
+             FROM file f LEFT JOIN collection c ON c.id = f.collection \
+             WHERE f.path = ? AND f.deletion IS NULL \
+             ORDER BY c.path \
+             LIMIT 1",
+            [relative],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            duckdb::Error::QueryReturnedNoRows => StatusCode::NOT_FOUND,
+            e => {
+                tracing::error!("stream: file lookup failed: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })
+    })?;
+    let format = format.parse().map_err(|e| {
+        tracing::error!("stream: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(TrackFile {
+        path: resolve_path(state, root, relative),
+        format,
+    })
+}
+
+/// `GET /files/stream?path=<library path>`: the file as it is, for clients
+/// that have a file's path rather than a track.
+pub async fn stream_file(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<FileParams>,
+    request: Request,
+) -> Response {
+    let file = match lookup_file(&state, &params.path) {
+        Ok(file) => file,
+        Err(StatusCode::NOT_FOUND) => {
+            return (StatusCode::NOT_FOUND, "file not found").into_response();
+        }
+        Err(status) => return (status, "database error").into_response(),
+    };
+
+    if !file.path.exists() {
+        return (StatusCode::NOT_FOUND, "file not found on disk").into_response();
+    }
+
+    passthrough_response(&file, request).await
+}
+
 async fn passthrough_response(track: &TrackFile, request: Request) -> Response {
-    let content_type = format_content_type(&track.format);
+    let content_type = track.format.content_type();
 
     let result = ServeFile::new(&track.path).oneshot(request).await;
     let mut response = match result {
         Ok(resp) => resp.into_response(),
         Err(err) => {
-            eprintln!(
+            tracing::error!(
                 "stream: ServeFile failed for {}: {err}",
                 track.path.display()
             );
@@ -204,7 +249,7 @@ fn run_transcode_pipeline(
         if let Some(ready) = ready_tx {
             let _ = ready.send(Err(e.to_string()));
         } else {
-            eprintln!("transcode streaming error: {e}");
+            tracing::error!("transcode streaming error: {e}");
         }
     }
 }
@@ -0,0 +1,320 @@
+//! `PATCH /tracks`: edits to the user-editable columns of many tracks at once.
+//!
+//! The batch is all-or-nothing. Every update is checked against
+//! [`EDITABLE_FIELDS`] before anything is written, then all of them run in one
+//! transaction that is rolled back if any track is missing or was edited since
+//! the client read it. Each edit bumps the track's `version`; an update that
+//! names the `version` it was based on only applies while the track is still at
+//! that version, so concurrent edits can't silently overwrite each other.
+//!
+//! `POST /tracks/{id}/rating` sets the rating of a single track, as the UI's
+//! stars do.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use duckdb::types::Value as SqlValue;
+use duckdb::{Connection, OptionalExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::server::AppState;
+
+/// The highest rating a track can have, in stars. Ratings go from 0 up to it.
+pub const MAX_RATING: f64 = 5.0;
+
+#[derive(Clone, Copy)]
+enum FieldKind {
+    Text,
+    Number,
+}
+
+/// The columns of `track` that `PATCH /tracks` may change. Everything else is
+/// derived from the files by scans.
+const EDITABLE_FIELDS: &[(&str, FieldKind)] = &[
+    ("title", FieldKind::Text),
+    ("primary_genre", FieldKind::Text),
+    ("rating", FieldKind::Number),
+];
+
+#[derive(Deserialize)]
+pub struct TrackUpdate {
+    id: String,
+    /// New values by column name. `null` clears a column.
+    fields: Map<String, Value>,
+    /// The version the edit is based on. Without it the update always applies.
+    version: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct TrackUpdateResult {
+    id: String,
+    /// The track's version after the update, absent when it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PatchTracksResponse {
+    /// Whether the updates were written. When false, nothing was, and the
+    /// failing updates carry an `error`.
+    applied: bool,
+    results: Vec<TrackUpdateResult>,
+}
+
+/// Converts one field of an update into its column name and SQL value.
+fn column_value(name: &str, value: &Value) -> Result<(&'static str, SqlValue), String> {
+    let &(column, kind) = EDITABLE_FIELDS
+        .iter()
+        .find(|(column, _)| *column == name)
+        .ok_or_else(|| format!("field not editable: {name}"))?;
+    let sql_value = match (kind, value) {
+        (_, Value::Null) => SqlValue::Null,
+        (FieldKind::Text, Value::String(s)) => SqlValue::Text(s.clone()),
+        (FieldKind::Number, Value::Number(n)) => {
+            SqlValue::Double(n.as_f64().ok_or_else(|| format!("{name}: not a number"))?)
+        }
+        (FieldKind::Text, _) => return Err(format!("{name}: expected a string or null")),
+        (FieldKind::Number, _) => return Err(format!("{name}: expected a number or null")),
+    };
+    if column == "rating"
+        && let SqlValue::Double(rating) = sql_value
+    {
+        check_rating(rating)?;
+    }
+    Ok((column, sql_value))
+}
+
+/// Checks that `rating` is between 0 and [`MAX_RATING`].
+fn check_rating(rating: f64) -> Result<(), String> {
+    if (0.0..=MAX_RATING).contains(&rating) {
+        Ok(())
+    } else {
+        Err(format!(
+            "rating: {rating} is not between 0 and {MAX_RATING}"
+        ))
+    }
+}
+
+/// Builds the `UPDATE` statement and its parameters for one update.
+fn update_statement(update: &TrackUpdate) -> Result<(String, Vec<SqlValue>), String> {
+    if update.fields.is_empty() {
+        return Err("no fields to update".to_string());
+    }
+    let mut assignments = Vec::new();
+    let mut params = Vec::new();
+    for (name, value) in &update.fields {
+        let (column, sql_value) = column_value(name, value)?;
+        assignments.push(format!("{column} = ?"));
+        params.push(sql_value);
+    }
+    let mut sql = format!(
+        "UPDATE track SET {}, version = version + 1 WHERE id = TRY_CAST(? AS UUID)",
+        assignments.join(", ")
+    );
+    params.push(SqlValue::Text(update.id.clone()));
+    if let Some(version) = update.version {
+        sql.push_str(" AND version = ?");
+        params.push(SqlValue::UInt(version));
+    }
+    sql.push_str(" RETURNING version");
+    Ok((sql, params))
+}
+
+/// Runs one update, returning the track's new version.
+fn apply(
+    conn: &Connection,
+    update: &TrackUpdate,
+    sql: &str,
+    params: Vec<SqlValue>,
+) -> Result<u32, String> {
+    let version: Option<u32> = conn
+        .query_row(sql, duckdb::params_from_iter(params), |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(version) = version {
+        return Ok(version);
+    }
+    let current: Option<u32> = conn
+        .query_row(
+            "SELECT version FROM track WHERE id = TRY_CAST(? AS UUID)",
+            [&update.id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Err(match current {
+        None => format!("track not found: {}", update.id),
+        Some(current) => format!("version conflict: the track is at version {current}"),
+    })
+}
+
+/// Applies every update in one transaction, rolling all of them back if any
+/// fails. Returns whether they were applied along with each one's result.
+fn apply_all(
+    conn: &Connection,
+    updates: &[TrackUpdate],
+    statements: Vec<(String, Vec<SqlValue>)>,
+) -> Result<(bool, Vec<TrackUpdateResult>), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut applied = true;
+    let mut results = Vec::new();
+    for (update, (sql, params)) in updates.iter().zip(statements) {
+        let (version, error) = match apply(&tx, update, &sql, params) {
+            Ok(version) => (Some(version), None),
+            Err(e) => {
+                applied = false;
+                (None, Some(e))
+            }
+        };
+        results.push(TrackUpdateResult {
+            id: update.id.clone(),
+            version,
+            error,
+        });
+    }
+    if applied {
+        tx.commit().map_err(|e| e.to_string())?;
+    } else {
+        // Versions of the updates that did run would never have been written.
+        for result in &mut results {
+            result.version = None;
+        }
+        tx.rollback().map_err(|e| e.to_string())?;
+    }
+    Ok((applied, results))
+}
+
+/// `PATCH /tracks`: `[{ "id", "fields": { column: value, ... }, "version"? }]`.
+///
+/// Answers 200 when every update was applied, 400 when an update names a field
+/// that isn't editable or gives it a value of the wrong type, and 409 when a
+/// track is missing or at another version. Only 200 means anything was written.
+pub async fn patch_tracks(
+    State(state): State<Arc<AppState>>,
+    Json(updates): Json<Vec<TrackUpdate>>,
+) -> Result<(StatusCode, Json<PatchTracksResponse>), (StatusCode, String)> {
+    let mut statements = Vec::new();
+    let mut invalid = false;
+    let mut results = Vec::new();
+    for update in &updates {
+        let error = match update_statement(update) {
+            Ok(statement) => {
+                statements.push(statement);
+                None
+            }
+            Err(e) => {
+                invalid = true;
+                Some(e)
+            }
+        };
+        results.push(TrackUpdateResult {
+            id: update.id.clone(),
+            version: None,
+            error,
+        });
+    }
+    if invalid {
+        let response = PatchTracksResponse {
+            applied: false,
+            results,
+        };
+        return Ok((StatusCode::BAD_REQUEST, Json(response)));
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        state.write(|conn| apply_all(conn, &updates, statements))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((applied, results))) => {
+            let status = if applied {
+                StatusCode::OK
+            } else {
+                StatusCode::CONFLICT
+            };
+            Ok((status, Json(PatchTracksResponse { applied, results })))
+        }
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "tracks task panicked".to_string(),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RatingBody {
+    /// The new rating. `null` clears it.
+    rating: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct TrackRating {
+    pub id: String,
+    /// The rating as stored.
+    pub rating: Option<f32>,
+    /// The track's version after the update.
+    pub version: u32,
+}
+
+/// Sets the rating of track `id`, or clears it with `None`, bumping the track's
+/// version. Returns the track's rating and version as written, or `None` when
+/// there's no such track.
+pub fn set_rating(
+    state: &AppState,
+    id: &str,
+    rating: Option<f64>,
+) -> Result<Option<TrackRating>, String> {
+    if let Some(rating) = rating {
+        check_rating(rating)?;
+    }
+    state.write(|conn| {
+        conn.query_row(
+            "UPDATE track SET rating = ?, version = version + 1 \
+             WHERE id = TRY_CAST(? AS UUID) RETURNING rating, version",
+            duckdb::params![rating, id],
+            |row| {
+                Ok(TrackRating {
+                    id: id.to_string(),
+                    rating: row.get(0)?,
+                    version: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    })
+}
+
+/// `POST /tracks/{id}/rating`: `{ "rating": n }`, or `null` to clear it.
+///
+/// Answers with the track's `id`, `rating` and `version` after the update, 400
+/// when the rating isn't between 0 and [`MAX_RATING`] and 404 when there's no
+/// such track.
+pub async fn post_rating(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(body): Json<RatingBody>,
+) -> Result<Json<TrackRating>, (StatusCode, String)> {
+    // Refused before waiting for the writer, which a scan may hold a while.
+    if let Some(rating) = body.rating {
+        check_rating(rating).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    let not_found = format!("track not found: {id}");
+    let result = tokio::task::spawn_blocking(move || set_rating(&state, &id, body.rating)).await;
+    match result {
+        Ok(Ok(Some(rating))) => Ok(Json(rating)),
+        Ok(Ok(None)) => Err((StatusCode::NOT_FOUND, not_found)),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "rating task panicked".to_string(),
+        )),
+    }
+}
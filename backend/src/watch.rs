@@ -0,0 +1,27 @@
+//! Keeping the library in sync with the collection alongside the server (see
+//! [`scanner::watch()`]).
+
+use std::sync::Arc;
+
+use crate::scanner::{self, ScanOptions};
+use crate::server::AppState;
+
+/// Starts watching each collection on a thread of its own. Each change is
+/// stored through [`AppState::write`], like the batches of a backfill.
+pub fn start(state: &Arc<AppState>, options: &ScanOptions) {
+    for i in 0..state.collection_paths.len() {
+        let state = Arc::clone(state);
+        let options = options.clone();
+        std::thread::spawn(move || {
+            let collection_path = &state.collection_paths[i];
+            let result = scanner::watch(collection_path, &options, |task| {
+                state
+                    .write(|conn| task(conn).map_err(|e| e.to_string()))
+                    .map_err(Into::into)
+            });
+            if let Err(e) = result {
+                tracing::error!("Watch of {} failed: {e}", collection_path.display());
+            }
+        });
+    }
+}
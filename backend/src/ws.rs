@@ -0,0 +1,135 @@
+//! `GET /ws`: queries over a WebSocket, for clients such as dashboards that run
+//! queries again and again and want their rows as they come.
+//!
+//! Each text message the client sends is a query, run as `/query` runs it, with
+//! the parameters of the `/ws` request (`?timeout=`, `?as_of=`, ...) applying
+//! to every one. Its rows come back as binary messages which together make an
+//! Arrow IPC stream, one message per flushed chunk, followed by a [`DONE`] text
+//! message. A statement without result columns is answered with a
+//! `{"rows_affected": n}` text message before [`DONE`], and a failed query
+//! with its [`QueryError`] as JSON in place of [`DONE`].
+//!
+//! Queries run one at a time, in the order they're sent. Closing the socket
+//! cancels the query running.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::Response;
+
+use crate::query::{QueryError, QueryParams, Ready, ResultFormat};
+use crate::server::{AppState, spawn_query};
+
+/// The text message that ends the answer to a query that went through.
+pub const DONE: &str = "done";
+
+pub async fn ws(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<QueryParams>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, state, params))
+}
+
+/// Answers the queries sent on `socket` until the client goes away.
+async fn serve(mut socket: WebSocket, state: Arc<AppState>, params: QueryParams) {
+    let params = QueryParams {
+        format: Some(ResultFormat::Arrow),
+        ..params
+    };
+    // The queries sent while another one ran.
+    let mut pending = VecDeque::new();
+    loop {
+        let Some(sql) = pending.pop_front() else {
+            if !received(socket.recv().await, &mut pending) {
+                return;
+            }
+            continue;
+        };
+        if !answer(&mut socket, &state, sql, params.clone(), &mut pending).await {
+            return;
+        }
+    }
+}
+
+/// Takes in a message from the client, queueing it in `pending` if it's a
+/// query. `false` once the client has gone away.
+fn received(message: Option<Result<Message, axum::Error>>, pending: &mut VecDeque<String>) -> bool {
+    match message {
+        Some(Ok(Message::Text(sql))) => {
+            pending.push_back(sql.to_string());
+            true
+        }
+        // Pings are answered by axum.
+        Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => true,
+        Some(Ok(Message::Close(_)) | Err(_)) | None => false,
+    }
+}
+
+/// Runs `sql` and sends its answer on `socket`, queueing in `pending` the
+/// queries sent meanwhile. `false` once the client has gone away, which
+/// cancels the query.
+async fn answer(
+    socket: &mut WebSocket,
+    state: &Arc<AppState>,
+    sql: String,
+    params: QueryParams,
+    pending: &mut VecDeque<String>,
+) -> bool {
+    let (mut ready_rx, mut rows) = spawn_query(Arc::clone(state), sql.clone(), Vec::new(), params);
+    let ready = loop {
+        tokio::select! {
+            ready = &mut ready_rx => break ready,
+            message = socket.recv() => if !received(message, pending) {
+                return false;
+            },
+        }
+    };
+    match ready {
+        Ok(Ok(Ready::Rows { .. })) => {}
+        Ok(Ok(Ready::RowsAffected(count))) => {
+            let rows_affected = serde_json::json!({ "rows_affected": count }).to_string();
+            return send(socket, Message::Text(rows_affected.into())).await
+                && send(socket, Message::Text(DONE.into())).await;
+        }
+        Ok(Err(msg)) => return send_error(socket, QueryError::new(msg, &sql)).await,
+        Err(_) => {
+            let error = QueryError {
+                error: "query task panicked".to_string(),
+                kind: "internal".to_string(),
+                position: None,
+            };
+            return send_error(socket, error).await;
+        }
+    }
+    loop {
+        tokio::select! {
+            chunk = rows.recv() => match chunk {
+                Some(Ok(bytes)) => {
+                    if !send(socket, Message::Binary(bytes)).await {
+                        return false;
+                    }
+                }
+                Some(Err(e)) => {
+                    return send_error(socket, QueryError::new(e.to_string(), &sql)).await;
+                }
+                None => return send(socket, Message::Text(DONE.into())).await,
+            },
+            message = socket.recv() => if !received(message, pending) {
+                return false;
+            },
+        }
+    }
+}
+
+/// Sends `message`. `false` when the client has gone away.
+async fn send(socket: &mut WebSocket, message: Message) -> bool {
+    socket.send(message).await.is_ok()
+}
+
+async fn send_error(socket: &mut WebSocket, error: QueryError) -> bool {
+    let json = serde_json::to_string(&error).unwrap();
+    send(socket, Message::Text(json.into())).await
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use duckdb::Connection;
+
+/// A library holding a track for each `(path, album, album artist, artist)`,
+/// with nothing but those tags stored.
+fn library(tracks: &[(&str, &str, Option<&str>, &str)]) -> Connection {
+    let conn = common::library();
+    for (n, &(path, album, album_artist, artist)) in tracks.iter().enumerate() {
+        let mut tags = vec![("ALBUM", "Album", album), ("ARTIST", "Artist", artist)];
+        if let Some(album_artist) = album_artist {
+            tags.push(("ALBUMARTIST", "AlbumArtist", album_artist));
+        }
+        common::add_file(&conn, n, path, &tags);
+    }
+    common::rederive(&conn);
+    conn
+}
+
+/// `(track path, album title, album artist)` for every track, in path order.
+fn albums(conn: &Connection) -> Vec<(String, String, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path, al.title, ar.name
+             FROM track t JOIN file f ON f.id = t.file JOIN album al ON al.id = t.album
+             LEFT JOIN artist ar ON ar.id = al.artist
+             ORDER BY f.path",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn tracks_of_different_performers_share_the_album_artist_album() {
+    let conn = library(&[
+        ("./box/1.flac", "Box", Some("Various Artists"), "First"),
+        ("./box/2.flac", "Box", Some("Various Artists"), "Second"),
+        ("./box/3.flac", "Box", Some("Various Artists"), "Third"),
+    ]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM album"), 1);
+    let albums = albums(&conn);
+    assert!(
+        albums.iter().all(
+            |(_, title, artist)| title == "Box" && artist.as_deref() == Some("Various Artists")
+        ),
+        "{albums:?}"
+    );
+    // The album artist is an artist of its own, though no track credits it.
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT count(*) FROM credit c JOIN artist ar ON ar.id = c.artist
+             WHERE ar.name = 'Various Artists'"
+        ),
+        0
+    );
+}
+
+#[test]
+fn the_track_artist_stands_in_for_a_missing_album_artist() {
+    let conn = library(&[
+        ("./hits/1.flac", "Greatest Hits", None, "Band"),
+        ("./hits/2.flac", "Greatest Hits", None, "Band"),
+        ("./hits/3.flac", "Greatest Hits", None, "Other Band"),
+    ]);
+    assert_eq!(
+        albums(&conn)
+            .into_iter()
+            .map(|(_, _, artist)| artist)
+            .collect::<Vec<_>>(),
+        [
+            Some("Band".to_string()),
+            Some("Band".to_string()),
+            Some("Other Band".to_string())
+        ]
+    );
+    assert_eq!(count(&conn, "SELECT count(*) FROM album"), 2);
+}
+
+#[test]
+fn the_album_directory_still_breaks_ties() {
+    let conn = library(&[
+        ("./one/1.flac", "Live", Some("Band"), "Band"),
+        ("./two/1.flac", "Live", Some("Band"), "Band"),
+        ("./two/CD2/1.flac", "Live", Some("Band"), "Band"),
+    ]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM album"), 2);
+    assert_eq!(count(&conn, "SELECT count(DISTINCT album) FROM track"), 2);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use duckdb::Connection;
+
+/// Rederives a library holding a track of one album for each `(track number,
+/// disc number)` tag pair, each given as `number/total` like the tags are.
+fn library(tracks: &[(&str, &str)]) -> Connection {
+    let conn = common::library();
+    for (i, &(track_number, disc_number)) in tracks.iter().enumerate() {
+        let mut tags = vec![("ALBUM", "Album", "Album"), ("ARTIST", "Artist", "Artist")];
+        tags.push(("TRACKNUMBER", "TrackNumber", track_number));
+        if !disc_number.is_empty() {
+            tags.push(("DISCNUMBER", "DiscNumber", disc_number));
+        }
+        common::add_file(&conn, i, &format!("./{i}.flac"), &tags);
+    }
+    common::rederive(&conn);
+    conn
+}
+
+/// The `is_complete` of the album of [`library`]`(tracks)`.
+fn is_complete(tracks: &[(&str, &str)]) -> Option<bool> {
+    album_is_complete(&library(tracks))
+}
+
+fn album_is_complete(conn: &Connection) -> Option<bool> {
+    conn.query_row("SELECT is_complete FROM album", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn albums_with_every_track_are_complete() {
+    assert_eq!(
+        is_complete(&[("1/3", ""), ("2/3", ""), ("3/3", "")]),
+        Some(true)
+    );
+}
+
+#[test]
+fn albums_missing_a_track_are_incomplete() {
+    assert_eq!(is_complete(&[("1/3", ""), ("3/3", "")]), Some(false));
+    // Two copies of one track don't make up for another.
+    assert_eq!(is_complete(&[("1/2", ""), ("1/2", "")]), Some(false));
+}
+
+#[test]
+fn each_disc_is_counted_on_its_own() {
+    let both_discs = [("1/2", "1/2"), ("2/2", "1/2"), ("1/1", "2/2")];
+    assert_eq!(is_complete(&both_discs), Some(true));
+    let missing_disc = [("1/2", "1/2"), ("2/2", "1/2")];
+    assert_eq!(is_complete(&missing_disc), Some(false));
+}
+
+#[test]
+fn albums_without_track_totals_are_unknown() {
+    assert_eq!(is_complete(&[("1", ""), ("2", "")]), None);
+}
+
+#[test]
+fn albums_left_without_live_tracks_are_unknown() {
+    let conn = library(&[("1/2", ""), ("2/2", "")]);
+    assert_eq!(album_is_complete(&conn), Some(true));
+    conn.execute_batch(
+        "INSERT INTO deletion (id) VALUES ('00000000-0000-0000-0000-0000000000d1');
+         UPDATE file SET deletion = '00000000-0000-0000-0000-0000000000d1';",
+    )
+    .unwrap();
+    common::rederive(&conn);
+    assert_eq!(album_is_complete(&conn), None);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::path::Path;
+
+use axum::body::{Body, to_bytes};
+use axum::http::Request;
+use backend::{scanner, server};
+use duckdb::Connection;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+const COLLECTION: &str = "tests/resources/collection";
+const CANONICAL: &str = "00000000-0000-0000-0000-0000000000c1";
+
+fn scanned_db(seed_sql: &str) -> Connection {
+    let conn = common::library();
+    conn.execute_batch(seed_sql).unwrap();
+    scanner::scan(
+        Path::new(COLLECTION),
+        &conn,
+        scanner::ScanOptions::default(),
+    )
+    .unwrap();
+    conn
+}
+
+/// The names of the artists the scanned tracks are credited to.
+fn credited_names(conn: &Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT a.name FROM credit c JOIN artist a ON a.id = c.artist ORDER BY 1")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+async fn rpc(app: &axum::Router, method: &str, params: Value) -> Value {
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let request = Request::post("/rpc")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[test]
+fn scan_credits_aliases_to_the_canonical_artist() {
+    let conn = scanned_db(&format!(
+        "INSERT INTO artist (id, name) VALUES ('{CANONICAL}', 'Announcers');
+         INSERT INTO artist_alias (alias, artist) VALUES ('The Announcers', '{CANONICAL}');"
+    ));
+    assert_eq!(credited_names(&conn), ["Announcers"]);
+    let artists: i64 = conn
+        .query_row("SELECT count(*) FROM artist", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(artists, 1);
+}
+
+#[test]
+fn names_match_whatever_their_case_and_spacing() {
+    let conn = scanned_db(&format!(
+        "INSERT INTO artist (id, name) VALUES ('{CANONICAL}', ' the  ANNOUNCERS');"
+    ));
+    assert_eq!(credited_names(&conn), [" the  ANNOUNCERS"]);
+
+    // Aliases match the same way.
+    let conn = scanned_db(&format!(
+        "INSERT INTO artist (id, name) VALUES ('{CANONICAL}', 'Announcers');
+         INSERT INTO artist_alias (alias, artist) VALUES ('THE Announcers', '{CANONICAL}');"
+    ));
+    assert_eq!(credited_names(&conn), ["Announcers"]);
+}
+
+#[tokio::test]
+async fn setting_an_alias_merges_the_existing_artist() {
+    let conn = scanned_db(&format!(
+        "INSERT INTO artist (id, name) VALUES ('{CANONICAL}', 'Announcers');"
+    ));
+    assert_eq!(credited_names(&conn), ["The Announcers"]);
+    let app = server::router(server::app_state(
+        conn.try_clone().unwrap(),
+        std::env::temp_dir(),
+    ));
+
+    let params = json!({ "alias": "The Announcers", "artist": CANONICAL });
+    let response = rpc(&app, "alias.set", params.clone()).await;
+    assert_eq!(response["error"], Value::Null, "{response}");
+    assert_eq!(credited_names(&conn), ["Announcers"]);
+
+    let response = rpc(&app, "alias.list", Value::Null).await;
+    assert_eq!(response["result"], json!([params]));
+
+    // An artist can't be an alias of itself.
+    let response = rpc(
+        &app,
+        "alias.set",
+        json!({ "alias": "Announcers", "artist": CANONICAL }),
+    )
+    .await;
+    assert!(response["error"]["message"].is_string());
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, to_bytes};
+use axum::http::Request;
+use backend::scanner::{self, BackfillProgress, BackfillStatus, ScanOptions};
+use backend::{backfill, server};
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+use serde_json::Value;
+use tower::ServiceExt;
+
+/// A collection holding the album's first three tracks, plus `bad.flac`, which
+/// isn't audio at all, when `with_bad_file`.
+fn collection(with_bad_file: bool) -> TempDir {
+    let dir = TempDir::new("backfill");
+    for name in ["01. Duck.flac", "02. Hens.flac", "03. Geese.flac"] {
+        dir.copy(
+            format!("{ALBUM}/{name}"),
+            &format!("The Announcers - First Test/{name}"),
+        );
+    }
+    if with_bad_file {
+        fs::write(dir.join("bad.flac"), b"not a flac file").unwrap();
+    }
+    dir
+}
+
+fn deferred() -> ScanOptions {
+    ScanOptions {
+        defer_metadata: true,
+        ..Default::default()
+    }
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn a_deferred_scan_only_records_files() {
+    let dir = collection(false);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, deferred()).unwrap();
+
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 3);
+    assert_eq!(
+        count(&conn, "SELECT count(*) FROM file WHERE duration IS NULL"),
+        3
+    );
+    assert_eq!(count(&conn, "SELECT count(*) FROM track"), 0);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_tag"), 0);
+}
+
+#[test]
+fn backfill_reads_the_deferred_metadata() {
+    let dir = collection(false);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, deferred()).unwrap();
+
+    let progress = BackfillProgress::default();
+    scanner::backfill(&dir, &deferred(), &progress, |task| task(&conn)).unwrap();
+
+    assert_eq!(
+        progress.status(),
+        BackfillStatus {
+            running: false,
+            total: 3,
+            read: 3,
+            failed: 0,
+        }
+    );
+    assert_eq!(
+        count(&conn, "SELECT count(*) FROM file WHERE duration > 0"),
+        3
+    );
+    assert_eq!(count(&conn, "SELECT count(*) FROM track"), 3);
+    assert_eq!(
+        count(&conn, "SELECT count(*) FROM track WHERE title <> ''"),
+        3
+    );
+    // The tracks of one directory end up on one album, as a full scan has it.
+    assert_eq!(count(&conn, "SELECT count(DISTINCT album) FROM track"), 1);
+
+    // Nothing is left to read, so another backfill changes nothing.
+    scanner::backfill(&dir, &deferred(), &progress, |task| task(&conn)).unwrap();
+    assert_eq!(count(&conn, "SELECT count(*) FROM track"), 3);
+}
+
+#[test]
+fn unreadable_files_are_recorded_as_failures() {
+    let dir = collection(true);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, deferred()).unwrap();
+
+    let progress = BackfillProgress::default();
+    scanner::backfill(&dir, &deferred(), &progress, |task| task(&conn)).unwrap();
+
+    assert_eq!(progress.status().read, 3);
+    assert_eq!(progress.status().failed, 1);
+    let failures = scanner::load_failures(&conn).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, "./bad.flac");
+    // The file stays in the library, waiting for the next backfill.
+    assert_eq!(
+        count(&conn, "SELECT count(*) FROM file WHERE duration IS NULL"),
+        1
+    );
+}
+
+#[tokio::test]
+async fn scan_status_reports_the_backfill() {
+    let dir = collection(false);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, deferred()).unwrap();
+    let state = server::app_state(conn, dir.to_path_buf());
+    backfill::start(state.clone(), deferred());
+    let app = server::router(state);
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let status = loop {
+        let request = Request::get("/scan/status").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let status: Value = serde_json::from_slice(&body).unwrap();
+        if status["backfill"]["read"] == 3 && status["backfill"]["running"] == false {
+            break status;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "backfill didn't finish: {status}"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(status["backfill"]["total"], 3);
+    assert_eq!(status["backfill"]["failed"], 0);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const SEED_SQL: &str = "
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion) VALUES
+    ('00000000-0000-0000-0000-000000000001', './old.flac', '', 1, 'flac', 1, 0,
+     TIMESTAMP '2024-01-01', TIMESTAMP '2024-06-01', NULL),
+    ('00000000-0000-0000-0000-000000000002', './new.flac', '', 1, 'flac', 1, 0,
+     TIMESTAMP '2024-03-01', TIMESTAMP '2024-03-01', NULL),
+    ('00000000-0000-0000-0000-000000000003', './gone.flac', '', 1, 'flac', 1, 0,
+     TIMESTAMP '2024-12-01', TIMESTAMP '2024-12-01', '00000000-0000-0000-0000-0000000000d1');
+INSERT INTO track (id, file, title) VALUES
+    ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-000000000001', 'Old'),
+    ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-000000000002', 'New'),
+    ('00000000-0000-0000-0000-0000000000a3', '00000000-0000-0000-0000-000000000003', 'Gone');
+INSERT INTO artist (id, name) VALUES ('00000000-0000-0000-0000-0000000000b1', 'The Announcers');
+INSERT INTO credit (track, artist, ord) VALUES
+    ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000b1', 0);
+";
+
+fn app() -> Router {
+    let conn = common::library();
+    conn.execute_batch(SEED_SQL).unwrap();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+async fn get_json(app: &Router, uri: &str) -> (StatusCode, Value) {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn titles(tracks: &Value) -> Vec<&str> {
+    tracks
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["title"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn recent_defaults_to_added() {
+    let (status, tracks) = get_json(&app(), "/recent").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&tracks), ["New", "Old"]);
+}
+
+#[tokio::test]
+async fn recent_by_modified() {
+    let (status, tracks) = get_json(&app(), "/recent?by=modified").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&tracks), ["Old", "New"]);
+    assert_eq!(tracks[0]["artists"], "The Announcers");
+    assert_eq!(tracks[0]["added"], 1_704_067_200);
+    assert_eq!(tracks[0]["modified"], 1_717_200_000);
+}
+
+#[tokio::test]
+async fn recent_respects_limit() {
+    let (_, tracks) = get_json(&app(), "/recent?by=added&limit=1").await;
+    assert_eq!(titles(&tracks), ["New"]);
+}
+
+#[tokio::test]
+async fn recent_rejects_unknown_order() {
+    let (status, _) = get_json(&app(), "/recent?by=played").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
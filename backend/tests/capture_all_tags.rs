@@ -0,0 +1,80 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// The FLAC `flac` with its Vorbis comments replaced by `comments`.
+fn with_comments(flac: &[u8], comments: &[&str]) -> Vec<u8> {
+    let mut out = flac[..4].to_vec();
+    let mut at = 4;
+    loop {
+        let header = flac[at];
+        let len = u32::from_be_bytes([0, flac[at + 1], flac[at + 2], flac[at + 3]]) as usize;
+        let mut body = flac[at + 4..at + 4 + len].to_vec();
+        if header & 0x7F == 4 {
+            body = b"\x00\x00\x00\x00".to_vec();
+            body.extend((comments.len() as u32).to_le_bytes());
+            for comment in comments {
+                body.extend((comment.len() as u32).to_le_bytes());
+                body.extend(comment.as_bytes());
+            }
+        }
+        out.push(header);
+        out.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend(body);
+        at += 4 + len;
+        if header & 0x80 != 0 {
+            break;
+        }
+    }
+    out.extend(&flac[at..]);
+    out
+}
+
+/// A collection of one file tagged with a title and a tag of no standard key.
+fn collection() -> TempDir {
+    let dir = TempDir::new("all-tags");
+    let flac = with_comments(
+        &fs::read(FIXTURE).unwrap(),
+        &["TITLE=Duck", "SET_POSITION=Opener"],
+    );
+    fs::write(dir.join("a.flac"), flac).unwrap();
+    dir
+}
+
+fn raw_tags(conn: &Connection) -> Vec<(String, String)> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM file_tag WHERE std_key IS NULL ORDER BY ord")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn tags_without_a_standard_key_are_only_kept_when_asked() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(raw_tags(&conn), []);
+
+    let conn = common::library();
+    let options = ScanOptions {
+        capture_all_tags: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(
+        raw_tags(&conn),
+        [("SET_POSITION".to_string(), "Opener".to_string())]
+    );
+    // The track is derived as usual.
+    let title: String = conn
+        .query_row("SELECT title FROM track", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(title, "Duck");
+}
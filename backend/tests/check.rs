@@ -0,0 +1,44 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner;
+use common::{ALBUM, TempDir};
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("check");
+    for name in ["01. Duck.flac", "02. Hens.flac"] {
+        dir.copy(format!("{ALBUM}/{name}"), name);
+    }
+    dir
+}
+
+#[test]
+fn check_diffs_library_paths_against_the_disk() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, scanner::ScanOptions::default()).unwrap();
+
+    let report = scanner::check(&conn, &dir).unwrap();
+    assert!(report.is_consistent());
+
+    fs::remove_file(dir.join("01. Duck.flac")).unwrap();
+    fs::create_dir(dir.join("more")).unwrap();
+    dir.copy(format!("{ALBUM}/03. Geese.flac"), "more/03. Geese.flac");
+    fs::write(dir.join("notes.txt"), "not audio").unwrap();
+
+    let report = scanner::check(&conn, &dir).unwrap();
+    assert!(!report.is_consistent());
+    assert_eq!(report.missing, ["./01. Duck.flac"]);
+    assert_eq!(report.untracked, ["./more/03. Geese.flac"]);
+
+    // Checking changes nothing: the missing file is still live.
+    let live: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM file WHERE deletion IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(live, 2);
+}
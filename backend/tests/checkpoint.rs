@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("checkpoint");
+    dir.copy(FIXTURE, "a.flac");
+    dir
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+fn live_files(conn: &Connection) -> u32 {
+    count(conn, "SELECT count(*) FROM file WHERE deletion IS NULL")
+}
+
+fn saved(conn: &Connection) -> bool {
+    count(
+        conn,
+        "SELECT count(*) FROM information_schema.schemata WHERE schema_name = 'scan_checkpoint'",
+    ) > 0
+}
+
+/// Runs a `--checkpoint` scan of `dir` whose write fails, leaving its findings
+/// saved: a credit of a missing track fails the write's integrity checks.
+fn interrupted_scan(dir: &Path, conn: &Connection) {
+    conn.execute(
+        "INSERT INTO credit (track, artist, ord, role) VALUES (uuid(), uuid(), 0, '')",
+        [],
+    )
+    .unwrap();
+    let options = ScanOptions {
+        checkpoint: true,
+        ..ScanOptions::default()
+    };
+    assert!(scanner::scan(dir, conn, options).is_err());
+    conn.execute("DELETE FROM credit", []).unwrap();
+    assert_eq!(live_files(conn), 0);
+    assert!(saved(conn));
+}
+
+#[test]
+fn checkpointed_scans_leave_nothing_behind() {
+    let dir = collection();
+    let conn = common::library();
+    let options = ScanOptions {
+        checkpoint: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 1);
+    assert!(!saved(&conn));
+}
+
+#[test]
+fn interrupted_scans_are_resumed() {
+    let dir = collection();
+    let conn = common::library();
+    interrupted_scan(&dir, &conn);
+
+    let error = scanner::scan(&dir, &conn, ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("--resume"), "{error}");
+
+    // The saved findings are written even once the file is gone.
+    fs::remove_file(dir.join("a.flac")).unwrap();
+    let options = ScanOptions {
+        resume: true,
+        no_delete: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 1);
+    assert!(!saved(&conn));
+}
+
+#[test]
+fn interrupted_scans_can_be_discarded() {
+    let dir = collection();
+    let conn = common::library();
+    interrupted_scan(&dir, &conn);
+
+    fs::remove_file(dir.join("a.flac")).unwrap();
+    let options = ScanOptions {
+        discard_checkpoint: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 0);
+    assert!(!saved(&conn));
+}
+
+#[test]
+fn only_the_interrupted_collection_is_resumed() {
+    let dir = collection();
+    let conn = common::library();
+    interrupted_scan(&dir, &conn);
+
+    let options = ScanOptions {
+        resume: true,
+        ..ScanOptions::default()
+    };
+    assert!(scanner::scan(&collection(), &conn, options).is_err());
+    assert!(saved(&conn));
+}
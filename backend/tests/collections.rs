@@ -0,0 +1,116 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::db;
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A fresh collection holding a copy of the fixture at each of `paths`.
+fn collection(paths: &[&str]) -> TempDir {
+    let dir = TempDir::new("collections");
+    for path in paths {
+        dir.copy(FIXTURE, path);
+    }
+    dir
+}
+
+/// The live files of the collection at `dir`, by path.
+fn live_files(conn: &Connection, dir: &Path) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path FROM file f JOIN collection c ON c.id = f.collection
+             WHERE c.path = ? AND f.deletion IS NULL ORDER BY f.path",
+        )
+        .unwrap();
+    let path = fs::canonicalize(dir).unwrap().display().to_string();
+    stmt.query_map([path], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn collections_are_scanned_into_one_library() {
+    let lossless = collection(&["Artist/01.flac", "Artist/02.flac"]);
+    let lossy = collection(&["Artist/01.flac"]);
+    let conn = common::library();
+    scanner::scan(&lossless, &conn, ScanOptions::default()).unwrap();
+    scanner::scan(&lossy, &conn, ScanOptions::default()).unwrap();
+
+    let collections: u32 = conn
+        .query_row("SELECT count(*) FROM collection", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(collections, 2);
+    assert_eq!(
+        live_files(&conn, &lossless),
+        ["./Artist/01.flac", "./Artist/02.flac"]
+    );
+    assert_eq!(live_files(&conn, &lossy), ["./Artist/01.flac"]);
+}
+
+#[test]
+fn scans_leave_other_collections_alone() {
+    let lossless = collection(&["Artist/01.flac", "Artist/02.flac"]);
+    let lossy = collection(&["Other/01.flac"]);
+    let conn = common::library();
+    scanner::scan(&lossless, &conn, ScanOptions::default()).unwrap();
+    scanner::scan(&lossy, &conn, ScanOptions::default()).unwrap();
+
+    // Scanning either again finds nothing of the other's missing.
+    scanner::scan(&lossless, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_files(&conn, &lossy), ["./Other/01.flac"]);
+    fs::remove_file(lossy.join("Other/01.flac")).unwrap();
+    scanner::scan(&lossy, &conn, ScanOptions::default()).unwrap();
+    assert!(live_files(&conn, &lossy).is_empty());
+    assert_eq!(live_files(&conn, &lossless).len(), 2);
+}
+
+/// Makes the library in `conn` look as if it was scanned before collections
+/// were recorded.
+fn forget_collections(conn: &Connection) {
+    conn.execute_batch("UPDATE file SET collection = NULL; DELETE FROM collection;")
+        .unwrap();
+}
+
+fn file_count(conn: &Connection) -> u32 {
+    conn.query_row("SELECT count(*) FROM file", [], |row| row.get(0))
+        .unwrap()
+}
+
+#[test]
+fn files_scanned_before_collections_are_taken_over_by_the_one_holding_the_database() {
+    let dir = collection(&["01.flac"]);
+    let other = collection(&["Other/01.flac"]);
+    let conn = db::get_db(&db::default_db_path(&dir)).unwrap();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    forget_collections(&conn);
+
+    // Another collection scanned first leaves them alone.
+    scanner::scan(&other, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_files(&conn, &other), ["./Other/01.flac"]);
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_files(&conn, &dir), ["./01.flac"]);
+    assert_eq!(file_count(&conn), 2);
+
+    drop(conn);
+}
+
+#[test]
+fn files_scanned_before_collections_can_be_adopted_by_another_collection() {
+    let dir = collection(&["01.flac"]);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    forget_collections(&conn);
+
+    // The database is kept outside the collection.
+    let adopt = ScanOptions {
+        adopt_legacy_files: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, adopt).unwrap();
+    assert_eq!(live_files(&conn, &dir), ["./01.flac"]);
+    assert_eq!(file_count(&conn), 1);
+}
@@ -0,0 +1,116 @@
+//! Helpers shared by the integration tests: temporary collections that clean up
+//! after themselves, and libraries made of stored tags. Each test crate uses
+//! only some of them.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use backend::db;
+use backend::scanner::{self, AlbumOptions, GenreOptions};
+use duckdb::{Connection, ToSql, params};
+
+/// The album the test collections are made of.
+pub const ALBUM: &str = "tests/resources/collection/The Announcers - First Test";
+
+/// The album's first track, for tests that need any audio file.
+pub const FIXTURE: &str = "tests/resources/collection/The Announcers - First Test/01. Duck.flac";
+
+/// A fresh directory under the system's temp dir, removed with everything in it
+/// when dropped, so that a failing test doesn't leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// A new empty directory, named after `name` to tell which test made it.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("collectune-{name}-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Copies `from` to `path` in the directory, creating the directories on the
+    /// way.
+    pub fn copy(&self, from: impl AsRef<Path>, path: &str) {
+        let to = self.join(path);
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::copy(from, to).unwrap();
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A collection holding a copy of the album's track `from` at each `(from,
+/// path)` of `tracks`.
+pub fn collection(name: &str, tracks: &[(&str, &str)]) -> TempDir {
+    let dir = TempDir::new(name);
+    for (from, path) in tracks {
+        dir.copy(Path::new(ALBUM).join(from), path);
+    }
+    dir
+}
+
+/// An empty library in memory.
+pub fn library() -> Connection {
+    db::get_db(Path::new(":memory:")).unwrap()
+}
+
+/// Adds a file at `path` and a track of it to `conn`, the file holding the
+/// stored `tags` as `(key, std_key, value)`, as a scan leaves them for
+/// [`rederive`]. Their ids end in `f{n}` and `a{n}`, for `n` below 10, and the
+/// file's format is its extension.
+pub fn add_file<S: ToSql>(conn: &Connection, n: usize, path: &str, tags: &[(&str, S, &str)]) {
+    let file = format!("00000000-0000-0000-0000-0000000000f{n}");
+    let format = Path::new(path).extension().unwrap().to_str().unwrap();
+    conn.execute(
+        "INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+         VALUES (?, ?, '', 1, ?, 1, 0, now(), now(), NULL)",
+        params![file, path, format],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO track (id, file, title) VALUES (?, ?, 'A')",
+        params![format!("00000000-0000-0000-0000-0000000000a{n}"), file],
+    )
+    .unwrap();
+    for (ord, (key, std_key, value)) in (0_i32..).zip(tags) {
+        conn.execute(
+            "INSERT INTO file_tag (file, ord, key, std_key, value) VALUES (?, ?, ?, ?, ?)",
+            params![file, ord, key, std_key, value],
+        )
+        .unwrap();
+    }
+}
+
+/// Rebuilds the library from its stored tags with the default options.
+pub fn rederive(conn: &Connection) {
+    scanner::rederive(conn, &GenreOptions::default(), &AlbumOptions::default()).unwrap();
+}
+
+/// A library holding the one file `./a.flac` with the stored `tags` (see
+/// [`add_file`]), rederived.
+pub fn rederived<S: ToSql>(tags: &[(&str, S, &str)]) -> Connection {
+    let conn = library();
+    add_file(&conn, 1, "./a.flac", tags);
+    rederive(&conn);
+    conn
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use backend::server;
+use common::{FIXTURE, TempDir};
+use tower::ServiceExt;
+
+/// The `Content-Encoding` of the response to `request`, and the size of its body.
+async fn encoding(app: &Router, request: Request<Body>) -> (Option<String>, usize) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (encoding, body.len())
+}
+
+fn query(accept_encoding: Option<&str>) -> Request<Body> {
+    let mut request = Request::post("/query");
+    if let Some(accept_encoding) = accept_encoding {
+        request = request.header(header::ACCEPT_ENCODING, accept_encoding);
+    }
+    request
+        .body(Body::from("SELECT 'row ' || range AS s FROM range(10000)"))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn query_results_are_compressed_as_accepted() {
+    let conn = common::library();
+    let app = server::router(server::app_state(conn, std::env::temp_dir()));
+
+    let (plain, plain_len) = encoding(&app, query(None)).await;
+    assert_eq!(plain, None);
+    for accepted in ["gzip", "zstd"] {
+        let (encoding, len) = encoding(&app, query(Some(accepted))).await;
+        assert_eq!(encoding.as_deref(), Some(accepted));
+        assert!(len < plain_len, "{accepted}: {len} >= {plain_len}");
+    }
+}
+
+#[tokio::test]
+async fn audio_is_not_compressed() {
+    let dir = TempDir::new("compression");
+    dir.copy(FIXTURE, "a.flac");
+    let conn = common::library();
+    conn.execute_batch(
+        "INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified)
+         VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 1, 0,
+                 now(), now())",
+    )
+    .unwrap();
+    let app = server::router(server::app_state(conn, dir.to_path_buf()));
+
+    let request = Request::get("/files/stream?path=./a.flac")
+        .header(header::ACCEPT_ENCODING, "gzip, zstd")
+        .body(Body::empty())
+        .unwrap();
+    let (encoding, len) = encoding(&app, request).await;
+    assert_eq!(encoding, None);
+    assert_eq!(len, fs::read(FIXTURE).unwrap().len());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use duckdb::Connection;
+
+/// A library holding one track of album `Album` whose file has the given
+/// stored tags as `(key, std_key, value)`, rederived.
+fn library(tags: &[(&str, &str, &str)]) -> Connection {
+    let album = ("ALBUM", "Album", "Album");
+    common::rederived(&[&[album][..], tags].concat())
+}
+
+/// `(artist, role)` of the track's credits in order.
+fn credits(conn: &Connection) -> Vec<(String, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT ar.name, nullif(c.role, '') FROM credit c JOIN artist ar ON ar.id = c.artist
+             ORDER BY c.ord",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn album_artist(conn: &Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT ar.name FROM album al LEFT JOIN artist ar ON ar.id = al.artist",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn people_are_credited_in_their_roles() {
+    let conn = library(&[
+        ("COMPOSER", "Composer", "J. S. Bach"),
+        ("ARTIST", "Artist", "Berliner Philharmoniker"),
+        ("CONDUCTOR", "Conductor", "Herbert von Karajan"),
+        ("PRODUCER", "Producer", "Herbert von Karajan"),
+        ("COMPOSER", "Composer", "J. S. Bach"),
+    ]);
+    let credit = |name: &str, role: Option<&str>| (name.to_string(), role.map(str::to_string));
+    assert_eq!(
+        credits(&conn),
+        [
+            credit("Berliner Philharmoniker", None),
+            credit("J. S. Bach", Some("composer")),
+            credit("Herbert von Karajan", Some("conductor")),
+            credit("Herbert von Karajan", Some("producer")),
+        ]
+    );
+    assert_eq!(
+        album_artist(&conn),
+        Some("Berliner Philharmoniker".to_string())
+    );
+}
+
+#[test]
+fn a_composer_is_not_the_album_artist() {
+    let conn = library(&[("COMPOSER", "Composer", "J. S. Bach")]);
+    assert_eq!(
+        credits(&conn),
+        [("J. S. Bach".to_string(), Some("composer".to_string()))]
+    );
+    assert_eq!(album_artist(&conn), None);
+}
+
+#[test]
+fn an_artist_is_credited_once_per_role() {
+    let conn = library(&[("ARTIST", "Artist", "Berliner Philharmoniker")]);
+    let duplicate = "INSERT INTO credit (track, artist, ord)
+                     SELECT track, artist, ord + 1 FROM credit";
+    assert!(conn.execute(duplicate, []).is_err());
+    assert_eq!(credits(&conn).len(), 1);
+}
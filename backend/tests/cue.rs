@@ -0,0 +1,210 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, AlbumOptions, GenreOptions, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A cue sheet splitting `file` into two tracks, the second starting 0.4s in.
+fn sheet(file: &str, title: &str, rem: &str) -> String {
+    format!(
+        "{rem}PERFORMER \"Sheet Band\"
+TITLE \"{title}\"
+FILE \"{file}\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"Opening\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Closing\"
+    PERFORMER \"Guest\"
+    INDEX 00 00:00:20
+    INDEX 01 00:00:30
+"
+    )
+}
+
+/// A collection holding `files`, each a copy of the fixture, and the cue
+/// sheets of `sheets` as `(path, contents)`.
+fn collection(files: &[&str], sheets: &[(&str, String)]) -> TempDir {
+    let dir = TempDir::new("cue");
+    for file in files {
+        dir.copy(FIXTURE, file);
+    }
+    for (path, contents) in sheets {
+        fs::write(dir.join(path), contents).unwrap();
+    }
+    dir
+}
+
+type Track = (
+    String,
+    String,
+    Option<f32>,
+    Option<f32>,
+    Option<u8>,
+    Option<u8>,
+);
+
+/// `(path, title, start, end, track number, disc number)` of every track.
+fn tracks(conn: &Connection) -> Vec<Track> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path, t.title, t.start_position, t.end_position, t.track_number,
+                    t.disc_number
+             FROM track t JOIN file f ON f.id = t.file
+             ORDER BY f.path, t.track_number",
+        )
+        .unwrap();
+    stmt.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            row.get(5)?,
+        ))
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect()
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+fn scanned(dir: &Path) -> Connection {
+    let conn = common::library();
+    scanner::scan(dir, &conn, ScanOptions::default()).unwrap();
+    conn
+}
+
+#[test]
+fn a_cue_sheet_splits_its_file_into_tracks() {
+    let dir = collection(
+        &["album/album.flac"],
+        &[("album/album.cue", sheet("album.wav", "Sheet Album", ""))],
+    );
+    let conn = scanned(&dir);
+
+    assert_eq!(
+        tracks(&conn),
+        [
+            (
+                "./album/album.flac".to_string(),
+                "Opening".to_string(),
+                Some(0.0),
+                Some(0.4),
+                Some(1),
+                None
+            ),
+            (
+                "./album/album.flac".to_string(),
+                "Closing".to_string(),
+                Some(0.4),
+                None,
+                Some(2),
+                None
+            ),
+        ]
+    );
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 1);
+    // The file's album tag wins over the sheet's title; the sheet's performer
+    // stands in for the missing album artist.
+    assert_eq!(
+        conn.query_row(
+            "SELECT al.title, ar.name FROM album al JOIN artist ar ON ar.id = al.artist",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .unwrap(),
+        ("First Test".to_string(), "Sheet Band".to_string())
+    );
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT count(*) FROM credit c JOIN artist ar ON ar.id = c.artist
+             JOIN track t ON t.id = c.track
+             WHERE (t.title = 'Opening' AND ar.name = 'Sheet Band')
+                OR (t.title = 'Closing' AND ar.name = 'Guest')"
+        ),
+        2
+    );
+
+    // Rederiving from the file's tags leaves the tracks of the sheet alone.
+    scanner::rederive(&conn, &GenreOptions::default(), &AlbumOptions::default()).unwrap();
+    assert_eq!(
+        tracks(&conn)
+            .into_iter()
+            .map(|(_, title, ..)| title)
+            .collect::<Vec<_>>(),
+        ["Opening", "Closing"]
+    );
+}
+
+#[test]
+fn each_file_goes_by_the_sheet_that_names_it() {
+    let dir = collection(
+        &["album/one.flac", "album/two.flac", "album/three.flac"],
+        &[
+            ("album/one.cue", sheet("one.flac", "One", "")),
+            ("album/two.cue", sheet("TWO.flac", "Two", "")),
+        ],
+    );
+    let conn = scanned(&dir);
+
+    let per_file: Vec<(String, u32)> = [
+        "./album/one.flac",
+        "./album/three.flac",
+        "./album/two.flac",
+    ]
+    .into_iter()
+    .map(|path| {
+        let sql = format!(
+            "SELECT count(*) FROM track t JOIN file f ON f.id = t.file WHERE f.path = '{path}'"
+        );
+        (path.to_string(), count(&conn, &sql))
+    })
+    .collect();
+    assert_eq!(
+        per_file,
+        [
+            ("./album/one.flac".to_string(), 2),
+            ("./album/three.flac".to_string(), 1),
+            ("./album/two.flac".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn disc_folders_number_the_discs_of_their_sheets() {
+    let dir = collection(
+        &["album/CD1/disc.flac", "album/CD2/disc.flac"],
+        &[
+            ("album/CD1/disc.cue", sheet("disc.flac", "Album", "")),
+            (
+                "album/CD2/disc.cue",
+                sheet("disc.flac", "Album", "REM DISCNUMBER 5\n"),
+            ),
+        ],
+    );
+    let conn = scanned(&dir);
+
+    assert_eq!(
+        tracks(&conn)
+            .into_iter()
+            .map(|(path, _, _, _, track, disc)| (path, track, disc))
+            .collect::<Vec<_>>(),
+        [
+            ("./album/CD1/disc.flac".to_string(), Some(1), Some(1)),
+            ("./album/CD1/disc.flac".to_string(), Some(2), Some(1)),
+            ("./album/CD2/disc.flac".to_string(), Some(1), Some(5)),
+            ("./album/CD2/disc.flac".to_string(), Some(2), Some(5)),
+        ]
+    );
+    // Both discs make one album.
+    assert_eq!(count(&conn, "SELECT count(*) FROM album"), 1);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use std::fs;
+
+use backend::db;
+use common::TempDir;
+
+#[test]
+fn the_database_can_live_anywhere() {
+    let dir = TempDir::new("db-path");
+    let db_path = dir.join("elsewhere.duckdb");
+    db::get_db(&db_path).unwrap();
+    assert!(db_path.exists());
+    // Nothing is left of the check that the directory is writable.
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert!(
+        names
+            .iter()
+            .all(|name| !name.to_string_lossy().starts_with(".collectune-write-test")),
+        "{names:?}"
+    );
+}
+
+#[test]
+fn the_database_directory_must_exist() {
+    let dir = TempDir::new("db-path");
+    let error = db::get_db(&dir.join("missing/collectune.db")).unwrap_err();
+    assert!(error.to_string().contains("does not exist"));
+}
+
+#[test]
+fn the_database_directory_must_be_writable() {
+    let dir = TempDir::new("db-path");
+    let mut permissions = fs::metadata(&dir).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&dir, permissions.clone()).unwrap();
+
+    // Root writes to read-only directories all the same.
+    let writable = fs::write(dir.join("probe"), "").is_ok();
+    let opened = db::get_db(&dir.join("collectune.db"));
+    if writable {
+        assert!(opened.is_ok());
+    } else {
+        let error = opened.unwrap_err().to_string();
+        assert!(error.contains("is not writable: "), "{error}");
+    }
+
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(&dir, permissions).unwrap();
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner::{DjTag, read_geob};
+use duckdb::Connection;
+
+fn geob(name: &str) -> Vec<u8> {
+    fs::read(format!("tests/resources/serato/{name}.geob")).unwrap()
+}
+
+#[test]
+fn serato_autotags_hold_the_bpm() {
+    assert_eq!(read_geob(&geob("autotags")), Some(DjTag::Bpm(115.0)));
+    assert_eq!(read_geob(&geob("autotags-utf16")), Some(DjTag::Bpm(128.5)));
+}
+
+#[test]
+fn unrecognized_frames_are_skipped() {
+    assert_eq!(read_geob(&geob("markers2")), None);
+    assert_eq!(read_geob(b""), None);
+    assert_eq!(read_geob(b"\x00application/octet-stream"), None);
+}
+
+/// A library holding one track per file, each with the given stored tags as
+/// `(key, std_key, value)`.
+fn library(files: &[&[(&str, Option<&str>, &str)]]) -> Connection {
+    let conn = common::library();
+    for (i, tags) in files.iter().enumerate() {
+        common::add_file(&conn, i, &format!("./{i}.mp3"), tags);
+    }
+    conn
+}
+
+/// Rederives the library, returning each track's BPM and key in file order.
+fn bpm_and_key(conn: &Connection) -> Vec<(Option<f32>, Option<String>)> {
+    common::rederive(conn);
+    let mut stmt = conn
+        .prepare("SELECT bpm, musical_key FROM track ORDER BY file")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+#[test]
+fn dj_tags_fill_bpm_and_key() {
+    let conn = library(&[
+        &[
+            ("TIT2", Some("TrackTitle"), "T"),
+            ("GEOB:Serato Autotags", None, "115"),
+            ("TKEY", None, "8A"),
+        ],
+        &[
+            ("TBPM", Some("Bpm"), "124"),
+            ("GEOB:Serato Autotags", None, "123.98"),
+            ("INITIALKEY", None, " Abm "),
+        ],
+        &[("TBPM", Some("Bpm"), "0"), ("TKEY", None, "")],
+    ]);
+    assert_eq!(
+        bpm_and_key(&conn),
+        [
+            (Some(115.0), Some("8A".to_string())),
+            (Some(124.0), Some("Abm".to_string())),
+            (None, None),
+        ]
+    );
+}
+
+#[test]
+fn camelot_and_open_key_values_are_kept() {
+    let conn = library(&[
+        &[("INITIALKEY", None, "12B")],
+        &[("TKEY", None, "1m")],
+        &[("KEY", None, "10d")],
+    ]);
+    let keys: Vec<Option<String>> = bpm_and_key(&conn).into_iter().map(|(_, key)| key).collect();
+    assert_eq!(
+        keys,
+        [Some("12B".into()), Some("1m".into()), Some("10d".into())]
+    );
+}
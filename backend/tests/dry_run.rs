@@ -0,0 +1,65 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, ScanOptions};
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("dry-run");
+    for file in ["01. Duck.flac", "02. Hens.flac"] {
+        dir.copy(Path::new(ALBUM).join(file), file);
+    }
+    dir
+}
+
+fn dry_run() -> ScanOptions {
+    ScanOptions {
+        dry_run: true,
+        ..ScanOptions::default()
+    }
+}
+
+/// `(path, deleted)` of every file, sorted by path.
+fn files(conn: &Connection) -> Vec<(String, bool)> {
+    let mut stmt = conn
+        .prepare("SELECT path, deletion IS NOT NULL FROM file ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn temp_tables(conn: &Connection) -> i64 {
+    conn.query_row(
+        "SELECT count(*) FROM duckdb_tables() WHERE temporary",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn dry_runs_write_nothing() {
+    let dir = collection();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, dry_run()).unwrap();
+    assert!(files(&conn).is_empty());
+    assert_eq!(temp_tables(&conn), 0);
+
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    let before = files(&conn);
+    fs::rename(dir.join("01. Duck.flac"), dir.join("duck.flac")).unwrap();
+    fs::remove_file(dir.join("02. Hens.flac")).unwrap();
+    dir.copy(Path::new(ALBUM).join("03. Geese.flac"), "geese.flac");
+
+    // A new connection has no temp tables left over from the scan above.
+    let conn = conn.try_clone().unwrap();
+    scanner::scan(&dir, &conn, dry_run()).unwrap();
+    assert_eq!(files(&conn), before);
+    assert_eq!(temp_tables(&conn), 0);
+}
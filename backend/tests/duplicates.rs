@@ -0,0 +1,41 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, ScanOptions, find_duplicates};
+use common::{ALBUM, TempDir};
+
+#[test]
+fn copies_of_a_file_are_grouped_by_hash() {
+    let dir = TempDir::new("duplicates");
+    for (fixture, copy) in [
+        ("01. Duck.flac", "a/duck.flac"),
+        ("01. Duck.flac", "b/duck.flac"),
+        ("01. Duck.flac", "b/duck again.flac"),
+        ("02. Hens.flac", "a/hens.flac"),
+    ] {
+        dir.copy(Path::new(ALBUM).join(fixture), copy);
+    }
+
+    let conn = common::library();
+    let options = ScanOptions {
+        report_duplicates: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+
+    let groups = find_duplicates(&conn).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(
+        groups[0].paths,
+        ["./a/duck.flac", "./b/duck again.flac", "./b/duck.flac"]
+    );
+    assert_eq!(groups[0].hash.as_ref().map(String::len), Some(64));
+
+    // A copy that's removed is no longer a duplicate.
+    fs::remove_file(dir.join("b/duck.flac")).unwrap();
+    fs::remove_file(dir.join("b/duck again.flac")).unwrap();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert!(find_duplicates(&conn).unwrap().is_empty());
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use std::path::Path;
+
+use backend::scanner;
+
+const COLLECTION: &str = "tests/resources/collection";
+
+fn scanned_durations(options: scanner::ScanOptions) -> Vec<f64> {
+    let conn = common::library();
+    scanner::scan(Path::new(COLLECTION), &conn, options).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT duration FROM file ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Only MPEG audio durations are measured by reading every packet; the FLAC
+/// fixtures keep the duration from their headers.
+#[test]
+fn accurate_duration_leaves_exact_headers_alone() {
+    let estimated = scanned_durations(scanner::ScanOptions::default());
+    let accurate = scanned_durations(scanner::ScanOptions {
+        accurate_duration: true,
+        ..Default::default()
+    });
+    assert_eq!(estimated.len(), 10);
+    assert!(estimated.iter().all(|&d| d > 0.0));
+    assert_eq!(estimated, accurate);
+}
+
+/// The fixtures are 16-bit mono at 22.05 kHz; the bitrate is averaged over the
+/// file, e.g. 31,265 bytes in 0.917s for the first.
+#[test]
+fn files_are_scanned_with_their_audio_properties() {
+    let conn = common::library();
+    scanner::scan(
+        Path::new(COLLECTION),
+        &conn,
+        scanner::ScanOptions::default(),
+    )
+    .unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT sample_rate, channels, bits_per_sample, bitrate FROM file ORDER BY path LIMIT 2",
+        )
+        .unwrap();
+    let properties: Vec<(u32, u8, u8, u32)> = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(properties, [(22050, 1, 16, 273), (22050, 1, 16, 320)]);
+}
+
+#[test]
+fn durations_are_stored_as_intervals_too() {
+    let conn = common::library();
+    scanner::scan(
+        Path::new(COLLECTION),
+        &conn,
+        scanner::ScanOptions::default(),
+    )
+    .unwrap();
+    let mismatched: u32 = conn
+        .query_row(
+            "SELECT count(*) FROM file
+             WHERE abs(epoch(duration_interval) - duration) > 0.001",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(mismatched, 0);
+}
+
+#[test]
+fn durations_are_formatted_by_fmt_duration() {
+    let conn = common::library();
+    let formatted: (String, String, String, Option<String>) = conn
+        .query_row(
+            "SELECT fmt_duration(225.4), fmt_duration(59.6), fmt_duration(3725),
+                    fmt_duration(NULL)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        formatted,
+        ("3:45".into(), "1:00".into(), "1:02:05".into(), None)
+    );
+}
@@ -0,0 +1,142 @@
+//! Runs `collectune-server` on a small collection, which scans it and starts
+//! serving, then queries it over HTTP, decoding the Arrow stream the way
+//! clients do.
+
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use arrow_ipc::reader::StreamReader;
+use common::{ALBUM, TempDir};
+use duckdb::arrow::util::display::array_value_to_string;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("e2e");
+    for name in ["01. Duck.flac", "02. Hens.flac"] {
+        dir.copy(
+            format!("{ALBUM}/{name}"),
+            &format!("The Announcers - First Test/{name}"),
+        );
+    }
+    dir
+}
+
+/// The server process, killed when dropped so a failing test doesn't leave it
+/// running.
+struct Server {
+    child: Child,
+    addr: SocketAddr,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Starts the server on `collection` and an ephemeral port, waiting until the
+/// scan is done and it accepts connections.
+async fn start_server(collection: &Path) -> Server {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_collectune-server"))
+        .arg(collection)
+        .args(["--port", &port.to_string(), "--log-level", "warn"])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut server = Server {
+        child,
+        addr: SocketAddr::from(([127, 0, 0, 1], port)),
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while TcpStream::connect(server.addr).await.is_err() {
+        if let Some(status) = server.child.try_wait().unwrap() {
+            panic!("server exited early: {status}");
+        }
+        assert!(Instant::now() < deadline, "server didn't start listening");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    server
+}
+
+/// Posts `sql` to `/query`, returning the status line and the body. HTTP/1.0
+/// makes the server end the streamed body by closing the connection, so there's
+/// no chunked encoding to undo.
+async fn post_query(addr: SocketAddr, sql: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "POST /query HTTP/1.0\r\nHost: {addr}\r\nContent-Length: {}\r\n\r\n{sql}",
+        sql.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .expect("response has no header terminator");
+    let head = String::from_utf8_lossy(&response[..split]);
+    let status = head.lines().next().unwrap_or_default().to_string();
+    (status, response[split + 4..].to_vec())
+}
+
+/// Decodes an Arrow IPC stream into rows of displayed values.
+fn decode_rows(body: &[u8]) -> Vec<Vec<String>> {
+    let reader = StreamReader::try_new(body, None).unwrap();
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch.unwrap();
+        for row in 0..batch.num_rows() {
+            let values = batch
+                .columns()
+                .iter()
+                .map(|column| array_value_to_string(column, row).unwrap())
+                .collect();
+            rows.push(values);
+        }
+    }
+    rows
+}
+
+#[tokio::test]
+async fn scanned_tracks_can_be_queried_over_http() {
+    let dir = collection();
+    let server = start_server(&dir).await;
+    let addr = server.addr;
+
+    let sql = "SELECT t.track_number, t.title, a.name
+               FROM track t
+               JOIN credit c ON c.track = t.id
+               JOIN artist a ON a.id = c.artist
+               ORDER BY t.track_number";
+    let (status, body) = post_query(addr, sql).await;
+    assert!(
+        status.ends_with("200 OK"),
+        "{status}: {}",
+        String::from_utf8_lossy(&body)
+    );
+    assert_eq!(
+        decode_rows(&body),
+        [
+            ["1", "Duck", "The Announcers"],
+            ["2", "Hens", "The Announcers"],
+        ]
+    );
+
+    let (status, _) = post_query(addr, "SELECT * FROM missing").await;
+    assert!(status.ends_with("400 Bad Request"), "{status}");
+
+    drop(server);
+}
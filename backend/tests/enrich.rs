@@ -0,0 +1,130 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, ScanOptions};
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+
+/// The FLAC `flac` with its Vorbis comments replaced by `comments`.
+fn with_comments(flac: &[u8], comments: &[&str]) -> Vec<u8> {
+    let mut out = flac[..4].to_vec();
+    let mut at = 4;
+    loop {
+        let header = flac[at];
+        let len = u32::from_be_bytes([0, flac[at + 1], flac[at + 2], flac[at + 3]]) as usize;
+        let mut body = flac[at + 4..at + 4 + len].to_vec();
+        if header & 0x7F == 4 {
+            body = b"\x00\x00\x00\x00".to_vec();
+            body.extend((comments.len() as u32).to_le_bytes());
+            for comment in comments {
+                body.extend((comment.len() as u32).to_le_bytes());
+                body.extend(comment.as_bytes());
+            }
+        }
+        out.push(header);
+        out.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend(body);
+        at += 4 + len;
+        if header & 0x80 != 0 {
+            break;
+        }
+    }
+    out.extend(&flac[at..]);
+    out
+}
+
+/// A collection of one file without tags, long enough to be fingerprinted.
+fn collection() -> TempDir {
+    let dir = TempDir::new("enrich");
+    let flac = with_comments(&fs::read(format!("{ALBUM}/09. Men.flac")).unwrap(), &[]);
+    fs::write(dir.join("01. Untitled.flac"), flac).unwrap();
+    dir
+}
+
+/// The hash `acoustid_lookup` keys the file's lookup by: that of its
+/// fingerprint, as a `--fingerprint` scan stores it.
+fn fingerprint_hash(dir: &Path) -> String {
+    let conn = common::library();
+    let options = ScanOptions {
+        fingerprint: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(dir, &conn, options).unwrap();
+    let blob: Vec<u8> = conn
+        .query_row("SELECT fingerprint FROM file", [], |row| row.get(0))
+        .unwrap();
+    blake3::hash(&blob).to_hex().to_string()
+}
+
+/// Caches `recording` as what `AcoustID` answered for the file in `dir`.
+fn cache(dir: &Path, conn: &Connection, recording: Option<&str>) {
+    conn.execute(
+        "INSERT INTO acoustid_lookup (fingerprint_hash, recording, looked_up)
+         VALUES (?, ?::JSON, now())",
+        duckdb::params![fingerprint_hash(dir), recording],
+    )
+    .unwrap();
+}
+
+fn enrich(dir: &Path, conn: &Connection) {
+    let options = ScanOptions {
+        enrich: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(dir, conn, options).unwrap();
+}
+
+fn track(conn: &Connection) -> (String, Option<String>, Option<String>) {
+    conn.query_row(
+        "SELECT t.title, ar.name, al.title
+         FROM track t
+         LEFT JOIN credit c ON c.track = t.id
+         LEFT JOIN artist ar ON ar.id = c.artist
+         LEFT JOIN album al ON al.id = t.album",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn untagged_files_take_the_recording_looked_up() {
+    let dir = collection();
+    let conn = common::library();
+    cache(
+        &dir,
+        &conn,
+        Some(
+            r#"{"id": "7f3b2e1a-0000-4000-8000-000000000001", "title": "Duck",
+                "artists": [{"id": "7f3b2e1a-0000-4000-8000-000000000002", "name": "The Announcers"}],
+                "releasegroups": [{"title": "First Test"}]}"#,
+        ),
+    );
+    enrich(&dir, &conn);
+    assert_eq!(
+        track(&conn),
+        (
+            "Duck".to_string(),
+            Some("The Announcers".to_string()),
+            Some("First Test".to_string())
+        )
+    );
+    // The fingerprint computed for the lookup is kept.
+    let fingerprinted: bool = conn
+        .query_row("SELECT fingerprint IS NOT NULL FROM file", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert!(fingerprinted);
+}
+
+#[test]
+fn files_without_a_match_keep_what_their_path_gives() {
+    let dir = collection();
+    let conn = common::library();
+    cache(&dir, &conn, None);
+    enrich(&dir, &conn);
+    assert_eq!(track(&conn).0, "Untitled");
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::path::Path;
+
+use backend::scanner;
+use common::{ALBUM, TempDir};
+use globset::Glob;
+
+/// A collection with an album, an artwork folder holding an audio file, and a
+/// backup copy of one of the album's files.
+fn collection() -> TempDir {
+    let dir = TempDir::new("exclude");
+    for (fixture, path) in [
+        ("01. Duck.flac", "album/01.flac"),
+        ("02. Hens.flac", "album/02.flac"),
+        ("03. Geese.flac", "album/02.bak.flac"),
+        ("04. Oysters.flac", "_artwork/preview.flac"),
+    ] {
+        dir.copy(format!("{ALBUM}/{fixture}"), path);
+    }
+    dir
+}
+
+fn scanned_paths(conn: &duckdb::Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT path FROM file WHERE deletion IS NULL ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn scan(dir: &Path, conn: &duckdb::Connection, patterns: &[&str]) {
+    let options = scanner::ScanOptions {
+        exclude: patterns.iter().map(|p| Glob::new(p).unwrap()).collect(),
+        ..Default::default()
+    };
+    scanner::scan(dir, conn, options).unwrap();
+}
+
+#[test]
+fn excluded_files_and_directories_are_skipped() {
+    let dir = collection();
+    let conn = common::library();
+
+    scan(&dir, &conn, &["_artwork", "*.bak.flac"]);
+    assert_eq!(scanned_paths(&conn), ["./album/01.flac", "./album/02.flac"]);
+
+    // Patterns are relative to the collection root.
+    scan(&dir, &conn, &["01.flac"]);
+    assert_eq!(scanned_paths(&conn).len(), 4);
+
+    // Files already in the library that are now excluded count as missing.
+    scan(&dir, &conn, &["album/**"]);
+    assert_eq!(scanned_paths(&conn), ["./_artwork/preview.flac"]);
+}
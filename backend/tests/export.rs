@@ -0,0 +1,93 @@
+mod common;
+
+use std::fs;
+
+use backend::export::{ExportFormat, export};
+use common::TempDir;
+use duckdb::Connection;
+
+/// A library holding a track on an album with two artists and two genres, a
+/// composer credit, and a track of a deleted file.
+fn library() -> Connection {
+    let conn = common::library();
+    conn.execute_batch(
+        "
+INSERT INTO deletion (id) VALUES ('00000000-0000-0000-0000-0000000000d1');
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 120, 0,
+        '2026-01-02 03:04:05', now(), NULL),
+       ('00000000-0000-0000-0000-0000000000f2', './b.flac', '', 1, 'flac', 60, 0,
+        now(), now(), '00000000-0000-0000-0000-0000000000d1');
+INSERT INTO artist (id, name) VALUES
+  ('00000000-0000-0000-0000-0000000000b1', 'One'),
+  ('00000000-0000-0000-0000-0000000000b2', 'Two'),
+  ('00000000-0000-0000-0000-0000000000b3', 'Composer');
+INSERT INTO album (id, title, year, artist)
+VALUES ('00000000-0000-0000-0000-0000000000c1', 'Album', 1999,
+        '00000000-0000-0000-0000-0000000000b1');
+INSERT INTO track (id, file, title, album, track_number, rating) VALUES
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000f1', 'Song',
+   '00000000-0000-0000-0000-0000000000c1', 3, 4.5),
+  ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-0000000000f2', 'Gone',
+   NULL, NULL, NULL);
+INSERT INTO credit (track, artist, ord, role) VALUES
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000b2', 1, ''),
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000b1', 0, ''),
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000b3', 2,
+   'composer');
+INSERT INTO genre (id, name) VALUES
+  ('00000000-0000-0000-0000-0000000000e1', 'Rock'),
+  ('00000000-0000-0000-0000-0000000000e2', 'Jazz');
+INSERT INTO track_genre (track, genre, ord) VALUES
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000e2', 0),
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000e1', 1);
+",
+    )
+    .unwrap();
+    conn
+}
+
+#[test]
+fn the_export_has_a_row_per_live_track() {
+    let conn = library();
+    let dir = TempDir::new("export");
+    let path = dir.join("export.csv");
+    assert_eq!(export(&conn, &path, ExportFormat::Csv).unwrap(), 1);
+
+    let csv = fs::read_to_string(&path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "track_id,path,title,artists,album,album_artist,year,release_date,disc_number,\
+         track_number,genres,primary_genre,duration,rating,bpm,musical_key,format,added"
+    );
+    let row = lines.next().unwrap();
+    assert!(row.starts_with("00000000-0000-0000-0000-0000000000a1,./a.flac,Song,"));
+    assert!(row.contains("\"[One, Two]\",Album,One,1999,"), "{row}");
+    assert!(row.contains(",3,\"[Jazz, Rock]\",,120.0,4.5,"), "{row}");
+    assert_eq!(lines.next(), None);
+}
+
+#[test]
+fn parquet_and_json_exports_read_back() {
+    let conn = library();
+    let dir = TempDir::new("export");
+    for (format, extension) in [
+        (ExportFormat::Parquet, "parquet"),
+        (ExportFormat::Json, "json"),
+    ] {
+        let path = dir.join(format!("export.{extension}"));
+        export(&conn, &path, format).unwrap();
+        let (title, artists): (String, String) = conn
+            .query_row(
+                &format!(
+                    "SELECT title, array_to_string(artists, '|') FROM '{}'",
+                    path.display()
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((title.as_str(), artists.as_str()), ("Song", "One|Two"));
+    }
+}
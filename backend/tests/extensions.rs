@@ -0,0 +1,64 @@
+mod common;
+
+use backend::scanner;
+use clap::Parser;
+use common::{FIXTURE, TempDir};
+
+/// A collection holding the same audio as `a.flac` and `b.mp4`.
+fn collection() -> TempDir {
+    let dir = TempDir::new("extensions");
+    dir.copy(FIXTURE, "a.flac");
+    dir.copy(FIXTURE, "b.mp4");
+    dir
+}
+
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    scan: scanner::ScanOptions,
+}
+
+fn options(args: &[&str]) -> Result<scanner::ScanOptions, clap::Error> {
+    Cli::try_parse_from(std::iter::once("scan").chain(args.iter().copied())).map(|cli| cli.scan)
+}
+
+/// The live files of the library with their format.
+fn scanned(conn: &duckdb::Connection) -> Vec<(String, String)> {
+    let mut stmt = conn
+        .prepare("SELECT path, format::TEXT FROM file WHERE deletion IS NULL ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn the_extensions_scanned_can_be_chosen() {
+    let dir = collection();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, options(&[]).unwrap()).unwrap();
+    assert_eq!(scanned(&conn), [("./a.flac".into(), "flac".into())]);
+
+    let with_mp4 = options(&["--extensions", ".MP4,flac"]).unwrap();
+    assert_eq!(with_mp4.extensions, ["mp4", "flac"]);
+    scanner::scan(&dir, &conn, with_mp4).unwrap();
+    assert_eq!(
+        scanned(&conn),
+        [
+            ("./a.flac".into(), "flac".into()),
+            ("./b.mp4".into(), "mp4".into()),
+        ]
+    );
+
+    // Files with the extensions left out count as missing.
+    scanner::scan(&dir, &conn, options(&["--extensions", "mp4"]).unwrap()).unwrap();
+    assert_eq!(scanned(&conn), [("./b.mp4".into(), "mp4".into())]);
+}
+
+#[test]
+fn extensions_without_a_format_are_refused() {
+    let error = options(&["--extensions", "flac,dsf"]).err().unwrap();
+    assert!(error.to_string().contains("'dsf'"), "{error}");
+}
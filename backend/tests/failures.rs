@@ -0,0 +1,34 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner;
+use common::{FIXTURE, TempDir};
+
+/// A fresh collection holding one readable and one unreadable file.
+fn collection() -> TempDir {
+    let dir = TempDir::new("failures");
+    dir.copy(FIXTURE, "good.flac");
+    fs::write(dir.join("broken.flac"), b"not audio at all").unwrap();
+    dir
+}
+
+#[test]
+fn unreadable_files_are_recorded_until_gone() {
+    let dir = collection();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, scanner::ScanOptions::default()).unwrap();
+    let failures = scanner::load_failures(&conn).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, "./broken.flac");
+    assert_eq!(failures[0].category, "unsupported");
+    let files: i64 = conn
+        .query_row("SELECT count(*) FROM file", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(files, 1);
+
+    fs::remove_file(dir.join("broken.flac")).unwrap();
+    scanner::scan(&dir, &conn, scanner::ScanOptions::default()).unwrap();
+    assert!(scanner::load_failures(&conn).unwrap().is_empty());
+}
@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use backend::scanner::{album_from_path, title_from_path};
+
+fn title(path: &str) -> String {
+    title_from_path(Path::new(path))
+}
+
+#[test]
+fn titles_come_from_the_file_name_without_its_track_number() {
+    assert_eq!(title("./Artist - Album/01. Duck.flac"), "Duck");
+    assert_eq!(title("./Album/03 - Goose.mp3"), "Goose");
+    assert_eq!(title("./Album/1-02 Swan.flac"), "Swan");
+    assert_eq!(title("./Album/7_Heron.ogg"), "Heron");
+    assert_eq!(title("./Album/Crane.flac"), "Crane");
+}
+
+#[test]
+fn numbers_that_are_no_track_numbers_are_kept() {
+    // A year, a name that is only a number, and a number without a separator.
+    assert_eq!(title("./Album/1999 Party.flac"), "1999 Party");
+    assert_eq!(title("./Album/07.flac"), "07");
+    assert_eq!(title("./Album/3am.flac"), "3am");
+}
+
+#[test]
+fn albums_come_from_the_album_directory() {
+    assert_eq!(
+        album_from_path(Path::new("./Artist/Album/01. Duck.flac")).as_deref(),
+        Some("Album")
+    );
+    assert_eq!(
+        album_from_path(Path::new("./Album/CD2/01. Duck.flac")).as_deref(),
+        Some("Album")
+    );
+    assert_eq!(album_from_path(Path::new("./01. Duck.flac")), None);
+}
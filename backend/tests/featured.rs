@@ -0,0 +1,109 @@
+mod common;
+
+use backend::scanner::{FeaturedSeparators, split_featured};
+use duckdb::Connection;
+
+fn split(text: &str) -> (String, Vec<String>) {
+    split_featured(text, &FeaturedSeparators::default())
+}
+
+fn featured(rest: &str, names: &[&str]) -> (String, Vec<String>) {
+    (
+        rest.to_string(),
+        names.iter().map(ToString::to_string).collect(),
+    )
+}
+
+#[test]
+fn featured_artists_are_split_out_after_a_marker() {
+    assert_eq!(split("Main feat. Guest"), featured("Main", &["Guest"]));
+    assert_eq!(split("Main Ft. Guest"), featured("Main", &["Guest"]));
+    assert_eq!(split("Song featuring Guest"), featured("Song", &["Guest"]));
+    assert_eq!(
+        split("Main feat. One, Two & Three"),
+        featured("Main", &["One", "Two", "Three"])
+    );
+}
+
+#[test]
+fn a_bracketed_marker_takes_out_only_its_brackets() {
+    assert_eq!(split("Song (feat. Guest)"), featured("Song", &["Guest"]));
+    assert_eq!(
+        split("Song [ft. One & Two] (Live)"),
+        featured("Song (Live)", &["One", "Two"])
+    );
+    // An unclosed bracket leaves the title alone.
+    assert_eq!(
+        split("Song (feat. Guest"),
+        featured("Song (feat. Guest", &[])
+    );
+}
+
+#[test]
+fn names_without_a_marker_are_left_whole() {
+    for text in [
+        "Earth, Wind & Fire",
+        "Simon & Garfunkel",
+        "Daft Punk",
+        "Left feat.",
+        "Feat. Guest",
+        "Featuring Five",
+    ] {
+        assert_eq!(split(text), featured(text, &[]), "{text}");
+    }
+}
+
+#[test]
+fn the_separators_are_configurable() {
+    let separators = FeaturedSeparators {
+        markers: &["with"],
+        lists: &[" and "],
+    };
+    assert_eq!(
+        split_featured("Song (with One and Two)", &separators),
+        featured("Song", &["One", "Two"])
+    );
+    assert_eq!(
+        split_featured("Main feat. Guest", &separators),
+        featured("Main feat. Guest", &[])
+    );
+}
+
+#[test]
+fn featured_artists_are_credited_and_the_title_cleaned() {
+    let conn = common::rederived(&[
+        ("TITLE", "TrackTitle", "Song (feat. Guest & Main)"),
+        ("ARTIST", "Artist", "Main ft. Other"),
+        ("ALBUM", "Album", "Album"),
+    ]);
+
+    let title: String = conn
+        .query_row("SELECT title FROM track", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(title, "Song");
+    let credits = credits(&conn);
+    // `Main` is the artist, so isn't credited as featured as well.
+    let credit = |name: &str, role: Option<&str>| (name.to_string(), role.map(str::to_string));
+    assert_eq!(
+        credits,
+        [
+            credit("Main", None),
+            credit("Guest", Some("featured")),
+            credit("Other", Some("featured")),
+        ]
+    );
+}
+
+/// `(artist, role)` of the track's credits in order.
+fn credits(conn: &Connection) -> Vec<(String, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT ar.name, nullif(c.role, '') FROM credit c JOIN artist ar ON ar.id = c.artist
+             ORDER BY c.ord",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
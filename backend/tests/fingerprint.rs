@@ -0,0 +1,105 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner::{self, ScanOptions, find_duplicates};
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+
+/// Copies the fixture to `path` with its title tag changed, which changes the
+/// file's bytes but not its audio.
+fn retagged_copy(fixture: &str, path: &Path) {
+    let mut bytes = fs::read(Path::new(ALBUM).join(fixture)).unwrap();
+    let at = bytes
+        .windows(9)
+        .position(|w| w == b"title=Men")
+        .expect("fixture has no title tag");
+    bytes[at + 7] = b'a';
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, bytes).unwrap();
+}
+
+fn copy(fixture: &str, path: &Path) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::copy(Path::new(ALBUM).join(fixture), path).unwrap();
+}
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("fingerprint");
+    copy("09. Men.flac", &dir.join("a/men.flac"));
+    retagged_copy("09. Men.flac", &dir.join("b/man.flac"));
+    copy("10. Denizens.flac", &dir.join("a/denizens.flac"));
+    dir
+}
+
+fn with_fingerprints() -> ScanOptions {
+    ScanOptions {
+        fingerprint: true,
+        ..ScanOptions::default()
+    }
+}
+
+fn fingerprinted(conn: &Connection) -> i64 {
+    conn.query_row(
+        "SELECT count(*) FROM file WHERE fingerprint IS NOT NULL",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn retagged_copies_are_duplicates_by_fingerprint() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, with_fingerprints()).unwrap();
+
+    assert_eq!(fingerprinted(&conn), 3);
+    let groups = find_duplicates(&conn).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].paths, ["./a/men.flac", "./b/man.flac"]);
+    assert_eq!(groups[0].hash, None);
+}
+
+#[test]
+fn files_are_only_fingerprinted_on_request() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+
+    assert_eq!(fingerprinted(&conn), 0);
+    assert!(find_duplicates(&conn).unwrap().is_empty());
+
+    // Unchanged files aren't fingerprinted by a later scan either.
+    scanner::scan(&dir, &conn, with_fingerprints()).unwrap();
+    assert_eq!(fingerprinted(&conn), 0);
+}
+
+#[test]
+fn moved_retagged_files_keep_user_data() {
+    let dir = TempDir::new("fingerprint");
+    copy("09. Men.flac", &dir.join("men.flac"));
+    copy("10. Denizens.flac", &dir.join("denizens.flac"));
+    let conn = common::library();
+    scanner::scan(&dir, &conn, with_fingerprints()).unwrap();
+    conn.execute_batch(
+        "UPDATE track SET rating = 4
+         WHERE file = (SELECT id FROM file WHERE path = './men.flac')",
+    )
+    .unwrap();
+
+    fs::remove_file(dir.join("men.flac")).unwrap();
+    retagged_copy("09. Men.flac", &dir.join("elsewhere/renamed.flac"));
+    scanner::scan(&dir, &conn, with_fingerprints()).unwrap();
+
+    let rating: Option<f32> = conn
+        .query_row(
+            "SELECT t.rating FROM track t JOIN file f ON f.id = t.file
+             WHERE f.path = './elsewhere/renamed.flac' AND f.deletion IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(rating, Some(4.0));
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use backend::format::Format;
+
+#[test]
+fn sql_enum_matches_format() {
+    let conn = common::library();
+    let mut stmt = conn
+        .prepare("SELECT unnest(enum_range(NULL::format))::VARCHAR")
+        .unwrap();
+    let values: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let expected: Vec<&str> = Format::ALL.iter().map(|f| f.as_str()).collect();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn every_format_inserts() {
+    let conn = common::library();
+    for (i, format) in Format::ALL.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO file (id, path, hash, size, format, duration, mtime, added)
+             VALUES (uuid(), ?, '', 0, ?, 0, 0, now())",
+            [format!("./{i}.{format}"), format.to_string()],
+        )
+        .unwrap();
+    }
+    let mka: String = conn
+        .query_row(
+            "SELECT format::VARCHAR FROM file WHERE format = 'mka'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(mka.parse::<Format>(), Ok(Format::Mka));
+}
+
+#[test]
+fn extensions_map_to_formats() {
+    assert_eq!(Format::from_extension("MKA"), Some(Format::Mka));
+    assert_eq!(Format::from_extension("m4a"), Some(Format::Mp4));
+    assert_eq!(Format::from_extension("txt"), None);
+    for ext in Format::DEFAULT_EXTENSIONS {
+        assert!(Format::from_extension(ext).is_some(), "{ext}");
+    }
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use backend::scanner::{self, parse_r128_gain};
+use common::TempDir;
+use duckdb::Connection;
+
+#[test]
+fn r128_gains_are_q7_8_towards_minus_23_lufs() {
+    // -2560/256 = -10 dB towards -23 LUFS is -5 dB towards -18 LUFS.
+    assert_eq!(parse_r128_gain("-2560"), Some(-5.0));
+    assert_eq!(parse_r128_gain("0"), Some(5.0));
+    assert_eq!(parse_r128_gain(" 384 "), Some(6.5));
+    assert_eq!(parse_r128_gain("-32768"), Some(-123.0));
+    assert_eq!(parse_r128_gain("32768"), None);
+    assert_eq!(parse_r128_gain("-6.5"), None);
+    assert_eq!(parse_r128_gain("-6.5 dB"), None);
+}
+
+/// A library holding one track whose file has the given stored tags as
+/// `(key, std_key, value)`.
+fn library(tags: &[(&str, Option<&str>, &str)]) -> Connection {
+    let conn = common::library();
+    common::add_file(&conn, 1, "./a.opus", tags);
+    conn
+}
+
+fn gains(conn: &Connection) -> (Option<f32>, Option<f32>) {
+    conn.query_row("SELECT track_gain, album_gain FROM track", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+}
+
+fn rederived_gains(tags: &[(&str, Option<&str>, &str)]) -> (Option<f32>, Option<f32>) {
+    let conn = library(tags);
+    common::rederive(&conn);
+    gains(&conn)
+}
+
+#[test]
+fn replay_gain_tags_are_read_in_db() {
+    let gains = rederived_gains(&[
+        (
+            "REPLAYGAIN_TRACK_GAIN",
+            Some("ReplayGainTrackGain"),
+            "-6.50 dB",
+        ),
+        (
+            "REPLAYGAIN_ALBUM_GAIN",
+            Some("ReplayGainAlbumGain"),
+            "+1.25",
+        ),
+    ]);
+    assert_eq!(gains, (Some(-6.5), Some(1.25)));
+}
+
+#[test]
+fn r128_tags_win_over_replay_gain_tags() {
+    let gains = rederived_gains(&[
+        (
+            "REPLAYGAIN_TRACK_GAIN",
+            Some("ReplayGainTrackGain"),
+            "-6.50 dB",
+        ),
+        ("R128_TRACK_GAIN", None, "-2560"),
+        (
+            "REPLAYGAIN_ALBUM_GAIN",
+            Some("ReplayGainAlbumGain"),
+            "-3.00 dB",
+        ),
+    ]);
+    assert_eq!(gains, (Some(-5.0), Some(-3.0)));
+}
+
+#[test]
+fn malformed_gains_are_ignored() {
+    let gains = rederived_gains(&[
+        ("R128_TRACK_GAIN", None, "loud"),
+        ("REPLAYGAIN_ALBUM_GAIN", Some("ReplayGainAlbumGain"), "n/a"),
+    ]);
+    assert_eq!(gains, (None, None));
+}
+
+/// `r128.opus` is a second of Ogg Opus (TOC-only packets, which demux but carry
+/// no audio) tagged `R128_TRACK_GAIN=-2560` and `R128_ALBUM_GAIN=-1536`.
+#[test]
+fn opus_files_are_scanned_with_their_r128_gains() {
+    let dir = TempDir::new("gain");
+    dir.copy("tests/resources/opus/r128.opus", "r128.opus");
+
+    let conn = common::library();
+    scanner::scan(&dir, &conn, scanner::ScanOptions::default()).unwrap();
+    assert_eq!(gains(&conn), (Some(-5.0), Some(-1.0)));
+
+    // The tags are stored, so re-deriving keeps the gains.
+    common::rederive(&conn);
+    assert_eq!(gains(&conn), (Some(-5.0), Some(-1.0)));
+}
+
+#[test]
+fn replay_gain_peaks_are_read_in_db() {
+    let conn = library(&[
+        (
+            "REPLAYGAIN_TRACK_PEAK",
+            Some("ReplayGainTrackPeak"),
+            "0.988547",
+        ),
+        ("REPLAYGAIN_ALBUM_PEAK", Some("ReplayGainAlbumPeak"), "-1"),
+    ]);
+    common::rederive(&conn);
+    let peaks: (Option<f32>, Option<f32>) = conn
+        .query_row("SELECT track_peak, album_peak FROM track", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    // A negative peak isn't one.
+    assert_eq!(peaks, (Some(0.988_547), None));
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use backend::scanner::{self, AlbumOptions, GenreOptions, PrimaryGenreRule};
+use duckdb::Connection;
+
+/// A library holding one track whose stored tags list three genres.
+fn library() -> Connection {
+    let conn = common::library();
+    common::add_file(
+        &conn,
+        1,
+        "./a.flac",
+        &[
+            ("TITLE", "TrackTitle", "A"),
+            ("GENRE", "Genre", "Rock"),
+            ("GENRE", "Genre", "Progressive Rock"),
+            ("GENRE", "Genre", "Jazz"),
+        ],
+    );
+    conn
+}
+
+/// The genres of the track, in order.
+fn genres(conn: &Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT g.name FROM track_genre tg JOIN genre g ON g.id = tg.genre
+             ORDER BY tg.ord",
+        )
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Rederives the library with `options`, returning the track's genres and
+/// primary genre.
+fn rederived_genres(conn: &Connection, options: &GenreOptions) -> (Vec<String>, Option<String>) {
+    scanner::rederive(conn, options, &AlbumOptions::default()).unwrap();
+    let primary = conn
+        .query_row("SELECT primary_genre FROM track", [], |row| row.get(0))
+        .unwrap();
+    (genres(conn), primary)
+}
+
+fn options(rule: PrimaryGenreRule, priority: &[&str]) -> GenreOptions {
+    GenreOptions {
+        primary_genre: rule,
+        genre_priority: priority.iter().map(ToString::to_string).collect(),
+    }
+}
+
+#[test]
+fn first_genre_is_primary_by_default() {
+    let conn = library();
+    let (genre, primary) = rederived_genres(&conn, &GenreOptions::default());
+    assert_eq!(genre, ["Rock", "Progressive Rock", "Jazz"]);
+    assert_eq!(primary.as_deref(), Some("Rock"));
+}
+
+#[test]
+fn most_specific_genre_has_the_most_words() {
+    let conn = library();
+    let rule = options(PrimaryGenreRule::MostSpecific, &[]);
+    let (_, primary) = rederived_genres(&conn, &rule);
+    assert_eq!(primary.as_deref(), Some("Progressive Rock"));
+}
+
+#[test]
+fn priority_list_picks_the_most_preferred_genre() {
+    let conn = library();
+    let rule = options(PrimaryGenreRule::Priority, &["Blues", "jazz", "Rock"]);
+    let (_, primary) = rederived_genres(&conn, &rule);
+    assert_eq!(primary.as_deref(), Some("Jazz"));
+
+    let rule = options(PrimaryGenreRule::Priority, &["Blues"]);
+    let (_, primary) = rederived_genres(&conn, &rule);
+    assert_eq!(primary.as_deref(), Some("Rock"));
+}
+
+#[test]
+fn tracks_without_genres_have_no_primary_genre() {
+    assert_eq!(GenreOptions::default().primary(&[]), None);
+}
+
+#[test]
+fn each_genre_tag_is_a_genre_of_its_own() {
+    let conn = library();
+    // A genre with a comma in its name stays one genre, and the genres of
+    // several tracks are shared.
+    conn.execute_batch("UPDATE file_tag SET value = 'Rock, Pop' WHERE ord = 2")
+        .unwrap();
+    common::add_file(&conn, 2, "./b.flac", &[("GENRE", "Genre", "Jazz")]);
+    common::rederive(&conn);
+
+    let count = |sql: &str| -> u32 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+    assert_eq!(count("SELECT count(*) FROM genre"), 3);
+    assert_eq!(
+        count(
+            "SELECT count(*) FROM track_genre tg JOIN genre g ON g.id = tg.genre WHERE g.name = 'Jazz'"
+        ),
+        2
+    );
+    assert_eq!(
+        count("SELECT count(*) FROM genre WHERE name = 'Rock, Pop'"),
+        1
+    );
+
+    // Genres no track has any more are dropped.
+    conn.execute_batch("DELETE FROM file_tag WHERE value = 'Rock, Pop'")
+        .unwrap();
+    common::rederive(&conn);
+    assert_eq!(count("SELECT count(*) FROM genre"), 2);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::sync::{Arc, mpsc};
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server::{self, AppState, ServeOptions};
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn state(connections: usize) -> Arc<AppState> {
+    let conn = common::library();
+    let options = ServeOptions {
+        connections,
+        ..ServeOptions::default()
+    };
+    server::app_state_with(conn, std::env::temp_dir(), &options)
+}
+
+async fn health(app: &Router) -> (StatusCode, Value) {
+    let request = Request::get("/health").body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn a_readable_database_is_healthy() {
+    let app = server::router(state(2));
+    let (status, body) = health(&app).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert!(body["schema_version"].as_u64().unwrap() > 0);
+    assert!(body["uptime_secs"].is_u64());
+    assert!(body.get("error").is_none());
+}
+
+#[tokio::test]
+async fn the_check_does_not_wait_for_busy_connections() {
+    let state = state(1);
+    let app = server::router(Arc::clone(&state));
+
+    let (busy_tx, busy_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let reader = std::thread::spawn(move || {
+        state.read(|_| {
+            busy_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        });
+    });
+    busy_rx.recv().unwrap();
+
+    let (status, body) = health(&app).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "busy");
+    assert!(body["schema_version"].is_null());
+
+    release_tx.send(()).unwrap();
+    reader.join().unwrap();
+    assert_eq!(health(&app).await.0, StatusCode::OK);
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use backend::scanner::{self, AlbumOptions, GenreOptions};
+use duckdb::Connection;
+
+/// A library with one track of one file, credited to one artist.
+fn library() -> Connection {
+    common::rederived(&[("ARTIST", "Artist", "First")])
+}
+
+fn rederive(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    scanner::rederive(conn, &GenreOptions::default(), &AlbumOptions::default())
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn writes_leaving_orphans_are_refused_and_rolled_back() {
+    let conn = library();
+    conn.execute(
+        "INSERT INTO credit (track, artist, ord, role)
+         SELECT '00000000-0000-0000-0000-0000000000a9', id, 0, '' FROM artist",
+        [],
+    )
+    .unwrap();
+    conn.execute("UPDATE track SET title = 'Before'", [])
+        .unwrap();
+
+    let error = rederive(&conn).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains("credit(s) would point at a missing track"),
+        "{error}"
+    );
+    // Nothing of the write was kept.
+    assert_eq!(
+        count(&conn, "SELECT count(*) FROM track WHERE title = 'Before'"),
+        1
+    );
+}
+
+#[test]
+fn writes_leaving_no_orphans_go_through() {
+    let conn = library();
+    rederive(&conn).unwrap();
+    assert_eq!(count(&conn, "SELECT count(*) FROM credit"), 1);
+}
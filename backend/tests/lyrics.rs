@@ -0,0 +1,61 @@
+mod common;
+
+use duckdb::Connection;
+
+const LYRICS: &str = "First line of the song\nA half-remembered line\n\nLast line";
+
+/// A library holding one track whose file has the given stored tags as
+/// `(key, std_key, value)`, re-derived from them.
+fn library(tags: &[(&str, Option<&str>, &str)]) -> Connection {
+    common::rederived(tags)
+}
+
+fn lyrics_and_comment(conn: &Connection) -> (Option<String>, Option<String>) {
+    conn.query_row("SELECT lyrics, comment FROM track", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+}
+
+#[test]
+fn lyrics_and_comments_are_kept_verbatim() {
+    let conn = library(&[
+        ("LYRICS", Some("Lyrics"), LYRICS),
+        ("COMMENT", Some("Comment"), "Ripped from vinyl"),
+        ("COMMENT", Some("Comment"), "Second comment"),
+    ]);
+    assert_eq!(
+        lyrics_and_comment(&conn),
+        (
+            Some(LYRICS.to_string()),
+            Some("Ripped from vinyl".to_string())
+        )
+    );
+}
+
+#[test]
+fn blank_lyrics_and_comments_are_none() {
+    let conn = library(&[
+        ("LYRICS", Some("Lyrics"), " \n "),
+        ("COMMENT", Some("Comment"), ""),
+    ]);
+    assert_eq!(lyrics_and_comment(&conn), (None, None));
+}
+
+#[test]
+fn lyrics_and_comments_are_searched() {
+    let conn = library(&[
+        ("LYRICS", Some("Lyrics"), LYRICS),
+        ("COMMENT", Some("Comment"), "Ripped from vinyl"),
+    ]);
+    for words in ["half-remembered line", "vinyl"] {
+        let count: u32 = conn
+            .query_row(
+                "SELECT count(*) FROM track_search WHERE contains(text, ?)",
+                [words],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1, "{words}");
+    }
+}
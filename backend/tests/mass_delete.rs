@@ -0,0 +1,110 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A fresh collection holding `n` files.
+fn collection(n: usize) -> TempDir {
+    let dir = TempDir::new("mass-delete");
+    for i in 0..n {
+        dir.copy(FIXTURE, &format!("{i:02}.flac"));
+    }
+    dir
+}
+
+fn live_files(conn: &Connection) -> u32 {
+    conn.query_row(
+        "SELECT count(*) FROM file WHERE deletion IS NULL",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+/// Scans a collection of 12 files, then removes all but one.
+fn emptied() -> (TempDir, Connection) {
+    let dir = collection(12);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    for i in 1..12 {
+        fs::remove_file(dir.join(format!("{i:02}.flac"))).unwrap();
+    }
+    (dir, conn)
+}
+
+#[test]
+fn mass_deletions_are_refused() {
+    let (dir, conn) = emptied();
+    let error = scanner::scan(&dir, &conn, ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("--allow-mass-delete"), "{error}");
+    assert_eq!(live_files(&conn), 12);
+}
+
+#[test]
+fn mass_deletions_can_be_allowed() {
+    let (dir, conn) = emptied();
+    let options = ScanOptions {
+        allow_mass_delete: true,
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 1);
+}
+
+#[test]
+fn the_share_deleted_is_configurable() {
+    let (dir, conn) = emptied();
+    let options = ScanOptions {
+        max_delete: Some(95.0),
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 1);
+}
+
+#[test]
+fn few_deletions_are_let_through() {
+    let dir = collection(3);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    fs::remove_file(dir.join("01.flac")).unwrap();
+    fs::remove_file(dir.join("02.flac")).unwrap();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_files(&conn), 1);
+}
+
+#[test]
+fn watched_mass_deletions_are_refused() {
+    let (dir, conn) = emptied();
+    // An event on the collection root scopes the whole library.
+    let error = scanner::sync_paths(
+        &dir,
+        &[dir.to_path_buf()],
+        &ScanOptions::default(),
+        |task| task(&conn),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("--allow-mass-delete"), "{error}");
+    assert_eq!(live_files(&conn), 12);
+}
+
+#[test]
+fn a_watched_collection_that_went_away_deletes_nothing() {
+    let dir = collection(3);
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    // As when its drive is unmounted.
+    fs::remove_dir_all(&dir).unwrap();
+    let error = scanner::sync_paths(
+        &dir,
+        &[dir.to_path_buf()],
+        &ScanOptions::default(),
+        |task| task(&conn),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("can't be read"), "{error}");
+    assert_eq!(live_files(&conn), 3);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use std::path::Path;
+
+use backend::scanner;
+use common::{ALBUM, TempDir};
+
+/// A collection with one file at each depth from 0 to 3.
+fn deep_collection() -> TempDir {
+    let dir = TempDir::new("depth");
+    let fixtures = [
+        "01. Duck.flac",
+        "02. Hens.flac",
+        "03. Geese.flac",
+        "04. Oysters.flac",
+    ];
+    let mut level = String::new();
+    for (depth, fixture) in fixtures.iter().enumerate() {
+        dir.copy(
+            format!("{ALBUM}/{fixture}"),
+            &format!("{level}{depth}.flac"),
+        );
+        level = format!("{level}level{}/", depth + 1);
+    }
+    dir
+}
+
+fn scanned_paths(dir: &Path, max_depth: Option<usize>) -> Vec<String> {
+    let conn = common::library();
+    let options = scanner::ScanOptions {
+        max_depth,
+        ..Default::default()
+    };
+    scanner::scan(dir, &conn, options).unwrap();
+    let mut stmt = conn.prepare("SELECT path FROM file ORDER BY path").unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn max_depth_limits_the_walk() {
+    let dir = deep_collection();
+
+    assert_eq!(scanned_paths(&dir, Some(0)), ["./0.flac"]);
+    assert_eq!(
+        scanned_paths(&dir, Some(1)),
+        ["./0.flac", "./level1/1.flac"]
+    );
+    assert_eq!(scanned_paths(&dir, None).len(), 4);
+}
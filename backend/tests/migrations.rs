@@ -0,0 +1,126 @@
+mod common;
+
+use std::path::Path;
+
+use backend::db;
+use common::TempDir;
+use duckdb::Connection;
+
+fn version(conn: &Connection) -> u32 {
+    conn.query_row("SELECT value FROM meta.version", [], |row| row.get(0))
+        .unwrap()
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    conn.query_row(
+        "SELECT count(*) > 0 FROM information_schema.columns
+         WHERE table_name = ? AND column_name = ?",
+        [table, column],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn migrations_roll_back_and_forward_again() {
+    let mut conn = common::library();
+    let latest = version(&conn);
+
+    // 0026, which drops orphans, is the newest migration without down SQL.
+    db::migrate_to(&mut conn, 26).unwrap();
+    assert_eq!(version(&conn), 26);
+    assert!(!has_column(&conn, "track", "lyrics"));
+    assert!(!has_column(&conn, "file", "duration_interval"));
+    assert!(!has_column(&conn, "file", "collection"));
+    assert!(has_column(&conn, "file", "bitrate"));
+    assert!(has_column(&conn, "track", "bpm"));
+    let recorded: u32 = conn
+        .query_row("SELECT max(version) FROM meta.migrations", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(recorded, 26);
+
+    db::migrate_to(&mut conn, latest).unwrap();
+    assert_eq!(version(&conn), latest);
+    assert!(has_column(&conn, "track", "lyrics"));
+    assert!(has_column(&conn, "file", "collection"));
+}
+
+#[test]
+fn migrations_before_0026_roll_back_too() {
+    let mut conn = db::open_db(Path::new(":memory:")).unwrap();
+    db::migrate_to(&mut conn, 25).unwrap();
+
+    // 0013 is the newest migration before 0026 without down SQL.
+    db::migrate_to(&mut conn, 13).unwrap();
+    assert_eq!(version(&conn), 13);
+    assert!(!has_column(&conn, "track", "track_gain"));
+    assert!(!has_column(&conn, "album", "artist"));
+    assert!(!has_column(&conn, "file", "bitrate"));
+    assert!(!has_column(&conn, "file_alias", "path"));
+    assert!(has_column(&conn, "track", "bpm"));
+
+    db::migrate_to(&mut conn, 25).unwrap();
+    assert!(has_column(&conn, "track", "track_gain"));
+    assert!(has_column(&conn, "file", "bitrate"));
+}
+
+#[test]
+fn migrations_without_down_sql_are_not_rolled_back() {
+    let mut conn = common::library();
+    let latest = version(&conn);
+
+    let error = db::migrate_to(&mut conn, 10).unwrap_err();
+    assert!(error.to_string().contains("0026"));
+    // Nothing was rolled back before the refusal.
+    assert_eq!(version(&conn), latest);
+    assert!(has_column(&conn, "file", "collection"));
+
+    assert!(db::migrate_to(&mut conn, latest + 1).is_err());
+}
+
+#[test]
+fn applied_migrations_are_recorded_with_their_checksums() {
+    let conn = common::library();
+    let (count, max): (u32, u32) = conn
+        .query_row(
+            "SELECT count(*), max(version) FROM meta.migrations WHERE length(checksum) = 64",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((count, max), (version(&conn), version(&conn)));
+}
+
+#[test]
+fn edited_migrations_are_refused() {
+    let dir = TempDir::new("migrations");
+    let path = dir.join("collectune.db");
+    let conn = db::get_db(&path).unwrap();
+    conn.execute(
+        "UPDATE meta.migrations SET checksum = 'edited' WHERE version = 3",
+        [],
+    )
+    .unwrap();
+    drop(conn);
+
+    let error = db::get_db(&path).unwrap_err();
+    assert!(error.to_string().contains("Migration 0003 was edited"));
+}
+
+#[test]
+fn checksums_are_recorded_for_databases_migrated_without_them() {
+    let dir = TempDir::new("migrations");
+    let path = dir.join("collectune.db");
+    let conn = db::get_db(&path).unwrap();
+    let latest = version(&conn);
+    conn.execute("DELETE FROM meta.migrations", []).unwrap();
+    drop(conn);
+
+    let conn = db::get_db(&path).unwrap();
+    let count: u32 = conn
+        .query_row("SELECT count(*) FROM meta.migrations", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, latest);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner;
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A FLAC file's byte offset just past its `fLaC` marker and metadata blocks,
+/// where the audio frames begin.
+fn audio_start(data: &[u8]) -> usize {
+    let mut offset = 4;
+    loop {
+        let header = &data[offset..offset + 4];
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        offset += 4 + length;
+        if header[0] & 0x80 != 0 {
+            return offset;
+        }
+    }
+}
+
+/// Writes the fixture to `path` without its tags: only the mandatory
+/// STREAMINFO block is kept, followed by `padding` bytes of padding so that
+/// files get different hashes.
+fn write_untagged(path: &Path, padding: u8) {
+    let data = fs::read(FIXTURE).unwrap();
+    let streaminfo_end = 4 + 4 + 34;
+    let mut untagged = data[..streaminfo_end].to_vec();
+    // A PADDING block, marked as the last metadata block.
+    untagged.extend_from_slice(&[0x81, 0, 0, padding]);
+    untagged.resize(untagged.len() + usize::from(padding), 0);
+    untagged.extend_from_slice(&data[audio_start(&data)..]);
+    fs::write(path, untagged).unwrap();
+}
+
+/// A fresh collection: a directory of two untagged singles, plus one tagged
+/// track of the album.
+fn collection() -> TempDir {
+    let dir = TempDir::new("singles");
+    fs::create_dir_all(dir.join("singles")).unwrap();
+    write_untagged(&dir.join("singles/first single.flac"), 1);
+    write_untagged(&dir.join("singles/second single.flac"), 2);
+    dir.copy(FIXTURE, "singles/duck.flac");
+    dir
+}
+
+/// `(track path, album title)` for every track, in path order.
+fn albums(conn: &Connection) -> Vec<(String, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.path, al.title
+             FROM track t JOIN file f ON f.id = t.file LEFT JOIN album al ON al.id = t.album
+             ORDER BY f.path",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn scan(rule: scanner::MissingAlbumRule) -> Vec<(String, Option<String>)> {
+    let dir = collection();
+    let conn = common::library();
+    let options = scanner::ScanOptions {
+        album: scanner::AlbumOptions {
+            missing_album: rule,
+        },
+        ..Default::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    albums(&conn)
+}
+
+#[test]
+fn untagged_singles_have_no_album_by_default() {
+    assert_eq!(
+        scan(scanner::MissingAlbumRule::default()),
+        [
+            (
+                "./singles/duck.flac".to_string(),
+                Some("First Test".to_string())
+            ),
+            ("./singles/first single.flac".to_string(), None),
+            ("./singles/second single.flac".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn untagged_singles_can_each_get_an_album() {
+    assert_eq!(
+        scan(scanner::MissingAlbumRule::Single),
+        [
+            (
+                "./singles/duck.flac".to_string(),
+                Some("First Test".to_string())
+            ),
+            (
+                "./singles/first single.flac".to_string(),
+                Some("first single".to_string())
+            ),
+            (
+                "./singles/second single.flac".to_string(),
+                Some("second single".to_string())
+            ),
+        ]
+    );
+}
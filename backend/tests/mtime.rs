@@ -0,0 +1,104 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+use serde_json::Value;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("mtime");
+    dir.copy(FIXTURE, "a.flac");
+    dir
+}
+
+/// The file's mtime on disk, in microseconds since the epoch.
+fn disk_mtime(path: &Path) -> i64 {
+    let modified = fs::metadata(path).unwrap().modified().unwrap();
+    i64::try_from(modified.duration_since(UNIX_EPOCH).unwrap().as_micros()).unwrap()
+}
+
+fn set_mtime(path: &Path, mtime: SystemTime) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+}
+
+/// `(path, mtime, modified)` of the live file.
+fn file(conn: &Connection) -> (String, i64, String) {
+    conn.query_row(
+        "SELECT path, mtime, modified::TEXT FROM file WHERE deletion IS NULL",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn the_mtime_follows_the_file_on_disk() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    let (_, mtime, modified) = file(&conn);
+    assert_eq!(mtime, disk_mtime(&dir.join("a.flac")));
+
+    // A touch leaves the content, and so `modified`, as it was.
+    set_mtime(
+        &dir.join("a.flac"),
+        SystemTime::now() + Duration::from_hours(1),
+    );
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    let (_, touched, kept) = file(&conn);
+    assert_eq!(touched, disk_mtime(&dir.join("a.flac")));
+    assert_ne!(touched, mtime);
+    assert_eq!(kept, modified);
+
+    // A moved file takes the mtime of its new path.
+    fs::rename(dir.join("a.flac"), dir.join("b.flac")).unwrap();
+    set_mtime(
+        &dir.join("b.flac"),
+        SystemTime::now() + Duration::from_hours(2),
+    );
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    let (path, moved, _) = file(&conn);
+    assert_eq!(path, "./b.flac");
+    assert_eq!(moved, disk_mtime(&dir.join("b.flac")));
+}
+
+/// The classification the scan of `dir` logs for `./a.flac`.
+fn classification(dir: &Path, conn: &Connection) -> String {
+    let log_file = dir.with_extension("jsonl");
+    let options = ScanOptions {
+        log_file: Some(log_file.clone()),
+        ..ScanOptions::default()
+    };
+    scanner::scan(dir, conn, options).unwrap();
+    let log = fs::read_to_string(&log_file).unwrap();
+    fs::remove_file(&log_file).unwrap();
+    log.lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .find(|entry| entry["path"] == "./a.flac")
+        .map(|entry| entry["classification"].as_str().unwrap().to_string())
+        .unwrap()
+}
+
+#[test]
+fn unchanged_files_are_skipped_by_their_size_and_mtime() {
+    let dir = collection();
+    let conn = common::library();
+    assert_eq!(classification(&dir, &conn), "new");
+    assert_eq!(classification(&dir, &conn), "skipped");
+
+    set_mtime(
+        &dir.join("a.flac"),
+        SystemTime::now() + Duration::from_hours(1),
+    );
+    assert_eq!(classification(&dir, &conn), "modified");
+    assert_eq!(classification(&dir, &conn), "skipped");
+}
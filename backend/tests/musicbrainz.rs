@@ -0,0 +1,100 @@
+mod common;
+
+use duckdb::Connection;
+
+const RELEASE: &str = "5e4f9ee0-5d8d-4a35-9a9b-6a4a1b1d2e3f";
+const RECORDING: &str = "0b5f2d3a-9c4e-4c1e-8f7a-2d6b1e9c3a4b";
+const ARTIST: &str = "a74b1b7f-71a5-4011-9441-d0b5e4122711";
+
+/// Stored tags as `(key, std_key, value)`.
+type Tags<'a> = &'a [(&'a str, &'a str, &'a str)];
+
+/// A library holding a track for each of `files`, given as its path and its
+/// stored tags, rederived.
+fn library(files: &[(&str, Tags)]) -> Connection {
+    let conn = common::library();
+    for (i, (path, tags)) in (1..).zip(files) {
+        common::add_file(&conn, i, path, tags);
+    }
+    common::rederive(&conn);
+    conn
+}
+
+fn strings(conn: &Connection, sql: &str) -> Vec<String> {
+    let mut stmt = conn.prepare(sql).unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn musicbrainz_ids_are_stored() {
+    let conn = library(&[(
+        "./a/1.flac",
+        &[
+            ("ARTIST", "Artist", "The Band"),
+            ("ALBUM", "Album", "Album"),
+            ("MUSICBRAINZ_TRACKID", "MusicBrainzTrackId", RECORDING),
+            ("MUSICBRAINZ_ALBUMID", "MusicBrainzAlbumId", RELEASE),
+            ("MUSICBRAINZ_ARTISTID", "MusicBrainzArtistId", ARTIST),
+        ],
+    )]);
+    assert_eq!(
+        strings(&conn, "SELECT musicbrainz_recording_id::TEXT FROM track"),
+        [RECORDING]
+    );
+    assert_eq!(
+        strings(&conn, "SELECT musicbrainz_release_id::TEXT FROM album"),
+        [RELEASE]
+    );
+    assert_eq!(
+        strings(
+            &conn,
+            "SELECT musicbrainz_id::TEXT FROM artist WHERE name = 'The Band'"
+        ),
+        [ARTIST]
+    );
+}
+
+#[test]
+fn artist_ids_are_only_matched_one_to_one() {
+    let conn = library(&[(
+        "./a/1.flac",
+        &[
+            ("ARTIST", "Artist", "One"),
+            ("ARTIST", "Artist", "Two"),
+            ("MUSICBRAINZ_ARTISTID", "MusicBrainzArtistId", ARTIST),
+        ],
+    )]);
+    assert_eq!(
+        strings(
+            &conn,
+            "SELECT name FROM artist WHERE musicbrainz_id IS NOT NULL"
+        ),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn a_release_groups_tracks_across_titles_and_folders() {
+    let release = ("MUSICBRAINZ_ALBUMID", "MusicBrainzAlbumId", RELEASE);
+    let conn = library(&[
+        ("./a/1.flac", &[("ALBUM", "Album", "Album"), release]),
+        (
+            "./b/2.flac",
+            &[("ALBUM", "Album", "Album (Bonus)"), release],
+        ),
+        // Without a release, the folder still keeps same-named albums apart.
+        ("./c/3.flac", &[("ALBUM", "Album", "Album")]),
+    ]);
+    assert_eq!(
+        strings(
+            &conn,
+            "SELECT al.title || ' ' || count(*) FROM track t JOIN album al ON al.id = t.album
+             GROUP BY al.id, al.title, al.musicbrainz_release_id
+             ORDER BY al.musicbrainz_release_id NULLS LAST"
+        ),
+        ["Album 2", "Album 1"]
+    );
+}
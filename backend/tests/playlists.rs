@@ -0,0 +1,218 @@
+mod common;
+
+use std::fs;
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A collection holding `files`, each a copy of the fixture.
+fn collection(files: &[&str]) -> TempDir {
+    let dir = TempDir::new("playlists");
+    for file in files {
+        dir.copy(FIXTURE, file);
+    }
+    dir
+}
+
+fn with_playlists() -> ScanOptions {
+    ScanOptions {
+        playlists: true,
+        ..Default::default()
+    }
+}
+
+/// `(playlist path, playlist name, entry path, file path)` of every entry, in
+/// order.
+fn entries(conn: &Connection) -> Vec<(String, String, String, Option<String>)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.path, p.name, e.path, f.path
+             FROM playlist_entry e
+             JOIN playlist p ON p.id = e.playlist
+             LEFT JOIN file f ON f.id = e.file
+             ORDER BY p.path, e.ord",
+        )
+        .unwrap();
+    stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })
+    .unwrap()
+    .map(Result::unwrap)
+    .collect()
+}
+
+fn entry(
+    playlist: &str,
+    name: &str,
+    path: &str,
+    file: Option<&str>,
+) -> (String, String, String, Option<String>) {
+    (
+        playlist.to_string(),
+        name.to_string(),
+        path.to_string(),
+        file.map(str::to_string),
+    )
+}
+
+#[test]
+fn playlist_entries_lead_to_the_files_of_their_paths() {
+    let dir = collection(&["Album/01.flac", "Album/Two Words.flac", "Other/03.flac"]);
+    let outside_dir = TempDir::new("outside");
+    outside_dir.copy(FIXTURE, "outside.flac");
+    let outside = outside_dir.join("outside.flac");
+    let absolute = dir.join("Other/03.flac");
+    let url = format!(
+        "file://{}",
+        dir.join("Album/Two Words.flac")
+            .display()
+            .to_string()
+            .replace(' ', "%20")
+    );
+    fs::create_dir_all(dir.join("Lists")).unwrap();
+    fs::write(
+        dir.join("Lists/mix.m3u8"),
+        format!(
+            "\u{feff}#EXTM3U\n#PLAYLIST:Road Trip\n#EXTINF:1,Duck\n../Album/01.flac\n\n{}\n{url}\n{}\nhttp://radio.example/stream\n../Album/missing.flac\n..\\Album\\01.flac\n",
+            absolute.display(),
+            outside.display(),
+        ),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("Album/album.pls"),
+        "[playlist]\nFile2=Two Words.flac\nTitle2=Two\nFile1=01.flac\nNumberOfEntries=2\n",
+    )
+    .unwrap();
+
+    let conn = common::library();
+    scanner::scan(&dir, &conn, with_playlists()).unwrap();
+
+    assert_eq!(
+        entries(&conn),
+        [
+            entry(
+                "./Album/album.pls",
+                "album",
+                "01.flac",
+                Some("./Album/01.flac")
+            ),
+            entry(
+                "./Album/album.pls",
+                "album",
+                "Two Words.flac",
+                Some("./Album/Two Words.flac")
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                "../Album/01.flac",
+                Some("./Album/01.flac")
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                &absolute.display().to_string(),
+                Some("./Other/03.flac")
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                &url,
+                Some("./Album/Two Words.flac")
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                &outside.display().to_string(),
+                None
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                "http://radio.example/stream",
+                None
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                "../Album/missing.flac",
+                None
+            ),
+            entry(
+                "./Lists/mix.m3u8",
+                "Road Trip",
+                "..\\Album\\01.flac",
+                Some("./Album/01.flac")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn playlists_are_read_again_on_each_scan_and_dropped_once_gone() {
+    let dir = collection(&["01.flac", "02.flac"]);
+    fs::write(dir.join("a.m3u"), "01.flac\n").unwrap();
+    fs::write(dir.join("b.m3u"), "02.flac\n").unwrap();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, with_playlists()).unwrap();
+    let id: String = conn
+        .query_row(
+            "SELECT id::TEXT FROM playlist WHERE path = './a.m3u'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    fs::write(dir.join("a.m3u"), "02.flac\n01.flac\n").unwrap();
+    fs::remove_file(dir.join("b.m3u")).unwrap();
+    // Without --playlists, the playlists are left as they were.
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(entries(&conn).len(), 2);
+
+    scanner::scan(&dir, &conn, with_playlists()).unwrap();
+    assert_eq!(
+        entries(&conn),
+        [
+            entry("./a.m3u", "a", "02.flac", Some("./02.flac")),
+            entry("./a.m3u", "a", "01.flac", Some("./01.flac")),
+        ]
+    );
+    // The playlist keeps its id.
+    let kept: String = conn
+        .query_row(
+            "SELECT id::TEXT FROM playlist WHERE path = './a.m3u'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(kept, id);
+}
+
+#[test]
+fn collections_keep_their_playlists_at_the_same_path_apart() {
+    let first = collection(&["01.flac"]);
+    let second = collection(&["01.flac"]);
+    for dir in [&first, &second] {
+        fs::write(dir.join("favorites.m3u"), "01.flac\n").unwrap();
+    }
+    let conn = common::library();
+    scanner::scan(&first, &conn, with_playlists()).unwrap();
+    scanner::scan(&second, &conn, with_playlists()).unwrap();
+
+    let entry = entry("./favorites.m3u", "favorites", "01.flac", Some("./01.flac"));
+    assert_eq!(entries(&conn), [entry.clone(), entry]);
+    // Each entry leads to the file of its own playlist's collection.
+    let strays: u32 = conn
+        .query_row(
+            "SELECT count(*) FROM playlist_entry e
+             JOIN playlist p ON p.id = e.playlist
+             JOIN file f ON f.id = e.file
+             WHERE f.collection <> p.collection",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(strays, 0);
+}
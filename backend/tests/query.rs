@@ -0,0 +1,712 @@
+mod common;
+
+use arrow_ipc::reader::StreamReader;
+use axum::Router;
+use axum::body::{Body, Bytes, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::query::{self, QueryParams, Ready, SortDir};
+use backend::server;
+use backend::server::ServeOptions;
+use duckdb::Connection;
+use duckdb::arrow::util::display::array_value_to_string;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let conn = Connection::open_in_memory().unwrap();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+/// An app whose database has the library schema.
+fn library_app() -> Router {
+    let conn = common::library();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+async fn post_query(app: &Router, sql: &str) -> (StatusCode, String, Bytes) {
+    post_query_to(app, "/query", sql).await
+}
+
+async fn post_query_to(app: &Router, uri: &str, sql: &str) -> (StatusCode, String, Bytes) {
+    let request = Request::post(uri)
+        .body(Body::from(sql.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, body)
+}
+
+async fn rows_affected(app: &Router, sql: &str) -> u64 {
+    let (status, content_type, body) = post_query(app, sql).await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    assert_eq!(content_type, "application/json");
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    json["rows_affected"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn create_reports_no_rows_affected() {
+    let app = app();
+    assert_eq!(rows_affected(&app, "CREATE TABLE t (n INTEGER)").await, 0);
+}
+
+#[tokio::test]
+async fn insert_reports_rows_affected() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    let sql = "-- seed\nINSERT INTO t VALUES (1), (2), (3)";
+    assert_eq!(rows_affected(&app, sql).await, 3);
+}
+
+#[tokio::test]
+async fn insert_returning_writes_and_returns_its_rows() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    let sql = "INSERT INTO t VALUES (1), (2) RETURNING n * 10";
+    assert_eq!(first_column(&app, "/query", sql).await, ["10", "20"]);
+    assert_eq!(
+        first_column(&app, "/query", "SELECT count(*) FROM t").await,
+        ["2"]
+    );
+}
+
+#[tokio::test]
+async fn pragmas_return_their_rows() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER, s VARCHAR)").await;
+    let sql = "PRAGMA table_info('t')";
+    assert_eq!(first_column(&app, "/query", sql).await, ["0", "1"]);
+}
+
+#[tokio::test]
+async fn update_reports_rows_affected() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    rows_affected(&app, "INSERT INTO t VALUES (1), (2), (3)").await;
+    assert_eq!(
+        rows_affected(&app, "update t set n = n * 10 where n > 1").await,
+        2
+    );
+}
+
+#[tokio::test]
+async fn select_streams_arrow() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    rows_affected(&app, "INSERT INTO t VALUES (1), (2), (3)").await;
+
+    let (status, content_type, body) = post_query(&app, "SELECT n FROM t").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/vnd.apache.arrow.stream");
+    let reader = StreamReader::try_new(body.as_ref(), None).unwrap();
+    let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+    assert_eq!(rows, 3);
+}
+
+#[tokio::test]
+async fn select_without_rows_streams_its_schema() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER, name VARCHAR)").await;
+
+    let (status, _, body) = post_query(&app, "SELECT n, name FROM t").await;
+    assert_eq!(status, StatusCode::OK);
+    let reader = StreamReader::try_new(body.as_ref(), None).unwrap();
+    let names: Vec<String> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+    assert_eq!(names, ["n", "name"]);
+    assert_eq!(reader.count(), 0);
+    // The stream is complete: it ends with the EOS marker.
+    assert!(body.ends_with(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]));
+}
+
+/// The rows of a JSON response, one object per line.
+fn json_rows(body: &[u8]) -> Vec<serde_json::Value> {
+    std::str::from_utf8(body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn select_streams_json_on_request() {
+    let app = app();
+    let sql = "SELECT * FROM (VALUES
+        (1, 'one', {'a': 1, 'b': [1, 2]}),
+        (2, NULL, NULL)
+    ) AS t(n, name, nested) ORDER BY n";
+
+    let (status, content_type, body) = post_query_to(&app, "/query?format=json", sql).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/x-ndjson");
+    assert_eq!(
+        json_rows(&body),
+        [
+            serde_json::json!({ "n": 1, "name": "one", "nested": { "a": 1, "b": [1, 2] } }),
+            serde_json::json!({ "n": 2, "name": null, "nested": null }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn the_accept_header_asks_for_json() {
+    let app = app();
+    let request = Request::post("/query")
+        .header("accept", "application/x-ndjson;q=0.9, */*;q=0.1")
+        .body(Body::from("SELECT 1 AS n"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(json_rows(&body), [serde_json::json!({ "n": 1 })]);
+
+    // `?format=` wins over the header.
+    let request = Request::post("/query?format=arrow")
+        .header("accept", "application/json")
+        .body(Body::from("SELECT 1 AS n"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/vnd.apache.arrow.stream"
+    );
+}
+
+#[tokio::test]
+async fn invalid_statement_is_a_bad_request() {
+    let app = app();
+    let (status, _, _) = post_query(&app, "INSERT INTO missing VALUES (1)").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Posts `sql` to `app`'s `/query` as a client that accepts JSON errors.
+async fn post_for_error(app: &Router, sql: &str) -> (StatusCode, String, serde_json::Value) {
+    let request = Request::post("/query")
+        .header("accept", "application/json")
+        .body(Body::from(sql.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn errors_are_json_for_clients_that_accept_it() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+
+    let (status, content_type, error) = post_for_error(&app, "SELECT * FORM t").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type, "application/json");
+    assert_eq!(error["kind"], "parser");
+    // `FORM` reads as an alias, so DuckDB stops at the `t` after it.
+    assert_eq!(error["position"], 14);
+    assert!(error["error"].as_str().unwrap().contains("\"t\""));
+
+    // The position counts from the start of the whole query.
+    let (_, _, error) = post_for_error(&app, "SELECT n\nFROM missing").await;
+    assert_eq!(error["kind"], "catalog");
+    assert_eq!(error["position"], 14);
+
+    let (_, _, error) = post_for_error(&read_only_app(), "DELETE FROM t").await;
+    assert_eq!(error["kind"], "read_only");
+    assert_eq!(error["position"], serde_json::Value::Null);
+
+    // Other clients get the message alone, as plain text.
+    let (status, content_type, body) = post_query(&app, "SELECT * FORM t").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_ne!(content_type, "application/json");
+    assert!(String::from_utf8_lossy(&body).contains("FORM"));
+}
+
+/// Posts `body` to `uri` as a JSON query body.
+async fn post_json(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, Bytes) {
+    let request = Request::post(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    (
+        status,
+        to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn json_bodies_bind_their_params() {
+    let app = app();
+    rows_affected(
+        &app,
+        "CREATE TABLE t (name TEXT, n INTEGER, x DOUBLE, b BOOLEAN)",
+    )
+    .await;
+    let (status, body) = post_json(
+        &app,
+        "/query",
+        serde_json::json!({
+            "sql": "INSERT INTO t VALUES (?, ?, ?, ?), (?, ?, ?, ?)",
+            "params": ["O'Brien'); DROP TABLE t; --", 1, 1.5, true, null, 2, null, false],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+
+    let (status, body) = post_json(
+        &app,
+        "/query?format=json",
+        serde_json::json!({
+            "sql": "SELECT name, n, x, b FROM t WHERE n >= ? ORDER BY n",
+            "params": [1],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    assert_eq!(
+        json_rows(&body),
+        [
+            serde_json::json!({ "name": "O'Brien'); DROP TABLE t; --", "n": 1, "x": 1.5, "b": true }),
+            serde_json::json!({ "name": null, "n": 2, "x": null, "b": false }),
+        ]
+    );
+
+    // Without params, the JSON body runs its SQL as is.
+    let (status, body) = post_json(
+        &app,
+        "/query?format=json&order_by=n&limit=1",
+        serde_json::json!({ "sql": "SELECT n FROM t" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    assert_eq!(json_rows(&body), [serde_json::json!({ "n": 1 })]);
+}
+
+#[tokio::test]
+async fn invalid_json_bodies_are_bad_requests() {
+    let app = app();
+    for body in [
+        serde_json::json!({ "query": "SELECT 1" }),
+        serde_json::json!({ "sql": "SELECT ?", "params": [[1, 2]] }),
+        serde_json::json!({ "sql": "SELECT ?", "params": [{ "a": 1 }] }),
+        serde_json::json!({ "sql": "SELECT ?, ?", "params": [1] }),
+    ] {
+        let (status, _) = post_json(&app, "/query", body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+}
+
+/// A read-only app whose database holds `t` with the rows 1, 2 and 3.
+fn read_only_app() -> Router {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch("CREATE TABLE t (n INTEGER); INSERT INTO t VALUES (1), (2), (3);")
+        .unwrap();
+    let options = ServeOptions {
+        read_only: true,
+        ..ServeOptions::default()
+    };
+    server::router(server::app_state_with(conn, std::env::temp_dir(), &options))
+}
+
+#[tokio::test]
+async fn read_only_server_runs_queries_that_read() {
+    let app = read_only_app();
+    assert_eq!(row_count(&app, "/query", "SELECT n FROM t").await, 3);
+    let sql = "WITH big AS (SELECT n FROM t WHERE n > 1) SELECT * FROM big;";
+    assert_eq!(row_count(&app, "/query", sql).await, 2);
+    assert_eq!(
+        row_count(&app, "/query", "SELECT n FROM t -- note").await,
+        3
+    );
+    assert!(row_count(&app, "/query", "EXPLAIN SELECT n FROM t").await > 0);
+}
+
+#[tokio::test]
+async fn read_only_server_rejects_statements_that_write() {
+    let app = read_only_app();
+    for sql in [
+        "DROP TABLE t",
+        "UPDATE t SET n = 0",
+        "INSERT INTO t VALUES (4)",
+        "-- comment\nDELETE FROM t",
+        "WITH old AS (SELECT 1) DELETE FROM t",
+        "SELECT 1; DROP TABLE t",
+        "EXPLAIN ANALYZE DELETE FROM t",
+        "CALL checkpoint()",
+    ] {
+        let (status, _, body) = post_query(&app, sql).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{sql}");
+        assert!(
+            String::from_utf8_lossy(&body).contains("read-only"),
+            "{sql}: {}",
+            String::from_utf8_lossy(&body)
+        );
+    }
+    assert_eq!(row_count(&app, "/query", "SELECT n FROM t").await, 3);
+}
+
+async fn row_count(app: &Router, uri: &str, sql: &str) -> usize {
+    let (status, _, body) = post_query_to(app, uri, sql).await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    let reader = StreamReader::try_new(body.as_ref(), None).unwrap();
+    reader.map(|batch| batch.unwrap().num_rows()).sum()
+}
+
+#[tokio::test]
+async fn as_of_queries_the_library_at_that_time() {
+    let app = library_app();
+    let seed = "
+        INSERT INTO deletion VALUES ('00000000-0000-0000-0000-00000000000d', '2026-03-01', NULL);
+        INSERT INTO file (id, path, hash, size, format, duration, mtime, added, deletion) VALUES
+          ('00000000-0000-0000-0000-00000000000a', './kept.flac', '', 1, 'flac', 1, 0,
+           '2026-01-01', NULL),
+          ('00000000-0000-0000-0000-00000000000b', './deleted.flac', '', 1, 'flac', 1, 0,
+           '2026-01-01', '00000000-0000-0000-0000-00000000000d'),
+          ('00000000-0000-0000-0000-00000000000c', './added.flac', '', 1, 'flac', 1, 0,
+           '2026-04-01', NULL);
+    ";
+    for statement in seed.split(';').filter(|s| !s.trim().is_empty()) {
+        rows_affected(&app, statement).await;
+    }
+    let live = "SELECT path FROM file WHERE deletion IS NULL";
+
+    assert_eq!(row_count(&app, "/query?as_of=2025-12-01", live).await, 0);
+    assert_eq!(row_count(&app, "/query?as_of=2026-02-01", live).await, 2);
+    let sql = "SELECT path FROM file WHERE deletion IS NULL AND path = './deleted.flac'";
+    assert_eq!(row_count(&app, "/query?as_of=2026-02-01", sql).await, 1);
+    assert_eq!(row_count(&app, "/query?as_of=2026-05-01", live).await, 2);
+    assert_eq!(row_count(&app, "/query?as_of=2026-05-01", sql).await, 0);
+
+    // Later queries see the current library again.
+    assert_eq!(row_count(&app, "/query", sql).await, 0);
+    assert_eq!(row_count(&app, "/query", "SELECT path FROM file").await, 3);
+}
+
+#[tokio::test]
+async fn invalid_as_of_is_a_bad_request() {
+    let app = library_app();
+    let (status, _, _) = post_query_to(&app, "/query?as_of=not-a-time", "SELECT * FROM file").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = post_query_to(&app, "/query?as_of=2026-01-01", "DELETE FROM file").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Percent-encodes `s` for use in a query string.
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect()
+}
+
+async fn get_html(app: &Router, sql: &str) -> (StatusCode, String, String) {
+    let uri = format!("/query?format=html&sql={}", encode(sql));
+    let response = app
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn get_renders_an_escaped_html_table() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER, s TEXT)").await;
+    rows_affected(&app, "INSERT INTO t VALUES (1, '<script>&'), (2, NULL)").await;
+
+    let (status, content_type, html) =
+        get_html(&app, "SELECT n, s AS \"a<b\" FROM t ORDER BY n").await;
+    assert_eq!(status, StatusCode::OK, "{html}");
+    assert!(content_type.starts_with("text/html"));
+    assert!(html.contains("<thead><tr><th>n</th><th>a&lt;b</th></tr></thead>"));
+    assert!(html.contains("<tr><td>1</td><td>&lt;script&gt;&amp;</td></tr>"));
+    assert!(html.contains("<tr><td>2</td><td></td></tr>"));
+    assert!(!html.contains("<script>"));
+}
+
+#[tokio::test]
+async fn queries_may_end_in_a_comment() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    let (status, _, html) = get_html(&app, "SELECT n FROM t -- note").await;
+    assert_eq!(status, StatusCode::OK, "{html}");
+}
+
+#[tokio::test]
+async fn get_refuses_statements_that_modify_data() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    let (status, _, _) = get_html(&app, "DROP TABLE t").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = get_html(&app, "INSERT INTO t VALUES (2) RETURNING n").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(rows_affected(&app, "INSERT INTO t VALUES (1)").await, 1);
+}
+
+/// The first column of each result row, in order.
+async fn first_column(app: &Router, uri: &str, sql: &str) -> Vec<String> {
+    let (status, _, body) = post_query_to(app, uri, sql).await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    let mut values = Vec::new();
+    for batch in StreamReader::try_new(body.as_ref(), None).unwrap() {
+        let batch = batch.unwrap();
+        for row in 0..batch.num_rows() {
+            values.push(array_value_to_string(batch.column(0), row).unwrap());
+        }
+    }
+    values
+}
+
+#[tokio::test]
+async fn order_by_and_limit_wrap_the_query() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER, \"odd name\" TEXT)").await;
+    rows_affected(&app, "INSERT INTO t VALUES (1, 'b'), (2, 'c'), (3, 'a')").await;
+    let sql = "SELECT n, \"odd name\" FROM t ORDER BY n;";
+
+    assert_eq!(
+        first_column(&app, "/query?order_by=n&dir=desc", sql).await,
+        ["3", "2", "1"]
+    );
+    assert_eq!(
+        first_column(&app, "/query?order_by=odd%20name", sql).await,
+        ["3", "1", "2"]
+    );
+    assert_eq!(
+        first_column(&app, "/query?order_by=n&dir=desc&limit=2", sql).await,
+        ["3", "2"]
+    );
+    assert_eq!(first_column(&app, "/query?limit=1", sql).await, ["1"]);
+    let sql = "SELECT n FROM t -- note";
+    assert_eq!(
+        first_column(&app, "/query?order_by=n&limit=2&offset=1", sql).await,
+        ["2", "3"]
+    );
+}
+
+#[tokio::test]
+async fn offset_pages_through_the_rows_and_their_total_is_given() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    rows_affected(&app, "INSERT INTO t SELECT range FROM range(1, 11)").await;
+
+    let page = |uri: &'static str, sql: &'static str| {
+        let app = app.clone();
+        async move {
+            let request = Request::post(uri).body(Body::from(sql)).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let total = response
+                .headers()
+                .get(server::TOTAL_COUNT_HEADER)
+                .map(|v| v.to_str().unwrap().to_string());
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let rows: Vec<i64> = json_rows(&body)
+                .iter()
+                .map(|row| row["n"].as_i64().unwrap())
+                .collect();
+            (rows, total)
+        }
+    };
+
+    let sql = "SELECT n FROM t ORDER BY n";
+    assert_eq!(
+        page("/query?format=json&limit=3&offset=3", sql).await,
+        (vec![4, 5, 6], Some("10".to_string()))
+    );
+    assert_eq!(
+        page(
+            "/query?format=json&order_by=n&dir=desc&limit=3&offset=9",
+            sql
+        )
+        .await,
+        (vec![1], Some("10".to_string()))
+    );
+    // The query's own LIMIT holds, and the total counts what it returns.
+    assert_eq!(
+        page(
+            "/query?format=json&limit=3&offset=3",
+            "SELECT n FROM t ORDER BY n LIMIT 5"
+        )
+        .await,
+        (vec![4, 5], Some("5".to_string()))
+    );
+    assert_eq!(page("/query?format=json", sql).await.1, None);
+}
+
+#[tokio::test]
+async fn statements_other_than_queries_ignore_paging() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER)").await;
+    let uri = "/query?limit=1&offset=1";
+    let (status, _, body) = post_query_to(&app, uri, "INSERT INTO t VALUES (1), (2), (3)").await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["rows_affected"], 3);
+    let sql = "INSERT INTO t VALUES (4), (5) RETURNING n";
+    assert_eq!(first_column(&app, uri, sql).await, ["4", "5"]);
+}
+
+#[tokio::test]
+async fn order_by_must_name_a_result_column() {
+    let app = app();
+    rows_affected(&app, "CREATE TABLE t (n INTEGER, m INTEGER)").await;
+    for uri in [
+        "/query?order_by=m",
+        "/query?order_by=n%22%20DESC%2C%20%22n",
+        "/query?order_by=n&dir=sideways",
+    ] {
+        let (status, _, _) = post_query_to(&app, uri, "SELECT n FROM t").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    let (status, _, _) = post_query_to(&app, "/query?order_by=n", "DELETE FROM t").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Runs `sql` through the in-process query service, returning what it reported
+/// and what it wrote.
+fn run_in_process(
+    state: &server::AppState,
+    sql: &str,
+    params: &QueryParams,
+) -> (Result<Ready, String>, Vec<u8>) {
+    let mut ready = None;
+    let mut out = Vec::new();
+    query::run(
+        state,
+        sql,
+        params,
+        || false,
+        |outcome| ready = Some(outcome),
+        &mut out,
+    )
+    .unwrap();
+    (ready.unwrap(), out)
+}
+
+#[test]
+fn queries_can_run_in_process() {
+    let state = server::app_state(Connection::open_in_memory().unwrap(), std::env::temp_dir());
+    let none = QueryParams::default();
+
+    let (ready, out) = run_in_process(&state, "CREATE TABLE t (n INTEGER)", &none);
+    assert_eq!(ready, Ok(Ready::RowsAffected(0)));
+    assert!(out.is_empty());
+    let (ready, _) = run_in_process(&state, "INSERT INTO t VALUES (1), (2), (3)", &none);
+    assert_eq!(ready, Ok(Ready::RowsAffected(3)));
+
+    let sorted = QueryParams {
+        order_by: Some("n".to_string()),
+        dir: SortDir::Desc,
+        limit: Some(2),
+        ..QueryParams::default()
+    };
+    let (ready, out) = run_in_process(&state, "SELECT n FROM t", &sorted);
+    assert_eq!(ready, Ok(Ready::Rows { total: Some(3) }));
+    let mut values = Vec::new();
+    for batch in StreamReader::try_new(out.as_slice(), None).unwrap() {
+        let batch = batch.unwrap();
+        for row in 0..batch.num_rows() {
+            values.push(array_value_to_string(batch.column(0), row).unwrap());
+        }
+    }
+    assert_eq!(values, ["3", "2"]);
+
+    let (ready, out) = run_in_process(&state, "SELECT * FROM missing", &none);
+    assert!(ready.is_err());
+    assert!(out.is_empty());
+    let (ready, _) = run_in_process(&state, "DELETE FROM t", &sorted);
+    assert!(ready.is_err());
+}
+
+#[test]
+fn reads_and_writes_go_on_while_a_read_is_held() {
+    let state = server::app_state(Connection::open_in_memory().unwrap(), std::env::temp_dir());
+    let (held_tx, held_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+
+    std::thread::scope(|s| {
+        let reader = &state;
+        s.spawn(move || {
+            reader.read(|_| {
+                held_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            });
+        });
+        held_rx.recv().unwrap();
+
+        state
+            .write(|conn| {
+                conn.execute_batch("CREATE TABLE t (n INTEGER); INSERT INTO t VALUES (7);")
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap();
+        let n: i32 = state.read(|conn| {
+            conn.query_row("SELECT n FROM t", [], |row| row.get(0))
+                .unwrap()
+        });
+        assert_eq!(n, 7);
+
+        release_tx.send(()).unwrap();
+    });
+}
+
+/// A query that would run for minutes.
+const SLOW_QUERY: &str = "SELECT count(*) FROM range(100000000000)";
+
+#[tokio::test]
+async fn queries_are_interrupted_after_their_timeout() {
+    let app = app();
+    let (status, _, body) = post_query_to(&app, "/query?timeout=1", SLOW_QUERY).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(String::from_utf8_lossy(&body), "query timed out after 1s");
+
+    // The connection is free for the next query.
+    assert_eq!(row_count(&app, "/query", "SELECT 1").await, 1);
+}
+
+#[test]
+fn queries_are_interrupted_once_cancelled() {
+    let state = server::app_state(Connection::open_in_memory().unwrap(), std::env::temp_dir());
+    let mut ready = None;
+    query::run(
+        &state,
+        SLOW_QUERY,
+        &QueryParams::default(),
+        || true,
+        |outcome| ready = Some(outcome),
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(ready, Some(Err("query cancelled".to_string())));
+}
@@ -0,0 +1,90 @@
+//! Rederiving rebuilds the tracks, albums, artists and credits of a scanned
+//! library from the tags the scan stored, as they've been edited since.
+
+mod common;
+
+use backend::scanner::{self, ScanOptions};
+use common::TempDir;
+use duckdb::Connection;
+
+fn collection() -> TempDir {
+    common::collection(
+        "rederive",
+        &[
+            ("01. Duck.flac", "The Announcers - First Test/01. Duck.flac"),
+            ("02. Hens.flac", "The Announcers - First Test/02. Hens.flac"),
+        ],
+    )
+}
+
+/// The values of the single text column `sql` selects, in order.
+fn column(conn: &Connection, sql: &str) -> Vec<String> {
+    let mut stmt = conn.prepare(sql).unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Each track as `title | album | artists`, by title.
+fn tracks(conn: &Connection) -> Vec<String> {
+    column(
+        conn,
+        "SELECT t.title || ' | ' || al.title || ' | '
+                || string_agg(ar.name, ', ' ORDER BY c.ord)
+         FROM track t
+         JOIN album al ON al.id = t.album
+         JOIN credit c ON c.track = t.id AND c.role = ''
+         JOIN artist ar ON ar.id = c.artist
+         GROUP BY t.title, al.title
+         ORDER BY t.title",
+    )
+}
+
+#[test]
+fn edited_tags_are_rederived() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(
+        tracks(&conn),
+        [
+            "Duck | First Test | The Announcers",
+            "Hens | First Test | The Announcers"
+        ]
+    );
+    let track_ids = column(&conn, "SELECT id::TEXT FROM track ORDER BY id");
+
+    conn.execute_batch(
+        "
+UPDATE file_tag SET value = 'Drake' WHERE std_key = 'TrackTitle' AND value = 'Duck';
+UPDATE file_tag SET value = 'Second Test' WHERE std_key = 'Album';
+UPDATE file_tag SET value = 'The Rederivers' WHERE std_key = 'Artist';
+",
+    )
+    .unwrap();
+    common::rederive(&conn);
+
+    assert_eq!(
+        tracks(&conn),
+        [
+            "Drake | Second Test | The Rederivers",
+            "Hens | Second Test | The Rederivers"
+        ]
+    );
+    // The tracks keep their ids, and nothing is left of the old album and
+    // artist.
+    assert_eq!(
+        column(&conn, "SELECT id::TEXT FROM track ORDER BY id"),
+        track_ids
+    );
+    assert_eq!(column(&conn, "SELECT title FROM album"), ["Second Test"]);
+    assert_eq!(column(&conn, "SELECT name FROM artist"), ["The Rederivers"]);
+    assert_eq!(
+        column(
+            &conn,
+            "SELECT ar.name FROM album al JOIN artist ar ON ar.id = al.artist"
+        ),
+        ["The Rederivers"]
+    );
+}
@@ -0,0 +1,36 @@
+mod common;
+
+/// Rederives a library holding one track of album `Album` dated `date`,
+/// returning the album's `(year, release_date)`.
+fn album_date(date: &str) -> (Option<u16>, Option<String>) {
+    let conn = common::rederived(&[("ALBUM", "Album", "Album"), ("DATE", "Date", date)]);
+    conn.query_row("SELECT year, release_date::VARCHAR FROM album", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+}
+
+#[test]
+fn full_dates_are_kept_alongside_the_year() {
+    assert_eq!(
+        album_date("2021-07-14"),
+        (Some(2021), Some("2021-07-14".to_string()))
+    );
+    assert_eq!(
+        album_date("2021/07/14"),
+        (Some(2021), Some("2021-07-14".to_string()))
+    );
+    assert_eq!(
+        album_date("2021-07-14T20:00:00"),
+        (Some(2021), Some("2021-07-14".to_string()))
+    );
+}
+
+#[test]
+fn partial_or_invalid_dates_only_give_the_year() {
+    assert_eq!(album_date("2021"), (Some(2021), None));
+    assert_eq!(album_date("2021-07"), (Some(2021), None));
+    assert_eq!(album_date("2021-02-30"), (Some(2021), None));
+    // The year's sanity check applies to full dates too.
+    assert_eq!(album_date("1492-10-12"), (None, None));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner;
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("replaced");
+    dir.copy(format!("{ALBUM}/01. Duck.flac"), "duck.flac");
+    dir
+}
+
+/// Scans `dir`, then rates its only track and records a play of it.
+fn scanned_and_rated(dir: &Path) -> Connection {
+    let conn = common::library();
+    scanner::scan(dir, &conn, scanner::ScanOptions::default()).unwrap();
+    conn.execute_batch(
+        "UPDATE track SET rating = 4;
+         UPDATE file SET added = '2020-01-01';
+         INSERT INTO play SELECT id, '2025-06-01 12:00:00' FROM track;",
+    )
+    .unwrap();
+    conn
+}
+
+/// `(path, rating, added, plays)` of the live file's track.
+fn live_track(conn: &Connection) -> (String, Option<f32>, String, i64) {
+    conn.query_row(
+        "SELECT f.path, t.rating, f.added::text,
+                (SELECT count(*) FROM play p WHERE p.track = t.id)
+         FROM track t JOIN file f ON f.id = t.file
+         WHERE f.deletion IS NULL",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn re_encoded_files_keep_user_data() {
+    let dir = collection();
+    let conn = scanned_and_rated(&dir);
+
+    fs::remove_file(dir.join("duck.flac")).unwrap();
+    dir.copy(format!("{ALBUM}/02. Hens.flac"), "duck.ogg");
+    scanner::scan(&dir, &conn, scanner::ScanOptions::default()).unwrap();
+
+    assert_eq!(
+        live_track(&conn),
+        (
+            "./duck.ogg".to_string(),
+            Some(4.0),
+            "2020-01-01 00:00:00".to_string(),
+            1
+        )
+    );
+    let orphaned_plays: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM play p JOIN track t ON t.id = p.track
+             JOIN file f ON f.id = t.file WHERE f.deletion IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(orphaned_plays, 0);
+}
+
+#[test]
+fn files_added_instead_of_moved_keep_user_data() {
+    let dir = collection();
+    let conn = scanned_and_rated(&dir);
+
+    fs::create_dir_all(dir.join("elsewhere")).unwrap();
+    fs::rename(dir.join("duck.flac"), dir.join("elsewhere/duck.flac")).unwrap();
+    let options = scanner::ScanOptions {
+        no_move: true,
+        ..Default::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+
+    let (path, rating, _, plays) = live_track(&conn);
+    assert_eq!(path, "./elsewhere/duck.flac");
+    assert_eq!(rating, Some(4.0));
+    assert_eq!(plays, 1);
+}
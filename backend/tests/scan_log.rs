@@ -0,0 +1,79 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use backend::scanner;
+use common::{FIXTURE, TempDir};
+use serde_json::Value;
+
+fn collection() -> TempDir {
+    let dir = TempDir::new("scan-log");
+    dir.copy(FIXTURE, "good.flac");
+    fs::write(dir.join("broken.flac"), b"not audio at all").unwrap();
+    dir
+}
+
+/// Scans `dir` with a log file, returning its entries sorted by path.
+fn scan_with_log(dir: &Path, conn: &duckdb::Connection) -> Vec<(String, Value)> {
+    let log_file = dir.with_extension("jsonl");
+    let options = scanner::ScanOptions {
+        log_file: Some(log_file.clone()),
+        ..Default::default()
+    };
+    scanner::scan(dir, conn, options).unwrap();
+    let log = fs::read_to_string(&log_file).unwrap();
+    fs::remove_file(&log_file).unwrap();
+    let mut entries: Vec<(String, Value)> = log
+        .lines()
+        .map(|line| {
+            let entry: Value = serde_json::from_str(line).unwrap();
+            (entry["path"].as_str().unwrap().to_string(), entry)
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+#[test]
+fn every_file_decision_is_logged() {
+    let dir = collection();
+    let conn = common::library();
+
+    let entries = scan_with_log(&dir, &conn);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].0, "./broken.flac");
+    assert_eq!(entries[0].1["classification"], "error");
+    assert_eq!(entries[0].1["category"], "unsupported");
+    assert_eq!(entries[1].0, "./good.flac");
+    assert_eq!(entries[1].1["classification"], "new");
+    assert_eq!(entries[1].1["format"], "flac");
+    assert_eq!(entries[1].1["metadata"]["title"], "Duck");
+
+    fs::remove_file(dir.join("broken.flac")).unwrap();
+    fs::rename(dir.join("good.flac"), dir.join("moved.flac")).unwrap();
+    let entries = scan_with_log(&dir, &conn);
+    let (_, moved) = entries
+        .iter()
+        .find(|(path, _)| path == "./moved.flac")
+        .unwrap();
+    assert_eq!(moved["classification"], "moved");
+    assert_eq!(moved["from"], "./good.flac");
+}
+
+#[test]
+fn modified_files_that_fail_to_read_are_logged_with_the_error() {
+    let dir = collection();
+    let conn = common::library();
+    scan_with_log(&dir, &conn);
+
+    fs::write(dir.join("good.flac"), b"no longer audio").unwrap();
+    let entries = scan_with_log(&dir, &conn);
+    let (_, good) = entries
+        .iter()
+        .find(|(path, _)| path == "./good.flac")
+        .unwrap();
+    assert_eq!(good["classification"], "modified");
+    assert_eq!(good["category"], "unsupported");
+    assert!(good["error"].is_string());
+}
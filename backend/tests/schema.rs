@@ -0,0 +1,66 @@
+mod common;
+
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+async fn get_schema() -> (u32, Value) {
+    let conn = common::library();
+    conn.execute_batch("CREATE TEMP TABLE scratch (n INTEGER)")
+        .unwrap();
+    let version = conn
+        .query_row("SELECT value FROM meta.version", [], |row| row.get(0))
+        .unwrap();
+    let app = server::router(server::app_state(conn, std::env::temp_dir()));
+
+    let request = Request::get("/schema").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (version, serde_json::from_slice(&body).unwrap())
+}
+
+fn table<'a>(schema: &'a Value, name: &str) -> Option<&'a Value> {
+    schema["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|table| table["name"] == name)
+}
+
+#[tokio::test]
+async fn schema_lists_tables_with_their_columns() {
+    let (version, schema) = get_schema().await;
+    assert_eq!(schema["version"], version);
+
+    let artist = table(&schema, "artist").unwrap();
+    assert_eq!(artist["schema"], "main");
+    assert_eq!(artist["kind"], "table");
+    assert_eq!(
+        artist["columns"],
+        json!([
+            { "name": "id", "type": "UUID", "nullable": false },
+            { "name": "name", "type": "VARCHAR", "nullable": false },
+            { "name": "musicbrainz_id", "type": "UUID", "nullable": true },
+        ])
+    );
+    let track = table(&schema, "track").unwrap();
+    assert_eq!(track["columns"][0]["name"], "id");
+    assert!(
+        track["columns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|column| column["name"] == "rating" && column["nullable"] == true)
+    );
+}
+
+#[tokio::test]
+async fn schema_leaves_out_bookkeeping_and_temporary_tables() {
+    let (_, schema) = get_schema().await;
+    assert!(table(&schema, "version").is_none());
+    assert!(table(&schema, "scratch").is_none());
+    assert!(table(&schema, "tables").is_none());
+}
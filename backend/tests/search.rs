@@ -0,0 +1,84 @@
+mod common;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server;
+use serde_json::Value;
+use tower::ServiceExt;
+
+/// An app whose library holds tracks by Beyoncé and others, one of them of a
+/// deleted file.
+fn app() -> Router {
+    let conn = common::library();
+    conn.execute_batch(
+        "
+INSERT INTO deletion (id) VALUES ('00000000-0000-0000-0000-0000000000d1');
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 1, 0,
+        now(), now(), NULL),
+       ('00000000-0000-0000-0000-0000000000f2', './b.flac', '', 1, 'flac', 1, 0,
+        now(), now(), '00000000-0000-0000-0000-0000000000d1');
+INSERT INTO artist (id, name) VALUES
+  ('00000000-0000-0000-0000-0000000000b1', 'Beyoncé'),
+  ('00000000-0000-0000-0000-0000000000b2', 'Other');
+INSERT INTO album (id, title) VALUES ('00000000-0000-0000-0000-0000000000c1', 'Halo Days');
+INSERT INTO track (id, file, title, album) VALUES
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000f1',
+   'Crazy in Love', NULL),
+  ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-0000000000f1',
+   'Halo', '00000000-0000-0000-0000-0000000000c1'),
+  ('00000000-0000-0000-0000-0000000000a3', '00000000-0000-0000-0000-0000000000f1',
+   'Sunrise', '00000000-0000-0000-0000-0000000000c1'),
+  ('00000000-0000-0000-0000-0000000000a4', '00000000-0000-0000-0000-0000000000f2',
+   'Halo', NULL);
+INSERT INTO credit (track, artist, ord, role) VALUES
+  ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000b1', 0, ''),
+  ('00000000-0000-0000-0000-0000000000a2', '00000000-0000-0000-0000-0000000000b1', 0, ''),
+  ('00000000-0000-0000-0000-0000000000a3', '00000000-0000-0000-0000-0000000000b2', 0, '');
+",
+    )
+    .unwrap();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+/// The titles of the tracks `/search` finds for `q`, in order.
+async fn search(app: &Router, q: &str) -> Vec<String> {
+    let request = Request::get(format!("/search?q={q}&format=json"))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let row: Value = serde_json::from_str(line).unwrap();
+            row["title"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn search_ignores_case_and_accents() {
+    let app = app();
+    assert_eq!(search(&app, "BEYONCE").await, ["Crazy in Love", "Halo"]);
+    assert_eq!(search(&app, "beyonc%C3%A9%20love").await, ["Crazy in Love"]);
+}
+
+#[tokio::test]
+async fn title_matches_rank_first() {
+    let app = app();
+    // Both tracks of the album match, but only one by its title; the deleted
+    // file's track is left out.
+    assert_eq!(search(&app, "halo").await, ["Halo", "Sunrise"]);
+    assert_eq!(search(&app, "nothing").await, Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn an_empty_search_is_refused() {
+    let request = Request::get("/search?q=%20").body(Body::empty()).unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
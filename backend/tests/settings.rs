@@ -0,0 +1,111 @@
+mod common;
+
+use arrow_ipc::reader::StreamReader;
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server;
+use duckdb::arrow::util::display::array_value_to_string;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+/// An app whose library holds one track whose stored tags list two genres.
+fn app() -> Router {
+    let conn = common::library();
+    conn.execute_batch(
+        "
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 1, 0,
+        now(), now(), NULL);
+INSERT INTO track (id, file, title, primary_genre)
+VALUES ('00000000-0000-0000-0000-0000000000a1', '00000000-0000-0000-0000-0000000000f1', 'A',
+        'Rock');
+INSERT INTO file_tag (file, ord, key, std_key, value) VALUES
+  ('00000000-0000-0000-0000-0000000000f1', 0, 'TITLE', 'TrackTitle', 'A'),
+  ('00000000-0000-0000-0000-0000000000f1', 1, 'GENRE', 'Genre', 'Rock'),
+  ('00000000-0000-0000-0000-0000000000f1', 2, 'GENRE', 'Genre', 'Progressive Rock');
+",
+    )
+    .unwrap();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn get_settings(app: &Router) -> Value {
+    let request = Request::get("/settings").body(Body::empty()).unwrap();
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    serde_json::from_str(&body).unwrap()
+}
+
+async fn put_settings(app: &Router, changes: Value) -> (StatusCode, String) {
+    let request = Request::put("/settings")
+        .header("content-type", "application/json")
+        .body(Body::from(changes.to_string()))
+        .unwrap();
+    send(app, request).await
+}
+
+async fn primary_genre(app: &Router) -> String {
+    let request = Request::post("/query")
+        .body(Body::from("SELECT primary_genre FROM track"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let batch = StreamReader::try_new(body.as_ref(), None)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    array_value_to_string(batch.column(0), 0).unwrap()
+}
+
+#[tokio::test]
+async fn unset_settings_read_as_their_defaults() {
+    let app = app();
+    assert_eq!(
+        get_settings(&app).await,
+        json!({ "primary_genre": "first", "genre_priority": [], "missing_album": "none" })
+    );
+}
+
+#[tokio::test]
+async fn changed_settings_are_stored_and_rederive_the_library() {
+    let app = app();
+    let (status, body) = put_settings(&app, json!({ "primary_genre": "most-specific" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let expected =
+        json!({ "primary_genre": "most-specific", "genre_priority": [], "missing_album": "none" });
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), expected);
+    assert_eq!(get_settings(&app).await, expected);
+    assert_eq!(primary_genre(&app).await, "Progressive Rock");
+
+    let (status, _) = put_settings(
+        &app,
+        json!({ "primary_genre": "priority", "genre_priority": ["Rock"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(primary_genre(&app).await, "Rock");
+}
+
+#[tokio::test]
+async fn unknown_keys_and_invalid_values_are_refused() {
+    let app = app();
+    for changes in [
+        json!({ "colour": "blue" }),
+        json!({ "primary_genre": "loudest" }),
+        json!({ "genre_priority": "Rock" }),
+        json!({ "missing_album": "single", "genre_priority": [1] }),
+    ] {
+        let (status, body) = put_settings(&app, changes.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{changes}: {body}");
+    }
+    assert_eq!(get_settings(&app).await["missing_album"], "none");
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use backend::server;
+
+#[test]
+fn writes_fail_once_the_state_is_closed() {
+    let state = server::app_state(common::library(), std::env::temp_dir());
+    state.close(Duration::from_secs(1));
+    let error = state.write(|_| Ok(())).unwrap_err();
+    assert!(error.contains("closed"), "{error}");
+}
+
+#[test]
+fn closing_gives_up_on_a_write_that_runs_too_long() {
+    let state = server::app_state(common::library(), std::env::temp_dir());
+    let (started_tx, started_rx) = mpsc::channel();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    thread::scope(|s| {
+        let writer = &state;
+        s.spawn(move || {
+            writer.write(|_| {
+                started_tx.send(()).unwrap();
+                let _ = done_rx.recv();
+                Ok(())
+            })
+        });
+        started_rx.recv().unwrap();
+        let start = Instant::now();
+        state.close(Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        drop(done_tx);
+    });
+    // The write that held the writer finished, and the state can still close.
+    state.close(Duration::from_secs(1));
+    assert!(state.write(|_| Ok(())).is_err());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use backend::scanner::{self, ScanOptions, Since, parse_since};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A collection with one file in `old/`.
+fn collection() -> TempDir {
+    let dir = TempDir::new("since");
+    dir.copy(FIXTURE, "old/a.flac");
+    dir
+}
+
+fn live_paths(conn: &Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT path FROM file WHERE deletion IS NULL ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn scan_since(dir: &Path, conn: &Connection, since: Since) {
+    let options = ScanOptions {
+        since: Some(since),
+        ..ScanOptions::default()
+    };
+    scanner::scan(dir, conn, options).unwrap();
+}
+
+#[test]
+fn files_of_unchanged_directories_are_kept_unread() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+
+    // Unreadable now, but its directory is as it was.
+    fs::write(dir.join("old/a.flac"), b"not audio").unwrap();
+    let future = SystemTime::now() + Duration::from_hours(1);
+    scan_since(&dir, &conn, Since::At(future));
+    assert_eq!(live_paths(&conn), ["./old/a.flac"]);
+    let failures: u32 = conn
+        .query_row("SELECT count(*) FROM scan_failure", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(failures, 0);
+}
+
+#[test]
+fn new_directories_are_scanned() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+
+    dir.copy(FIXTURE, "new/b.flac");
+    scan_since(&dir, &conn, Since::LastScan);
+    assert_eq!(live_paths(&conn), ["./new/b.flac", "./old/a.flac"]);
+}
+
+#[test]
+fn since_last_without_a_recorded_scan_scans_everything() {
+    let dir = collection();
+    let conn = common::library();
+    scan_since(&dir, &conn, Since::LastScan);
+    assert_eq!(live_paths(&conn), ["./old/a.flac"]);
+}
+
+#[test]
+fn since_values_are_parsed() {
+    assert!(matches!(parse_since("last"), Ok(Since::LastScan)));
+    assert!(matches!(
+        parse_since("2026-10-01T12:00:00Z"),
+        Ok(Since::At(_))
+    ));
+    assert!(matches!(parse_since("2026-10-01"), Ok(Since::At(_))));
+    assert!(parse_since("yesterday").is_err());
+}
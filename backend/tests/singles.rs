@@ -0,0 +1,115 @@
+mod common;
+
+use backend::scanner::{self, AlbumOptions, GenreOptions, MissingAlbumRule};
+use duckdb::Connection;
+
+/// A library holding a track for each `(path, album, artist, date)`, with
+/// nothing but those tags stored (and no album tag when the album is empty),
+/// derived with `missing_album`.
+fn library(tracks: &[(&str, &str, &str, &str)], missing_album: MissingAlbumRule) -> Connection {
+    let conn = common::library();
+    for (i, &(path, album, artist, date)) in tracks.iter().enumerate() {
+        let mut tags = vec![("ARTIST", "Artist", artist), ("DATE", "Date", date)];
+        if !album.is_empty() {
+            tags.push(("ALBUM", "Album", album));
+        }
+        common::add_file(&conn, i, path, &tags);
+    }
+    let options = AlbumOptions { missing_album };
+    scanner::rederive(&conn, &GenreOptions::default(), &options).unwrap();
+    conn
+}
+
+/// The album of each track, as the number of the first track in path order
+/// that shares it, or `None` when it has none.
+fn groups(conn: &Connection) -> Vec<Option<usize>> {
+    let mut stmt = conn
+        .prepare("SELECT t.album::TEXT FROM track t JOIN file f ON f.id = t.file ORDER BY f.path")
+        .unwrap();
+    let albums: Vec<Option<String>> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    albums
+        .iter()
+        .map(|album| {
+            let album = album.as_ref()?;
+            albums
+                .iter()
+                .position(|other| other.as_ref() == Some(album))
+        })
+        .collect()
+}
+
+#[test]
+fn same_titled_singles_of_different_artists_stay_apart() {
+    let conn = library(
+        &[
+            ("./singles/1.flac", "Home", "First", "2001"),
+            ("./singles/2.flac", "Home", "Second", "2001"),
+            ("./singles/3.flac", "Home", "First", "2001"),
+        ],
+        MissingAlbumRule::Omit,
+    );
+    assert_eq!(groups(&conn), [Some(0), Some(1), Some(0)]);
+}
+
+#[test]
+fn same_titled_singles_of_different_years_stay_apart() {
+    let conn = library(
+        &[
+            ("./singles/1.flac", "Home", "First", "2001"),
+            ("./singles/2.flac", "Home", "First", "2015"),
+            ("./singles/3.flac", "Home", "First", "2001-06-01"),
+        ],
+        MissingAlbumRule::Omit,
+    );
+    assert_eq!(groups(&conn), [Some(0), Some(1), Some(0)]);
+}
+
+#[test]
+fn tracks_without_an_album_title_are_never_grouped() {
+    let tracks = [
+        ("./singles/1.flac", "", "First", "2001"),
+        ("./singles/2.flac", "", "First", "2001"),
+    ];
+    let conn = library(&tracks, MissingAlbumRule::Omit);
+    assert_eq!(groups(&conn), [None, None]);
+    let conn = library(&tracks, MissingAlbumRule::Single);
+    assert_eq!(groups(&conn), [Some(0), Some(1)]);
+}
+
+#[test]
+fn the_directory_rule_titles_albums_after_their_folder() {
+    let conn = library(
+        &[
+            ("./singles/1.flac", "", "First", "2001"),
+            ("./singles/2.flac", "", "First", "2001"),
+            ("./loose.flac", "", "First", "2001"),
+        ],
+        MissingAlbumRule::Directory,
+    );
+    // A track at the root of the collection has no folder to go by.
+    assert_eq!(groups(&conn), [None, Some(1), Some(1)]);
+    let titles: Vec<String> = conn
+        .prepare("SELECT title FROM album ORDER BY title")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(titles, ["singles"]);
+}
+
+#[test]
+fn tracks_without_a_title_are_titled_after_their_file() {
+    let conn = library(
+        &[("./singles/01. Home.flac", "", "First", "2001")],
+        MissingAlbumRule::Omit,
+    );
+    let title: String = conn
+        .query_row("SELECT title FROM track", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(title, "Home");
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::fs;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode, header};
+use backend::server;
+use common::{FIXTURE, TempDir};
+use tower::ServiceExt;
+
+/// An app over a collection holding the fixture as `./a.flac`, and whose
+/// library also has `./gone.flac` (deleted) and `./missing.flac` (not on disk).
+/// The collection lasts as long as the returned [`TempDir`].
+fn app() -> (TempDir, Router) {
+    let dir = TempDir::new("stream");
+    dir.copy(FIXTURE, "a.flac");
+    dir.copy(FIXTURE, "gone.flac");
+    let conn = common::library();
+    conn.execute_batch(
+        "
+INSERT INTO deletion (id) VALUES ('00000000-0000-0000-0000-0000000000d1');
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 1, 0,
+        now(), now(), NULL),
+       ('00000000-0000-0000-0000-0000000000f2', './gone.flac', '', 1, 'flac', 1, 0,
+        now(), now(), '00000000-0000-0000-0000-0000000000d1'),
+       ('00000000-0000-0000-0000-0000000000f3', './missing.flac', '', 1, 'flac', 1, 0,
+        now(), now(), NULL);
+",
+    )
+    .unwrap();
+    let app = server::router(server::app_state(conn, dir.to_path_buf()));
+    (dir, app)
+}
+
+async fn get(app: &Router, path: &str) -> (StatusCode, Option<String>, Vec<u8>) {
+    let request = Request::get(format!("/files/stream?path={path}"))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, body.to_vec())
+}
+
+#[tokio::test]
+async fn files_are_served_by_their_library_path() {
+    let (_dir, app) = app();
+    let (status, content_type, body) = get(&app, "./a.flac").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("audio/flac"));
+    assert_eq!(body, fs::read(FIXTURE).unwrap());
+}
+
+#[tokio::test]
+async fn only_live_files_of_the_library_are_served() {
+    let (_dir, app) = app();
+    for path in ["./gone.flac", "./missing.flac", "./other.flac", "../a.flac"] {
+        let (status, _, _) = get(&app, path).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{path}");
+    }
+}
@@ -0,0 +1,152 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use backend::scanner::{self, ScanOptions, SymlinkRule};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A collection holding `track.flac` and `link.flac`, a symlink to it.
+fn collection() -> TempDir {
+    let dir = TempDir::new("symlinks");
+    dir.copy(FIXTURE, "track.flac");
+    symlink(dir.join("track.flac"), dir.join("link.flac")).unwrap();
+    dir
+}
+
+fn options(symlinks: SymlinkRule) -> ScanOptions {
+    ScanOptions {
+        symlinks,
+        ..Default::default()
+    }
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+fn live_paths(conn: &Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT path FROM file WHERE deletion IS NULL ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn symlinks_to_indexed_files_are_skipped_by_default() {
+    let dir = collection();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_paths(&conn), ["./track.flac"]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM track"), 1);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_alias"), 0);
+
+    // Rescanning neither adds the link nor mistakes it for a move.
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_paths(&conn), ["./track.flac"]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 1);
+}
+
+#[test]
+fn symlinks_can_be_recorded_as_aliases() {
+    let dir = collection();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, options(SymlinkRule::Alias)).unwrap();
+    assert_eq!(live_paths(&conn), ["./track.flac"]);
+    let (path, target): (String, String) = conn
+        .query_row(
+            "SELECT a.path, f.path FROM file_alias a JOIN file f ON f.id = a.file",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(path, "./link.flac");
+    assert_eq!(target, "./track.flac");
+
+    // Aliases are found afresh by every scan.
+    scanner::scan(&dir, &conn, options(SymlinkRule::Alias)).unwrap();
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_alias"), 1);
+    fs::remove_file(dir.join("link.flac")).unwrap();
+    scanner::scan(&dir, &conn, options(SymlinkRule::Alias)).unwrap();
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_alias"), 0);
+}
+
+#[test]
+fn a_symlink_added_later_is_an_alias_of_the_indexed_file() {
+    let dir = collection();
+    fs::remove_file(dir.join("link.flac")).unwrap();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, options(SymlinkRule::Alias)).unwrap();
+
+    symlink(dir.join("track.flac"), dir.join("link.flac")).unwrap();
+    scanner::scan(&dir, &conn, options(SymlinkRule::Alias)).unwrap();
+
+    assert_eq!(live_paths(&conn), ["./track.flac"]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM deletion"), 0);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_alias"), 1);
+}
+
+/// A collection holding `album/track.flac`, and a directory outside it holding
+/// `other.flac`.
+fn collection_with_album() -> (TempDir, TempDir) {
+    let dir = TempDir::new("symlinks");
+    dir.copy(FIXTURE, "album/track.flac");
+    let outside = TempDir::new("symlinks-outside");
+    fs::write(
+        outside.join("other.flac"),
+        [fs::read(FIXTURE).unwrap(), vec![0]].concat(),
+    )
+    .unwrap();
+    (dir, outside)
+}
+
+fn following(symlinks: SymlinkRule) -> ScanOptions {
+    ScanOptions {
+        follow_symlinks: true,
+        ..options(symlinks)
+    }
+}
+
+#[test]
+fn symlinked_directories_are_only_followed_when_asked() {
+    let (dir, outside) = collection_with_album();
+    symlink(&outside, dir.join("elsewhere")).unwrap();
+
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    assert_eq!(live_paths(&conn), ["./album/track.flac"]);
+
+    let conn = common::library();
+    scanner::scan(&dir, &conn, following(SymlinkRule::Skip)).unwrap();
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 2);
+}
+
+#[test]
+fn a_followed_symlink_to_a_directory_of_the_collection_adds_nothing() {
+    let (dir, _outside) = collection_with_album();
+    symlink(dir.join("album"), dir.join("latest")).unwrap();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, following(SymlinkRule::Alias)).unwrap();
+    assert_eq!(live_paths(&conn), ["./album/track.flac"]);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file_alias"), 0);
+}
+
+#[test]
+fn followed_symlink_loops_end() {
+    let (dir, _outside) = collection_with_album();
+    symlink(&dir, dir.join("album/loop")).unwrap();
+    symlink(dir.join("album"), dir.join("album/self")).unwrap();
+    let conn = common::library();
+
+    scanner::scan(&dir, &conn, following(SymlinkRule::Skip)).unwrap();
+    assert_eq!(live_paths(&conn), ["./album/track.flac"]);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+use backend::scanner;
+
+const COLLECTION: &str = "tests/resources/collection";
+
+fn scanned_paths(threads: Option<NonZeroUsize>) -> Vec<String> {
+    let conn = common::library();
+    let options = scanner::ScanOptions {
+        threads,
+        ..Default::default()
+    };
+    scanner::scan(Path::new(COLLECTION), &conn, options).unwrap();
+    let mut stmt = conn.prepare("SELECT path FROM file ORDER BY path").unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn scans_with_one_thread_find_what_others_do() {
+    let paths = scanned_paths(NonZeroUsize::new(1));
+    assert!(!paths.is_empty());
+    assert_eq!(paths, scanned_paths(None));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use duckdb::Connection;
+
+type Numbers = (Option<u8>, Option<u8>, Option<u8>, Option<u8>);
+
+/// Rederives a library holding one track with the given stored tags, returning
+/// its `(track_number, track_total, disc_number, disc_total)`.
+fn numbers(tags: &[(&str, &str, &str)]) -> Numbers {
+    read_numbers(&common::rederived(tags))
+}
+
+fn read_numbers(conn: &Connection) -> Numbers {
+    conn.query_row(
+        "SELECT track_number, track_total, disc_number, disc_total FROM track",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn numbers_and_totals_are_read_from_one_tag() {
+    assert_eq!(
+        numbers(&[
+            ("TRACKNUMBER", "TrackNumber", "7/12"),
+            ("DISCNUMBER", "DiscNumber", "1/2"),
+        ]),
+        (Some(7), Some(12), Some(1), Some(2))
+    );
+}
+
+#[test]
+fn either_half_may_be_missing() {
+    assert_eq!(
+        numbers(&[
+            ("TRACKNUMBER", "TrackNumber", "7"),
+            ("DISCNUMBER", "DiscNumber", "/2"),
+        ]),
+        (Some(7), None, None, Some(2))
+    );
+}
+
+#[test]
+fn separate_total_tags_fill_in_missing_totals() {
+    assert_eq!(
+        numbers(&[
+            ("TRACKNUMBER", "TrackNumber", "7"),
+            ("TRACKTOTAL", "TrackTotal", "12"),
+            ("DISCNUMBER", "DiscNumber", "1/2"),
+            ("DISCTOTAL", "DiscTotal", "3"),
+        ]),
+        (Some(7), Some(12), Some(1), Some(2))
+    );
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use arrow_ipc::reader::StreamReader;
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Request, StatusCode};
+use backend::server;
+use duckdb::arrow::util::display::array_value_to_string;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+const TRACK_A: &str = "00000000-0000-0000-0000-0000000000a1";
+const TRACK_B: &str = "00000000-0000-0000-0000-0000000000a2";
+
+/// An app whose library holds tracks A (rating NULL) and B (rating 2).
+fn app() -> Router {
+    let conn = common::library();
+    conn.execute_batch(&format!(
+        "
+INSERT INTO file (id, path, hash, size, format, duration, mtime, added, modified, deletion)
+VALUES ('00000000-0000-0000-0000-0000000000f1', './a.flac', '', 1, 'flac', 1, 0,
+        now(), now(), NULL);
+INSERT INTO track (id, file, title, primary_genre, rating) VALUES
+  ('{TRACK_A}', '00000000-0000-0000-0000-0000000000f1', 'A', 'Rock', NULL),
+  ('{TRACK_B}', '00000000-0000-0000-0000-0000000000f1', 'B', 'Jazz', 2);
+"
+    ))
+    .unwrap();
+    server::router(server::app_state(conn, std::env::temp_dir()))
+}
+
+async fn patch(app: &Router, body: Value) -> (StatusCode, Value) {
+    let request = Request::patch("/tracks")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+async fn rate(app: &Router, id: &str, body: Value) -> (StatusCode, String) {
+    let request = Request::post(format!("/tracks/{id}/rating"))
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// The tracks' `title|primary_genre|rating|version`, in id order.
+async fn tracks(app: &Router) -> Vec<String> {
+    let sql = "SELECT concat_ws('|', title, primary_genre, coalesce(rating::text, '-'), version) \
+               FROM track ORDER BY id";
+    let request = Request::post("/query").body(Body::from(sql)).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let mut rows = Vec::new();
+    for batch in StreamReader::try_new(body.as_ref(), None).unwrap() {
+        let batch = batch.unwrap();
+        for row in 0..batch.num_rows() {
+            rows.push(array_value_to_string(batch.column(0), row).unwrap());
+        }
+    }
+    rows
+}
+
+#[tokio::test]
+async fn updates_are_applied_together() {
+    let app = app();
+    let (status, response) = patch(
+        &app,
+        json!([
+            { "id": TRACK_A, "fields": { "rating": 4.5, "primary_genre": "Blues" }, "version": 0 },
+            { "id": TRACK_B, "fields": { "rating": null } },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{response}");
+    assert_eq!(response["applied"], true);
+    assert_eq!(response["results"][0]["version"], 1);
+    assert_eq!(response["results"][1]["version"], 1);
+    assert_eq!(tracks(&app).await, ["A|Blues|4.5|1", "B|Jazz|-|1"]);
+}
+
+#[tokio::test]
+async fn a_stale_version_rolls_back_the_whole_batch() {
+    let app = app();
+    patch(&app, json!([{ "id": TRACK_B, "fields": { "rating": 3 } }])).await;
+
+    let (status, response) = patch(
+        &app,
+        json!([
+            { "id": TRACK_A, "fields": { "rating": 5 } },
+            { "id": TRACK_B, "fields": { "rating": 1 }, "version": 0 },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(response["applied"], false);
+    assert!(response["results"][0]["error"].is_null());
+    assert_eq!(
+        response["results"][1]["error"],
+        "version conflict: the track is at version 1"
+    );
+    assert_eq!(tracks(&app).await, ["A|Rock|-|0", "B|Jazz|3.0|1"]);
+}
+
+#[tokio::test]
+async fn missing_tracks_roll_back_the_whole_batch() {
+    let app = app();
+    let (status, response) = patch(
+        &app,
+        json!([
+            { "id": TRACK_A, "fields": { "rating": 5 } },
+            { "id": "00000000-0000-0000-0000-00000000dead", "fields": { "rating": 1 } },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(
+        response["results"][1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("track not found")
+    );
+    assert_eq!(tracks(&app).await, ["A|Rock|-|0", "B|Jazz|2.0|0"]);
+}
+
+#[tokio::test]
+async fn only_editable_fields_are_accepted() {
+    let app = app();
+    let (status, response) = patch(
+        &app,
+        json!([
+            { "id": TRACK_A, "fields": { "rating": 5 } },
+            { "id": TRACK_B, "fields": { "file": "00000000-0000-0000-0000-000000000000" } },
+            { "id": TRACK_B, "fields": { "rating": "five" } },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(response["applied"], false);
+    assert!(response["results"][0]["error"].is_null());
+    assert_eq!(response["results"][1]["error"], "field not editable: file");
+    assert_eq!(
+        response["results"][2]["error"],
+        "rating: expected a number or null"
+    );
+    assert_eq!(tracks(&app).await, ["A|Rock|-|0", "B|Jazz|2.0|0"]);
+}
+
+#[tokio::test]
+async fn ratings_out_of_range_are_refused() {
+    let app = app();
+    let (status, response) =
+        patch(&app, json!([{ "id": TRACK_A, "fields": { "rating": 6 } }])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response["results"][0]["error"],
+        "rating: 6 is not between 0 and 5"
+    );
+
+    let (status, _) = rate(&app, TRACK_A, json!({ "rating": -1 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(tracks(&app).await, ["A|Rock|-|0", "B|Jazz|2.0|0"]);
+}
+
+#[tokio::test]
+async fn ratings_are_set_one_track_at_a_time() {
+    let app = app();
+    let (status, body) = rate(&app, TRACK_A, json!({ "rating": 3.5 })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let response: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        response,
+        json!({ "id": TRACK_A, "rating": 3.5, "version": 1 })
+    );
+
+    let (status, _) = rate(&app, TRACK_B, json!({ "rating": null })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tracks(&app).await, ["A|Rock|3.5|1", "B|Jazz|-|1"]);
+
+    let (status, body) = rate(
+        &app,
+        "00000000-0000-0000-0000-00000000dead",
+        json!({ "rating": 1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.starts_with("track not found"), "{body}");
+}
@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use backend::scanner::{self, ScanOptions};
+use common::{FIXTURE, TempDir};
+use duckdb::Connection;
+
+/// A fresh collection holding two files.
+fn collection() -> TempDir {
+    let dir = TempDir::new("unavailable");
+    dir.copy(FIXTURE, "a.flac");
+    dir.copy(FIXTURE, "b.flac");
+    dir
+}
+
+/// Touches `path`, so the next scan reads it again, and makes it unreadable.
+/// `false` when it can be read all the same, as by root.
+fn make_unreadable(path: &Path) -> bool {
+    let later = SystemTime::now() + Duration::from_secs(10);
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o000)).unwrap();
+    fs::File::open(path).is_err()
+}
+
+fn live_files(conn: &Connection) -> u32 {
+    conn.query_row(
+        "SELECT count(*) FROM file WHERE deletion IS NULL",
+        [],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn scans_stop_when_too_many_files_cant_be_read() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    fs::remove_file(dir.join("b.flac")).unwrap();
+    if !make_unreadable(&dir.join("a.flac")) {
+        return;
+    }
+
+    // One of one file is well over the default share.
+    let error = scanner::scan(&dir, &conn, ScanOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("--max-unreadable"), "{error}");
+    assert_eq!(live_files(&conn), 2);
+}
+
+#[test]
+fn unreadable_files_are_not_deleted() {
+    let dir = collection();
+    let conn = common::library();
+    scanner::scan(&dir, &conn, ScanOptions::default()).unwrap();
+    if !make_unreadable(&dir.join("a.flac")) {
+        return;
+    }
+
+    let options = ScanOptions {
+        max_unreadable: Some(100.0),
+        ..ScanOptions::default()
+    };
+    scanner::scan(&dir, &conn, options).unwrap();
+    assert_eq!(live_files(&conn), 2);
+    let failures = scanner::load_failures(&conn).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, "./a.flac");
+    assert_eq!(failures[0].category, "io");
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use backend::scanner::{self, ScanOptions};
+use common::{ALBUM, TempDir};
+use duckdb::Connection;
+
+/// A collection holding `album/duck.flac` and `album/hens.flac`.
+fn collection() -> TempDir {
+    let dir = TempDir::new("watch");
+    dir.copy(format!("{ALBUM}/01. Duck.flac"), "album/duck.flac");
+    dir.copy(format!("{ALBUM}/02. Hens.flac"), "album/hens.flac");
+    dir
+}
+
+fn scanned(dir: &Path) -> Connection {
+    let conn = common::library();
+    scanner::scan(dir, &conn, ScanOptions::default()).unwrap();
+    conn
+}
+
+/// Applies the changes at `paths`, as the watcher does once they settle.
+fn sync(dir: &Path, conn: &Connection, paths: &[PathBuf]) {
+    scanner::sync_paths(dir, paths, &ScanOptions::default(), |task| task(conn)).unwrap();
+}
+
+/// `(path, id)` of every live file, in path order.
+fn live_files(conn: &Connection) -> Vec<(String, String)> {
+    let mut stmt = conn
+        .prepare("SELECT path, id::text FROM file WHERE deletion IS NULL ORDER BY path")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn count(conn: &Connection, sql: &str) -> u32 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn a_temp_file_renamed_over_the_original_modifies_it() {
+    let dir = collection();
+    let conn = scanned(&dir);
+    let before = live_files(&conn);
+    let hash = |conn: &Connection| -> Vec<u8> {
+        conn.query_row(
+            "SELECT hash FROM file WHERE path = './album/duck.flac'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+    let old_hash = hash(&conn);
+
+    // As editors save: write a temp file beside the original, then rename it
+    // over the original. Both paths show up in the events.
+    let temp = dir.join("album/.duck.flac.tmp");
+    fs::copy(format!("{ALBUM}/03. Geese.flac"), &temp).unwrap();
+    fs::rename(&temp, dir.join("album/duck.flac")).unwrap();
+    sync(&dir, &conn, &[temp, dir.join("album/duck.flac")]);
+
+    assert_eq!(live_files(&conn), before);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 2);
+    assert_ne!(hash(&conn), old_hash);
+}
+
+#[test]
+fn a_moved_directory_moves_each_of_its_files() {
+    let dir = collection();
+    let conn = scanned(&dir);
+    let ids: Vec<String> = live_files(&conn).into_iter().map(|(_, id)| id).collect();
+
+    fs::rename(dir.join("album"), dir.join("renamed")).unwrap();
+    sync(&dir, &conn, &[dir.join("album"), dir.join("renamed")]);
+
+    let after = live_files(&conn);
+    assert_eq!(
+        after
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>(),
+        ["./renamed/duck.flac", "./renamed/hens.flac"]
+    );
+    assert_eq!(after.into_iter().map(|(_, id)| id).collect::<Vec<_>>(), ids);
+    assert_eq!(count(&conn, "SELECT count(*) FROM file"), 2);
+}
+
+#[test]
+fn a_removed_directory_deletes_its_files() {
+    let dir = collection();
+    dir.copy(format!("{ALBUM}/03. Geese.flac"), "other/geese.flac");
+    let conn = scanned(&dir);
+
+    fs::remove_dir_all(dir.join("album")).unwrap();
+    sync(&dir, &conn, &[dir.join("album")]);
+
+    assert_eq!(
+        live_files(&conn)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>(),
+        ["./other/geese.flac"]
+    );
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT count(*) FROM file WHERE deletion IS NOT NULL"
+        ),
+        2
+    );
+}
+
+#[test]
+fn files_outside_the_changed_paths_are_left_alone() {
+    let dir = collection();
+    let conn = scanned(&dir);
+
+    // Removed behind the watcher's back: nothing names the file, so it stays.
+    fs::remove_file(dir.join("album/hens.flac")).unwrap();
+    dir.copy(format!("{ALBUM}/03. Geese.flac"), "geese.flac");
+    sync(&dir, &conn, &[dir.join("geese.flac")]);
+
+    assert_eq!(
+        live_files(&conn)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>(),
+        ["./album/duck.flac", "./album/hens.flac", "./geese.flac"]
+    );
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use arrow_ipc::reader::StreamReader;
+use backend::{server, ws};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Serves a library on an ephemeral port and opens a socket to its `/ws`.
+async fn connect() -> Socket {
+    let conn = common::library();
+    let app = server::router(server::app_state(conn, std::env::temp_dir()));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+        .await
+        .unwrap();
+    socket
+}
+
+/// Sends `sql`, then reads the answer up to its first text message: the bytes
+/// of the binary messages before it, and its text.
+async fn ask(socket: &mut Socket, sql: &str) -> (Vec<u8>, String) {
+    socket.send(Message::Text(sql.into())).await.unwrap();
+    let mut ipc = Vec::new();
+    loop {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Binary(bytes) => ipc.extend_from_slice(&bytes),
+            Message::Text(text) => return (ipc, text.to_string()),
+            _ => {}
+        }
+    }
+}
+
+async fn next_text(socket: &mut Socket) -> String {
+    match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => text.to_string(),
+        message => panic!("expected a text message, got {message:?}"),
+    }
+}
+
+#[tokio::test]
+async fn rows_come_as_an_arrow_stream_then_done() {
+    let mut socket = connect().await;
+    for _ in 0..2 {
+        let (ipc, text) = ask(&mut socket, "SELECT range AS n FROM range(3)").await;
+        assert_eq!(text, ws::DONE);
+        let rows: usize = StreamReader::try_new(ipc.as_slice(), None)
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum();
+        assert_eq!(rows, 3);
+    }
+}
+
+#[tokio::test]
+async fn statements_report_the_rows_affected() {
+    let mut socket = connect().await;
+    let (ipc, text) = ask(&mut socket, "CREATE TABLE t (n INTEGER)").await;
+    assert!(ipc.is_empty());
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json["rows_affected"], 0);
+    assert_eq!(next_text(&mut socket).await, ws::DONE);
+}
+
+#[tokio::test]
+async fn failed_queries_answer_their_error() {
+    let mut socket = connect().await;
+    let (ipc, text) = ask(&mut socket, "SELECT * FROM missing").await;
+    assert!(ipc.is_empty());
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json["kind"], "catalog");
+
+    // The socket still takes queries.
+    let (_, text) = ask(&mut socket, "SELECT 1").await;
+    assert_eq!(text, ws::DONE);
+}
+
+#[tokio::test]
+async fn large_results_come_in_several_chunks() {
+    let mut socket = connect().await;
+    let sql = "SELECT repeat('x', 1000) AS s FROM range(5000)";
+    socket.send(Message::Text(sql.into())).await.unwrap();
+    let mut chunks = 0;
+    let mut ipc = Vec::new();
+    loop {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Binary(bytes) => {
+                chunks += 1;
+                ipc.extend_from_slice(&bytes);
+            }
+            Message::Text(text) => {
+                assert_eq!(text.as_str(), ws::DONE);
+                break;
+            }
+            _ => {}
+        }
+    }
+    // Some 5MB, flushed every 256KB or so.
+    assert!(ipc.len() > 5_000_000);
+    assert!(chunks > 10, "{chunks} chunks");
+    let rows: usize = StreamReader::try_new(ipc.as_slice(), None)
+        .unwrap()
+        .map(|batch| batch.unwrap().num_rows())
+        .sum();
+    assert_eq!(rows, 5000);
+}
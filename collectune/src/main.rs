@@ -1,10 +1,9 @@
 use axum::Router;
 use axum::http::{StatusCode, Uri, header};
 use axum::response::{IntoResponse, Response};
-use backend::{db, scanner, server};
+use backend::cli::{Command, LogOptions, ServerArgs};
 use clap::Parser;
 use rust_embed::Embed;
-use std::path::{Path, PathBuf};
 
 #[derive(Embed)]
 #[folder = "../frontend/dist/"]
@@ -12,33 +11,17 @@ struct Assets;
 
 #[derive(Parser)]
 #[command(name = "collectune")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 #[command(about = "Collectune — manage and play your audio collection")]
 struct Args {
-    /// Path to the collection of audio files
-    collection_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Start without running a full collection scan
-    #[arg(long)]
-    no_scan: bool,
+    #[command(flatten)]
+    server: ServerArgs,
 
-    /// Path to the database file (defaults to `collectune.db` in the collection root)
-    #[arg(long)]
-    db_path: Option<PathBuf>,
-
-    /// Port to listen on
-    #[arg(short, long, default_value_t = 3000)]
-    port: u16,
-}
-
-fn get_collection_path(path_str: &str) -> Result<&Path, String> {
-    let path = Path::new(path_str);
-    if !path.exists() {
-        return Err(format!("The path '{path_str}' does not exist."));
-    }
-    if !path.is_dir() {
-        return Err(format!("The path '{path_str}' is not a directory."));
-    }
-    Ok(path)
+    #[command(flatten)]
+    log: LogOptions,
 }
 
 async fn static_handler(uri: Uri) -> Response {
@@ -57,23 +40,11 @@ async fn static_handler(uri: Uri) -> Response {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let collection_path = get_collection_path(&args.collection_path)?;
-    let db_path = args
-        .db_path
-        .unwrap_or_else(|| db::default_db_path(collection_path));
-    let conn = db::get_db(&db_path)?;
-    if !args.no_scan {
-        scanner::scan(collection_path, &conn)?;
+    args.log.init();
+    if let Some(command) = &args.command {
+        return command.run();
     }
-    let state = server::app_state(conn, collection_path.to_path_buf());
-
-    let app = Router::new()
-        .nest("/api", server::router(state))
-        .fallback(static_handler);
-
-    let addr = format!("0.0.0.0:{}", args.port);
-    println!("Listening on {addr}");
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
-    Ok(())
+    args.server
+        .run(|api| Router::new().nest("/api", api).fallback(static_handler))
+        .await
 }
@@ -34,7 +34,10 @@ pub(crate) enum FontSize {
 /// Horizontal alignment of a column's text within its cell.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub(crate) enum TextAlign {
+    /// Right for numeric columns, so their digits line up, and left otherwise.
+    /// Resolved against the result's schema by [`TextAlign::for_column`].
     #[default]
+    Auto,
     Left,
     Right,
     Center,
@@ -131,9 +134,22 @@ fn resolve_font_size(value: Option<&str>) -> FontSize {
 
 fn resolve_text_align(value: Option<&str>) -> TextAlign {
     match value {
+        Some("left") => TextAlign::Left,
         Some("right") => TextAlign::Right,
         Some("center") => TextAlign::Center,
-        _ => TextAlign::Left,
+        _ => TextAlign::Auto,
+    }
+}
+
+impl TextAlign {
+    /// The alignment to draw a column with, given whether its values are numbers.
+    /// Only [`TextAlign::Auto`] depends on that.
+    pub(crate) fn for_column(self, numeric: bool) -> Self {
+        match self {
+            TextAlign::Auto if numeric => TextAlign::Right,
+            TextAlign::Auto => TextAlign::Left,
+            align => align,
+        }
     }
 }
 
@@ -226,7 +242,17 @@ mod tests {
         ]);
         let m = ColumnMetadata::from_meta(Some(&meta));
         assert_eq!(m.font_color, FontColor::Default);
-        assert_eq!(m.text_align, TextAlign::Left);
+        assert_eq!(m.text_align, TextAlign::Auto);
+    }
+
+    #[test]
+    fn auto_alignment_right_aligns_numbers() {
+        assert_eq!(TextAlign::Auto.for_column(true), TextAlign::Right);
+        assert_eq!(TextAlign::Auto.for_column(false), TextAlign::Left);
+        // An explicit alignment wins over the column's type.
+        let meta = object(vec![("align", AnnotationValue::String("left".to_string()))]);
+        let m = ColumnMetadata::from_meta(Some(&meta));
+        assert_eq!(m.text_align.for_column(true), TextAlign::Left);
     }
 
     #[test]
@@ -24,6 +24,7 @@ use crate::query_def::{CompileSource, QuerySections};
 const PRELUDE: &str = r"#track.firstplay = #play.timestamp%min
 #track.lastplay = #play.timestamp%max
 #track.artists = #artist.name%list(\\name)
+#track.genres = #genre.name%list(\\name)
 #track.year = album.year
 #track.added = file.added
 #track.duration = file.duration
@@ -31,7 +32,7 @@ const PRELUDE: &str = r"#track.firstplay = #play.timestamp%min
 #track.number = track_number
 #track.__querydown_default_text_search:@x = [
   title:@x
-  genre:@x
+  ++#genre{name:@x}
   album.title:@x
   ++#artist{name:@x}
 ]
@@ -0,0 +1,168 @@
+//! The backend linked into the app (the `embedded` feature), so a desktop build
+//! needs no server: once [`install`]ed, queries and RPC calls run in-process on
+//! a background thread instead of going over HTTP.
+//!
+//! The backend still hands query results over as an Arrow IPC stream. Its
+//! version of Arrow differs from ours, so its `RecordBatch`es can't be used
+//! directly; decoding the stream from memory is cheap next to a round trip.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use arrow_array::RecordBatch;
+use arrow_ipc::reader::StreamDecoder;
+use backend::query::{self, QueryParams, Ready, SortDir};
+use backend::server::AppState;
+use bytes::Bytes;
+use serde_json::Value;
+
+use crate::http::{Outcome, RESULT_PAGE_SIZE, ResultSort, feed_decoder};
+use crate::query_error::QueryFailure;
+
+static BACKEND: OnceLock<Arc<AppState>> = OnceLock::new();
+
+/// Runs the app's queries against `state` from now on, rather than against the
+/// server. Only the first call has any effect.
+pub fn install(state: Arc<AppState>) {
+    let _ = BACKEND.set(state);
+}
+
+/// The installed backend, if any.
+pub(crate) fn backend() -> Option<&'static AppState> {
+    BACKEND.get().map(Arc::as_ref)
+}
+
+/// Decodes the IPC stream the backend writes, handing each batch to `handler`
+/// as soon as it's complete.
+struct BatchWriter<H> {
+    decoder: StreamDecoder,
+    handler: H,
+    /// The first error `handler` returned, which ends the stream.
+    error: Option<String>,
+}
+
+impl<H> Write for BatchWriter<H>
+where
+    H: FnMut(&RecordBatch) -> Result<(), String>,
+{
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let chunk = Bytes::copy_from_slice(data);
+        if let Err(e) = feed_decoder(&mut self.decoder, chunk, &mut self.handler) {
+            self.error = Some(e.clone());
+            return Err(io::Error::other(e));
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `query` against `state` as `POST /query` would, blocking until all of
+/// its rows have gone through `handler`. With a `page`, only the rows from that
+/// one on are fetched, [`RESULT_PAGE_SIZE`] at most, and their total is
+/// returned with them. Setting `cancel` interrupts it.
+pub(crate) fn run_query<H>(
+    state: &AppState,
+    query: &str,
+    sort: Option<&ResultSort>,
+    page: Option<u64>,
+    cancel: &AtomicBool,
+    handler: H,
+) -> Result<Outcome, QueryFailure>
+where
+    H: FnMut(&RecordBatch) -> Result<(), String>,
+{
+    let params = QueryParams {
+        order_by: sort.map(|sort| sort.column.clone()),
+        dir: if sort.is_some_and(|sort| sort.descending) {
+            SortDir::Desc
+        } else {
+            SortDir::Asc
+        },
+        limit: page.map(|_| RESULT_PAGE_SIZE),
+        offset: page,
+        ..QueryParams::default()
+    };
+    let mut ready = None;
+    let mut out = BatchWriter {
+        decoder: StreamDecoder::new(),
+        handler,
+        error: None,
+    };
+    let streamed = query::run(
+        state,
+        query,
+        &params,
+        || cancel.load(Ordering::Relaxed),
+        |outcome| ready = Some(outcome),
+        &mut out,
+    );
+    // The message quotes where the query failed, as the server's would.
+    let outcome = match ready.unwrap_or_else(|| Err("query reported no result".to_string()))? {
+        Ready::Rows { total } => Outcome::Rows { total },
+        Ready::RowsAffected(n) => Outcome::RowsAffected(n as u64),
+    };
+    out.error
+        .map_or(streamed, Err)
+        .map(|()| outcome)
+        .map_err(QueryFailure::from)
+}
+
+/// Calls the RPC `method` on `state` as `POST /rpc` would.
+pub(crate) fn call(state: &AppState, method: &str, params: Value) -> Result<Value, String> {
+    backend::rpc::dispatch(state, method, params)
+}
+
+/// Rates track `id` on `state` as `POST /tracks/{id}/rating` would, returning
+/// the rating stored.
+pub(crate) fn set_rating(
+    state: &AppState,
+    id: &str,
+    rating: Option<f64>,
+) -> Result<Option<f64>, String> {
+    let rated = backend::tracks::set_rating(state, id, rating)?
+        .ok_or_else(|| format!("track not found: {id}"))?;
+    Ok(rated.rating.map(f64::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::AtomicBool;
+
+    use backend::db;
+    use backend::server;
+
+    use super::run_query;
+    use crate::http::Outcome;
+
+    #[test]
+    fn statements_other_than_queries_run_on_the_first_page() {
+        let conn = db::get_db(Path::new(":memory:")).unwrap();
+        let state = server::app_state(conn, std::env::temp_dir());
+        let run = |sql: &str| {
+            let mut rows = 0;
+            let cancel = AtomicBool::new(false);
+            let outcome = run_query(&state, sql, None, Some(0), &cancel, |batch| {
+                rows += batch.num_rows();
+                Ok(())
+            });
+            (outcome.map_err(|e| e.message), rows)
+        };
+        assert_eq!(
+            run("CREATE TABLE t (n INTEGER)"),
+            (Ok(Outcome::RowsAffected(0)), 0)
+        );
+        assert_eq!(
+            run("INSERT INTO t VALUES (1), (2)"),
+            (Ok(Outcome::RowsAffected(2)), 0)
+        );
+        assert_eq!(
+            run("EXPLAIN SELECT n FROM t").0,
+            Ok(Outcome::Rows { total: None })
+        );
+    }
+}
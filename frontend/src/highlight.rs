@@ -0,0 +1,298 @@
+//! Syntax highlighting for SQL: keywords, quoted strings, numbers and comments
+//! each get a color of their own, picked to suit the light or dark visuals.
+//!
+//! The tokenizer is deliberately small. It only tells those four kinds of token
+//! apart from the rest, which is all the coloring needs, and runs in one pass
+//! over the text. The laid-out job is cached per text, so a long query isn't
+//! tokenized again every frame.
+
+use std::ops::Range;
+
+use eframe::egui;
+use egui::text::LayoutJob;
+
+/// The SQL keywords that are highlighted, in upper case and sorted for
+/// [`slice::binary_search`].
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "ALTER",
+    "AND",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CREATE",
+    "CROSS",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "FALSE",
+    "FILTER",
+    "FIRST",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LAST",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "QUALIFY",
+    "RECURSIVE",
+    "RIGHT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+/// What a piece of SQL is, as far as its color goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+}
+
+/// Splits `sql` into the byte ranges of its tokens, in order and covering all of
+/// it. Runs of plain text are kept as one range.
+pub(crate) fn tokenize(sql: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut tokens: Vec<(TokenKind, Range<usize>)> = Vec::new();
+    let mut start = 0;
+    while let Some(c) = sql[start..].chars().next() {
+        let rest = &sql[start..];
+        let (kind, len) = if rest.starts_with("--") {
+            (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("/*") {
+            (
+                TokenKind::Comment,
+                rest.find("*/").map_or(rest.len(), |at| at + 2),
+            )
+        } else if c == '\'' {
+            (TokenKind::String, quoted_len(rest, '\''))
+        } else if c == '"' {
+            // A quoted identifier: kept whole so the words in it aren't keywords.
+            (TokenKind::Plain, quoted_len(rest, '"'))
+        } else if c.is_ascii_digit() {
+            (TokenKind::Number, number_len(rest))
+        } else if is_word_char(c) {
+            let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let word = rest[..len].to_ascii_uppercase();
+            let kind = if KEYWORDS.binary_search(&word.as_str()).is_ok() {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Plain
+            };
+            (kind, len)
+        } else {
+            (TokenKind::Plain, c.len_utf8())
+        };
+        let end = start + len;
+        match tokens.last_mut() {
+            Some((TokenKind::Plain, range)) if kind == TokenKind::Plain => range.end = end,
+            _ => tokens.push((kind, start..end)),
+        }
+        start = end;
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The length of the `quote`d text `text` starts with, quotes included. A
+/// doubled quote stands for one inside it; an unclosed one runs to the end.
+fn quoted_len(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((at, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().is_some_and(|(_, next)| *next == quote) {
+                chars.next();
+            } else {
+                return at + 1;
+            }
+        }
+    }
+    text.len()
+}
+
+/// The length of the number `text` starts with, with its fraction if any.
+fn number_len(text: &str) -> usize {
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let whole = digits(text);
+    match text[whole..].strip_prefix('.') {
+        Some(fraction) if fraction.starts_with(|c: char| c.is_ascii_digit()) => {
+            whole + 1 + digits(fraction)
+        }
+        _ => whole,
+    }
+}
+
+/// The colors of each kind of token.
+#[derive(Clone, Copy, Hash)]
+struct Palette {
+    plain: egui::Color32,
+    keyword: egui::Color32,
+    string: egui::Color32,
+    number: egui::Color32,
+    comment: egui::Color32,
+}
+
+impl Palette {
+    fn new(visuals: &egui::Visuals) -> Self {
+        let (keyword, string, number) = if visuals.dark_mode {
+            (
+                egui::Color32::from_rgb(0x7A, 0xB8, 0xFF),
+                egui::Color32::from_rgb(0xA5, 0xD6, 0x77),
+                egui::Color32::from_rgb(0xF0, 0xA0, 0x5A),
+            )
+        } else {
+            (
+                egui::Color32::from_rgb(0x1F, 0x5F, 0xBF),
+                egui::Color32::from_rgb(0x2E, 0x7D, 0x32),
+                egui::Color32::from_rgb(0xB3, 0x50, 0x00),
+            )
+        };
+        Self {
+            plain: visuals.text_color(),
+            keyword,
+            string,
+            number,
+            comment: visuals.weak_text_color(),
+        }
+    }
+
+    fn color(&self, kind: TokenKind) -> egui::Color32 {
+        match kind {
+            TokenKind::Plain => self.plain,
+            TokenKind::Keyword => self.keyword,
+            TokenKind::String => self.string,
+            TokenKind::Number => self.number,
+            TokenKind::Comment => self.comment,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Highlighter;
+
+impl egui::cache::ComputerMut<(Palette, &egui::FontId, &str), LayoutJob> for Highlighter {
+    fn compute(&mut self, (palette, font_id, sql): (Palette, &egui::FontId, &str)) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        for (kind, range) in tokenize(sql) {
+            let format = egui::TextFormat::simple(font_id.clone(), palette.color(kind));
+            job.append(&sql[range], 0.0, format);
+        }
+        job
+    }
+}
+
+type HighlightCache = egui::cache::FrameCache<LayoutJob, Highlighter>;
+
+/// `sql` in the monospace font, colored for `ui`'s visuals.
+pub(crate) fn highlight(ui: &egui::Ui, sql: &str) -> LayoutJob {
+    let palette = Palette::new(ui.visuals());
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    ui.ctx().memory_mut(|memory| {
+        memory
+            .caches
+            .cache::<HighlightCache>()
+            .get((palette, &font_id, sql))
+            .clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KEYWORDS, TokenKind, tokenize};
+
+    /// The tokens of `sql` other than plain text, as text.
+    fn marked(sql: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(sql)
+            .into_iter()
+            .filter(|(kind, _)| *kind != TokenKind::Plain)
+            .map(|(kind, range)| (kind, &sql[range]))
+            .collect()
+    }
+
+    #[test]
+    fn keywords_are_sorted_for_binary_search() {
+        assert!(KEYWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn tokens_cover_the_whole_text() {
+        let sql = "SELECT \"select\", 'it''s' -- note\nFROM t WHERE n > 1.5 /* é */";
+        let tokens = tokenize(sql);
+        let mut end = 0;
+        for (_, range) in &tokens {
+            assert_eq!(range.start, end);
+            end = range.end;
+        }
+        assert_eq!(end, sql.len());
+    }
+
+    #[test]
+    fn each_kind_is_told_apart() {
+        use TokenKind::{Comment, Keyword, Number, String};
+        assert_eq!(
+            marked("select \"from\", 'it''s', n1, 42, 1.5 -- a 'comment'\nFrom t /* x */"),
+            [
+                (Keyword, "select"),
+                (String, "'it''s'"),
+                (Number, "42"),
+                (Number, "1.5"),
+                (Comment, "-- a 'comment'"),
+                (Keyword, "From"),
+                (Comment, "/* x */"),
+            ]
+        );
+        // Unclosed strings and comments run to the end.
+        assert_eq!(
+            marked("SELECT 'abc"),
+            [(Keyword, "SELECT"), (String, "'abc")]
+        );
+        assert_eq!(marked("1. /* x"), [(Number, "1"), (Comment, "/* x")]);
+    }
+}
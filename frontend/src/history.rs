@@ -0,0 +1,274 @@
+//! The history of queries run: every definition that ran without error, newest
+//! first, so an earlier iteration of a query can be brought back after it's
+//! been edited away. Persisted across sessions in eframe storage (on native,
+//! a file in the user's config directory).
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::App;
+use crate::query_def::QueryDefinition;
+
+/// The eframe storage key the history is persisted under.
+pub(crate) const STORAGE_KEY: &str = "query_history";
+
+/// How many entries the history keeps unless the user changes it.
+const DEFAULT_LIMIT: usize = 50;
+/// The most entries the history can be set to keep.
+const MAX_LIMIT: usize = 1000;
+/// Width of the history's entries; longer SQL previews are truncated.
+const PREVIEW_WIDTH: f32 = 420.0;
+
+/// One successful run of a query.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HistoryEntry {
+    /// The query's name when it ran.
+    pub(crate) name: String,
+    /// When it ran, in epoch seconds.
+    pub(crate) ran_at: i64,
+    pub(crate) definition: QueryDefinition,
+    /// The SQL it compiled to, shown as a preview of the entry.
+    pub(crate) sql: String,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct QueryHistory {
+    /// Newest first.
+    pub(crate) entries: Vec<HistoryEntry>,
+    /// How many entries to keep; older ones are dropped.
+    pub(crate) limit: usize,
+}
+
+impl Default for QueryHistory {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl QueryHistory {
+    /// Adds `entry` as the newest. Running the same definition again in a row
+    /// only moves the newest entry's time forward, so re-running a query doesn't
+    /// flood the history.
+    pub(crate) fn record(&mut self, entry: HistoryEntry) {
+        match self.entries.first_mut() {
+            Some(newest) if newest.definition == entry.definition => *newest = entry,
+            _ => self.entries.insert(0, entry),
+        }
+        self.entries.truncate(self.limit);
+    }
+
+    /// Keeps at most `limit` entries from now on, dropping the oldest beyond it.
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.clamp(1, MAX_LIMIT);
+        self.entries.truncate(self.limit);
+    }
+}
+
+/// `ran_at` as local time with minute precision, e.g. `2026-06-04 15:30`.
+fn format_ran_at(ran_at: i64) -> String {
+    jiff::Timestamp::from_second(ran_at).map_or_else(
+        |_| String::new(),
+        |t| {
+            t.to_zoned(jiff::tz::TimeZone::system())
+                .strftime("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    )
+}
+
+/// `sql` with its whitespace collapsed, to preview an entry on one line.
+fn preview(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl App {
+    /// Moves the runs that have finished since the last frame into the history:
+    /// those without an error are recorded, the others forgotten.
+    pub(crate) fn record_finished_runs(&mut self) {
+        for page in &self.pages {
+            let mut s = page.results.lock().unwrap();
+            if s.running || s.history_entry.is_none() {
+                continue;
+            }
+            let entry = s.history_entry.take();
+            if s.error.is_none()
+                && let Some(entry) = entry
+            {
+                self.history.record(entry);
+            }
+        }
+    }
+
+    /// Loads the definition of history entry `index` into the current query (or a
+    /// new one, when no query is open) and runs it.
+    fn load_history_entry(&mut self, index: usize, ctx: &egui::Context) {
+        let Some(definition) = self
+            .history
+            .entries
+            .get(index)
+            .map(|entry| entry.definition.clone())
+        else {
+            return;
+        };
+        if self.current_page().is_none() {
+            self.add_query_page();
+        }
+        if let Some(page) = self.current_page_mut() {
+            page.live.definition = definition;
+        }
+        self.run_query(ctx);
+    }
+
+    /// The history modal: the queries run, newest first, each loaded back into the
+    /// current query on click, and how many of them to keep.
+    pub(crate) fn render_history_modal(&mut self, ctx: &egui::Context) {
+        if !self.history_open {
+            return;
+        }
+        let mut load = None;
+        let mut clear = false;
+        let mut close = false;
+        let mut limit = self.history.limit;
+        let modal = egui::Modal::new(egui::Id::new("query_history")).show(ctx, |ui| {
+            ui.set_width(PREVIEW_WIDTH + 24.0);
+            ui.heading("History");
+            ui.add_space(8.0);
+            if self.history.entries.is_empty() {
+                ui.weak("Queries you run show up here.");
+            }
+            egui::ScrollArea::vertical()
+                .max_height(360.0)
+                .auto_shrink([false, true])
+                .show(ui, |ui| {
+                    for (i, entry) in self.history.entries.iter().enumerate() {
+                        let response = ui
+                            .vertical(|ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label(&entry.name);
+                                    ui.weak(format_ran_at(entry.ran_at));
+                                });
+                                ui.add(
+                                    egui::Label::new(
+                                        egui::RichText::new(preview(&entry.sql)).monospace().weak(),
+                                    )
+                                    .truncate(),
+                                );
+                            })
+                            .response
+                            .interact(egui::Sense::click())
+                            .on_hover_cursor(egui::CursorIcon::PointingHand);
+                        if response.clicked() {
+                            load = Some(i);
+                        }
+                        ui.separator();
+                    }
+                });
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                ui.label("Keep the last");
+                ui.add(egui::DragValue::new(&mut limit).range(1..=MAX_LIMIT));
+                ui.label("queries");
+            });
+            ui.add_space(12.0);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Close").clicked() {
+                    close = true;
+                }
+                if ui
+                    .add_enabled(!self.history.entries.is_empty(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    clear = true;
+                }
+            });
+        });
+
+        if limit != self.history.limit {
+            self.history.set_limit(limit);
+        }
+        if clear {
+            self.history.entries.clear();
+        }
+        if let Some(index) = load {
+            self.history_open = false;
+            self.load_history_entry(index, ctx);
+        } else if close || modal.should_close() {
+            self.history_open = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(base: &str, ran_at: i64) -> HistoryEntry {
+        HistoryEntry {
+            name: "q".to_string(),
+            ran_at,
+            definition: QueryDefinition {
+                base: base.to_string(),
+                ..QueryDefinition::default()
+            },
+            sql: format!("SELECT * FROM {base}"),
+        }
+    }
+
+    /// `(base, ran_at)` of each entry, newest first.
+    fn runs(history: &QueryHistory) -> Vec<(&str, i64)> {
+        history
+            .entries
+            .iter()
+            .map(|e| (e.definition.base.as_str(), e.ran_at))
+            .collect()
+    }
+
+    #[test]
+    fn newest_entries_come_first() {
+        let mut history = QueryHistory::default();
+        history.record(entry("track", 1));
+        history.record(entry("album", 2));
+        assert_eq!(runs(&history), [("album", 2), ("track", 1)]);
+    }
+
+    #[test]
+    fn consecutive_duplicates_are_recorded_once() {
+        let mut history = QueryHistory::default();
+        history.record(entry("track", 1));
+        history.record(entry("track", 2));
+        assert_eq!(runs(&history), [("track", 2)]);
+        // Only consecutive runs are merged.
+        history.record(entry("album", 3));
+        history.record(entry("track", 4));
+        assert_eq!(runs(&history), [("track", 4), ("album", 3), ("track", 2)]);
+    }
+
+    #[test]
+    fn the_limit_drops_the_oldest_entries() {
+        let mut history = QueryHistory::default();
+        for (i, base) in (0..).zip(["a", "b", "c"]) {
+            history.record(entry(base, i));
+        }
+        history.set_limit(2);
+        assert_eq!(runs(&history), [("c", 2), ("b", 1)]);
+        history.record(entry("d", 3));
+        assert_eq!(runs(&history), [("d", 3), ("c", 2)]);
+        history.set_limit(0);
+        assert_eq!(history.limit, 1);
+    }
+
+    #[test]
+    fn history_deserializes_with_missing_fields() {
+        let history: QueryHistory = serde_json::from_str(r#"{"entries":[]}"#).unwrap();
+        assert_eq!(history.limit, DEFAULT_LIMIT);
+    }
+
+    #[test]
+    fn preview_fits_the_sql_on_one_line() {
+        assert_eq!(preview("SELECT a,\n  b\nFROM t"), "SELECT a, b FROM t");
+    }
+}
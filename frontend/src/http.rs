@@ -1,34 +1,218 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use arrow_array::{
     Array, ArrayRef, LargeListArray, LargeStringArray, ListArray, RecordBatch, StringArray,
 };
 use arrow_buffer::Buffer;
-use arrow_cast::display::{ArrayFormatter, FormatOptions};
 use arrow_ipc::reader::StreamDecoder;
 use bytes::Bytes;
 use eframe::egui;
 
 use crate::QueryState;
 use crate::now_playing::CurrentTrack;
+use crate::query_error::{ErrorLocation, QueryFailure};
+use crate::settings::DisplaySettings;
 
+/// Where the web UI reaches the API: under the server it was loaded from.
 #[cfg(target_arch = "wasm32")]
-pub(crate) const BASE: &str = "/api";
+const WEB_BASE: &str = "/api";
+/// The server the native UI talks to until the user sets another.
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) const BASE: &str = "http://localhost:3000";
+pub(crate) const DEFAULT_SERVER_URL: &str = "http://localhost:3000";
 
-pub(crate) fn run_query(query: String, state: &Arc<Mutex<QueryState>>, ctx: &egui::Context) {
+/// The server set with [`set_server_url`], or empty for [`DEFAULT_SERVER_URL`].
+static SERVER_URL: Mutex<String> = Mutex::new(String::new());
+
+/// Sends the native UI's requests to `url` (a URL [`parse_server_url`]
+/// accepted) from now on. The web UI always talks to the server it was loaded
+/// from.
+pub(crate) fn set_server_url(url: &str) {
+    url.clone_into(&mut SERVER_URL.lock().unwrap());
+}
+
+/// The base URL of the API, which request paths are appended to.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn base_url() -> String {
+    let url = SERVER_URL.lock().unwrap();
+    if url.is_empty() {
+        DEFAULT_SERVER_URL.to_string()
+    } else {
+        url.clone()
+    }
+}
+
+/// The base URL of the API, which request paths are appended to.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn base_url() -> String {
+    WEB_BASE.to_string()
+}
+
+/// Checks that `url` can be a server's base URL: `http` or `https`, a host with
+/// an optional numeric port, and an optional path prefix (for a server behind a
+/// reverse proxy). Returns it without surrounding whitespace or a trailing `/`.
+pub(crate) fn parse_server_url(url: &str) -> Result<String, String> {
+    let url = url.trim().trim_end_matches('/');
+    let invalid = |reason: &str| Err(format!("Invalid server URL \"{url}\": {reason}"));
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return invalid("it must start with http:// or https://");
+    };
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return invalid("it must start with http:// or https://");
+    }
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = match authority.rsplit_once(':') {
+        // An IPv6 address's colons are inside its brackets.
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (authority, None),
+    };
+    if host.is_empty() {
+        return invalid("it has no host");
+    }
+    if url.chars().any(char::is_whitespace) || authority.contains(['@', '?', '#']) {
+        return invalid("it isn't a plain server address");
+    }
+    if port.is_some_and(|port| port.parse::<u16>().is_err()) {
+        return invalid("its port isn't a number up to 65535");
+    }
+    Ok(url.to_string())
+}
+
+/// Describes a request that got no response, telling a server that couldn't be
+/// reached apart from one that answered with an error.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn request_error(e: &reqwest::Error) -> String {
+    // The innermost error says why: "Connection refused", a failed DNS lookup…
+    let cause = std::iter::successors(std::error::Error::source(e), |e| e.source())
+        .last()
+        .map_or_else(|| e.to_string(), ToString::to_string);
+    if e.is_connect() {
+        format!("Couldn't connect to the server at {}: {cause}", base_url())
+    } else if e.is_timeout() {
+        format!("The server at {} didn't respond in time", base_url())
+    } else {
+        format!("Request to the server at {} failed: {cause}", base_url())
+    }
+}
+
+/// A sort of the current results by one of their columns, which the server applies
+/// to the whole result so it doesn't just reorder the rows fetched so far.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ResultSort {
+    /// The result column's name, as reported in the Arrow schema.
+    pub(crate) column: String,
+    pub(crate) descending: bool,
+}
+
+/// How many result rows are fetched at a time; the results panel pages through
+/// the rest.
+pub(crate) const RESULT_PAGE_SIZE: u64 = 1000;
+
+/// The response header in which the server gives a paged query's total rows.
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// How often a running query checks whether it has been cancelled.
+const CANCEL_POLL_MS: u32 = 50;
+
+/// `text` escaped for a URL's query string.
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(b));
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded
+}
+
+/// The URL of the file at library path `path` (like `./Artist/01.flac`).
+pub(crate) fn file_url(path: &str) -> String {
+    format!("{}/files/stream?path={}", base_url(), percent_encode(path))
+}
+
+/// Fetches the whole file at library path `path` from the server, blocking
+/// until it's in.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn fetch_file(path: &str) -> Result<Vec<u8>, String> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("build tokio runtime");
+    rt.block_on(async {
+        let resp = reqwest::get(file_url(path))
+            .await
+            .map_err(|e| request_error(&e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let msg = resp.text().await.unwrap_or_default();
+            return Err(format!("{status}: {msg}"));
+        }
+        let bytes = resp.bytes().await.map_err(|e| request_error(&e))?;
+        Ok(bytes.to_vec())
+    })
+}
+
+/// The `/query` URL, with the parameters that make the server apply `sort` and
+/// return the page of [`RESULT_PAGE_SIZE`] rows starting at row `page`, if any.
+fn query_url(sort: Option<&ResultSort>, page: Option<u64>) -> String {
+    // Errors are asked for as JSON, for their position, so the rows have to be
+    // asked for as Arrow explicitly.
+    let mut params = vec!["format=arrow".to_string()];
+    if let Some(sort) = sort {
+        let column = percent_encode(&sort.column);
+        let dir = if sort.descending { "desc" } else { "asc" };
+        params.push(format!("order_by={column}&dir={dir}"));
+    }
+    if let Some(offset) = page {
+        params.push(format!("limit={RESULT_PAGE_SIZE}&offset={offset}"));
+    }
+    format!("{}/query?{}", base_url(), params.join("&"))
+}
+
+/// Runs `query` sorted by `sort`, filling `state` with the page of its rows
+/// starting at row `offset`, and their total. A query still running into
+/// `state` is cancelled. Once this one is cancelled in turn (see
+/// [`QueryState::cancel`]), it stops touching `state`.
+pub(crate) fn run_query(
+    query: String,
+    sort: Option<&ResultSort>,
+    offset: u64,
+    state: &Arc<Mutex<QueryState>>,
+    settings: &DisplaySettings,
+    ctx: &egui::Context,
+) {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let previous = std::mem::replace(&mut state.lock().unwrap().cancel, Arc::clone(&cancel));
+    previous.store(true, Ordering::Relaxed);
     let handler = {
         let state = Arc::clone(state);
+        let settings = settings.clone();
         let ctx = ctx.clone();
-        move |batch: &RecordBatch| push_batch(batch, &state, &ctx)
+        let cancel = Arc::clone(&cancel);
+        move |batch: &RecordBatch| {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(CANCELLED.to_string());
+            }
+            push_batch(batch, &settings, &state, &ctx)
+        }
     };
     let state_done = Arc::clone(state);
     let ctx_done = ctx.clone();
-    let on_done = move |result: Result<(), String>| finish(result, &state_done, &ctx_done);
-    stream_query(query, handler, on_done);
+    let cancel_done = Arc::clone(&cancel);
+    let on_done = move |result: Result<Outcome, QueryFailure>| {
+        if !cancel_done.load(Ordering::Relaxed) {
+            finish(result, &state_done, &ctx_done);
+        }
+    };
+    stream_query(sort, Some(offset), query, cancel, handler, on_done);
 }
 
+/// The error of a query that was cancelled.
+pub(crate) const CANCELLED: &str = "Query cancelled";
+
 /// Introspects the database into Querydown schema JSON once at startup and stores
 /// the result in `schema`.
 ///
@@ -47,7 +231,7 @@ pub(crate) fn fetch_schema(schema: Arc<Mutex<Option<String>>>, ctx: egui::Contex
             Ok::<(), String>(())
         }
     };
-    let on_done = move |result: Result<(), String>| {
+    let on_done = move |result: Result<Outcome, QueryFailure>| {
         if result.is_err() {
             return;
         }
@@ -59,7 +243,14 @@ pub(crate) fn fetch_schema(schema: Arc<Mutex<Option<String>>>, ctx: egui::Contex
             ctx.request_repaint();
         }
     };
-    stream_query(crate::schema::introspection_sql(), handler, on_done);
+    stream_query(
+        None,
+        None,
+        crate::schema::introspection_sql(),
+        Arc::default(),
+        handler,
+        on_done,
+    );
 }
 
 /// Extracts the first row's first column as a string, for queries (like schema
@@ -88,6 +279,7 @@ pub(crate) fn fetch_track_metadata(
         "with a as (\
            select c.track, array_agg(ar.name order by c.ord) as artists \
            from credit c join artist ar on ar.id = c.artist \
+           where c.role = '' \
            group by c.track\
          ) \
          select t.id::text as id, t.title, a.artists \
@@ -107,8 +299,8 @@ pub(crate) fn fetch_track_metadata(
         );
         Ok::<(), String>(())
     };
-    let on_done = |_result: Result<(), String>| {};
-    stream_query(sql, handler, on_done);
+    let on_done = |_result: Result<Outcome, QueryFailure>| {};
+    stream_query(None, None, sql, Arc::default(), handler, on_done);
 }
 
 fn extract_string_list(col: &ArrayRef) -> Vec<String> {
@@ -158,39 +350,131 @@ fn apply_metadata_batch(
     }
 }
 
+/// Runs `query` sorted by `sort` on a background thread, handing each batch of
+/// rows to `handler` and then the outcome to `on_done`: the total number of
+/// rows when only the page starting at row `page` is fetched. With the
+/// `embedded` feature the query goes to the linked backend once one is
+/// installed, rather than to the server.
+///
+/// Setting `cancel` drops the request, which the server takes as the client
+/// going away and so interrupts the query; the linked backend checks it too.
 #[cfg(not(target_arch = "wasm32"))]
-fn stream_query<H, D>(query: String, handler: H, on_done: D)
-where
+fn stream_query<H, D>(
+    sort: Option<&ResultSort>,
+    page: Option<u64>,
+    query: String,
+    cancel: Arc<AtomicBool>,
+    handler: H,
+    on_done: D,
+) where
     H: FnMut(&RecordBatch) -> Result<(), String> + Send + 'static,
-    D: FnOnce(Result<(), String>) + Send + 'static,
+    D: FnOnce(Result<Outcome, QueryFailure>) + Send + 'static,
 {
+    #[cfg(feature = "embedded")]
+    if let Some(state) = crate::embedded::backend() {
+        let sort = sort.cloned();
+        std::thread::spawn(move || {
+            on_done(crate::embedded::run_query(
+                state,
+                &query,
+                sort.as_ref(),
+                page,
+                &cancel,
+                handler,
+            ));
+        });
+        return;
+    }
+    let url = query_url(sort, page);
     std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .expect("build tokio runtime");
-        let result = rt.block_on(stream_query_native(&query, handler));
+        let result = rt.block_on(async {
+            tokio::select! {
+                result = stream_query_native(&url, &query, handler) => result,
+                () = cancelled(&cancel) => Err(CANCELLED.to_string().into()),
+            }
+        });
         on_done(result);
     });
 }
 
+/// Resolves once `cancel` is set.
+#[cfg(not(target_arch = "wasm32"))]
+async fn cancelled(cancel: &AtomicBool) {
+    while !cancel.load(Ordering::Relaxed) {
+        tokio::time::sleep(std::time::Duration::from_millis(CANCEL_POLL_MS.into())).await;
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
-fn stream_query<H, D>(query: String, handler: H, on_done: D)
-where
+fn stream_query<H, D>(
+    sort: Option<&ResultSort>,
+    page: Option<u64>,
+    query: String,
+    cancel: Arc<AtomicBool>,
+    handler: H,
+    on_done: D,
+) where
     H: FnMut(&RecordBatch) -> Result<(), String> + 'static,
-    D: FnOnce(Result<(), String>) + 'static,
+    D: FnOnce(Result<Outcome, QueryFailure>) + 'static,
 {
+    use futures_util::future::{Either, select};
+
+    let url = query_url(sort, page);
     wasm_bindgen_futures::spawn_local(async move {
         let mut handler = handler;
-        let result = stream_query_wasm(&query, &mut handler).await;
+        let abort = web_sys::AbortController::new().ok();
+        let signal = abort.as_ref().map(web_sys::AbortController::signal);
+        let request = std::pin::pin!(stream_query_wasm(
+            &url,
+            &query,
+            signal.as_ref(),
+            &mut handler
+        ));
+        let result = match select(request, std::pin::pin!(cancelled(&cancel))).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => {
+                if let Some(abort) = &abort {
+                    abort.abort();
+                }
+                Err(CANCELLED.to_string().into())
+            }
+        };
         on_done(result);
     });
 }
 
-fn finish(result: Result<(), String>, state: &Mutex<QueryState>, ctx: &egui::Context) {
+/// Resolves once `cancel` is set.
+#[cfg(target_arch = "wasm32")]
+async fn cancelled(cancel: &AtomicBool) {
+    while !cancel.load(Ordering::Relaxed) {
+        let tick = js_sys::Promise::new(&mut |resolve, _| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                    &resolve,
+                    CANCEL_POLL_MS.cast_signed(),
+                );
+            }
+        });
+        let _ = wasm_bindgen_futures::JsFuture::from(tick).await;
+    }
+}
+
+fn finish(result: Result<Outcome, QueryFailure>, state: &Mutex<QueryState>, ctx: &egui::Context) {
     let mut s = state.lock().unwrap();
-    if let Err(e) = result {
-        s.error = Some(e);
+    match result {
+        Ok(Outcome::Rows { total }) => s.total = total,
+        Ok(Outcome::RowsAffected(n)) => s.rows_affected = Some(n),
+        Err(failure) => {
+            s.error_location = s
+                .sql
+                .as_deref()
+                .and_then(|sql| ErrorLocation::find(sql, &failure));
+            s.error = Some(failure.message);
+        }
     }
     s.running = false;
     drop(s);
@@ -199,22 +483,30 @@ fn finish(result: Result<(), String>, state: &Mutex<QueryState>, ctx: &egui::Con
 
 fn push_batch(
     batch: &RecordBatch,
+    settings: &DisplaySettings,
     state: &Mutex<QueryState>,
     ctx: &egui::Context,
 ) -> Result<(), String> {
-    let fmt_opts = FormatOptions::default();
+    if batch.num_columns() == 0 {
+        return Ok(());
+    }
     let formatters: Vec<_> = batch
         .columns()
         .iter()
-        .map(|col| ArrayFormatter::try_new(col.as_ref(), &fmt_opts))
-        .collect::<Result<_, _>>()
-        .map_err(|e| e.to_string())?;
+        .map(|col| settings.column_cells(col.as_ref()))
+        .collect::<Result<_, _>>()?;
     let mut s = state.lock().unwrap();
-    for row in 0..batch.num_rows() {
-        let cells: Vec<String> = formatters
+    if s.column_names.is_empty() {
+        let schema = batch.schema();
+        s.column_names = schema.fields().iter().map(|f| f.name().clone()).collect();
+        s.numeric_columns = schema
+            .fields()
             .iter()
-            .map(|fmt| fmt.value(row).to_string())
+            .map(|f| f.data_type().is_numeric())
             .collect();
+    }
+    for row in 0..batch.num_rows() {
+        let cells: Vec<String> = formatters.iter().map(|fmt| fmt.cell(row)).collect();
         s.rows.push(cells);
     }
     drop(s);
@@ -222,13 +514,55 @@ fn push_batch(
     Ok(())
 }
 
-fn feed_decoder<H>(decoder: &mut StreamDecoder, chunk: Bytes, handler: &mut H) -> Result<(), String>
+/// What a finished query produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// Result rows, and how many the query has in all when only a page of them
+    /// was fetched.
+    Rows { total: Option<u64> },
+    /// No result columns; the statement changed this many rows.
+    RowsAffected(u64),
+}
+
+/// Statements without result columns (DDL/DML) are answered with a JSON row
+/// count instead of an Arrow stream, so there is nothing to decode.
+fn is_rows_affected_response(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|ct| ct.starts_with("application/json"))
+}
+
+/// The row count of a `{"rows_affected": n}` response body.
+fn rows_affected(body: &str) -> Result<u64, String> {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| json["rows_affected"].as_u64())
+        .ok_or_else(|| format!("unexpected response: {body}"))
+}
+
+/// The total number of rows the server gives for a paged query.
+fn total_count(header: Option<&str>) -> Option<u64> {
+    header.and_then(|total| total.parse().ok())
+}
+
+/// Decodes `chunk` of an Arrow IPC stream, handing each batch it completes to
+/// `handler`. The stream's schema goes to `handler` first, as a batch without
+/// rows, so that a result without rows (and so without batches) still has its
+/// columns.
+pub(crate) fn feed_decoder<H>(
+    decoder: &mut StreamDecoder,
+    chunk: Bytes,
+    handler: &mut H,
+) -> Result<(), String>
 where
     H: FnMut(&RecordBatch) -> Result<(), String>,
 {
     let mut buf = Buffer::from(chunk);
     while !buf.is_empty() {
-        match decoder.decode(&mut buf).map_err(|e| e.to_string())? {
+        let had_schema = decoder.schema().is_some();
+        let batch = decoder.decode(&mut buf).map_err(|e| e.to_string())?;
+        if !had_schema && let Some(schema) = decoder.schema() {
+            handler(&RecordBatch::new_empty(schema))?;
+        }
+        match batch {
             Some(batch) => handler(&batch)?,
             None => break,
         }
@@ -237,37 +571,58 @@ where
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-async fn stream_query_native<H>(query: &str, mut handler: H) -> Result<(), String>
+async fn stream_query_native<H>(
+    url: &str,
+    query: &str,
+    mut handler: H,
+) -> Result<Outcome, QueryFailure>
 where
     H: FnMut(&RecordBatch) -> Result<(), String>,
 {
     use futures_util::StreamExt;
 
-    let url = format!("{BASE}/query");
+    // reqwest sends `Accept-Encoding: gzip, zstd` and decompresses the body as
+    // it streams, so the decoder is fed the Arrow stream itself.
     let resp = reqwest::Client::new()
-        .post(&url)
+        .post(url)
+        .header(reqwest::header::ACCEPT, "application/json")
         .body(query.to_string())
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| request_error(&e))?;
     if !resp.status().is_success() {
         let status = resp.status();
         let msg = resp.text().await.unwrap_or_default();
-        return Err(format!("{status}: {msg}"));
+        return Err(QueryFailure::from_response(status, &msg));
+    }
+    let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE);
+    if is_rows_affected_response(content_type.and_then(|v| v.to_str().ok())) {
+        let body = resp.text().await.map_err(|e| request_error(&e))?;
+        return Ok(Outcome::RowsAffected(rows_affected(&body)?));
     }
+    let total = total_count(
+        resp.headers()
+            .get(TOTAL_COUNT_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    );
 
     let mut stream = resp.bytes_stream();
     let mut decoder = StreamDecoder::new();
     while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| e.to_string())?;
+        let chunk = chunk.map_err(|e| request_error(&e))?;
         feed_decoder(&mut decoder, chunk, &mut handler)?;
     }
-    Ok(())
+    Ok(Outcome::Rows { total })
 }
 
 #[cfg(target_arch = "wasm32")]
 #[allow(unsafe_code)]
-async fn stream_query_wasm<H>(query: &str, handler: &mut H) -> Result<(), String>
+async fn stream_query_wasm<H>(
+    url: &str,
+    query: &str,
+    signal: Option<&web_sys::AbortSignal>,
+    handler: &mut H,
+) -> Result<Outcome, QueryFailure>
 where
     H: FnMut(&RecordBatch) -> Result<(), String>,
 {
@@ -276,8 +631,9 @@ where
     use wasm_bindgen::JsCast;
     use wasm_streams::ReadableStream;
 
-    let url = format!("{BASE}/query");
-    let resp = gloo_net::http::Request::post(&url)
+    let resp = gloo_net::http::Request::post(url)
+        .header("accept", "application/json")
+        .abort_signal(signal)
         .body(query.to_string())
         .map_err(|e| e.to_string())?
         .send()
@@ -286,8 +642,13 @@ where
     if !resp.ok() {
         let status = resp.status();
         let msg = resp.text().await.unwrap_or_default();
-        return Err(format!("{status}: {msg}"));
+        return Err(QueryFailure::from_response(status, &msg));
     }
+    if is_rows_affected_response(resp.headers().get("content-type").as_deref()) {
+        let body = resp.text().await.map_err(|e| e.to_string())?;
+        return Ok(Outcome::RowsAffected(rows_affected(&body)?));
+    }
+    let total = total_count(resp.headers().get(TOTAL_COUNT_HEADER).as_deref());
 
     let body = resp
         .body()
@@ -302,5 +663,141 @@ where
         let bytes = Bytes::from(array.to_vec());
         feed_decoder(&mut decoder, bytes, handler)?;
     }
-    Ok(())
+    Ok(Outcome::Rows { total })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int32Array, RecordBatch};
+    use arrow_ipc::reader::StreamDecoder;
+    use arrow_ipc::writer::StreamWriter;
+    use bytes::Bytes;
+
+    use super::{
+        ResultSort, base_url, feed_decoder, parse_server_url, query_url, rows_affected, total_count,
+    };
+
+    /// A batch with a column `n` holding `values`.
+    fn batch(values: Vec<i32>) -> RecordBatch {
+        let column: ArrayRef = Arc::new(Int32Array::from(values));
+        RecordBatch::try_from_iter([("n", column)]).unwrap()
+    }
+
+    /// The Arrow IPC stream of `batches`, of the schema of `first`.
+    fn ipc_stream(first: &RecordBatch, batches: &[RecordBatch]) -> Bytes {
+        let mut writer = StreamWriter::try_new(Vec::new(), &first.schema()).unwrap();
+        for batch in batches {
+            writer.write(batch).unwrap();
+        }
+        writer.finish().unwrap();
+        Bytes::from(writer.into_inner().unwrap())
+    }
+
+    /// The column names and row counts of the batches decoded from `stream`.
+    fn decoded(stream: Bytes) -> Vec<(Vec<String>, usize)> {
+        let mut batches = Vec::new();
+        let mut handler = |batch: &RecordBatch| {
+            let names = batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().clone())
+                .collect();
+            batches.push((names, batch.num_rows()));
+            Ok(())
+        };
+        feed_decoder(&mut StreamDecoder::new(), stream, &mut handler).unwrap();
+        batches
+    }
+
+    #[test]
+    fn results_without_rows_still_have_their_columns() {
+        let rows = batch(vec![1, 2]);
+        assert_eq!(
+            decoded(ipc_stream(&rows, &[])),
+            [(vec!["n".to_string()], 0)]
+        );
+        assert_eq!(
+            decoded(ipc_stream(&rows, std::slice::from_ref(&rows))),
+            [(vec!["n".to_string()], 0), (vec!["n".to_string()], 2)]
+        );
+    }
+
+    #[test]
+    fn query_url_encodes_the_sort() {
+        let base = base_url();
+        assert_eq!(query_url(None, None), format!("{base}/query?format=arrow"));
+        let sort = ResultSort {
+            column: "play count/ø".to_string(),
+            descending: true,
+        };
+        assert_eq!(
+            query_url(Some(&sort), None),
+            format!("{base}/query?format=arrow&order_by=play%20count%2F%C3%B8&dir=desc")
+        );
+    }
+
+    #[test]
+    fn query_url_asks_for_a_page() {
+        let base = base_url();
+        assert_eq!(
+            query_url(None, Some(2000)),
+            format!("{base}/query?format=arrow&limit=1000&offset=2000")
+        );
+        let sort = ResultSort {
+            column: "n".to_string(),
+            descending: false,
+        };
+        assert_eq!(
+            query_url(Some(&sort), Some(0)),
+            format!("{base}/query?format=arrow&order_by=n&dir=asc&limit=1000&offset=0")
+        );
+    }
+
+    #[test]
+    fn total_counts_are_parsed() {
+        assert_eq!(total_count(Some("1234")), Some(1234));
+        assert_eq!(total_count(Some("many")), None);
+        assert_eq!(total_count(None), None);
+    }
+
+    #[test]
+    fn rows_affected_responses_are_parsed() {
+        assert_eq!(rows_affected(r#"{"rows_affected": 3}"#), Ok(3));
+        assert!(rows_affected("<html>").is_err());
+    }
+
+    #[test]
+    fn server_urls_are_normalized() {
+        assert_eq!(
+            parse_server_url(" http://music.local:3000/ ").unwrap(),
+            "http://music.local:3000"
+        );
+        assert_eq!(
+            parse_server_url("HTTPS://example.com/collectune/api").unwrap(),
+            "HTTPS://example.com/collectune/api"
+        );
+        assert_eq!(
+            parse_server_url("http://[::1]:3000").unwrap(),
+            "http://[::1]:3000"
+        );
+    }
+
+    #[test]
+    fn invalid_server_urls_are_rejected() {
+        for url in [
+            "localhost:3000",
+            "ftp://example.com",
+            "http://",
+            "http://:3000",
+            "http://example.com:port",
+            "http://example.com:70000",
+            "http://exa mple.com",
+            "http://user@example.com",
+        ] {
+            assert!(parse_server_url(url).is_err(), "{url}");
+        }
+    }
 }
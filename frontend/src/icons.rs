@@ -46,8 +46,19 @@ pub(crate) const SAVE: MaterialIcon = mi::ICON_SAVE;
 pub(crate) const REVERT: MaterialIcon = mi::ICON_UNDO;
 /// (Re-)run the current query.
 pub(crate) const RUN: MaterialIcon = mi::ICON_REFRESH;
+/// Show the plan of the current query.
+pub(crate) const EXPLAIN: MaterialIcon = mi::ICON_ACCOUNT_TREE;
+/// Cancel the running query.
+pub(crate) const CANCEL: MaterialIcon = mi::ICON_STOP;
+/// Open the history of queries run.
+pub(crate) const HISTORY: MaterialIcon = mi::ICON_HISTORY;
+/// Open the app-wide display settings.
+pub(crate) const SETTINGS: MaterialIcon = mi::ICON_SETTINGS;
 /// Reload a list from the backend.
 pub(crate) const REFRESH: MaterialIcon = mi::ICON_REFRESH;
+/// Go to the previous or next page of results.
+pub(crate) const PREVIOUS_PAGE: MaterialIcon = mi::ICON_CHEVRON_LEFT;
+pub(crate) const NEXT_PAGE: MaterialIcon = mi::ICON_CHEVRON_RIGHT;
 /// Expanded disclosure arrow on a collapsible preset card.
 pub(crate) const EXPAND_OPEN: MaterialIcon = mi::ICON_EXPAND_MORE;
 /// Collapsed disclosure arrow on a collapsible preset card.
@@ -65,10 +76,21 @@ pub(crate) const SHUFFLE: MaterialIcon = mi::ICON_SHUFFLE;
 /// Manage the whole preset library ("toolbox").
 pub(crate) const MANAGE_PRESETS: MaterialIcon = mi::ICON_HANDYMAN;
 
+// Results.
+/// The results are sorted by this column, ascending.
+pub(crate) const SORTED_ASC: MaterialIcon = mi::ICON_ARROW_UPWARD;
+/// The results are sorted by this column, descending.
+pub(crate) const SORTED_DESC: MaterialIcon = mi::ICON_ARROW_DOWNWARD;
+
 // Playback.
 pub(crate) const PLAY: MaterialIcon = mi::ICON_PLAY_ARROW;
 pub(crate) const PAUSE: MaterialIcon = mi::ICON_PAUSE;
 pub(crate) const NEXT: MaterialIcon = mi::ICON_SKIP_NEXT;
+/// Preview a result row's file, and stop the preview.
+pub(crate) const PREVIEW: MaterialIcon = mi::ICON_PLAY_CIRCLE;
+pub(crate) const STOP: MaterialIcon = mi::ICON_STOP;
+/// A star of a track's rating.
+pub(crate) const RATING: MaterialIcon = mi::ICON_STAR;
 /// Scroll the results to the now-playing track.
 pub(crate) const LOCATE: MaterialIcon = mi::ICON_MY_LOCATION;
 
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use eframe::egui;
@@ -11,8 +12,12 @@ mod builder;
 mod button;
 mod columns;
 mod compile;
+#[cfg(all(feature = "embedded", not(target_arch = "wasm32")))]
+pub mod embedded;
 mod field_layout;
 mod format;
+mod highlight;
+mod history;
 mod http;
 mod icons;
 mod lineage;
@@ -20,10 +25,16 @@ mod menu_bar;
 mod now_playing;
 mod organizer;
 mod page;
+mod plan;
+mod preview;
 mod query_def;
+mod query_error;
+mod rating;
 mod results;
 mod rpc;
 mod schema;
+mod settings;
+mod tabs;
 mod text_input;
 #[cfg(target_arch = "wasm32")]
 mod web;
@@ -33,10 +44,14 @@ use audio::AudioPlayer;
 use builder::{PresetEdit, PresetSave};
 use columns::ColumnMetadata;
 use field_layout::FieldLayout;
+use history::{HistoryEntry, QueryHistory};
 use now_playing::CurrentTrack;
 use organizer::Organizer;
 use page::{CurrentPage, QueryPage};
+use preview::FilePreview;
 use query_def::{QueryDefinition, Section, SectionContent};
+use settings::{DisplaySettings, SettingsEdit};
+use tabs::{OpenTabs, QueryTab};
 
 pub(crate) const ORGANIZER_WIDTH: f32 = 200.0;
 const ORGANIZER_ANIM_TIME: f32 = 0.1;
@@ -103,11 +118,33 @@ pub(crate) struct QueryState {
     /// Resolved display metadata for each result column, positionally aligned with each
     /// row's cells. Empty until the query is (re)compiled.
     pub(crate) columns: Vec<ColumnMetadata>,
+    /// The result columns' names, from the first batch's schema.
+    pub(crate) column_names: Vec<String>,
+    /// Whether each result column holds numbers, from the same schema.
+    pub(crate) numeric_columns: Vec<bool>,
+    /// The compiled SQL the rows came from, kept so a sort can re-issue it.
+    pub(crate) sql: Option<String>,
+    /// The header sort applied to the rows, if any.
+    pub(crate) sort: Option<http::ResultSort>,
+    /// Which of the query's rows the page in `rows` starts at.
+    pub(crate) offset: u64,
+    /// How many rows the query has in all, once the server has said.
+    pub(crate) total: Option<u64>,
+    /// How many rows the statement changed, when it has no result columns.
+    pub(crate) rows_affected: Option<u64>,
     pub(crate) error: Option<String>,
+    /// Where in `sql` the query failed, when the error says.
+    pub(crate) error_location: Option<query_error::ErrorLocation>,
     pub(crate) running: bool,
+    /// Set to stop the query running into these results. Each run gets a flag of
+    /// its own, so a cancelled run can't touch the results of the next.
+    pub(crate) cancel: Arc<AtomicBool>,
     pub(crate) track_id_column: Option<usize>,
     pub(crate) lineage_done: bool,
     pub(crate) needs_revalidation: bool,
+    /// The run to record in the history once it finishes without an error (see
+    /// [`App::record_finished_runs`]).
+    pub(crate) history_entry: Option<HistoryEntry>,
 }
 
 /// Which surface initiated an in-progress rename. Both surfaces edit the same
@@ -145,6 +182,12 @@ pub struct App {
     pub(crate) pages: Vec<QueryPage>,
     /// The currently displayed page.
     pub(crate) current: CurrentPage,
+    /// The open query tabs, in the order they were opened. The selected tab is
+    /// the one showing [`App::current`]. Persisted across sessions.
+    pub(crate) tabs: Vec<QueryTab>,
+    /// The tabs persisted by the last session, reopened once the query list has
+    /// loaded (see [`App::restore_tabs`]).
+    pub(crate) restored_tabs: Option<OpenTabs>,
     /// Whether the one-time, on-open auto-selection of the most-recent query has
     /// happened yet. Keeps later list refreshes from hijacking the current page.
     pub(crate) auto_selected_initial: bool,
@@ -207,8 +250,21 @@ pub struct App {
     /// SQL the current query would send to the query API, or a compile-error
     /// message to show in its place. `None` when the modal is closed.
     pub(crate) view_sql: Option<String>,
+    /// How result cells are rendered. Persisted across sessions.
+    pub(crate) display_settings: DisplaySettings,
+    /// The server the native UI sends requests to, or empty for the default
+    /// (`http://localhost:3000`). Persisted across sessions.
+    pub(crate) server_url: String,
+    /// The drafts being edited in the settings modal; `None` when closed.
+    pub(crate) settings_edit: Option<SettingsEdit>,
+    /// The queries run, newest first. Persisted across sessions.
+    pub(crate) history: QueryHistory,
+    /// Whether the history modal is open.
+    pub(crate) history_open: bool,
     pub(crate) current_track: Arc<Mutex<Option<CurrentTrack>>>,
     pub(crate) audio: Box<dyn AudioPlayer>,
+    /// Plays the file of a result row with a path (see [`preview`]).
+    pub(crate) preview: Box<dyn FilePreview>,
     pub(crate) pending_scroll_to_row: Option<usize>,
     /// Database schema JSON, fetched once at startup and used to compile Querydown.
     pub(crate) schema: Arc<Mutex<Option<String>>>,
@@ -223,6 +279,8 @@ impl Default for App {
         Self {
             pages: Vec::new(),
             current: CurrentPage::default(),
+            tabs: Vec::new(),
+            restored_tabs: None,
             auto_selected_initial: false,
             filter: String::new(),
             loaded_queries: Arc::new(Mutex::new(None)),
@@ -246,8 +304,14 @@ impl Default for App {
             manage_presets: false,
             manage_expanded: None,
             view_sql: None,
+            display_settings: DisplaySettings::default(),
+            server_url: String::new(),
+            settings_edit: None,
+            history: QueryHistory::default(),
+            history_open: false,
             current_track: Arc::new(Mutex::new(None)),
             audio: audio::new_player(),
+            preview: preview::new_preview(),
             pending_scroll_to_row: None,
             schema: Arc::new(Mutex::new(None)),
             schema_fetch_started: false,
@@ -256,7 +320,52 @@ impl Default for App {
     }
 }
 
+impl App {
+    /// Creates the app, restoring persisted settings, open tabs and history from
+    /// eframe storage. A persisted server URL that no longer parses is dropped for
+    /// the default.
+    #[must_use]
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let display_settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, settings::STORAGE_KEY))
+            .unwrap_or_default();
+        let restored_tabs = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, tabs::STORAGE_KEY));
+        let history = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, history::STORAGE_KEY))
+            .unwrap_or_default();
+        let server_url = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<String>(storage, settings::SERVER_URL_KEY))
+            .and_then(|url| http::parse_server_url(&url).ok())
+            .unwrap_or_default();
+        http::set_server_url(&server_url);
+        Self {
+            restored_tabs,
+            display_settings,
+            server_url,
+            history,
+            ..Self::default()
+        }
+    }
+}
+
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, settings::STORAGE_KEY, &self.display_settings);
+        eframe::set_value(storage, tabs::STORAGE_KEY, &self.open_tabs());
+        eframe::set_value(storage, history::STORAGE_KEY, &self.history);
+        eframe::set_value(storage, settings::SERVER_URL_KEY, &self.server_url);
+    }
+
+    /// Only the settings, tabs and history above are persisted; window and widget state starts fresh.
+    fn persist_egui_memory(&self) -> bool {
+        false
+    }
+
     fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
         let ctx = ui.ctx().clone();
         self.bootstrap(&ctx);
@@ -267,6 +376,7 @@ impl eframe::App for App {
         let persistent = ctx.viewport_rect().width() >= PERSISTENT_ORGANIZER_MIN_WIDTH;
 
         self.ensure_current_results(&ctx);
+        self.record_finished_runs();
 
         // On wide screens the organizer is a persistent left panel that reserves
         // its own space, so it must be added before the top/central panels for
@@ -275,8 +385,12 @@ impl eframe::App for App {
             self.render_persistent_organizer(ui, panel_fill);
         }
 
-        // Top panels: each page type renders its own bar (including the explorer
-        // button). Top/bottom panels must be added before the central panel.
+        // Top panels: the tab strip, then each page type renders its own bar
+        // (including the explorer button). Top/bottom panels must be added
+        // before the central panel.
+        if !self.tabs.is_empty() {
+            self.render_tab_bar(ui);
+        }
         match self.current {
             CurrentPage::Query(_) => {
                 self.render_menu_bar(ui);
@@ -332,6 +446,8 @@ impl eframe::App for App {
         self.render_preset_save_modal(&ctx);
         self.render_manage_presets_modal(&ctx);
         self.render_view_sql_modal(&ctx);
+        self.render_display_settings_modal(&ctx);
+        self.render_history_modal(&ctx);
     }
 }
 
@@ -384,24 +500,39 @@ impl App {
             })
             .collect();
 
-        if !self.auto_selected_initial {
-            // On first load, open the most-recently-created query (or the welcome
-            // page if there are none yet).
-            self.auto_selected_initial = true;
-            self.current = self
-                .pages
+        if self.auto_selected_initial {
+            // Tabs of queries that no longer exist close.
+            let gone: Vec<Uuid> = self
+                .tabs
                 .iter()
-                .max_by_key(|p| p.live.created_at)
-                .map_or(CurrentPage::Welcome, |p| CurrentPage::Query(p.live.id));
-        } else if let CurrentPage::Query(cur) = self.current
-            && !self.pages.iter().any(|p| p.live.id == cur)
-        {
-            self.current = CurrentPage::Welcome;
+                .map(|tab| tab.id)
+                .filter(|id| !self.pages.iter().any(|p| p.live.id == *id))
+                .collect();
+            for id in gone {
+                self.close_tab(id);
+            }
+        } else {
+            // On first load, reopen the last session's tabs, or else open the
+            // most-recently-created query (or the welcome page if there are none
+            // yet).
+            self.auto_selected_initial = true;
+            if !self.restore_tabs() {
+                self.open_most_recent();
+            }
         }
         self.selection.clear();
         self.selection_anchor = None;
     }
 
+    /// Opens the most-recently-created query, or the welcome page if there are
+    /// none.
+    fn open_most_recent(&mut self) {
+        match self.pages.iter().max_by_key(|p| p.live.created_at) {
+            Some(page) => self.select_page(page.live.id),
+            None => self.current = CurrentPage::Welcome,
+        }
+    }
+
     fn organizer_progress(&self, ctx: &egui::Context) -> f32 {
         let (anim_target, anim_time) = if self.organizer.dragging {
             (self.organizer.dragged_progress, 0.0)
@@ -528,7 +659,9 @@ impl App {
         self.full_editor_open = true;
     }
 
+    /// Shows the query page `id`, opening a tab for it if it has none.
     pub(crate) fn select_page(&mut self, id: Uuid) {
+        self.open_tab(id);
         self.current = CurrentPage::Query(id);
         self.selection.clear();
         self.selection_anchor = None;
@@ -607,9 +740,11 @@ impl App {
         }
     }
 
-    /// Deletes a query: drops its page, deletes it on the backend if it was
-    /// persisted, and — if it was the open page — navigates to the top-listed
-    /// (most-recently-created) remaining query, or the welcome page if none.
+    /// Deletes a query: drops its page and tab, deletes it on the backend if it
+    /// was persisted, and — if it was the open page — selects the neighbouring
+    /// tab (see [`App::close_tab`]). With no tabs left it navigates to the
+    /// top-listed (most-recently-created) remaining query, or the welcome page if
+    /// none.
     pub(crate) fn delete_query(&mut self, id: Uuid) {
         let was_persisted = self
             .pages
@@ -623,14 +758,10 @@ impl App {
         if self.rename.as_ref().is_some_and(|r| r.id == id) {
             self.rename = None;
         }
-        if self.current.query_id() == Some(id) {
-            self.current = self
-                .pages
-                .iter()
-                .max_by_key(|p| p.live.created_at)
-                .map_or(CurrentPage::Welcome, |p| CurrentPage::Query(p.live.id));
-            self.selection.clear();
-            self.selection_anchor = None;
+        let was_current = self.current.query_id() == Some(id);
+        self.close_tab(id);
+        if was_current && self.tabs.is_empty() {
+            self.open_most_recent();
         }
     }
 
@@ -715,7 +846,7 @@ impl App {
     }
 
     /// Renders the "View SQL" modal when open: a scrollable, selectable view of the
-    /// compiled SQL with a button to copy it to the clipboard. Closing (button,
+    /// compiled SQL, syntax highlighted, with a button to copy it to the clipboard. Closing (button,
     /// backdrop click, or Esc) dismisses it. The modal sizes itself to the viewport
     /// so it stays usable on small windows.
     pub(crate) fn render_view_sql_modal(&mut self, ctx: &egui::Context) {
@@ -753,7 +884,7 @@ impl App {
                 .auto_shrink([false, true])
                 .show(ui, |ui| {
                     ui.add(
-                        egui::Label::new(egui::RichText::new(&sql).monospace())
+                        egui::Label::new(highlight::highlight(ui, &sql))
                             .selectable(true)
                             .wrap_mode(egui::TextWrapMode::Wrap),
                     );
@@ -775,10 +906,24 @@ impl App {
 
     /// Compiles and runs the current page's live query, replacing its results.
     pub(crate) fn run_query(&mut self, ctx: &egui::Context) {
-        let Some((results, definition)) = self
-            .current_page()
-            .map(|p| (Arc::clone(&p.results), p.live.definition.clone()))
-        else {
+        self.start_query(ctx, false);
+    }
+
+    /// Compiles the current page's live query and runs an `EXPLAIN` of it in
+    /// place of its results, which then show its plan (see [`plan`]). Plans
+    /// aren't added to the history.
+    pub(crate) fn explain_query(&mut self, ctx: &egui::Context) {
+        self.start_query(ctx, true);
+    }
+
+    fn start_query(&mut self, ctx: &egui::Context, explain: bool) {
+        let Some((results, definition, name)) = self.current_page().map(|p| {
+            (
+                Arc::clone(&p.results),
+                p.live.definition.clone(),
+                p.live.name.clone(),
+            )
+        }) else {
             return;
         };
         let ctx = ctx.clone();
@@ -793,11 +938,20 @@ impl App {
             let mut s = results.lock().unwrap();
             s.rows.clear();
             s.columns.clear();
+            s.column_names.clear();
+            s.numeric_columns.clear();
+            s.sql = None;
+            s.sort = None;
+            s.offset = 0;
+            s.total = None;
+            s.rows_affected = None;
             s.error = None;
+            s.error_location = None;
             s.running = true;
             s.track_id_column = None;
             s.lineage_done = false;
             s.needs_revalidation = true;
+            s.history_entry = None;
         }
 
         // Resolve the four query parts into per-section Querydown source, then
@@ -816,9 +970,21 @@ impl App {
             }
         };
         let sql = match compiled {
+            Ok(compiled) if explain => {
+                let sql = format!("EXPLAIN {}", compiled.sql);
+                results.lock().unwrap().sql = Some(sql.clone());
+                sql
+            }
             Ok(compiled) => {
                 let mut s = results.lock().unwrap();
                 s.columns = compiled.columns;
+                s.sql = Some(compiled.sql.clone());
+                s.history_entry = Some(HistoryEntry {
+                    name,
+                    ran_at: rpc::now_epoch(),
+                    definition,
+                    sql: compiled.sql.clone(),
+                });
                 compiled.sql
             }
             Err(e) => {
@@ -831,8 +997,105 @@ impl App {
             }
         };
 
-        lineage::detect_track_column(sql.clone(), Arc::clone(&results), ctx.clone());
-        http::run_query(sql, &results, &ctx);
+        if !explain {
+            lineage::detect_track_column(sql.clone(), Arc::clone(&results), ctx.clone());
+        }
+        http::run_query(sql, None, 0, &results, &self.display_settings, &ctx);
+    }
+
+    /// Stops the current page's running query. The rows it fetched so far stay,
+    /// under an error saying it was cancelled, and it isn't added to the history.
+    pub(crate) fn cancel_query(&mut self) {
+        let Some(page) = self.current_page() else {
+            return;
+        };
+        let mut s = page.results.lock().unwrap();
+        if !s.running {
+            return;
+        }
+        s.cancel.store(true, Ordering::Relaxed);
+        s.running = false;
+        s.error = Some(http::CANCELLED.to_string());
+        s.history_entry = None;
+    }
+
+    /// Re-runs the current page's results sorted by result column `column`. Clicking
+    /// the sorted column again reverses the sort, and a third click drops it, going
+    /// back to the query's own order.
+    pub(crate) fn sort_results(&mut self, column: usize, ctx: &egui::Context) {
+        let Some(results) = self.current_page().map(|p| Arc::clone(&p.results)) else {
+            return;
+        };
+        let (sql, sort) = {
+            let mut s = results.lock().unwrap();
+            let (Some(sql), Some(name)) = (s.sql.clone(), s.column_names.get(column).cloned())
+            else {
+                return;
+            };
+            if s.running {
+                return;
+            }
+            let sort = match s.sort.take() {
+                Some(sort) if sort.column == name && sort.descending => None,
+                Some(sort) if sort.column == name => Some(http::ResultSort {
+                    column: name,
+                    descending: true,
+                }),
+                _ => Some(http::ResultSort {
+                    column: name,
+                    descending: false,
+                }),
+            };
+            // The columns, and so the track id column, stay as they are: only the
+            // order of the rows changes.
+            s.rows.clear();
+            s.sort.clone_from(&sort);
+            s.offset = 0;
+            s.error = None;
+            s.error_location = None;
+            s.running = true;
+            s.needs_revalidation = true;
+            (sql, sort)
+        };
+
+        self.selection.clear();
+        self.selection_anchor = None;
+        http::run_query(sql, sort.as_ref(), 0, &results, &self.display_settings, ctx);
+    }
+
+    /// Replaces the current page's results with their page starting at row
+    /// `offset`, keeping their sort.
+    pub(crate) fn show_results_page(&mut self, offset: u64, ctx: &egui::Context) {
+        let Some(results) = self.current_page().map(|p| Arc::clone(&p.results)) else {
+            return;
+        };
+        let (sql, sort) = {
+            let mut s = results.lock().unwrap();
+            let Some(sql) = s.sql.clone() else {
+                return;
+            };
+            if s.running {
+                return;
+            }
+            s.rows.clear();
+            s.offset = offset;
+            s.error = None;
+            s.error_location = None;
+            s.running = true;
+            s.needs_revalidation = true;
+            (sql, s.sort.clone())
+        };
+
+        self.selection.clear();
+        self.selection_anchor = None;
+        http::run_query(
+            sql,
+            sort.as_ref(),
+            offset,
+            &results,
+            &self.display_settings,
+            ctx,
+        );
     }
 
     /// Persists the current page's live query. Inserts it if it's new, otherwise
@@ -871,6 +1134,7 @@ impl App {
                 artist_names: Vec::new(),
             });
         }
+        self.preview.stop();
         self.audio.load(id);
         self.audio.play();
         http::fetch_track_metadata(id, &self.current_track, ctx);
@@ -8,10 +8,54 @@ mod native {
         /// UI scale factor (e.g. 1.5, 2)
         #[arg(long, short)]
         scale: Option<f32>,
+
+        /// Path to a scanned collection to query in-process, instead of
+        /// connecting to a server
+        #[cfg(feature = "embedded")]
+        collection_path: Option<String>,
+
+        /// Path to the database file (defaults to `collectune.db` in the collection root)
+        #[cfg(feature = "embedded")]
+        #[arg(long, requires = "collection_path")]
+        db_path: Option<std::path::PathBuf>,
+
+        #[cfg(feature = "embedded")]
+        #[command(flatten)]
+        log: backend::cli::LogOptions,
+    }
+
+    /// Opens the collection's library and installs it as the app's backend.
+    #[cfg(feature = "embedded")]
+    fn embed(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+        use backend::cli::{CollectionArgs, get_collection_path};
+        use backend::{backfill, scanner, server};
+
+        let Some(collection_path) = &cli.collection_path else {
+            return Ok(());
+        };
+        let args = CollectionArgs {
+            collection_path: collection_path.clone(),
+            db_path: cli.db_path.clone(),
+        };
+        let conn = args.open_db()?;
+        let state = server::app_state(conn, get_collection_path(collection_path)?.to_path_buf());
+        backfill::start(
+            std::sync::Arc::clone(&state),
+            scanner::ScanOptions::default(),
+        );
+        frontend::embedded::install(state);
+        Ok(())
     }
 
     pub fn run() -> eframe::Result {
         let cli = Cli::parse();
+        #[cfg(feature = "embedded")]
+        cli.log.init();
+        #[cfg(feature = "embedded")]
+        if let Err(e) = embed(&cli) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
         let options = eframe::NativeOptions::default();
         eframe::run_native(
             "Collectune",
@@ -21,7 +65,7 @@ mod native {
                 if let Some(s) = cli.scale {
                     cc.egui_ctx.set_pixels_per_point(s);
                 }
-                Ok(Box::new(App::default()))
+                Ok(Box::new(App::new(cc)))
             }),
         )
     }
@@ -1,9 +1,9 @@
 //! The top menu bar: the explorer toggle, the current query's name (with a
 //! superscript unsaved-changes marker and an inline save button right after it),
 //! the query options ("⋮") menu — which also holds the Base-table selector as a
-//! submenu — and the builder toggles plus the run button. The builder toggles are
-//! the Filter/Sort/Display buttons in sectioned mode, or a single "Querydown"
-//! toggle in full-querydown mode.
+//! submenu — and the builder toggles plus the run and explain buttons. The
+//! builder toggles are the Filter/Sort/Display buttons in sectioned mode, or a
+//! single "Querydown" toggle in full-querydown mode.
 
 use std::sync::{Arc, Mutex};
 
@@ -34,6 +34,8 @@ enum PageMenu {
     ManagePresets,
     /// Open the "View SQL" modal showing the compiled query SQL.
     ViewSql,
+    /// Open the history of queries run.
+    History,
 }
 
 /// A choice from the Base submenu: a specific base table, or full-querydown mode.
@@ -80,8 +82,11 @@ impl App {
         let mut convert_to_full = false;
         let mut manage_presets = false;
         let mut view_sql = false;
+        let mut history = false;
         let mut base_choice = None;
         let mut run_now = false;
+        let mut explain_now = false;
+        let mut cancel_now = false;
         let mut save_now = false;
         let mut begin_rename = false;
         let mut rename_commit = false;
@@ -131,6 +136,7 @@ impl App {
                                 Some(PageMenu::ConvertToFull) => convert_to_full = true,
                                 Some(PageMenu::ManagePresets) => manage_presets = true,
                                 Some(PageMenu::ViewSql) => view_sql = true,
+                                Some(PageMenu::History) => history = true,
                                 Some(PageMenu::Action(QueryAction::Rename)) => want_rename = true,
                                 Some(PageMenu::Action(QueryAction::Revert)) => want_revert = true,
                                 Some(PageMenu::Action(QueryAction::Duplicate)) => {
@@ -160,6 +166,22 @@ impl App {
                             {
                                 run_now = true;
                             }
+                            if Button::icon(icons::EXPLAIN)
+                                .enabled(!running)
+                                .show(ui)
+                                .on_hover_text("Explain query")
+                                .clicked()
+                            {
+                                explain_now = true;
+                            }
+                            if running
+                                && Button::icon(icons::CANCEL)
+                                    .show(ui)
+                                    .on_hover_text("Cancel query")
+                                    .clicked()
+                            {
+                                cancel_now = true;
+                            }
                             // The name + save button fill the remaining middle,
                             // laid out left-aligned.
                             ui.with_layout(
@@ -215,6 +237,9 @@ impl App {
         if view_sql {
             self.open_view_sql();
         }
+        if history {
+            self.history_open = true;
+        }
         // The "Querydown" button is a toggle for the full-query editor panel.
         if toggle_full_editor {
             self.full_editor_open = !self.full_editor_open;
@@ -239,6 +264,12 @@ impl App {
         if run_now {
             self.run_query(ui.ctx());
         }
+        if explain_now {
+            self.explain_query(ui.ctx());
+        }
+        if cancel_now {
+            self.cancel_query();
+        }
         if save_now {
             self.save_current();
         }
@@ -319,9 +350,9 @@ fn draw_page_name(
 }
 
 /// The page's "⋮" options button, opening a menu with the Base-table submenu and
-/// the Rename/Revert/Duplicate/View-SQL/Delete actions. "Revert changes" is shown
-/// only when `show_revert` (a saved query with unsaved edits). Returns the chosen
-/// item, if any.
+/// the Rename/Revert/Duplicate/View-SQL/History/Delete actions. "Revert changes"
+/// is shown only when `show_revert` (a saved query with unsaved edits). Returns the
+/// chosen item, if any.
 fn draw_page_menu_button(
     ui: &mut egui::Ui,
     base_table: &str,
@@ -368,6 +399,9 @@ fn draw_page_menu_button(
             if menu_item(ui, icons::VIEW_SQL, "View SQL", true, None).clicked() {
                 chosen = Some(PageMenu::ViewSql);
             }
+            if menu_item(ui, icons::HISTORY, "History", true, None).clicked() {
+                chosen = Some(PageMenu::History);
+            }
             if menu_item(ui, icons::DELETE, "Delete", true, Some(DELETE_RED)).clicked() {
                 chosen = Some(PageMenu::Action(QueryAction::Delete));
             }
@@ -56,6 +56,7 @@ impl App {
             if playing {
                 self.audio.pause();
             } else {
+                self.preview.stop();
                 self.audio.play();
             }
         }
@@ -73,7 +74,7 @@ impl App {
             Some(MenuAction::Locate) => {
                 if let Some(idx) = ct.row_index {
                     // Switch to the page the track lives on, then scroll to it.
-                    self.current = crate::CurrentPage::Query(ct.source_page);
+                    self.select_page(ct.source_page);
                     self.pending_scroll_to_row = Some(idx);
                     ctx.request_repaint();
                 }
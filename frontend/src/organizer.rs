@@ -42,6 +42,7 @@ struct ListItem {
 #[derive(Default)]
 struct ListActions {
     add: bool,
+    settings: bool,
     refresh: bool,
     clicked: Option<Uuid>,
     rename_request: Option<Uuid>,
@@ -176,6 +177,9 @@ impl App {
         if actions.add {
             self.add_query_page();
         }
+        if actions.settings {
+            self.open_display_settings();
+        }
         if let Some(id) = actions.clicked {
             self.select_page(id);
             if close_on_select {
@@ -255,8 +259,8 @@ impl App {
     }
 }
 
-/// Renders the "Queries" header (+ add button), the filter/refresh row, and the
-/// query list. Returns the deferred actions the caller should apply.
+/// Renders the "Queries" header (+ add and settings buttons), the filter/refresh
+/// row, and the query list. Returns the deferred actions the caller should apply.
 fn draw_query_list(
     ui: &mut egui::Ui,
     items: &[ListItem],
@@ -284,6 +288,13 @@ fn draw_query_list(
             if Button::icon(icons::ADD).show(ui).clicked() {
                 actions.add = true;
             }
+            if Button::icon(icons::SETTINGS)
+                .tint(ui.visuals().weak_text_color())
+                .show(ui)
+                .clicked()
+            {
+                actions.settings = true;
+            }
         });
     });
 
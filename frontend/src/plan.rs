@@ -0,0 +1,263 @@
+//! Query plans: the results of an `EXPLAIN`, which `DuckDB` gives as a text
+//! drawing with a box per operator, each box's inputs in the row of boxes below
+//! it. The drawing is read back into a tree and shown as collapsible operators
+//! rather than as flat text.
+
+use eframe::egui;
+
+/// An operator of a query plan.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PlanNode {
+    pub(crate) name: String,
+    /// The lines below the name, such as the operator's columns, filters and
+    /// estimated rows.
+    pub(crate) details: Vec<String>,
+    /// The operators it takes its rows from.
+    pub(crate) children: Vec<PlanNode>,
+}
+
+/// Whether results with the columns `column_names` are query plans: those of
+/// `EXPLAIN` and `EXPLAIN ANALYZE`.
+pub(crate) fn is_plan(column_names: &[String]) -> bool {
+    matches!(column_names, [key, value] if key == "explain_key" && value == "explain_value")
+}
+
+/// A box of the drawing, by the rows and columns of its corners.
+struct DrawnBox {
+    top: usize,
+    bottom: usize,
+    left: usize,
+    right: usize,
+    /// Whether a line comes into its top from the box it's an input of.
+    has_parent: bool,
+}
+
+impl DrawnBox {
+    fn encloses(&self, other: &DrawnBox) -> bool {
+        self.top < other.top
+            && other.bottom < self.bottom
+            && self.left < other.left
+            && other.right < self.right
+    }
+}
+
+/// The operators at the roots of the plan drawn in `text`, in the order drawn.
+/// Empty when it has no boxes.
+pub(crate) fn parse(text: &str) -> Vec<PlanNode> {
+    let grid: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    let boxes = find_boxes(&grid);
+    // The boxes around other boxes only frame headings, such as the total time
+    // of `EXPLAIN ANALYZE`.
+    let framing: Vec<bool> = boxes
+        .iter()
+        .map(|outer| boxes.iter().any(|inner| outer.encloses(inner)))
+        .collect();
+    // A box's parent is the one just above whose column it falls under, as each
+    // operator's inputs are drawn below it, the first one in its own column.
+    let parents: Vec<Option<usize>> = boxes
+        .iter()
+        .map(|child| {
+            boxes
+                .iter()
+                .enumerate()
+                .filter(|(i, parent)| {
+                    child.has_parent
+                        && !framing[*i]
+                        && parent.bottom + 1 == child.top
+                        && parent.left <= child.left
+                })
+                .max_by_key(|(_, parent)| parent.left)
+                .map(|(i, _)| i)
+        })
+        .collect();
+    let node = |i: usize| build(&grid, &boxes, &parents, i);
+    (0..boxes.len())
+        .filter(|&i| !framing[i] && parents[i].is_none())
+        .map(node)
+        .collect()
+}
+
+/// The boxes drawn in `grid`, by their top left corners from the top.
+fn find_boxes(grid: &[Vec<char>]) -> Vec<DrawnBox> {
+    let at = |row: usize, col: usize| grid.get(row).and_then(|line| line.get(col)).copied();
+    let mut boxes = Vec::new();
+    for (top, line) in grid.iter().enumerate() {
+        for (left, &corner) in line.iter().enumerate() {
+            if corner != '┌' {
+                continue;
+            }
+            let Some(right) = (left + 1..line.len()).find(|&col| !matches!(line[col], '─' | '┴'))
+            else {
+                continue;
+            };
+            let Some(bottom) = (top + 1..grid.len()).find(|&row| at(row, left) != Some('│'))
+            else {
+                continue;
+            };
+            if line[right] != '┐' || at(bottom, left) != Some('└') || at(bottom, right) != Some('┘')
+            {
+                continue;
+            }
+            boxes.push(DrawnBox {
+                top,
+                bottom,
+                left,
+                right,
+                has_parent: line[left + 1..right].contains(&'┴'),
+            });
+        }
+    }
+    boxes
+}
+
+/// The operator drawn in box `i`, with those of the boxes whose parent it is.
+fn build(grid: &[Vec<char>], boxes: &[DrawnBox], parents: &[Option<usize>], i: usize) -> PlanNode {
+    let drawn = &boxes[i];
+    let lines = grid[drawn.top + 1..drawn.bottom].iter().map(|line| {
+        let end = drawn.right.min(line.len());
+        let start = (drawn.left + 1).min(end);
+        line[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    });
+    // A rule inside the box parts the name from the details; without one, the
+    // name is the first line.
+    let lines: Vec<String> = lines.filter(|line| !line.is_empty()).collect();
+    let is_rule = |line: &String| line.chars().all(|c| c == '─');
+    let name_lines = lines.iter().position(is_rule).unwrap_or(1.min(lines.len()));
+    let details = lines[name_lines..]
+        .iter()
+        .filter(|line| !is_rule(line))
+        .cloned()
+        .collect();
+    PlanNode {
+        name: lines[..name_lines].join(" "),
+        details,
+        children: (0..boxes.len())
+            .filter(|&child| parents[child] == Some(i))
+            .map(|child| build(grid, boxes, parents, child))
+            .collect(),
+    }
+}
+
+/// Draws the plans of `rows`, the results of an `EXPLAIN`: each as a tree of
+/// collapsible operators under the name of the plan, or as its text when it
+/// draws no boxes.
+pub(crate) fn draw_plans(ui: &mut egui::Ui, rows: &[Vec<String>]) {
+    egui::ScrollArea::both()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for (i, row) in rows.iter().enumerate() {
+                let [key, value] = row.as_slice() else {
+                    continue;
+                };
+                ui.label(egui::RichText::new(key.replace('_', " ")).strong());
+                let roots = parse(value);
+                if roots.is_empty() {
+                    ui.label(egui::RichText::new(value).monospace());
+                }
+                for (j, root) in roots.iter().enumerate() {
+                    draw_node(ui, root, egui::Id::new(("plan", i, j)));
+                }
+                ui.add_space(8.0);
+            }
+        });
+}
+
+fn draw_node(ui: &mut egui::Ui, node: &PlanNode, id: egui::Id) {
+    if node.details.is_empty() && node.children.is_empty() {
+        ui.label(egui::RichText::new(&node.name).strong());
+        return;
+    }
+    egui::CollapsingHeader::new(egui::RichText::new(&node.name).strong())
+        .id_salt(id)
+        .default_open(true)
+        .show(ui, |ui| {
+            for detail in &node.details {
+                ui.label(egui::RichText::new(detail).weak());
+            }
+            for (i, child) in node.children.iter().enumerate() {
+                draw_node(ui, child, id.with(i));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlanNode, is_plan, parse};
+
+    fn node(name: &str, details: &[&str], children: Vec<PlanNode>) -> PlanNode {
+        PlanNode {
+            name: name.to_string(),
+            details: details.iter().map(ToString::to_string).collect(),
+            children,
+        }
+    }
+
+    #[test]
+    fn plans_are_told_by_their_columns() {
+        assert!(is_plan(&["explain_key", "explain_value"].map(String::from)));
+        assert!(!is_plan(&["explain_key"].map(String::from)));
+        assert!(!is_plan(&["title", "explain_value"].map(String::from)));
+    }
+
+    #[test]
+    fn inputs_are_the_boxes_below_in_their_column() {
+        let drawing = "\
+┌─────────┐
+│HASH_JOIN│
+│─────────│
+│INNER    ├──────┐
+└────┬────┘      │
+┌────┴────┐┌─────┴───┐
+│SEQ_SCAN ││SEQ_SCAN │
+│─────────││─────────│
+│track    ││album    │
+└────┬────┘└─────────┘
+┌────┴────┐
+│FILTER   │
+└─────────┘";
+        assert_eq!(
+            parse(drawing),
+            [node(
+                "HASH_JOIN",
+                &["INNER"],
+                vec![
+                    node("SEQ_SCAN", &["track"], vec![node("FILTER", &[], vec![])]),
+                    node("SEQ_SCAN", &["album"], vec![]),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn framed_headings_are_roots_of_their_own() {
+        let drawing = "\
+┌───────────┐
+│┌─────────┐│
+││Total: 1s││
+│└─────────┘│
+└───────────┘
+ EXPLAIN ANALYZE SELECT 1
+┌─────────┐
+│QUERY    │
+└────┬────┘
+┌────┴────┐
+│SEQ_SCAN │
+└─────────┘";
+        assert_eq!(
+            parse(drawing),
+            [
+                node("Total: 1s", &[], vec![]),
+                node("QUERY", &[], vec![node("SEQ_SCAN", &[], vec![])]),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_without_boxes_has_no_plan() {
+        assert_eq!(parse("no plan here"), []);
+    }
+}
@@ -0,0 +1,287 @@
+//! Previewing the file of a result row, for queries that return file paths
+//! rather than tracks. One file plays at a time, and never alongside the
+//! now-playing track.
+//!
+//! The desktop UI decodes the file with rodio (through symphonia) and plays it
+//! on the default output device. It reads the file from the linked backend's
+//! collection when there is one, and otherwise fetches it from the server's
+//! `/files/stream`. The web UI plays that URL in an audio element.
+
+use eframe::egui;
+
+pub trait FilePreview {
+    /// Starts playing the file at library path `path`, stopping any other.
+    fn play(&mut self, path: &str, ctx: &egui::Context);
+    fn pause(&mut self);
+    fn resume(&mut self);
+    fn stop(&mut self);
+    /// The path of the file playing or paused, until it ends, fails or is
+    /// stopped.
+    fn current(&mut self) -> Option<&str>;
+    fn is_paused(&self) -> bool;
+    /// Why the last file couldn't be played, if it couldn't.
+    fn error(&self) -> Option<String>;
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn new_preview() -> Box<dyn FilePreview> {
+    Box::<wasm_impl::WebPreview>::default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn new_preview() -> Box<dyn FilePreview> {
+    Box::<native_impl::RodioPreview>::default()
+}
+
+/// The result column holding file paths: one named `path`, or else the first
+/// whose name ends in it, ignoring case.
+pub(crate) fn path_column(column_names: &[String]) -> Option<usize> {
+    column_names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case("path"))
+        .or_else(|| {
+            column_names
+                .iter()
+                .position(|name| name.to_ascii_lowercase().ends_with("path"))
+        })
+}
+
+/// Whether the cell `value` of a path column holds a path to preview: a
+/// library path such as `./Artist/01.flac`, or an absolute one.
+pub(crate) fn is_path(value: &str) -> bool {
+    value.starts_with("./") || value.starts_with('/')
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm_impl {
+    use eframe::egui;
+    use web_sys::HtmlAudioElement;
+
+    use super::FilePreview;
+
+    #[derive(Default)]
+    pub struct WebPreview {
+        /// The element playing the file, created by the first preview.
+        audio: Option<HtmlAudioElement>,
+        path: Option<String>,
+        error: Option<String>,
+    }
+
+    impl FilePreview for WebPreview {
+        fn play(&mut self, path: &str, _ctx: &egui::Context) {
+            self.stop();
+            self.error = None;
+            if self.audio.is_none() {
+                self.audio = HtmlAudioElement::new().ok();
+            }
+            let Some(audio) = &self.audio else {
+                self.error = Some("Couldn't create an audio element".to_string());
+                return;
+            };
+            audio.set_src(&crate::http::file_url(path));
+            let _ = audio.play();
+            self.path = Some(path.to_string());
+        }
+
+        fn pause(&mut self) {
+            if let Some(audio) = &self.audio {
+                let _ = audio.pause();
+            }
+        }
+
+        fn resume(&mut self) {
+            if let Some(audio) = &self.audio {
+                let _ = audio.play();
+            }
+        }
+
+        fn stop(&mut self) {
+            if let Some(audio) = &self.audio {
+                let _ = audio.pause();
+                audio.remove_attribute("src").ok();
+            }
+            self.path = None;
+        }
+
+        fn current(&mut self) -> Option<&str> {
+            let audio = self.audio.as_ref()?;
+            if let (Some(path), Some(error)) = (&self.path, audio.error()) {
+                self.error = Some(format!(
+                    "Couldn't play {path}: media error {}",
+                    error.code()
+                ));
+                self.stop();
+            } else if audio.ended() {
+                self.stop();
+            }
+            self.path.as_deref()
+        }
+
+        fn is_paused(&self) -> bool {
+            self.audio.as_ref().is_some_and(HtmlAudioElement::paused)
+        }
+
+        fn error(&self) -> Option<String> {
+            self.error.clone()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native_impl {
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use eframe::egui;
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+
+    use super::FilePreview;
+
+    #[derive(Default)]
+    pub struct RodioPreview {
+        /// The default output device, opened by the first preview.
+        output: Option<(OutputStream, OutputStreamHandle)>,
+        playing: Option<Playing>,
+        /// Why the last file couldn't be played. Each preview has its own, so a
+        /// file that failed to load late can't report on the next.
+        error: Arc<Mutex<Option<String>>>,
+    }
+
+    struct Playing {
+        path: String,
+        sink: Arc<Sink>,
+        /// Set once the file is decoded and queued, after which an empty sink
+        /// means it has ended.
+        queued: Arc<AtomicBool>,
+    }
+
+    /// The bytes of the file at library path `path`.
+    fn read(path: &str) -> Result<Vec<u8>, String> {
+        #[cfg(feature = "embedded")]
+        if let Some(state) = crate::embedded::backend() {
+            let relative = path.strip_prefix("./").unwrap_or(path);
+            return std::fs::read(state.collection_path().join(relative))
+                .map_err(|e| e.to_string());
+        }
+        crate::http::fetch_file(path)
+    }
+
+    impl RodioPreview {
+        fn fail(&self, path: &str, e: impl std::fmt::Display) {
+            *self.error.lock().unwrap() = Some(format!("Couldn't play {path}: {e}"));
+        }
+    }
+
+    impl FilePreview for RodioPreview {
+        fn play(&mut self, path: &str, ctx: &egui::Context) {
+            self.stop();
+            self.error = Arc::default();
+            if self.output.is_none() {
+                match OutputStream::try_default() {
+                    Ok(output) => self.output = Some(output),
+                    Err(e) => return self.fail(path, e),
+                }
+            }
+            let Some((_, handle)) = &self.output else {
+                return;
+            };
+            let sink = match Sink::try_new(handle) {
+                Ok(sink) => Arc::new(sink),
+                Err(e) => return self.fail(path, e),
+            };
+            let queued = Arc::new(AtomicBool::new(false));
+
+            // Dropping the sink stops it, so once the preview is stopped the
+            // file is dropped as soon as it's loaded.
+            let weak_sink = Arc::downgrade(&sink);
+            let queued_by_loader = Arc::clone(&queued);
+            let error = Arc::clone(&self.error);
+            let file = path.to_string();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let decoded = read(&file)
+                    .and_then(|bytes| Decoder::new(Cursor::new(bytes)).map_err(|e| e.to_string()));
+                match decoded {
+                    Ok(source) => {
+                        if let Some(sink) = weak_sink.upgrade() {
+                            sink.append(source);
+                            queued_by_loader.store(true, Ordering::Relaxed);
+                        }
+                    }
+                    Err(e) => *error.lock().unwrap() = Some(format!("Couldn't play {file}: {e}")),
+                }
+                ctx.request_repaint();
+            });
+
+            self.playing = Some(Playing {
+                path: path.to_string(),
+                sink,
+                queued,
+            });
+        }
+
+        fn pause(&mut self) {
+            if let Some(playing) = &self.playing {
+                playing.sink.pause();
+            }
+        }
+
+        fn resume(&mut self) {
+            if let Some(playing) = &self.playing {
+                playing.sink.play();
+            }
+        }
+
+        fn stop(&mut self) {
+            self.playing = None;
+        }
+
+        fn current(&mut self) -> Option<&str> {
+            let ended = self
+                .playing
+                .as_ref()
+                .is_some_and(|p| p.queued.load(Ordering::Relaxed) && p.sink.empty());
+            if ended || self.error.lock().unwrap().is_some() {
+                self.playing = None;
+            }
+            self.playing.as_ref().map(|p| p.path.as_str())
+        }
+
+        fn is_paused(&self) -> bool {
+            self.playing.as_ref().is_some_and(|p| p.sink.is_paused())
+        }
+
+        fn error(&self) -> Option<String> {
+            self.error.lock().unwrap().clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_path, path_column};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn the_path_column_is_found_by_name() {
+        assert_eq!(path_column(&names(&["title", "Path"])), Some(1));
+        assert_eq!(
+            path_column(&names(&["file_path", "title", "path"])),
+            Some(2)
+        );
+        assert_eq!(path_column(&names(&["title", "file_path"])), Some(1));
+        assert_eq!(path_column(&names(&["title", "paths_count"])), None);
+    }
+
+    #[test]
+    fn only_paths_are_previewed() {
+        assert!(is_path("./Artist/01.flac"));
+        assert!(is_path("/music/01.flac"));
+        assert!(!is_path(""));
+        assert!(!is_path("http://radio.example/stream"));
+    }
+}
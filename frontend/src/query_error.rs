@@ -0,0 +1,263 @@
+//! Where in its SQL a failed query went wrong, so the results can point at it.
+//!
+//! The server gives the position of an error to clients asking for JSON errors.
+//! Otherwise it's read from the message itself: `DuckDB` quotes the line it
+//! failed on with a caret under the spot.
+
+use serde::Deserialize;
+
+/// How many characters of the failed line are shown on either side of the
+/// error.
+const EXCERPT_CONTEXT: usize = 40;
+
+/// Why a query failed, and where in its SQL when known.
+#[derive(Debug, PartialEq)]
+pub(crate) struct QueryFailure {
+    pub(crate) message: String,
+    /// The character offset in the SQL that the error points at.
+    pub(crate) position: Option<usize>,
+}
+
+impl From<String> for QueryFailure {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            position: None,
+        }
+    }
+}
+
+impl QueryFailure {
+    /// The failure a `/query` response with `status` describes in `body`: a
+    /// JSON error object, or else the message as plain text.
+    pub(crate) fn from_response(status: impl std::fmt::Display, body: &str) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: String,
+            position: Option<usize>,
+        }
+        match serde_json::from_str::<ErrorBody>(body) {
+            Ok(error) => Self {
+                message: format!("{status}: {}", error.error),
+                position: error.position,
+            },
+            Err(_) => format!("{status}: {body}").into(),
+        }
+    }
+}
+
+/// A spot in a query's SQL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ErrorLocation {
+    /// The character offset from the start of the SQL.
+    pub(crate) offset: usize,
+    /// The line, counting from 1.
+    pub(crate) line: usize,
+    /// The character in the line, counting from 1.
+    pub(crate) column: usize,
+}
+
+impl ErrorLocation {
+    /// Where running `sql` failed: at `position` when the server gave it, or
+    /// else where the message quotes it.
+    pub(crate) fn find(sql: &str, failure: &QueryFailure) -> Option<Self> {
+        let offset = failure
+            .position
+            .or_else(|| quoted_position(&failure.message, sql))?;
+        Self::at(sql, offset)
+    }
+
+    /// The location of the character offset `offset` in `sql`, which may be
+    /// just past its end.
+    fn at(sql: &str, offset: usize) -> Option<Self> {
+        let before: String = sql.chars().take(offset).collect();
+        if before.chars().count() < offset {
+            return None;
+        }
+        let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+        Some(Self {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+
+    /// The part of the location's line around it, with `…` where it's cut
+    /// short, split into the text before the error, the character at it (empty
+    /// at the end of the line) and the text after.
+    pub(crate) fn excerpt(&self, sql: &str) -> (String, String, String) {
+        let line: Vec<char> = sql
+            .split('\n')
+            .nth(self.line - 1)
+            .unwrap_or_default()
+            .trim_end_matches('\r')
+            .chars()
+            .collect();
+        let at = (self.column - 1).min(line.len());
+        let start = at.saturating_sub(EXCERPT_CONTEXT);
+        let end = (at + 1 + EXCERPT_CONTEXT).min(line.len());
+        let mut before: String = line[start..at].iter().collect();
+        if start > 0 {
+            before.insert(0, '…');
+        }
+        let spot: String = line[at..(at + 1).min(line.len())].iter().collect();
+        let mut after: String = line[(at + 1).min(line.len())..end].iter().collect();
+        if end < line.len() {
+            after.push('…');
+        }
+        (before, spot, after)
+    }
+}
+
+/// Where in `sql` the `DuckDB` error `message` points, from the line it quotes
+/// and the caret under it:
+///
+/// ```text
+/// LINE 1: SELECT * FORM file
+///                  ^
+/// ```
+///
+/// The quoted line may be cut short with `...` at either end, or hold the
+/// statement the server wrapped `sql` in.
+fn quoted_position(message: &str, sql: &str) -> Option<usize> {
+    let mut lines = message.lines();
+    let (number, quoted, prefix) = lines.by_ref().find_map(|line| {
+        let (number, quoted) = line.strip_prefix("LINE ")?.split_once(": ")?;
+        Some((
+            number.parse::<usize>().ok()?,
+            quoted,
+            line.len() - quoted.len(),
+        ))
+    })?;
+    let caret = lines.next()?.find('^')?.checked_sub(prefix)?;
+    let (quoted, caret) = match quoted.strip_prefix("...") {
+        Some(quoted) => (quoted, caret.checked_sub(3)?),
+        None => (quoted, caret),
+    };
+    let quoted = quoted.strip_suffix("...").unwrap_or(quoted);
+
+    let index = number.checked_sub(1)?;
+    let line = sql.split('\n').nth(index)?;
+    let column = if let Some(at) = line.find(quoted) {
+        line[..at].chars().count() + caret
+    } else {
+        let at = quoted.find(line)?;
+        caret
+            .checked_sub(quoted[..at].chars().count())
+            .filter(|column| *column <= line.chars().count())?
+    };
+    let line_start: usize = sql
+        .split('\n')
+        .take(index)
+        .map(|line| line.chars().count() + 1)
+        .sum();
+    Some(line_start + column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorLocation, QueryFailure};
+
+    fn location(sql: &str, message: &str) -> Option<ErrorLocation> {
+        ErrorLocation::find(sql, &message.to_string().into())
+    }
+
+    #[test]
+    fn json_error_bodies_give_the_position() {
+        let body = r#"{"error": "Parser Error: oops", "kind": "parser", "position": 9}"#;
+        assert_eq!(
+            QueryFailure::from_response("400 Bad Request", body),
+            QueryFailure {
+                message: "400 Bad Request: Parser Error: oops".to_string(),
+                position: Some(9),
+            }
+        );
+        assert_eq!(
+            QueryFailure::from_response("400 Bad Request", "query timed out after 60s"),
+            QueryFailure {
+                message: "400 Bad Request: query timed out after 60s".to_string(),
+                position: None,
+            }
+        );
+    }
+
+    #[test]
+    fn the_position_locates_the_line_and_column() {
+        let sql = "SELECT n\nFROM missing";
+        let failure = QueryFailure {
+            message: "Catalog Error: no table".to_string(),
+            position: Some(14),
+        };
+        assert_eq!(
+            ErrorLocation::find(sql, &failure),
+            Some(ErrorLocation {
+                offset: 14,
+                line: 2,
+                column: 6,
+            })
+        );
+        let failure = QueryFailure {
+            position: Some(100),
+            ..failure
+        };
+        assert_eq!(ErrorLocation::find(sql, &failure), None);
+    }
+
+    #[test]
+    fn quoted_lines_locate_the_error() {
+        let message = format!(
+            "400 Bad Request: Parser Error: syntax error at or near \"FORM\"\n\n\
+             LINE 1: SELECT * FORM t\n{}^",
+            " ".repeat(17)
+        );
+        assert_eq!(
+            location("SELECT * FORM t", &message).map(|l| l.offset),
+            Some(9)
+        );
+
+        // The server wraps queries in a statement of its own.
+        let message = format!(
+            "Catalog Error: Table with name missing does not exist!\n\n\
+             LINE 2: FROM missing) AS q LIMIT 0\n{}^",
+            " ".repeat(13)
+        );
+        assert_eq!(
+            location("SELECT n\nFROM missing", &message),
+            Some(ErrorLocation {
+                offset: 14,
+                line: 2,
+                column: 6,
+            })
+        );
+
+        // Long lines are cut short.
+        let sql = format!("SELECT {}FORM t", "n, ".repeat(40));
+        let message = format!(
+            "Parser Error: syntax error\n\nLINE 1: ...n, n, n, FORM t\n{}^",
+            " ".repeat(20)
+        );
+        assert_eq!(location(&sql, &message).map(|l| l.offset), sql.find("FORM"));
+
+        assert_eq!(location("SELECT 1", "query timed out after 60s"), None);
+    }
+
+    #[test]
+    fn excerpts_cut_long_lines_around_the_error() {
+        let sql = format!("SELECT 1\n{}^{}", "a".repeat(50), "b".repeat(50));
+        let location = ErrorLocation::at(&sql, 9 + 50).unwrap();
+        assert_eq!(
+            location.excerpt(&sql),
+            (
+                format!("…{}", "a".repeat(40)),
+                "^".to_string(),
+                format!("{}…", "b".repeat(40)),
+            )
+        );
+
+        let location = ErrorLocation::at("SELECT", 6).unwrap();
+        assert_eq!(
+            location.excerpt("SELECT"),
+            ("SELECT".to_string(), String::new(), String::new())
+        );
+    }
+}
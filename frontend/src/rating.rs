@@ -0,0 +1,238 @@
+//! Rating tracks from the results: a row of stars drawn in place of the
+//! `rating` column of results that have a track id, written back with the
+//! server's `POST /tracks/{id}/rating` (or the linked backend, when there is
+//! one).
+//!
+//! A click shows its rating straight away; once the write is through, the cell
+//! takes the rating the server stored, and if it fails, goes back to what it
+//! was.
+
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use serde_json::{Value, json};
+
+use crate::{QueryState, icons};
+
+/// How many stars a row has: the highest rating.
+pub(crate) const STARS: u8 = 5;
+/// The color of a star counting toward the rating.
+const STAR_COLOR: egui::Color32 = egui::Color32::from_rgb(0xF2, 0xA9, 0x00);
+
+/// The result column holding track ratings: one named `rating`, ignoring case.
+pub(crate) fn rating_column(column_names: &[String]) -> Option<usize> {
+    column_names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case("rating"))
+}
+
+/// How many stars the rating cell `value` fills: the rating rounded to a whole
+/// star, none for an empty cell.
+pub(crate) fn stars(value: &str) -> u8 {
+    value.trim().parse::<f64>().map_or(0, |rating| {
+        rating.round().clamp(0.0, f64::from(STARS)) as u8
+    })
+}
+
+/// The rating a click on star `star` (from 1) sets on a track filled with
+/// `current` stars: that many stars, or none when it's the star the rating
+/// already ends at.
+pub(crate) fn clicked_rating(current: u8, star: u8) -> Option<f64> {
+    (star != current).then_some(f64::from(star))
+}
+
+/// Draws the stars of the rating cell `value` within `rect`, filling them up to
+/// the one hovered. Returns the star (from 1) that was clicked, if any; see
+/// [`clicked_rating`] for the rating it sets.
+pub(crate) fn draw_stars(
+    ui: &mut egui::Ui,
+    rect: egui::Rect,
+    id: egui::Id,
+    value: &str,
+) -> Option<u8> {
+    let size = rect.height();
+    let current = stars(value);
+    let font = icons::font_id(size);
+    let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+    let star_rects: Vec<egui::Rect> = (0..STARS)
+        .map(|i| {
+            egui::Rect::from_min_size(
+                egui::pos2(rect.left() + f32::from(i) * size, rect.top()),
+                egui::vec2(size, size),
+            )
+        })
+        .collect();
+    let responses: Vec<egui::Response> = star_rects
+        .iter()
+        .enumerate()
+        .map(|(i, star_rect)| {
+            ui.interact(star_rect.intersect(rect), id.with(i), egui::Sense::click())
+        })
+        .collect();
+    let hovered = responses
+        .iter()
+        .position(egui::Response::hovered)
+        .map(|i| i as u8 + 1);
+    let filled = hovered.unwrap_or(current);
+    for (i, star_rect) in star_rects.iter().enumerate() {
+        let color = if (i as u8) < filled {
+            STAR_COLOR
+        } else {
+            ui.visuals().weak_text_color()
+        };
+        painter.text(
+            star_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            icons::RATING.codepoint,
+            font.clone(),
+            color,
+        );
+    }
+    responses
+        .iter()
+        .position(egui::Response::clicked)
+        .map(|i| i as u8 + 1)
+}
+
+/// Rates track `id`, shown in row `row` of `results`: shows `rating` in the
+/// row's `column` at once, then writes it in the background.
+pub(crate) fn set_rating(
+    results: &Arc<Mutex<QueryState>>,
+    row: usize,
+    column: usize,
+    id: String,
+    rating: Option<f64>,
+    ctx: &egui::Context,
+) {
+    let previous = {
+        let mut state = results.lock().unwrap();
+        let Some(cell) = state.rows.get_mut(row).and_then(|r| r.get_mut(column)) else {
+            return;
+        };
+        std::mem::replace(cell, rating.map(|r| r.to_string()).unwrap_or_default())
+    };
+    let results = Arc::clone(results);
+    let ctx = ctx.clone();
+    dispatch(id.clone(), rating, move |result| {
+        let mut guard = results.lock().unwrap();
+        let state = &mut *guard;
+        // The results may have been run again since; only touch this track's row.
+        let track_column = state.track_id_column;
+        let cell = state.rows.get_mut(row).filter(|cells| {
+            track_column.and_then(|i| cells.get(i)).map(String::as_str) == Some(id.as_str())
+        });
+        let Some(cell) = cell.and_then(|cells| cells.get_mut(column)) else {
+            return;
+        };
+        match result {
+            Ok(stored) => *cell = stored.map(|r| r.to_string()).unwrap_or_default(),
+            Err(e) => {
+                *cell = previous;
+                state.error = Some(format!("Couldn't rate the track: {e}"));
+            }
+        }
+        ctx.request_repaint();
+    });
+}
+
+/// The rating the server answered a rating write with.
+fn stored_rating(response: &Value) -> Option<f64> {
+    response.get("rating").and_then(Value::as_f64)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn dispatch<D>(id: String, rating: Option<f64>, on_done: D)
+where
+    D: FnOnce(Result<Option<f64>, String>) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        #[cfg(feature = "embedded")]
+        if let Some(state) = crate::embedded::backend() {
+            on_done(crate::embedded::set_rating(state, &id, rating));
+            return;
+        }
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build tokio runtime");
+        on_done(rt.block_on(post_rating(&id, rating)));
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn dispatch<D>(id: String, rating: Option<f64>, on_done: D)
+where
+    D: FnOnce(Result<Option<f64>, String>) + 'static,
+{
+    wasm_bindgen_futures::spawn_local(async move {
+        on_done(post_rating(&id, rating).await);
+    });
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn post_rating(id: &str, rating: Option<f64>) -> Result<Option<f64>, String> {
+    let url = format!("{}/tracks/{id}/rating", crate::http::base_url());
+    let resp = reqwest::Client::new()
+        .post(&url)
+        .header("content-type", "application/json")
+        .body(json!({ "rating": rating }).to_string())
+        .send()
+        .await
+        .map_err(|e| crate::http::request_error(&e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let msg = resp.text().await.unwrap_or_default();
+        return Err(format!("{status}: {msg}"));
+    }
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    Ok(stored_rating(&value))
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn post_rating(id: &str, rating: Option<f64>) -> Result<Option<f64>, String> {
+    let url = format!("{}/tracks/{id}/rating", crate::http::base_url());
+    let resp = gloo_net::http::Request::post(&url)
+        .header("content-type", "application/json")
+        .body(json!({ "rating": rating }).to_string())
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.ok() {
+        let status = resp.status();
+        let msg = resp.text().await.unwrap_or_default();
+        return Err(format!("{status}: {msg}"));
+    }
+    let text = resp.text().await.map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    Ok(stored_rating(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clicked_rating, rating_column, stars};
+
+    #[test]
+    fn cells_fill_stars_to_the_nearest_whole_star() {
+        assert_eq!(stars(""), 0);
+        assert_eq!(stars("3.0"), 3);
+        assert_eq!(stars("3.5"), 4);
+        assert_eq!(stars("0.2"), 0);
+        assert_eq!(stars("9"), 5);
+    }
+
+    #[test]
+    fn clicking_the_last_filled_star_clears_the_rating() {
+        assert_eq!(clicked_rating(0, 3), Some(3.0));
+        assert_eq!(clicked_rating(4, 2), Some(2.0));
+        assert_eq!(clicked_rating(3, 3), None);
+    }
+
+    #[test]
+    fn the_rating_column_is_found_by_name() {
+        let names = ["title", "Rating"].map(String::from);
+        assert_eq!(rating_column(&names), Some(1));
+        assert_eq!(rating_column(&names[..1]), None);
+    }
+}
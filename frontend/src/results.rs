@@ -7,14 +7,23 @@ use eframe::egui;
 use egui::emath::GuiRounding;
 use egui::text::{LayoutJob, TextWrapping};
 
+use crate::button::{self, Button};
 use crate::columns::{ColumnMetadata, FontColor, FontSize, TextAlign};
 use crate::field_layout::{ColSize, FieldLayout, LayoutKey, Placement, compute_field_layout};
-use crate::{ACCENT_BLUE, App, QueryState};
+use crate::http::RESULT_PAGE_SIZE;
+use crate::plan;
+use crate::preview;
+use crate::query_error::ErrorLocation;
+use crate::rating;
+use crate::{ACCENT_BLUE, App, QueryState, icons};
 
 /// Vertical padding above and below a row's content.
 const ROW_PAD_Y: f32 = 6.0;
 /// Horizontal padding on the left and right of a row's content.
 const TEXT_PAD_X: f32 = 8.0;
+/// How often the results are redrawn while a file is previewed, to notice its
+/// end.
+const PREVIEW_REPAINT: std::time::Duration = std::time::Duration::from_millis(250);
 /// Horizontal gap between adjacent columns on a line.
 const COL_GAP: f32 = 16.0;
 /// How much darker an un-selected row gets on hover (per RGB channel). Small, so
@@ -53,7 +62,12 @@ impl App {
         // visible ones paired with their original cell index.
         let col_count = state.rows.first().map_or(0, Vec::len);
         let visible: Vec<(usize, ColumnMetadata)> = (0..col_count)
-            .map(|i| (i, state.columns.get(i).cloned().unwrap_or_default()))
+            .map(|i| {
+                let mut meta = state.columns.get(i).cloned().unwrap_or_default();
+                let numeric = state.numeric_columns.get(i).copied().unwrap_or(false);
+                meta.text_align = meta.text_align.for_column(numeric);
+                (i, meta)
+            })
             .filter(|(_, meta)| !meta.hide)
             .collect();
         let col_sizes: Vec<ColSize> = visible
@@ -101,6 +115,7 @@ impl App {
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     pub(crate) fn render_results(&mut self, ui: &mut egui::Ui) {
         let Some(current_id) = self.current.query_id() else {
             return;
@@ -116,14 +131,39 @@ impl App {
 
             if let Some(err) = &state.error {
                 ui.colored_label(egui::Color32::RED, err);
+                if let (Some(location), Some(sql)) = (&state.error_location, &state.sql) {
+                    draw_error_location(ui, location, sql);
+                }
+            }
+            if let Some(err) = self.preview.error() {
+                ui.colored_label(egui::Color32::RED, err);
             }
 
-            if state.rows.is_empty() {
+            let turn_to = state
+                .total
+                .and_then(|total| draw_pager(ui, state.offset, total, state.running));
+
+            // Without columns there's nothing to head; a result with columns but
+            // no rows still shows them.
+            if state.rows.is_empty() && state.column_names.is_empty() {
+                if let Some(n) = state.rows_affected {
+                    ui.label(rows_affected_label(n));
+                }
+                drop(state);
+                if let Some(offset) = turn_to {
+                    self.show_results_page(offset, &ctx);
+                }
+                return;
+            }
+            if plan::is_plan(&state.column_names) {
+                plan::draw_plans(ui, &state.rows);
                 return;
             }
 
             let mut clicked: Option<(usize, egui::Modifiers)> = None;
             let mut double_clicked: Option<(usize, String)> = None;
+            let mut preview_action: Option<(PreviewAction, String)> = None;
+            let mut rated: Option<(usize, String, Option<f64>)> = None;
 
             let pending_locate = self
                 .pending_scroll_to_row
@@ -137,6 +177,9 @@ impl App {
 
             let metrics = self.row_metrics(ui, &state);
             let row_height = metrics.row_height;
+            // Ratings can only be set on rows that say which track they are.
+            let rating_column = rating::rating_column(&state.column_names)
+                .filter(|_| state.track_id_column.is_some());
             let row_layout = RowLayout {
                 visible: &metrics.visible,
                 placements: &metrics.layout.placements,
@@ -147,7 +190,10 @@ impl App {
                 text_color: metrics.text_color,
                 weak_color: metrics.weak_color,
                 row_height,
+                stars_column: rating_column,
             };
+            let stars_cell = rating_column
+                .and_then(|column| metrics.visible.iter().position(|(i, _)| *i == column));
 
             // Only highlight the now-playing row when it belongs to this page.
             let current_row = {
@@ -156,9 +202,24 @@ impl App {
                     .filter(|c| c.source_page == current_id)
                     .and_then(|c| c.row_index)
             };
+            let sorted_by = state.sort.as_ref().and_then(|sort| {
+                let column = state.column_names.iter().position(|n| *n == sort.column)?;
+                Some((column, sort.descending))
+            });
+            let header_clicked = draw_header(ui, &row_layout, &state.column_names, sorted_by);
+            if state.rows.is_empty() && !state.running {
+                ui.weak("No rows");
+            }
+
             let rows = &state.rows;
             let selection = &self.selection;
             let track_id_column = state.track_id_column;
+            let path_column = preview::path_column(&state.column_names);
+            let previewing = self.preview.current().map(str::to_string);
+            let preview_paused = self.preview.is_paused();
+            if previewing.is_some() {
+                ctx.request_repaint_after(PREVIEW_REPAINT);
+            }
 
             let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false, false]);
             if let Some(idx) = pending_locate {
@@ -172,13 +233,34 @@ impl App {
                     let cells = &rows[index];
                     let track_id = track_id_column.and_then(|i| cells.get(i).map(String::as_str));
                     let is_current = current_row == Some(index);
-                    let resp = draw_row(
-                        ui,
-                        &row_layout,
-                        cells,
-                        selection.contains(&index),
-                        is_current,
-                    );
+                    let selected = selection.contains(&index);
+                    let resp = draw_row(ui, &row_layout, cells, selected, is_current);
+                    if let (Some(id), Some(vis_idx), Some(column)) =
+                        (track_id, stars_cell, rating_column)
+                    {
+                        let rect = cell_rect(&row_layout, resp.rect, vis_idx);
+                        let value = cells.get(column).map_or("", String::as_str);
+                        if let Some(star) =
+                            rating::draw_stars(ui, rect, resp.id.with("rating"), value)
+                        {
+                            let rating = rating::clicked_rating(rating::stars(value), star);
+                            rated = Some((index, id.to_string(), rating));
+                        }
+                    }
+                    let path = path_column
+                        .and_then(|i| cells.get(i))
+                        .filter(|path| preview::is_path(path));
+                    if let Some(path) = path {
+                        let this_previewing = previewing.as_deref() == Some(path.as_str());
+                        if this_previewing || resp.contains_pointer() {
+                            let paused = this_previewing && preview_paused;
+                            let action =
+                                draw_preview_buttons(ui, &resp, selected, this_previewing, paused);
+                            if let Some(action) = action {
+                                preview_action = Some((action, path.clone()));
+                            }
+                        }
+                    }
                     if resp.double_clicked() {
                         if let Some(id) = track_id {
                             double_clicked = Some((index, id.to_string()));
@@ -191,15 +273,44 @@ impl App {
             });
             drop(state);
 
+            if let Some(column) = header_clicked {
+                self.sort_results(column, &ctx);
+            }
+            if let Some(offset) = turn_to {
+                self.show_results_page(offset, &ctx);
+            }
             if let Some((index, mods)) = clicked {
                 self.handle_row_click(index, mods);
             }
             if let Some((index, id)) = double_clicked {
                 self.play_track(current_id, index, &id, &ctx);
             }
+            if let Some((action, path)) = preview_action {
+                self.apply_preview_action(action, &path, &ctx);
+            }
+            if let (Some((index, id, rating)), Some(column)) = (rated, rating_column) {
+                rating::set_rating(&results, index, column, id, rating, &ctx);
+            }
         });
     }
 
+    /// Plays, pauses or stops the preview of the file at `path`. Starting or
+    /// resuming a preview pauses the now-playing track.
+    fn apply_preview_action(&mut self, action: PreviewAction, path: &str, ctx: &egui::Context) {
+        match action {
+            PreviewAction::Play => {
+                self.audio.pause();
+                self.preview.play(path, ctx);
+            }
+            PreviewAction::Resume => {
+                self.audio.pause();
+                self.preview.resume();
+            }
+            PreviewAction::Pause => self.preview.pause(),
+            PreviewAction::Stop => self.preview.stop(),
+        }
+    }
+
     pub(crate) fn handle_row_click(&mut self, index: usize, modifiers: egui::Modifiers) {
         if modifiers.shift {
             let anchor = self.selection_anchor.unwrap_or(index);
@@ -251,6 +362,265 @@ struct RowLayout<'a> {
     text_color: egui::Color32,
     weak_color: egui::Color32,
     row_height: f32,
+    /// The column drawn as rating stars rather than text, if any.
+    stars_column: Option<usize>,
+}
+
+/// The rect of the cell of visible column `vis_idx` in the row drawn at `row`.
+fn cell_rect(layout: &RowLayout, row: egui::Rect, vis_idx: usize) -> egui::Rect {
+    let placement = layout.placements[vis_idx];
+    egui::Rect::from_min_size(
+        egui::pos2(
+            row.left() + TEXT_PAD_X + placement.x,
+            row.top() + ROW_PAD_Y + layout.line_tops[placement.line],
+        ),
+        egui::vec2(
+            placement.width.max(0.0),
+            layout.line_heights[placement.line],
+        ),
+    )
+}
+
+/// What was clicked among a row's preview buttons.
+#[derive(Clone, Copy)]
+enum PreviewAction {
+    Play,
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// Draws the preview buttons at the right end of `row`: a play button, or
+/// pause/resume and stop buttons while its file is previewed.
+fn draw_preview_buttons(
+    ui: &mut egui::Ui,
+    row: &egui::Response,
+    selected: bool,
+    previewing: bool,
+    paused: bool,
+) -> Option<PreviewAction> {
+    let count = if previewing { 2.0 } else { 1.0 };
+    let rect = egui::Rect::from_min_max(
+        egui::pos2(
+            row.rect.right() - TEXT_PAD_X - count * button::SIZE,
+            row.rect.top(),
+        ),
+        egui::pos2(row.rect.right() - TEXT_PAD_X, row.rect.bottom()),
+    );
+    // Cover the cells under the buttons with the row's own background.
+    let bg = row_background(ui.visuals(), selected, row.hovered());
+    ui.painter().rect_filled(rect, 0.0, bg);
+
+    let mut buttons = ui.new_child(
+        egui::UiBuilder::new()
+            .max_rect(rect)
+            .layout(egui::Layout::right_to_left(egui::Align::Center)),
+    );
+    buttons.spacing_mut().item_spacing.x = 0.0;
+    if !previewing {
+        let play = Button::icon(icons::PREVIEW)
+            .show(&mut buttons)
+            .on_hover_text("Preview file");
+        return play.clicked().then_some(PreviewAction::Play);
+    }
+    let mut action = None;
+    if Button::icon(icons::STOP)
+        .show(&mut buttons)
+        .on_hover_text("Stop preview")
+        .clicked()
+    {
+        action = Some(PreviewAction::Stop);
+    }
+    let (icon, hover, toggle) = if paused {
+        (icons::PLAY, "Resume preview", PreviewAction::Resume)
+    } else {
+        (icons::PAUSE, "Pause preview", PreviewAction::Pause)
+    };
+    if Button::icon(icon)
+        .tint(ACCENT_BLUE)
+        .show(&mut buttons)
+        .on_hover_text(hover)
+        .clicked()
+    {
+        action = Some(toggle);
+    }
+    action
+}
+
+/// A result row's background: the selection color when selected, darkened a
+/// little while hovered.
+fn row_background(visuals: &egui::Visuals, selected: bool, hovered: bool) -> egui::Color32 {
+    if selected {
+        let base = visuals.selection.bg_fill;
+        if hovered { darken(base, 20) } else { base }
+    } else if hovered {
+        // Only a slight darkening on hover, so the effect is subtle.
+        darken(visuals.extreme_bg_color, ROW_HOVER_DARKEN)
+    } else {
+        visuals.extreme_bg_color
+    }
+}
+
+/// Describes which of a query's `total` rows the page starting at row `offset`
+/// holds, e.g. "Showing 1–1000 of 5321".
+fn page_label(offset: u64, total: u64) -> String {
+    if total == 0 {
+        return "No rows".to_string();
+    }
+    if offset >= total {
+        return format!("Showing none of {total}");
+    }
+    let end = (offset + RESULT_PAGE_SIZE).min(total);
+    format!("Showing {}–{end} of {total}", offset + 1)
+}
+
+/// Describes how many rows a statement without result columns changed.
+fn rows_affected_label(n: u64) -> String {
+    if n == 1 {
+        "1 row affected".to_string()
+    } else {
+        format!("{n} rows affected")
+    }
+}
+
+/// Draws where in `sql` the query failed: its line and column, over the part of
+/// the line around it with the failing spot underlined.
+fn draw_error_location(ui: &mut egui::Ui, location: &ErrorLocation, sql: &str) {
+    ui.weak(format!(
+        "Line {}, column {}",
+        location.line, location.column
+    ));
+    let (before, spot, after) = location.excerpt(sql);
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let plain = egui::TextFormat {
+        font_id: font_id.clone(),
+        color: ui.visuals().text_color(),
+        ..Default::default()
+    };
+    let marked = egui::TextFormat {
+        color: egui::Color32::RED,
+        background: egui::Color32::RED.gamma_multiply(0.2),
+        underline: egui::Stroke::new(1.5, egui::Color32::RED),
+        ..plain.clone()
+    };
+    let mut job = LayoutJob::default();
+    job.append(&before, 0.0, plain.clone());
+    // At the end of the line there's no character to mark, so a space stands in.
+    job.append(if spot.is_empty() { " " } else { &spot }, 0.0, marked);
+    job.append(&after, 0.0, plain);
+    ui.label(job);
+}
+
+/// Draws which rows of the query's `total` the page starting at row `offset`
+/// holds, between buttons to the previous and next pages. Returns the offset
+/// of the page whose button was clicked.
+fn draw_pager(ui: &mut egui::Ui, offset: u64, total: u64, running: bool) -> Option<u64> {
+    let mut turn_to = None;
+    ui.horizontal(|ui| {
+        let previous = Button::icon(icons::PREVIOUS_PAGE)
+            .enabled(!running && offset > 0)
+            .show(ui)
+            .on_hover_text("Previous page");
+        if previous.clicked() {
+            turn_to = Some(offset.saturating_sub(RESULT_PAGE_SIZE));
+        }
+        let next_offset = offset + RESULT_PAGE_SIZE;
+        let next = Button::icon(icons::NEXT_PAGE)
+            .enabled(!running && next_offset < total)
+            .show(ui)
+            .on_hover_text("Next page");
+        if next.clicked() {
+            turn_to = Some(next_offset);
+        }
+        ui.label(page_label(offset, total));
+    });
+    turn_to
+}
+
+/// Draws the header above the rows: each visible column's name where the row layout
+/// places its cells, with an arrow on the column the results are sorted by. Returns
+/// the result column whose name was clicked.
+fn draw_header(
+    ui: &mut egui::Ui,
+    layout: &RowLayout,
+    names: &[String],
+    sorted_by: Option<(usize, bool)>,
+) -> Option<usize> {
+    let line_h = ui.text_style_height(&egui::TextStyle::Small);
+    let line_count = layout.line_tops.len().max(1);
+    let desired = egui::vec2(ui.available_width(), line_h * line_count as f32 + ROW_PAD_Y);
+    let (rect, response) = ui.allocate_exact_size(desired, egui::Sense::click());
+    let rect = rect.round_to_pixels(ui.ctx().pixels_per_point());
+    ui.painter()
+        .rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+    let font = egui::TextStyle::Small.resolve(ui.style());
+    let color = ui.visuals().weak_text_color();
+    let pointer = response
+        .clicked()
+        .then(|| response.interact_pointer_pos())
+        .flatten();
+    let mut clicked = None;
+    for (vis_idx, (col_idx, meta)) in layout.visible.iter().enumerate() {
+        let placement = layout.placements[vis_idx];
+        let cell = egui::Rect::from_min_size(
+            egui::pos2(
+                rect.left() + TEXT_PAD_X + placement.x,
+                rect.top() + ROW_PAD_Y * 0.5 + line_h * placement.line as f32,
+            ),
+            egui::vec2(placement.width.max(0.0), line_h),
+        );
+        if pointer.is_some_and(|p| cell.contains(p)) {
+            clicked = Some(*col_idx);
+        }
+
+        let arrow = sorted_by
+            .filter(|(sorted, _)| sorted == col_idx)
+            .map(|(_, descending)| {
+                if descending {
+                    icons::SORTED_DESC
+                } else {
+                    icons::SORTED_ASC
+                }
+            });
+        let arrow_w = if arrow.is_some() { line_h } else { 0.0 };
+        let name = names.get(*col_idx).map_or("", String::as_str);
+        let mut job = LayoutJob::single_section(
+            name.to_string(),
+            egui::TextFormat {
+                font_id: font.clone(),
+                color,
+                ..Default::default()
+            },
+        );
+        job.wrap = TextWrapping::truncate_at_width((cell.width() - arrow_w).max(0.0));
+        let galley = ui.painter().layout_job(job);
+        let width = galley.size().x + arrow_w;
+
+        let slack = (cell.width() - width).max(0.0);
+        let x = match meta.text_align {
+            TextAlign::Auto | TextAlign::Left => cell.left(),
+            TextAlign::Right => cell.left() + slack,
+            TextAlign::Center => cell.left() + slack * 0.5,
+        };
+        let y = cell.top() + (line_h - galley.size().y) * 0.5;
+        let name_w = galley.size().x;
+        ui.painter().galley(egui::pos2(x, y), galley, color);
+        if let Some(arrow) = arrow {
+            ui.painter().text(
+                egui::pos2(x + name_w + arrow_w * 0.5, cell.center().y),
+                egui::Align2::CENTER_CENTER,
+                arrow.codepoint,
+                icons::font_id(font.size),
+                color,
+            );
+        }
+    }
+
+    if response.hovered() {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    }
+    clicked
 }
 
 fn draw_row(
@@ -272,19 +642,7 @@ fn draw_row(
     let rect = rect.round_to_pixels(ppp);
 
     let visuals = ui.visuals();
-    let base_bg = if selected {
-        let base = visuals.selection.bg_fill;
-        if response.hovered() {
-            darken(base, 20)
-        } else {
-            base
-        }
-    } else if response.hovered() {
-        // Only a slight darkening on hover, so the effect is subtle.
-        darken(visuals.extreme_bg_color, ROW_HOVER_DARKEN)
-    } else {
-        visuals.extreme_bg_color
-    };
+    let base_bg = row_background(visuals, selected, response.hovered());
 
     ui.painter().rect_filled(rect, 0.0, base_bg);
 
@@ -321,6 +679,9 @@ fn draw_row(
     ui.painter().rect_filled(sep_rect, 0.0, sep_color);
 
     for (vis_idx, (col_idx, meta)) in layout.visible.iter().enumerate() {
+        if layout.stars_column == Some(*col_idx) {
+            continue;
+        }
         let placement = layout.placements[vis_idx];
         let value = cells.get(*col_idx).map_or("", String::as_str);
         let formatted = match &meta.formatter {
@@ -354,7 +715,7 @@ fn draw_row(
         let cell_left = rect.left() + TEXT_PAD_X + placement.x;
         let slack = (placement.width - size.x).max(0.0);
         let x = match meta.text_align {
-            TextAlign::Left => cell_left,
+            TextAlign::Auto | TextAlign::Left => cell_left,
             TextAlign::Right => cell_left + slack,
             TextAlign::Center => cell_left + slack * 0.5,
         };
@@ -365,3 +726,24 @@ fn draw_row(
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{page_label, rows_affected_label};
+
+    #[test]
+    fn page_labels_give_the_rows_shown() {
+        assert_eq!(page_label(0, 5321), "Showing 1–1000 of 5321");
+        assert_eq!(page_label(5000, 5321), "Showing 5001–5321 of 5321");
+        assert_eq!(page_label(0, 1), "Showing 1–1 of 1");
+        assert_eq!(page_label(0, 0), "No rows");
+        assert_eq!(page_label(2000, 5), "Showing none of 5");
+    }
+
+    #[test]
+    fn rows_affected_labels_give_the_count() {
+        assert_eq!(rows_affected_label(0), "0 rows affected");
+        assert_eq!(rows_affected_label(1), "1 row affected");
+        assert_eq!(rows_affected_label(3), "3 rows affected");
+    }
+}
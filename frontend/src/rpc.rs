@@ -157,6 +157,11 @@ where
     D: FnOnce(Result<Value, String>) + Send + 'static,
 {
     std::thread::spawn(move || {
+        #[cfg(feature = "embedded")]
+        if let Some(state) = crate::embedded::backend() {
+            on_done(crate::embedded::call(state, method, params));
+            return;
+        }
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
@@ -187,14 +192,14 @@ fn request_body(method: &str, params: Value) -> String {
 
 #[cfg(not(target_arch = "wasm32"))]
 async fn post_rpc(method: &str, params: Value) -> Result<Value, String> {
-    let url = format!("{}/rpc", crate::http::BASE);
+    let url = format!("{}/rpc", crate::http::base_url());
     let resp = reqwest::Client::new()
         .post(&url)
         .header("content-type", "application/json")
         .body(request_body(method, params))
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| crate::http::request_error(&e))?;
     if !resp.status().is_success() {
         let status = resp.status();
         let msg = resp.text().await.unwrap_or_default();
@@ -207,7 +212,7 @@ async fn post_rpc(method: &str, params: Value) -> Result<Value, String> {
 
 #[cfg(target_arch = "wasm32")]
 async fn post_rpc(method: &str, params: Value) -> Result<Value, String> {
-    let url = format!("{}/rpc", crate::http::BASE);
+    let url = format!("{}/rpc", crate::http::base_url());
     let resp = gloo_net::http::Request::post(&url)
         .header("content-type", "application/json")
         .body(request_body(method, params))
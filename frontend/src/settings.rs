@@ -0,0 +1,333 @@
+//! User-adjustable settings: how result cells are displayed, and which server
+//! the native UI talks to.
+//!
+//! Cells are rendered to strings once, as batches arrive (see `http::push_batch`),
+//! so these settings shape the text every column starts from. Per-column
+//! formatters (see [`crate::format`]) then parse that text; one that can't parse a
+//! customized rendering falls back to showing it unchanged.
+
+use std::fmt::Write as _;
+
+use arrow_array::{Array, Float32Array, Float64Array};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use chrono::format::{Item, StrftimeItems};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::App;
+
+/// The eframe storage key the settings are persisted under.
+pub(crate) const STORAGE_KEY: &str = "display_settings";
+/// The eframe storage key the server URL is persisted under.
+pub(crate) const SERVER_URL_KEY: &str = "server_url";
+
+/// Fraction digits offered by the decimal places control.
+const MAX_FLOAT_PRECISION: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DisplaySettings {
+    /// Text shown in place of NULL cells. Empty by default.
+    pub(crate) null_placeholder: String,
+    /// A `strftime`-style format for timestamp cells. Empty keeps Arrow's RFC 3339
+    /// rendering.
+    pub(crate) timestamp_format: String,
+    /// Fraction digits for floating-point cells. `None` keeps Arrow's shortest
+    /// round-trip rendering.
+    pub(crate) float_precision: Option<usize>,
+}
+
+impl DisplaySettings {
+    fn format_options(&self) -> FormatOptions<'_> {
+        let timestamp_format =
+            (!self.timestamp_format.is_empty()).then_some(self.timestamp_format.as_str());
+        FormatOptions::default()
+            .with_null(&self.null_placeholder)
+            .with_timestamp_format(timestamp_format)
+            .with_timestamp_tz_format(timestamp_format)
+    }
+
+    /// Builds the renderer for one result column.
+    pub(crate) fn column_cells<'a>(
+        &'a self,
+        col: &'a dyn Array,
+    ) -> Result<ColumnCells<'a>, String> {
+        if let Some(precision) = self.float_precision {
+            if let Some(arr) = col.as_any().downcast_ref::<Float64Array>() {
+                return Ok(ColumnCells::Float64(arr, precision, &self.null_placeholder));
+            }
+            if let Some(arr) = col.as_any().downcast_ref::<Float32Array>() {
+                return Ok(ColumnCells::Float32(arr, precision, &self.null_placeholder));
+            }
+        }
+        let fallback = FormatOptions::default().with_null(&self.null_placeholder);
+        let fallback = ArrayFormatter::try_new(col, &fallback).map_err(|e| e.to_string())?;
+        ArrayFormatter::try_new(col, &self.format_options())
+            .map(|fmt| ColumnCells::Arrow(fmt, fallback))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Checks that `format` is a `strftime` format chrono can render, so a typo is
+/// caught in the settings modal rather than when cells are formatted.
+pub(crate) fn validate_timestamp_format(format: &str) -> Result<(), String> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("\"{format}\" is not a valid timestamp format"));
+    }
+    Ok(())
+}
+
+/// Renders the cells of one result column as display strings.
+pub(crate) enum ColumnCells<'a> {
+    /// The formatter built from the settings, and one with Arrow's default
+    /// rendering for cells the settings can't format.
+    Arrow(ArrayFormatter<'a>, ArrayFormatter<'a>),
+    /// Floats rounded to a fixed number of fraction digits, with a null placeholder.
+    Float64(&'a Float64Array, usize, &'a str),
+    Float32(&'a Float32Array, usize, &'a str),
+}
+
+impl ColumnCells<'_> {
+    pub(crate) fn cell(&self, row: usize) -> String {
+        match self {
+            ColumnCells::Arrow(fmt, fallback) => {
+                let mut cell = String::new();
+                if write!(cell, "{}", fmt.value(row)).is_err() {
+                    cell.clear();
+                    let _ = write!(cell, "{}", fallback.value(row));
+                }
+                cell
+            }
+            ColumnCells::Float64(arr, precision, null) => {
+                format_float(arr.is_valid(row).then(|| arr.value(row)), *precision, null)
+            }
+            ColumnCells::Float32(arr, precision, null) => format_float(
+                arr.is_valid(row).then(|| f64::from(arr.value(row))),
+                *precision,
+                null,
+            ),
+        }
+    }
+}
+
+fn format_float(value: Option<f64>, precision: usize, null: &str) -> String {
+    value.map_or_else(|| null.to_string(), |v| format!("{v:.precision$}"))
+}
+
+/// The drafts being edited in the settings modal, applied together.
+pub(crate) struct SettingsEdit {
+    display: DisplaySettings,
+    server_url: String,
+    /// Why the timestamp format can't be applied, shown under it.
+    timestamp_format_error: Option<String>,
+    /// Why the server URL can't be applied, shown under it.
+    server_url_error: Option<String>,
+}
+
+impl App {
+    /// Opens the settings modal on a copy of the current settings.
+    pub(crate) fn open_display_settings(&mut self) {
+        self.settings_edit = Some(SettingsEdit {
+            display: self.display_settings.clone(),
+            server_url: self.server_url.clone(),
+            timestamp_format_error: None,
+            server_url_error: None,
+        });
+    }
+
+    /// Sends requests to `url` from now on, refetching the schema, queries and
+    /// presets from it. Open queries re-run when next shown.
+    fn change_server(&mut self, url: String) {
+        crate::http::set_server_url(&url);
+        self.server_url = url;
+        self.schema_fetch_started = false;
+        self.queries_fetch_started = false;
+        self.presets_fetch_started = false;
+        for page in &mut self.pages {
+            page.results_fetched = false;
+        }
+    }
+
+    /// The settings modal. Edits apply on "Apply", which re-runs the open queries
+    /// so their cells are rendered with the new settings. A timestamp format or
+    /// server URL that doesn't parse keeps the modal open with the reason.
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn render_display_settings_modal(&mut self, ctx: &egui::Context) {
+        let Some(edit) = self.settings_edit.as_mut() else {
+            return;
+        };
+        let mut apply = false;
+        let mut close = false;
+        let modal = egui::Modal::new(egui::Id::new("display_settings")).show(ctx, |ui| {
+            ui.set_width(320.0);
+            ui.heading("Settings");
+            ui.add_space(8.0);
+            egui::Grid::new("display_settings_grid")
+                .num_columns(2)
+                .spacing([12.0, 8.0])
+                .show(ui, |ui| {
+                    ui.label("Empty value");
+                    crate::text_input::add(
+                        ui,
+                        egui::TextEdit::singleline(&mut edit.display.null_placeholder)
+                            .hint_text("(blank)")
+                            .desired_width(160.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Timestamp format");
+                    crate::text_input::add(
+                        ui,
+                        egui::TextEdit::singleline(&mut edit.display.timestamp_format)
+                            .hint_text("%Y-%m-%d %H:%M")
+                            .desired_width(160.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Decimal places");
+                    ui.horizontal(|ui| {
+                        let mut fixed = edit.display.float_precision.is_some();
+                        if ui.checkbox(&mut fixed, "").changed() {
+                            edit.display.float_precision = fixed.then_some(2);
+                        }
+                        if let Some(precision) = edit.display.float_precision.as_mut() {
+                            ui.add(egui::DragValue::new(precision).range(0..=MAX_FLOAT_PRECISION));
+                        } else {
+                            ui.weak("automatic");
+                        }
+                    });
+                    ui.end_row();
+
+                    // The web UI always talks to the server it was loaded from.
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.label("Server");
+                        crate::text_input::add(
+                            ui,
+                            egui::TextEdit::singleline(&mut edit.server_url)
+                                .hint_text(crate::http::DEFAULT_SERVER_URL)
+                                .desired_width(160.0),
+                        );
+                        ui.end_row();
+                    }
+                });
+            if let Some(error) = &edit.timestamp_format_error {
+                ui.add_space(4.0);
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if let Some(error) = &edit.server_url_error {
+                ui.add_space(4.0);
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            ui.add_space(12.0);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Apply").clicked() {
+                    apply = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    close = true;
+                }
+            });
+        });
+
+        if apply {
+            edit.timestamp_format_error =
+                validate_timestamp_format(&edit.display.timestamp_format).err();
+            if edit.timestamp_format_error.is_some() {
+                return;
+            }
+            let server_url = match &edit.server_url {
+                url if url.trim().is_empty() => Ok(String::new()),
+                url => crate::http::parse_server_url(url),
+            };
+            let server_url = match server_url {
+                Ok(url) => url,
+                Err(e) => {
+                    edit.server_url_error = Some(e);
+                    return;
+                }
+            };
+            let Some(SettingsEdit { display, .. }) = self.settings_edit.take() else {
+                return;
+            };
+            if server_url != self.server_url {
+                self.change_server(server_url);
+            }
+            if display != self.display_settings {
+                self.display_settings = display;
+                // Cells are rendered on arrival, so every page needs a fresh run;
+                // the current one re-runs on the next frame, others when shown.
+                for page in &mut self.pages {
+                    page.results_fetched = false;
+                }
+            }
+        } else if close || modal.should_close() {
+            self.settings_edit = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(settings: &DisplaySettings, col: &dyn Array) -> Vec<String> {
+        let cells = settings.column_cells(col).unwrap();
+        (0..col.len()).map(|row| cells.cell(row)).collect()
+    }
+
+    #[test]
+    fn defaults_match_arrow() {
+        let col = Float64Array::from(vec![Some(1.5), None]);
+        assert_eq!(cells(&DisplaySettings::default(), &col), ["1.5", ""]);
+    }
+
+    #[test]
+    fn float_precision_rounds() {
+        let settings = DisplaySettings {
+            float_precision: Some(2),
+            ..DisplaySettings::default()
+        };
+        let col = Float64Array::from(vec![1.0, 2.345_67]);
+        assert_eq!(cells(&settings, &col), ["1.00", "2.35"]);
+        let col = Float32Array::from(vec![0.5]);
+        assert_eq!(cells(&settings, &col), ["0.50"]);
+    }
+
+    #[test]
+    fn null_placeholder_applies_to_every_type() {
+        let settings = DisplaySettings {
+            null_placeholder: "—".to_string(),
+            float_precision: Some(1),
+            ..DisplaySettings::default()
+        };
+        let floats = Float64Array::from(vec![None, Some(1.0)]);
+        assert_eq!(cells(&settings, &floats), ["—", "1.0"]);
+        let strings = arrow_array::StringArray::from(vec![None, Some("a")]);
+        assert_eq!(cells(&settings, &strings), ["—", "a"]);
+    }
+
+    #[test]
+    fn invalid_timestamp_format_is_rejected() {
+        assert!(validate_timestamp_format("%Y-%m-%d %H:%M").is_ok());
+        assert!(validate_timestamp_format("").is_ok());
+        assert!(validate_timestamp_format("%Q").is_err());
+    }
+
+    #[test]
+    fn invalid_timestamp_format_falls_back_to_arrow() {
+        let settings = DisplaySettings {
+            timestamp_format: "%Q".to_string(),
+            ..DisplaySettings::default()
+        };
+        let col = arrow_array::TimestampSecondArray::from(vec![0]);
+        assert_eq!(cells(&settings, &col), ["1970-01-01T00:00:00"]);
+    }
+
+    #[test]
+    fn settings_deserialize_with_missing_fields() {
+        let settings: DisplaySettings = serde_json::from_str(r#"{"float_precision":3}"#).unwrap();
+        assert_eq!(settings.float_precision, Some(3));
+        assert_eq!(settings.null_placeholder, "");
+    }
+}
@@ -0,0 +1,377 @@
+//! The tab strip above a query page: the queries the user has open, in the
+//! order they were opened. A tab is a view onto its query's page, so each tab
+//! keeps that page's own definition, results and running state — a query left
+//! running in one tab keeps streaming into its own page while another tab is
+//! edited, and switching back shows wherever it got to.
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::App;
+use crate::button::Button;
+use crate::icons;
+use crate::page::{CurrentPage, MARKER_GAP, layout_query_name};
+
+/// The eframe storage key the open tabs are persisted under.
+pub(crate) const STORAGE_KEY: &str = "open_tabs";
+
+const TAB_BAR_HEIGHT: f32 = 28.0;
+/// Widest a tab's name gets before it's truncated with an ellipsis.
+const TAB_NAME_WIDTH: f32 = 160.0;
+/// Horizontal padding on either side of a tab's contents.
+const TAB_PAD_X: f32 = 10.0;
+/// Size of the close button (and the running spinner that stands in for it).
+const TAB_CLOSE_SIZE: f32 = 18.0;
+
+/// An open tab, showing the query page with `id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct QueryTab {
+    pub(crate) id: Uuid,
+}
+
+/// The open tabs and which of them is selected, as persisted across sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct OpenTabs {
+    pub(crate) tabs: Vec<QueryTab>,
+    pub(crate) selected: Option<usize>,
+}
+
+impl OpenTabs {
+    /// Drops the tabs whose query `exists` no longer reports, keeping the
+    /// selection on the same tab if it survives.
+    pub(crate) fn retain(&mut self, exists: impl Fn(Uuid) -> bool) {
+        let selected = self
+            .selected
+            .and_then(|i| self.tabs.get(i))
+            .map(|tab| tab.id);
+        self.tabs.retain(|tab| exists(tab.id));
+        self.selected = selected.and_then(|id| self.tabs.iter().position(|tab| tab.id == id));
+    }
+}
+
+/// One tab, as displayed in the strip.
+struct TabItem {
+    id: Uuid,
+    name: String,
+    unsaved: bool,
+    running: bool,
+}
+
+/// Per-tab outcome from [`tab_widget`].
+#[derive(Default)]
+struct TabOutcome {
+    clicked: bool,
+    close: bool,
+}
+
+impl App {
+    /// Opens a tab for `id` at the end of the strip, unless it already has one.
+    pub(crate) fn open_tab(&mut self, id: Uuid) {
+        if !self.tabs.iter().any(|tab| tab.id == id) {
+            self.tabs.push(QueryTab { id });
+        }
+    }
+
+    /// The index of the selected tab: the one showing the current page.
+    pub(crate) fn selected_tab(&self) -> Option<usize> {
+        let id = self.current.query_id()?;
+        self.tabs.iter().position(|tab| tab.id == id)
+    }
+
+    /// Closes the tab for `id`, leaving its query (and any unsaved edits) in the
+    /// organizer. Closing the selected tab selects the tab that slides into its
+    /// place, or the one before it if it was last, or the welcome page once no
+    /// tabs are left.
+    pub(crate) fn close_tab(&mut self, id: Uuid) {
+        let Some(index) = self.tabs.iter().position(|tab| tab.id == id) else {
+            return;
+        };
+        self.tabs.remove(index);
+        if self.current.query_id() != Some(id) {
+            return;
+        }
+        if let Some(next) = self.tabs.get(index).or_else(|| self.tabs.last()).copied() {
+            self.select_page(next.id);
+        } else {
+            self.current = CurrentPage::Welcome;
+            self.selection.clear();
+            self.selection_anchor = None;
+        }
+    }
+
+    /// The open tabs, for persisting.
+    pub(crate) fn open_tabs(&self) -> OpenTabs {
+        OpenTabs {
+            tabs: self.tabs.clone(),
+            selected: self.selected_tab(),
+        }
+    }
+
+    /// Reopens the tabs persisted by the last session, once the query list has
+    /// loaded. Tabs of queries that are gone (including never-saved ones) are
+    /// dropped. Returns whether a tab was selected.
+    pub(crate) fn restore_tabs(&mut self) -> bool {
+        let Some(mut open) = self.restored_tabs.take() else {
+            return false;
+        };
+        open.retain(|id| self.pages.iter().any(|p| p.live.id == id));
+        self.tabs = open.tabs;
+        // With the selected tab gone, fall back to the first.
+        let selected = open.selected.unwrap_or(0);
+        let Some(&tab) = self.tabs.get(selected) else {
+            return false;
+        };
+        self.select_page(tab.id);
+        true
+    }
+
+    /// Renders the tab strip: a tab per open query, with its name, unsaved
+    /// marker and a close button (a spinner while its query runs), then a "+"
+    /// button that opens a new query in a new tab.
+    pub(crate) fn render_tab_bar(&mut self, ui: &mut egui::Ui) {
+        let panel_fill = ui.style().visuals.panel_fill;
+        let current = self.current.query_id();
+        let items: Vec<TabItem> = self
+            .tabs
+            .iter()
+            .filter_map(|tab| {
+                let page = self.pages.iter().find(|p| p.live.id == tab.id)?;
+                Some(TabItem {
+                    id: tab.id,
+                    name: page.live.name.clone(),
+                    unsaved: page.unsaved(),
+                    running: page.results.lock().unwrap().running,
+                })
+            })
+            .collect();
+
+        let mut clicked = None;
+        let mut close = None;
+        let mut add = false;
+        egui::Panel::top("tab_bar")
+            .exact_size(TAB_BAR_HEIGHT)
+            .frame(
+                egui::Frame::new()
+                    .fill(panel_fill)
+                    .inner_margin(egui::Margin::same(0)),
+            )
+            .show_inside(ui, |ui| {
+                egui::ScrollArea::horizontal()
+                    .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
+                    .show(ui, |ui| {
+                        ui.horizontal_centered(|ui| {
+                            // Adjacent tabs butt together, leaving no dead gap.
+                            ui.spacing_mut().item_spacing.x = 0.0;
+                            for item in &items {
+                                let out = tab_widget(ui, item, current == Some(item.id));
+                                if out.close {
+                                    close = Some(item.id);
+                                } else if out.clicked {
+                                    clicked = Some(item.id);
+                                }
+                            }
+                            ui.add_space(4.0);
+                            if Button::icon(icons::ADD).show(ui).clicked() {
+                                add = true;
+                            }
+                        });
+                    });
+            });
+
+        if let Some(id) = close {
+            self.close_tab(id);
+        }
+        if let Some(id) = clicked {
+            self.select_page(id);
+        }
+        if add {
+            self.add_query_page();
+        }
+    }
+}
+
+/// A single tab. Styled like the organizer's rows (see `organizer::query_list_widget`)
+/// so the selected query looks the same in both. Clicking selects it; clicking
+/// its close button or middle-clicking it closes it.
+fn tab_widget(ui: &mut egui::Ui, item: &TabItem, selected: bool) -> TabOutcome {
+    let font_id = egui::TextStyle::Body.resolve(ui.style());
+    let text_color = ui.visuals().text_color();
+    let (name_galley, marker_galley) = layout_query_name(
+        ui,
+        &item.name,
+        item.unsaved,
+        font_id,
+        text_color,
+        TAB_NAME_WIDTH,
+    );
+    let name_size = name_galley.size();
+    let marker_w = marker_galley
+        .as_ref()
+        .map_or(0.0, |g| g.size().x + MARKER_GAP);
+    let width = TAB_PAD_X + name_size.x + marker_w + 4.0 + TAB_CLOSE_SIZE + TAB_PAD_X / 2.0;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(width, ui.available_height()),
+        egui::Sense::click(),
+    );
+
+    let (sel_fill, hover_fill, sep_color, weak_text) = {
+        let v = ui.visuals();
+        (
+            v.selection.bg_fill,
+            crate::results::darken(v.panel_fill, crate::results::ROW_HOVER_DARKEN),
+            v.widgets.noninteractive.bg_stroke.color,
+            v.weak_text_color(),
+        )
+    };
+    if selected {
+        ui.painter().rect_filled(rect, 0.0, sel_fill);
+    } else if response.hovered() {
+        ui.painter().rect_filled(rect, 0.0, hover_fill);
+    }
+    // Thin separator on the right, between this tab and the next.
+    ui.painter().line_segment(
+        [rect.right_top(), rect.right_bottom()],
+        egui::Stroke::new(1.0, sep_color),
+    );
+
+    let name_pos = egui::pos2(rect.left() + TAB_PAD_X, rect.center().y - name_size.y / 2.0);
+    ui.painter().galley(name_pos, name_galley, text_color);
+    if let Some(marker) = marker_galley {
+        ui.painter().galley(
+            egui::pos2(name_pos.x + name_size.x + MARKER_GAP, name_pos.y),
+            marker,
+            text_color,
+        );
+    }
+
+    let close_rect = egui::Rect::from_center_size(
+        egui::pos2(
+            rect.right() - TAB_PAD_X / 2.0 - TAB_CLOSE_SIZE / 2.0,
+            rect.center().y,
+        ),
+        egui::vec2(TAB_CLOSE_SIZE, TAB_CLOSE_SIZE),
+    );
+    let close_resp = ui.interact(
+        close_rect,
+        egui::Id::new(("tab-close", item.id)),
+        egui::Sense::click(),
+    );
+    // A running query shows a spinner in place of the close button, until the
+    // tab is hovered and the button is wanted.
+    if item.running && !response.hovered() && !close_resp.hovered() {
+        egui::Spinner::new()
+            .size(TAB_CLOSE_SIZE - 6.0)
+            .color(weak_text)
+            .paint_at(ui, close_rect.shrink(3.0));
+    } else {
+        ui.painter().text(
+            close_rect.center(),
+            egui::Align2::CENTER_CENTER,
+            icons::CLOSE.codepoint,
+            icons::font_id(14.0),
+            if close_resp.hovered() {
+                text_color
+            } else {
+                weak_text
+            },
+        );
+    }
+
+    TabOutcome {
+        clicked: response.clicked(),
+        close: close_resp.clicked() || response.middle_clicked(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{OpenTabs, QueryTab};
+    use crate::App;
+    use crate::page::{CurrentPage, QueryPage};
+    use crate::rpc::Query;
+
+    fn tab(n: u128) -> QueryTab {
+        QueryTab {
+            id: Uuid::from_u128(n),
+        }
+    }
+
+    fn app_with_tabs(ids: &[u128], current: u128) -> App {
+        let pages = ids
+            .iter()
+            .map(|&n| {
+                QueryPage::persisted(Query {
+                    id: Uuid::from_u128(n),
+                    name: format!("Query {n}"),
+                    created_at: 0,
+                    modified_at: 0,
+                    last_play: 0,
+                    definition: crate::query_def::QueryDefinition::default(),
+                })
+            })
+            .collect();
+        App {
+            pages,
+            tabs: ids.iter().map(|&n| tab(n)).collect(),
+            current: CurrentPage::Query(Uuid::from_u128(current)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retain_keeps_the_selected_tab() {
+        let mut open = OpenTabs {
+            tabs: vec![tab(1), tab(2), tab(3)],
+            selected: Some(2),
+        };
+        open.retain(|id| id != Uuid::from_u128(1));
+        assert_eq!(open.tabs, vec![tab(2), tab(3)]);
+        assert_eq!(open.selected, Some(1));
+
+        // The selected tab's query is gone: nothing is selected.
+        open.retain(|id| id != Uuid::from_u128(3));
+        assert_eq!(open.tabs, vec![tab(2)]);
+        assert_eq!(open.selected, None);
+    }
+
+    #[test]
+    fn closing_the_selected_tab_selects_the_next() {
+        let mut app = app_with_tabs(&[1, 2, 3], 2);
+        app.close_tab(Uuid::from_u128(2));
+        assert_eq!(app.current.query_id(), Some(Uuid::from_u128(3)));
+
+        // The last tab closes onto the one before it.
+        app.close_tab(Uuid::from_u128(3));
+        assert_eq!(app.current.query_id(), Some(Uuid::from_u128(1)));
+
+        app.close_tab(Uuid::from_u128(1));
+        assert!(app.current == CurrentPage::Welcome);
+        // Closing a tab leaves its query in the organizer.
+        assert_eq!(app.pages.len(), 3);
+    }
+
+    #[test]
+    fn closing_another_tab_keeps_the_selection() {
+        let mut app = app_with_tabs(&[1, 2], 2);
+        app.close_tab(Uuid::from_u128(1));
+        assert_eq!(app.current.query_id(), Some(Uuid::from_u128(2)));
+        assert_eq!(app.selected_tab(), Some(0));
+    }
+
+    #[test]
+    fn restore_reopens_the_persisted_tabs() {
+        let mut app = app_with_tabs(&[1, 2], 1);
+        app.tabs.clear();
+        app.restored_tabs = Some(OpenTabs {
+            tabs: vec![tab(9), tab(2), tab(1)],
+            selected: Some(1),
+        });
+        assert!(app.restore_tabs());
+        assert_eq!(app.tabs, vec![tab(2), tab(1)]);
+        assert_eq!(app.current.query_id(), Some(Uuid::from_u128(2)));
+    }
+}
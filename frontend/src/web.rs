@@ -27,7 +27,7 @@ pub fn auto_start() {
                 eframe::WebOptions::default(),
                 Box::new(|cc| {
                     setup_fonts(&cc.egui_ctx);
-                    Ok(Box::new(App::default()))
+                    Ok(Box::new(App::new(cc)))
                 }),
             )
             .await